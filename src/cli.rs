@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use pcap_parser::Linktype;
+
+use crate::pkt::timestamp::TimeFormat;
+
+/// A terminal packet capture viewer
+#[derive(Parser, Debug)]
+#[command(name = "tuishark", version, about)]
+pub struct Args {
+    /// Capture file to open (pcap or pcapng)
+    pub path: PathBuf,
+
+    /// Override the link type from the capture header, by name (ethernet, raw, ipv4, ipv6,
+    /// null, loop, linux_sll) or DLT number
+    #[arg(short = 'l', long = "linktype", value_parser = parse_linktype)]
+    pub linktype: Option<Linktype>,
+
+    /// Display filter applied when the capture is opened
+    #[arg(short = 'Y', long = "filter")]
+    pub filter: Option<String>,
+
+    /// Packet number to select on start
+    #[arg(short = 's', long = "start-packet")]
+    pub start_packet: Option<usize>,
+
+    /// Time display format: absolute, utc, relative, delta or epoch
+    #[arg(short = 't', long = "time-format", value_parser = parse_time_format)]
+    pub time_format: Option<TimeFormat>,
+
+    /// Don't verify IPv4, TCP, UDP and ICMP checksums, for captures taken on a host that
+    /// offloads checksumming to its network card
+    #[arg(long = "no-checksum-validation")]
+    pub no_checksum_validation: bool,
+
+    /// Disable interactive editing (display filter bar, preference toggles)
+    #[arg(long = "read-only")]
+    pub read_only: bool,
+
+    /// Print packet summaries to stdout instead of starting the TUI
+    #[arg(long = "headless")]
+    pub headless: bool,
+}
+
+fn parse_linktype(s: &str) -> Result<Linktype, String> {
+    let linktype = match s.to_ascii_lowercase().as_str() {
+        "null" => Linktype::NULL,
+        "ethernet" | "eth" => Linktype::ETHERNET,
+        "raw" => Linktype::RAW,
+        "loop" => Linktype::LOOP,
+        "linux_sll" | "sll" => Linktype::LINUX_SLL,
+        "ipv4" => Linktype::IPV4,
+        "ipv6" => Linktype::IPV6,
+        other => match other.parse::<i32>() {
+            Ok(num) if num >= 0 => Linktype(num),
+            _ => return Err(format!("unknown link type '{}'", s)),
+        },
+    };
+    Ok(linktype)
+}
+
+fn parse_time_format(s: &str) -> Result<TimeFormat, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "absolute" | "a" => Ok(TimeFormat::Absolute),
+        "utc" | "u" => Ok(TimeFormat::Utc),
+        "relative" | "r" => Ok(TimeFormat::Relative),
+        "delta" | "d" => Ok(TimeFormat::Delta),
+        "epoch" | "e" => Ok(TimeFormat::Epoch),
+        _ => Err(format!("unknown time format '{}'", s)),
+    }
+}
@@ -0,0 +1,312 @@
+// Conversations and Endpoints pane state. One table per layer, whose rows can be sorted by any
+//   column and turned into a display filter.
+
+use core::fmt;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use tui::widgets::TableState;
+
+use crate::pkt::conversation::{self, ConvType, Conversation, Endpoint, EndpointStats};
+use crate::pkt::timestamp::{self, Timestamp};
+use crate::pkt::Packet;
+
+/// Whether the tables list pairs of endpoints or single ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsKind {
+    Conversations,
+    Endpoints,
+}
+
+impl fmt::Display for StatsKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsKind::Conversations => write!(f, "Conversations"),
+            StatsKind::Endpoints => write!(f, "Endpoints"),
+        }
+    }
+}
+
+/// What a column sorts by: addresses in numeric order where they are IP addresses, and
+/// counts and times as numbers
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Ip(IpAddr),
+    Num(i128),
+}
+
+/// One cell of a table, as shown and as sorted
+struct Cell {
+    text: String,
+    key: SortKey,
+}
+
+impl Cell {
+    fn num<N: Into<i128> + ToString + Copy>(n: N) -> Self {
+        Cell {
+            text: n.to_string(),
+            key: SortKey::Num(n.into()),
+        }
+    }
+
+    fn address(address: &str) -> Self {
+        Cell {
+            text: address.to_string(),
+            key: match address.parse() {
+                Ok(ip) => SortKey::Ip(ip),
+                Err(_) => SortKey::Text(address.to_string()),
+            },
+        }
+    }
+
+    fn port(port: Option<u16>) -> Self {
+        Cell::num(port.unwrap_or_default())
+    }
+
+    fn seconds(nanos: i128, digits: u8) -> Self {
+        Cell {
+            text: timestamp::format_interval(nanos, digits),
+            key: SortKey::Num(nanos),
+        }
+    }
+}
+
+/// A table row and the display filter for it
+pub struct Row {
+    pub cells: Vec<String>,
+    pub filter: String,
+}
+
+pub struct ConversationsView {
+    conversations: HashMap<ConvType, Vec<Conversation>>,
+    /// Time of the first packet, which start times are relative to
+    first: Timestamp,
+    pub conv_type: ConvType,
+    pub kind: StatsKind,
+    /// Column the rows are sorted by and whether highest first, or none to keep them in order
+    ///   of their first packet
+    pub sort: Option<(usize, bool)>,
+    pub state: TableState,
+}
+
+impl ConversationsView {
+    pub fn new(pkts: &[Packet]) -> Self {
+        let mut view = ConversationsView {
+            conversations: conversation::conversations(pkts),
+            first: pkts.first().map(|p| p.timestamp).unwrap_or_default(),
+            conv_type: ConvType::Ethernet,
+            kind: StatsKind::Conversations,
+            sort: None,
+            state: TableState::default(),
+        };
+        if let Some(conv_type) = ConvType::ALL.into_iter().find(|t| view.count(*t) > 0) {
+            view.conv_type = conv_type;
+        }
+        view.state.select(Some(0));
+        view
+    }
+
+    fn table(&self, conv_type: ConvType) -> &[Conversation] {
+        self.conversations
+            .get(&conv_type)
+            .map_or(&[], |table| table.as_slice())
+    }
+
+    /// Rows in the table of `conv_type`
+    pub fn count(&self, conv_type: ConvType) -> usize {
+        match self.kind {
+            StatsKind::Conversations => self.table(conv_type).len(),
+            StatsKind::Endpoints => conversation::endpoints(self.table(conv_type)).len(),
+        }
+    }
+
+    pub fn headers(&self) -> Vec<&'static str> {
+        let ports = self.conv_type.has_ports();
+        let mut headers = vec![];
+        match self.kind {
+            StatsKind::Conversations => {
+                headers.push("Address A");
+                if ports {
+                    headers.push("Port A");
+                }
+                headers.push("Address B");
+                if ports {
+                    headers.push("Port B");
+                }
+                headers.extend([
+                    "Packets",
+                    "Bytes",
+                    "Packets A\u{2192}B",
+                    "Bytes A\u{2192}B",
+                    "Packets B\u{2192}A",
+                    "Bytes B\u{2192}A",
+                    "Rel Start",
+                    "Duration",
+                ]);
+            }
+            StatsKind::Endpoints => {
+                headers.push("Address");
+                if ports {
+                    headers.push("Port");
+                }
+                headers.extend([
+                    "Packets",
+                    "Bytes",
+                    "Tx Packets",
+                    "Tx Bytes",
+                    "Rx Packets",
+                    "Rx Bytes",
+                ]);
+            }
+        }
+        headers
+    }
+
+    fn endpoint_cells(&self, endpoint: &Endpoint) -> Vec<Cell> {
+        let mut cells = vec![Cell::address(&endpoint.address)];
+        if self.conv_type.has_ports() {
+            cells.push(Cell::port(endpoint.port));
+        }
+        cells
+    }
+
+    fn conversation_cells(&self, conversation: &Conversation) -> Vec<Cell> {
+        let total = conversation.total();
+        let mut cells = self.endpoint_cells(&conversation.a);
+        cells.extend(self.endpoint_cells(&conversation.b));
+        cells.extend([
+            Cell::num(total.packets as u64),
+            Cell::num(total.bytes as u64),
+            Cell::num(conversation.a_to_b.packets as u64),
+            Cell::num(conversation.a_to_b.bytes as u64),
+            Cell::num(conversation.b_to_a.packets as u64),
+            Cell::num(conversation.b_to_a.bytes as u64),
+            Cell::seconds(
+                conversation.start.nanos_since(&self.first),
+                conversation.start.digits,
+            ),
+            Cell::seconds(
+                conversation.end.nanos_since(&conversation.start),
+                conversation.start.digits,
+            ),
+        ]);
+        cells
+    }
+
+    fn endpoint_stats_cells(&self, stats: &EndpointStats) -> Vec<Cell> {
+        let total = stats.total();
+        let mut cells = self.endpoint_cells(&stats.endpoint);
+        cells.extend([
+            Cell::num(total.packets as u64),
+            Cell::num(total.bytes as u64),
+            Cell::num(stats.tx.packets as u64),
+            Cell::num(stats.tx.bytes as u64),
+            Cell::num(stats.rx.packets as u64),
+            Cell::num(stats.rx.bytes as u64),
+        ]);
+        cells
+    }
+
+    /// Rows of the current table, in the current order
+    pub fn rows(&self) -> Vec<Row> {
+        let table = self.table(self.conv_type);
+        let mut rows: Vec<(Vec<Cell>, String)> = match self.kind {
+            StatsKind::Conversations => table
+                .iter()
+                .map(|c| (self.conversation_cells(c), c.filter(self.conv_type)))
+                .collect(),
+            StatsKind::Endpoints => conversation::endpoints(table)
+                .iter()
+                .map(|e| (self.endpoint_stats_cells(e), e.filter(self.conv_type)))
+                .collect(),
+        };
+        if let Some((column, descending)) = self.sort {
+            rows.sort_by(|(a, _), (b, _)| {
+                let order = match (a.get(column), b.get(column)) {
+                    (Some(a), Some(b)) => a.key.cmp(&b.key),
+                    _ => Ordering::Equal,
+                };
+                if descending {
+                    order.reverse()
+                } else {
+                    order
+                }
+            });
+        }
+        rows.into_iter()
+            .map(|(cells, filter)| Row {
+                cells: cells.into_iter().map(|c| c.text).collect(),
+                filter,
+            })
+            .collect()
+    }
+
+    /// Shows the next or previous layer's table
+    pub fn step_type(&mut self, forward: bool) {
+        let types = ConvType::ALL;
+        let idx = types.iter().position(|t| *t == self.conv_type).unwrap_or(0);
+        let idx = if forward {
+            (idx + 1) % types.len()
+        } else {
+            (idx + types.len() - 1) % types.len()
+        };
+        self.conv_type = types[idx];
+        self.reset();
+    }
+
+    pub fn toggle_kind(&mut self) {
+        self.kind = match self.kind {
+            StatsKind::Conversations => StatsKind::Endpoints,
+            StatsKind::Endpoints => StatsKind::Conversations,
+        };
+        self.reset();
+    }
+
+    /// Sorts by the next column, after the last going back to the order of first packets.
+    /// Counts sort highest first, and everything else lowest first.
+    pub fn next_sort(&mut self) {
+        let headers = self.headers();
+        let column = match self.sort {
+            None => 0,
+            Some((column, _)) if column + 1 < headers.len() => column + 1,
+            Some(_) => {
+                self.sort = None;
+                return;
+            }
+        };
+        let descending = !(headers[column].starts_with("Address")
+            || headers[column].starts_with("Port")
+            || headers[column] == "Rel Start");
+        self.sort = Some((column, descending));
+    }
+
+    pub fn reverse_sort(&mut self) {
+        if let Some((_, descending)) = &mut self.sort {
+            *descending = !*descending;
+        }
+    }
+
+    /// Moves the selection by `delta` rows, staying within the table
+    pub fn move_selection(&mut self, delta: isize) {
+        let count = self.count(self.conv_type);
+        if count == 0 {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or(0) as isize;
+        let row = (selected + delta).clamp(0, count as isize - 1);
+        self.state.select(Some(row as usize));
+    }
+
+    /// Display filter for the selected row
+    pub fn selected_filter(&self) -> Option<String> {
+        let selected = self.state.selected()?;
+        self.rows().into_iter().nth(selected).map(|row| row.filter)
+    }
+
+    /// Goes back to the first row and the order of first packets, as when the table is new
+    fn reset(&mut self) {
+        self.sort = None;
+        self.state.select(Some(0));
+    }
+}
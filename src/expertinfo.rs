@@ -0,0 +1,98 @@
+// Expert Info pane state. Every expert info item of the displayed packets, grouped by what it
+//   says, most severe first, with the packets that raised each group under it.
+
+use tui_tree_widget::TreeItem;
+
+use crate::pkt::expert::ExpertInfo;
+use crate::pkt::Packet;
+use crate::statefultree::StatefulTree;
+
+/// Packets that raised the same expert info
+struct ExpertGroup {
+    info: ExpertInfo,
+    /// Index and info column of each packet, in capture order
+    packets: Vec<(usize, String)>,
+}
+
+pub struct ExpertView<'a> {
+    groups: Vec<ExpertGroup>,
+    pub tree: StatefulTree<'a>,
+}
+
+impl<'a> ExpertView<'a> {
+    /// Gathers the expert info of `pkts` at the indices in `displayed`
+    pub fn new(pkts: &[Packet], displayed: &[usize]) -> Self {
+        let mut groups: Vec<ExpertGroup> = vec![];
+        for &idx in displayed {
+            let pkt = &pkts[idx];
+            let infos = pkt.expert_infos();
+            if infos.is_empty() {
+                continue;
+            }
+            let (_, _, _, summary) = pkt.columns();
+            for info in infos {
+                let entry = (idx, format!("Frame {}: {}", pkt.num, summary));
+                match groups.iter_mut().find(|g| g.info == info) {
+                    Some(group) => {
+                        if group.packets.last().map(|(last, _)| *last) != Some(idx) {
+                            group.packets.push(entry);
+                        }
+                    }
+                    None => groups.push(ExpertGroup {
+                        info,
+                        packets: vec![entry],
+                    }),
+                }
+            }
+        }
+        groups.sort_by(|a, b| {
+            b.info
+                .severity
+                .cmp(&a.info.severity)
+                .then_with(|| a.info.group.cmp(b.info.group))
+                .then_with(|| a.info.protocol.cmp(&b.info.protocol))
+                .then_with(|| a.info.summary.cmp(&b.info.summary))
+        });
+
+        let items = groups
+            .iter()
+            .map(|group| {
+                let info = &group.info;
+                let text = format!(
+                    "{:<8} {:<10} {:<10} {} ({})",
+                    info.severity.to_string(),
+                    info.group,
+                    info.protocol,
+                    info.summary,
+                    group.packets.len()
+                );
+                let children: Vec<TreeItem> = group
+                    .packets
+                    .iter()
+                    .map(|(_, text)| TreeItem::new_leaf(text.clone()))
+                    .collect();
+                TreeItem::new(text, children).style(info.severity.style())
+            })
+            .collect();
+        let mut tree = StatefulTree::with_items(items);
+        tree.first();
+        ExpertView { groups, tree }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Index of the packet under the cursor, when it is on a packet rather than a group
+    pub fn selected_packet(&self) -> Option<usize> {
+        match self.tree.state.selected()[..] {
+            [group, packet] => self
+                .groups
+                .get(group)?
+                .packets
+                .get(packet)
+                .map(|(idx, _)| *idx),
+            _ => None,
+        }
+    }
+}
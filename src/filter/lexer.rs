@@ -0,0 +1,241 @@
+use crate::filter::FilterError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    And,
+    Or,
+    Not,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    In,
+    /// Field name or unquoted literal: numbers, addresses, byte strings
+    Word(String),
+    /// Double-quoted string literal
+    Quoted(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Character offset of the token in the filter text
+    pub pos: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-' | '/')
+}
+
+/// Splits filter text into tokens
+pub fn tokenize(text: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let pos = i;
+
+        let (kind, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (TokenKind::LParen, 1),
+            (')', _) => (TokenKind::RParen, 1),
+            ('{', _) => (TokenKind::LBrace, 1),
+            ('}', _) => (TokenKind::RBrace, 1),
+            (',', _) => (TokenKind::Comma, 1),
+            ('&', Some('&')) => (TokenKind::And, 2),
+            ('|', Some('|')) => (TokenKind::Or, 2),
+            ('=', Some('=')) => (TokenKind::Eq, 2),
+            ('!', Some('=')) => (TokenKind::Ne, 2),
+            ('>', Some('=')) => (TokenKind::Ge, 2),
+            ('<', Some('=')) => (TokenKind::Le, 2),
+            ('!', _) => (TokenKind::Not, 1),
+            ('>', _) => (TokenKind::Gt, 1),
+            ('<', _) => (TokenKind::Lt, 1),
+            ('"', _) => {
+                let mut value = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(FilterError::new("unterminated string", pos)),
+                        Some('"') => break,
+                        Some('\\') if j + 1 < chars.len() => {
+                            value.push(chars[j + 1]);
+                            j += 2;
+                        }
+                        Some(ch) => {
+                            value.push(*ch);
+                            j += 1;
+                        }
+                    }
+                }
+                (TokenKind::Quoted(value), j + 1 - i)
+            }
+            (c, _) if is_word_char(c) => {
+                let mut j = i;
+                while j < chars.len() && is_word_char(chars[j]) {
+                    j += 1;
+                }
+                let word: String = chars[i..j].iter().collect();
+                let kind = match word.as_str() {
+                    "and" => TokenKind::And,
+                    "or" => TokenKind::Or,
+                    "not" => TokenKind::Not,
+                    "eq" => TokenKind::Eq,
+                    "ne" => TokenKind::Ne,
+                    "gt" => TokenKind::Gt,
+                    "ge" => TokenKind::Ge,
+                    "lt" => TokenKind::Lt,
+                    "le" => TokenKind::Le,
+                    "contains" => TokenKind::Contains,
+                    "in" => TokenKind::In,
+                    _ => TokenKind::Word(word),
+                };
+                (kind, j - i)
+            }
+            (c, _) => {
+                return Err(FilterError::new(
+                    format!("unexpected character '{}'", c),
+                    pos,
+                ))
+            }
+        };
+
+        tokens.push(Token { kind, pos });
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<TokenKind> {
+        tokenize(text)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    fn word(text: &str) -> TokenKind {
+        TokenKind::Word(text.to_string())
+    }
+
+    #[test]
+    fn symbols_and_words_are_split_apart() {
+        assert_eq!(
+            kinds("!(tcp.port>=502||ip.src!=10.0.0.0/8)"),
+            vec![
+                TokenKind::Not,
+                TokenKind::LParen,
+                word("tcp.port"),
+                TokenKind::Ge,
+                word("502"),
+                TokenKind::Or,
+                word("ip.src"),
+                TokenKind::Ne,
+                word("10.0.0.0/8"),
+                TokenKind::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords_stand_for_operators() {
+        assert_eq!(
+            kinds("not a and b or c eq d ne e gt f ge g lt h le i contains j in k"),
+            vec![
+                TokenKind::Not,
+                word("a"),
+                TokenKind::And,
+                word("b"),
+                TokenKind::Or,
+                word("c"),
+                TokenKind::Eq,
+                word("d"),
+                TokenKind::Ne,
+                word("e"),
+                TokenKind::Gt,
+                word("f"),
+                TokenKind::Ge,
+                word("g"),
+                TokenKind::Lt,
+                word("h"),
+                TokenKind::Le,
+                word("i"),
+                TokenKind::Contains,
+                word("j"),
+                TokenKind::In,
+                word("k"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sets_and_ranges() {
+        assert_eq!(
+            kinds("tcp.port in {502, 20000..20010}"),
+            vec![
+                word("tcp.port"),
+                TokenKind::In,
+                TokenKind::LBrace,
+                word("502"),
+                TokenKind::Comma,
+                word("20000..20010"),
+                TokenKind::RBrace,
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_strings_keep_spaces_and_escapes() {
+        assert_eq!(
+            kinds(r#"x contains "a \"b\" c\\""#),
+            vec![
+                word("x"),
+                TokenKind::Contains,
+                TokenKind::Quoted(r#"a "b" c\"#.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_carry_their_character_offset() {
+        let positions: Vec<usize> = tokenize("  ip.src ==\t\"é\" && x")
+            .unwrap()
+            .iter()
+            .map(|t| t.pos)
+            .collect();
+        assert_eq!(positions, vec![2, 9, 12, 16, 19]);
+    }
+
+    #[test]
+    fn unterminated_string_is_reported_where_it_starts() {
+        let err = tokenize(r#"x == "abc"#).unwrap_err();
+        assert_eq!(err.message, "unterminated string");
+        assert_eq!(err.pos, 5);
+    }
+
+    #[test]
+    fn unexpected_character_is_reported_where_it_is() {
+        let err = tokenize("a == 1 & b").unwrap_err();
+        assert_eq!(err.message, "unexpected character '&'");
+        assert_eq!(err.pos, 7);
+    }
+}
@@ -0,0 +1,338 @@
+// Display filters: a Wireshark-like expression language over the fields each dissector exposes,
+//   e.g. `ip.src == 10.0.0.5 && tcp.port in {502 20000..20010} && !modbus.exception`
+
+use core::fmt;
+use std::cmp::Ordering;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::pkt::field::{Field, FieldKind, FieldValue};
+use crate::pkt::registry::Registry;
+use crate::pkt::Packet;
+
+mod lexer;
+mod parser;
+
+use parser::{CmpOp, Expr, Literal, SetItem};
+
+/// What a name used in a filter refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameKind {
+    /// A protocol, under the name its layers carry
+    Protocol(&'static str),
+    Field(FieldKind),
+}
+
+/// A syntax or type error in filter text
+#[derive(Clone, Debug)]
+pub struct FilterError {
+    pub message: String,
+    /// Character offset in the filter text the error was found at
+    pub pos: usize,
+}
+
+impl FilterError {
+    pub fn new<S: Into<String>>(message: S, pos: usize) -> Self {
+        FilterError {
+            message: message.into(),
+            pos,
+        }
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.pos + 1)
+    }
+}
+
+/// A compiled display filter
+#[derive(Clone, Debug)]
+pub struct Filter {
+    pub text: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses `text`, checking every name against the fields and protocols in `registry`
+    pub fn compile(text: &str, registry: &Registry) -> Result<Self, FilterError> {
+        let tokens = lexer::tokenize(text)?;
+        let lookup = |name: &str| registry.lookup_name(name);
+        let expr = parser::Parser::new(tokens, text.chars().count(), &lookup).parse()?;
+        Ok(Filter {
+            text: text.to_string(),
+            expr,
+        })
+    }
+
+    pub fn matches(&self, pkt: &Packet) -> bool {
+        let fields = pkt.fields();
+        let protocols: Vec<&str> = pkt.layers.iter().map(|l| l.name()).collect();
+        eval(&self.expr, &fields, &protocols)
+    }
+}
+
+fn values<'f>(fields: &'f [Field], name: &'f str) -> impl Iterator<Item = &'f FieldValue> {
+    fields
+        .iter()
+        .filter(move |(n, _)| *n == name)
+        .map(|(_, v)| v)
+}
+
+fn eval(expr: &Expr, fields: &[Field], protocols: &[&str]) -> bool {
+    match expr {
+        Expr::And(lhs, rhs) => eval(lhs, fields, protocols) && eval(rhs, fields, protocols),
+        Expr::Or(lhs, rhs) => eval(lhs, fields, protocols) || eval(rhs, fields, protocols),
+        Expr::Not(inner) => !eval(inner, fields, protocols),
+        Expr::Exists(name) => {
+            protocols.contains(&name.as_str()) || values(fields, name).next().is_some()
+        }
+        // "!=" holds when no occurrence is equal, so `ip.addr != 10.0.0.1` excludes packets
+        //   with that address on either side
+        Expr::Compare(name, CmpOp::Ne, literal) => {
+            !values(fields, name).any(|v| compare(v, CmpOp::Eq, literal))
+        }
+        Expr::Compare(name, op, literal) => values(fields, name).any(|v| compare(v, *op, literal)),
+        Expr::In(name, set) => values(fields, name).any(|v| {
+            set.iter().any(|item| match (item, v) {
+                (SetItem::One(literal), _) => compare(v, CmpOp::Eq, literal),
+                (SetItem::Range(low, high), FieldValue::UInt(v)) => low <= v && v <= high,
+                _ => false,
+            })
+        }),
+    }
+}
+
+fn in_ipv4_net(addr: &Ipv4Addr, net: &Ipv4Addr, prefix: u8) -> bool {
+    let mask = if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix as u32)
+    };
+    u32::from(*addr) & mask == u32::from(*net) & mask
+}
+
+fn in_ipv6_net(addr: &Ipv6Addr, net: &Ipv6Addr, prefix: u8) -> bool {
+    let mask = if prefix == 0 {
+        0
+    } else {
+        u128::MAX << (128 - prefix as u32)
+    };
+    u128::from(*addr) & mask == u128::from(*net) & mask
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn ordering(value: &FieldValue, other: &FieldValue) -> Option<Ordering> {
+    match (value, other) {
+        (FieldValue::UInt(a), FieldValue::UInt(b)) => Some(a.cmp(b)),
+        (FieldValue::Bool(a), FieldValue::Bool(b)) => Some(a.cmp(b)),
+        (FieldValue::Ipv4(a), FieldValue::Ipv4(b)) => Some(a.cmp(b)),
+        (FieldValue::Ipv6(a), FieldValue::Ipv6(b)) => Some(a.cmp(b)),
+        (FieldValue::Ether(a), FieldValue::Ether(b)) => Some(a.cmp(b)),
+        (FieldValue::Bytes(a), FieldValue::Bytes(b)) => Some(a.cmp(b)),
+        (FieldValue::Str(a), FieldValue::Str(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn compare(value: &FieldValue, op: CmpOp, literal: &Literal) -> bool {
+    let other = match literal {
+        Literal::Value(other) => other,
+        Literal::Ipv4Net(net, prefix) => {
+            return match value {
+                FieldValue::Ipv4(addr) => op == CmpOp::Eq && in_ipv4_net(addr, net, *prefix),
+                _ => false,
+            }
+        }
+        Literal::Ipv6Net(net, prefix) => {
+            return match value {
+                FieldValue::Ipv6(addr) => op == CmpOp::Eq && in_ipv6_net(addr, net, *prefix),
+                _ => false,
+            }
+        }
+    };
+
+    if op == CmpOp::Contains {
+        return match (value, other) {
+            (FieldValue::Str(a), FieldValue::Str(b)) => a.contains(b.as_str()),
+            (FieldValue::Bytes(a), FieldValue::Bytes(b)) => contains(a, b),
+            _ => false,
+        };
+    }
+
+    match ordering(value, other) {
+        Some(ord) => match op {
+            CmpOp::Eq => ord == Ordering::Equal,
+            CmpOp::Ne => ord != Ordering::Equal,
+            CmpOp::Gt => ord == Ordering::Greater,
+            CmpOp::Ge => ord != Ordering::Less,
+            CmpOp::Lt => ord == Ordering::Less,
+            CmpOp::Le => ord != Ordering::Greater,
+            CmpOp::Contains => false,
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `text` matches a packet with `fields` and the layers named in `protocols`
+    fn matches(text: &str, fields: &[Field], protocols: &[&str]) -> bool {
+        let registry = Registry::with_all_dissectors();
+        let filter = Filter::compile(text, &registry).unwrap();
+        eval(&filter.expr, fields, protocols)
+    }
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> FieldValue {
+        FieldValue::Ipv4(Ipv4Addr::new(a, b, c, d))
+    }
+
+    /// A Modbus/TCP response from 10.0.0.5:502 to 10.0.0.1:40000
+    fn modbus_response(exception: Option<u8>) -> (Vec<Field>, Vec<&'static str>) {
+        let mut fields: Vec<Field> = vec![
+            ("ip.src", ipv4(10, 0, 0, 5)),
+            ("ip.addr", ipv4(10, 0, 0, 5)),
+            ("ip.dst", ipv4(10, 0, 0, 1)),
+            ("ip.addr", ipv4(10, 0, 0, 1)),
+            ("tcp.srcport", 502u16.into()),
+            ("tcp.port", 502u16.into()),
+            ("tcp.dstport", 40000u16.into()),
+            ("tcp.port", 40000u16.into()),
+            ("modbus.func_code", 3u8.into()),
+            ("modbus.request", false.into()),
+        ];
+        if let Some(code) = exception {
+            fields.push(("modbus.exception", true.into()));
+            fields.push(("modbus.exception_code", code.into()));
+        }
+        (fields, vec!["eth", "ip", "tcp", "mbtcp"])
+    }
+
+    #[test]
+    fn backlog_example() {
+        let text = "ip.src == 10.0.0.5 && tcp.port in {502 20000..20010} && !modbus.exception";
+        let (fields, protocols) = modbus_response(None);
+        assert!(matches(text, &fields, &protocols));
+        let (fields, protocols) = modbus_response(Some(2));
+        assert!(!matches(text, &fields, &protocols));
+    }
+
+    #[test]
+    fn review_example() {
+        let text = "ip.src == 10.0.0.5 && tcp.port == 502 && !modbus.exception";
+        let (fields, protocols) = modbus_response(None);
+        assert!(matches(text, &fields, &protocols));
+        let (fields, protocols) = modbus_response(Some(2));
+        assert!(!matches(text, &fields, &protocols));
+    }
+
+    #[test]
+    fn protocols_match_by_name_or_alias() {
+        let (fields, protocols) = modbus_response(None);
+        assert!(matches("modbus", &fields, &protocols));
+        assert!(matches("mbtcp && tcp", &fields, &protocols));
+        assert!(!matches("udp", &fields, &protocols));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let (fields, protocols) = modbus_response(None);
+        // Read as `tcp || (udp && arp)`, not `(tcp || udp) && arp`
+        assert!(matches("tcp || udp && arp", &fields, &protocols));
+        assert!(!matches("(tcp || udp) && arp", &fields, &protocols));
+        assert!(!matches("!tcp || udp", &fields, &protocols));
+    }
+
+    #[test]
+    fn eq_holds_when_any_occurrence_is_equal() {
+        let (fields, protocols) = modbus_response(None);
+        assert!(matches("ip.addr == 10.0.0.1", &fields, &protocols));
+        assert!(matches("ip.addr == 10.0.0.5", &fields, &protocols));
+        assert!(matches("tcp.port > 1024", &fields, &protocols));
+    }
+
+    #[test]
+    fn ne_holds_when_no_occurrence_is_equal() {
+        let (fields, protocols) = modbus_response(None);
+        assert!(!matches("ip.addr != 10.0.0.1", &fields, &protocols));
+        assert!(!matches("tcp.port != 40000", &fields, &protocols));
+        assert!(matches("ip.addr != 10.0.0.9", &fields, &protocols));
+        // A field the packet doesn't have has no occurrence equal to anything
+        assert!(matches("udp.port != 53", &fields, &protocols));
+    }
+
+    #[test]
+    fn in_matches_values_and_inclusive_ranges() {
+        let fields: Vec<Field> = vec![("tcp.port", 20010u16.into())];
+        assert!(matches("tcp.port in {502 20000..20010}", &fields, &[]));
+        assert!(matches("tcp.port in {20010..20010}", &fields, &[]));
+        assert!(!matches("tcp.port in {502, 20000..20009}", &fields, &[]));
+        assert!(matches("tcp.port in {20010, 502}", &fields, &[]));
+        assert!(!matches("tcp.port in {502}", &[], &[]));
+    }
+
+    #[test]
+    fn cidr_literals_match_whole_subnets() {
+        let fields: Vec<Field> = vec![("ip.src", ipv4(10, 1, 2, 3))];
+        assert!(matches("ip.src == 10.0.0.0/8", &fields, &[]));
+        assert!(matches("ip.src == 10.1.2.3/32", &fields, &[]));
+        assert!(matches("ip.src == 192.168.0.0/0", &fields, &[]));
+        assert!(!matches("ip.src == 10.1.2.0/31", &fields, &[]));
+        assert!(!matches("ip.src == 11.0.0.0/8", &fields, &[]));
+        assert!(matches(
+            "ip.src in {192.168.0.0/16 10.1.0.0/16}",
+            &fields,
+            &[]
+        ));
+        assert!(!matches("ip.src != 10.0.0.0/8", &fields, &[]));
+
+        let addr: Ipv6Addr = "fe80::1".parse().unwrap();
+        let fields: Vec<Field> = vec![("ipv6.src", FieldValue::Ipv6(addr))];
+        assert!(matches("ipv6.src == fe80::/10", &fields, &[]));
+        assert!(matches("ipv6.src == fe80::1/128", &fields, &[]));
+        assert!(!matches("ipv6.src == fe80::2/127", &fields, &[]));
+    }
+
+    #[test]
+    fn cidr_prefixes_past_the_address_length_are_rejected() {
+        let registry = Registry::with_all_dissectors();
+        assert!(Filter::compile("ip.src == 10.0.0.0/33", &registry).is_err());
+        assert!(Filter::compile("ipv6.src == ::/129", &registry).is_err());
+    }
+
+    #[test]
+    fn quoted_strings_match_byte_fields() {
+        let fields: Vec<Field> = vec![("modbus.data", FieldValue::Bytes(b"\x00ABC".to_vec()))];
+        assert!(matches(r#"modbus.data contains "AB""#, &fields, &[]));
+        assert!(matches("modbus.data contains 42:43", &fields, &[]));
+        assert!(!matches(r#"modbus.data contains "ABD""#, &fields, &[]));
+        assert!(matches("modbus.data == 00:41:42:43", &fields, &[]));
+    }
+
+    #[test]
+    fn bool_fields_exist_only_when_set() {
+        let (fields, protocols) = modbus_response(Some(2));
+        assert!(matches("modbus.exception", &fields, &protocols));
+        assert!(matches("modbus.exception == 1", &fields, &protocols));
+        assert!(matches("modbus.exception_code == 2", &fields, &protocols));
+        let (fields, protocols) = modbus_response(None);
+        assert!(!matches("modbus.exception", &fields, &protocols));
+    }
+
+    #[test]
+    fn errors_are_reported_at_their_column() {
+        let registry = Registry::with_all_dissectors();
+        let err = Filter::compile("ip.src == 10.0.0.5 && modbus.exceptoin", &registry).unwrap_err();
+        assert_eq!(err.pos, 22);
+        assert_eq!(
+            err.to_string(),
+            "\"modbus.exceptoin\" is neither a field nor a protocol name (at column 23)"
+        );
+        let err = Filter::compile("tcp.port == 502 &", &registry).unwrap_err();
+        assert_eq!(err.pos, 16);
+    }
+}
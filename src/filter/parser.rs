@@ -0,0 +1,593 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::filter::lexer::{Token, TokenKind};
+use crate::filter::{FilterError, NameKind};
+use crate::pkt::field::{FieldKind, FieldValue};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+}
+
+/// A filter literal, already converted to the type of the field it is compared against
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Literal {
+    Value(FieldValue),
+    /// Address with a prefix length, matching a whole subnet
+    Ipv4Net(Ipv4Addr, u8),
+    Ipv6Net(Ipv6Addr, u8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetItem {
+    One(Literal),
+    /// Inclusive range of integers, written `low..high`
+    Range(u64, u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// Bare field or protocol name: true when present in the packet
+    Exists(String),
+    Compare(String, CmpOp, Literal),
+    In(String, Vec<SetItem>),
+}
+
+fn parse_uint(text: &str) -> Option<u64> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse::<u64>().ok()
+    }
+}
+
+/// Parses hex bytes separated by ':', '-' or '.', e.g. `00:11:22` or `0a-0b`
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    let parts: Vec<&str> = text.split([':', '-', '.']).collect();
+    if parts.iter().any(|p| p.is_empty() || p.len() > 2) {
+        return None;
+    }
+    parts
+        .iter()
+        .map(|p| u8::from_str_radix(p, 16).ok())
+        .collect()
+}
+
+fn split_prefix(text: &str) -> (&str, Option<&str>) {
+    match text.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (text, None),
+    }
+}
+
+/// Converts the literal `text` to a value of `kind`. `quoted` literals are always strings, and
+/// can also be matched against byte fields.
+fn parse_literal(kind: FieldKind, text: &str, quoted: bool) -> Option<Literal> {
+    let value = match (kind, quoted) {
+        (FieldKind::Str, _) => FieldValue::Str(text.to_string()),
+        (FieldKind::Bytes, true) => FieldValue::Bytes(text.as_bytes().to_vec()),
+        (_, true) => return None,
+        (FieldKind::UInt, false) => FieldValue::UInt(parse_uint(text)?),
+        (FieldKind::Bool, false) => match text {
+            "true" | "1" => FieldValue::Bool(true),
+            "false" | "0" => FieldValue::Bool(false),
+            _ => return None,
+        },
+        (FieldKind::Ipv4, false) => {
+            let (addr, prefix) = split_prefix(text);
+            let addr: Ipv4Addr = addr.parse().ok()?;
+            return match prefix {
+                Some(prefix) => {
+                    let prefix: u8 = prefix.parse().ok().filter(|p| *p <= 32)?;
+                    Some(Literal::Ipv4Net(addr, prefix))
+                }
+                None => Some(Literal::Value(FieldValue::Ipv4(addr))),
+            };
+        }
+        (FieldKind::Ipv6, false) => {
+            let (addr, prefix) = split_prefix(text);
+            let addr: Ipv6Addr = addr.parse().ok()?;
+            return match prefix {
+                Some(prefix) => {
+                    let prefix: u8 = prefix.parse().ok().filter(|p| *p <= 128)?;
+                    Some(Literal::Ipv6Net(addr, prefix))
+                }
+                None => Some(Literal::Value(FieldValue::Ipv6(addr))),
+            };
+        }
+        (FieldKind::Ether, false) => {
+            let bytes = parse_bytes(text).filter(|b| b.len() == 6)?;
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&bytes);
+            FieldValue::Ether(mac)
+        }
+        (FieldKind::Bytes, false) => FieldValue::Bytes(parse_bytes(text)?),
+    };
+    Some(Literal::Value(value))
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    idx: usize,
+    /// Length of the filter text, reported as the position of errors at its end
+    end: usize,
+    lookup: &'a dyn Fn(&str) -> Option<NameKind>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(
+        tokens: Vec<Token>,
+        end: usize,
+        lookup: &'a dyn Fn(&str) -> Option<NameKind>,
+    ) -> Self {
+        Parser {
+            tokens,
+            idx: 0,
+            end,
+            lookup,
+        }
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.idx).map(|t| &t.kind)
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens.get(self.idx).map(|t| t.pos).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<(), FilterError> {
+        if self.peek() == Some(&kind) {
+            self.idx += 1;
+            Ok(())
+        } else {
+            Err(FilterError::new(format!("expected {}", what), self.pos()))
+        }
+    }
+
+    /// Parses the whole token stream as one expression
+    pub fn parse(mut self) -> Result<Expr, FilterError> {
+        if self.tokens.is_empty() {
+            return Err(FilterError::new("empty filter", 0));
+        }
+        let expr = self.parse_or()?;
+        if self.idx < self.tokens.len() {
+            return Err(FilterError::new(
+                "unexpected token after expression",
+                self.pos(),
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&TokenKind::Or) {
+            self.idx += 1;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut lhs = self.parse_unary()?;
+        while self.peek() == Some(&TokenKind::And) {
+            self.idx += 1;
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, FilterError> {
+        if self.peek() == Some(&TokenKind::Not) {
+            self.idx += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, FilterError> {
+        let pos = self.pos();
+        let mut name = match self.next().map(|t| t.kind) {
+            Some(TokenKind::LParen) => {
+                let expr = self.parse_or()?;
+                self.expect(TokenKind::RParen, "')'")?;
+                return Ok(expr);
+            }
+            Some(TokenKind::Word(name)) => name,
+            Some(_) => return Err(FilterError::new("expected a field name", pos)),
+            None => return Err(FilterError::new("expected a field name", self.end)),
+        };
+
+        let kind = match (self.lookup)(&name) {
+            Some(NameKind::Field(kind)) => Some(kind),
+            Some(NameKind::Protocol(layer_name)) => {
+                name = layer_name.to_string();
+                None
+            }
+            None => {
+                return Err(FilterError::new(
+                    format!("\"{}\" is neither a field nor a protocol name", name),
+                    pos,
+                ))
+            }
+        };
+
+        let op = match self.peek() {
+            Some(TokenKind::Eq) => CmpOp::Eq,
+            Some(TokenKind::Ne) => CmpOp::Ne,
+            Some(TokenKind::Gt) => CmpOp::Gt,
+            Some(TokenKind::Ge) => CmpOp::Ge,
+            Some(TokenKind::Lt) => CmpOp::Lt,
+            Some(TokenKind::Le) => CmpOp::Le,
+            Some(TokenKind::Contains) => CmpOp::Contains,
+            Some(TokenKind::In) => {
+                self.idx += 1;
+                let kind = self.field_kind(&name, kind, pos)?;
+                return Ok(Expr::In(name, self.parse_set(kind)?));
+            }
+            _ => return Ok(Expr::Exists(name)),
+        };
+        self.idx += 1;
+
+        let kind = self.field_kind(&name, kind, pos)?;
+        let check_ordered = matches!(op, CmpOp::Gt | CmpOp::Ge | CmpOp::Lt | CmpOp::Le);
+        if check_ordered && kind == FieldKind::Bool {
+            return Err(FilterError::new(
+                format!("\"{}\" is a boolean and cannot be ordered", name),
+                pos,
+            ));
+        }
+        if op == CmpOp::Contains && !matches!(kind, FieldKind::Str | FieldKind::Bytes) {
+            return Err(FilterError::new(
+                format!(
+                    "\"{}\" is a {}; contains needs a string or bytes",
+                    name, kind
+                ),
+                pos,
+            ));
+        }
+        let literal = self.parse_value(kind)?;
+        Ok(Expr::Compare(name, op, literal))
+    }
+
+    fn field_kind(
+        &self,
+        name: &str,
+        kind: Option<FieldKind>,
+        pos: usize,
+    ) -> Result<FieldKind, FilterError> {
+        kind.ok_or_else(|| {
+            FilterError::new(
+                format!("\"{}\" is a protocol and cannot be compared", name),
+                pos,
+            )
+        })
+    }
+
+    fn parse_value(&mut self, kind: FieldKind) -> Result<Literal, FilterError> {
+        let pos = self.pos();
+        let (text, quoted) = match self.next().map(|t| t.kind) {
+            Some(TokenKind::Word(text)) => (text, false),
+            Some(TokenKind::Quoted(text)) => (text, true),
+            _ => return Err(FilterError::new("expected a value", pos.min(self.end))),
+        };
+        parse_literal(kind, &text, quoted)
+            .ok_or_else(|| FilterError::new(format!("\"{}\" is not a valid {}", text, kind), pos))
+    }
+
+    fn parse_set(&mut self, kind: FieldKind) -> Result<Vec<SetItem>, FilterError> {
+        self.expect(TokenKind::LBrace, "'{'")?;
+        let mut items = vec![];
+        loop {
+            match self.peek() {
+                Some(TokenKind::RBrace) => {
+                    self.idx += 1;
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.idx += 1;
+                }
+                Some(TokenKind::Word(text)) if kind == FieldKind::UInt && text.contains("..") => {
+                    let pos = self.pos();
+                    let text = text.clone();
+                    self.idx += 1;
+                    let (low, high) = text.split_once("..").unwrap();
+                    match (parse_uint(low), parse_uint(high)) {
+                        (Some(low), Some(high)) if low <= high => {
+                            items.push(SetItem::Range(low, high))
+                        }
+                        _ => {
+                            return Err(FilterError::new(
+                                format!("\"{}\" is not a valid range", text),
+                                pos,
+                            ))
+                        }
+                    }
+                }
+                Some(_) => items.push(SetItem::One(self.parse_value(kind)?)),
+                None => return Err(FilterError::new("expected '}'", self.end)),
+            }
+        }
+        if items.is_empty() {
+            return Err(FilterError::new("empty set", self.pos()));
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::lexer::tokenize;
+
+    fn lookup(name: &str) -> Option<NameKind> {
+        match name {
+            "tcp" => Some(NameKind::Protocol("tcp")),
+            "modbus" => Some(NameKind::Protocol("mbtcp")),
+            "tcp.port" | "modbus.func_code" => Some(NameKind::Field(FieldKind::UInt)),
+            "modbus.exception" => Some(NameKind::Field(FieldKind::Bool)),
+            "ip.src" => Some(NameKind::Field(FieldKind::Ipv4)),
+            "ipv6.src" => Some(NameKind::Field(FieldKind::Ipv6)),
+            "eth.src" => Some(NameKind::Field(FieldKind::Ether)),
+            "modbus.data" => Some(NameKind::Field(FieldKind::Bytes)),
+            "modbus.object_str_value" => Some(NameKind::Field(FieldKind::Str)),
+            _ => None,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Expr, FilterError> {
+        Parser::new(tokenize(text)?, text.chars().count(), &lookup).parse()
+    }
+
+    fn exists(name: &str) -> Box<Expr> {
+        Box::new(Expr::Exists(name.to_string()))
+    }
+
+    fn uint(name: &str, op: CmpOp, value: u64) -> Expr {
+        Expr::Compare(
+            name.to_string(),
+            op,
+            Literal::Value(FieldValue::UInt(value)),
+        )
+    }
+
+    /// Message and position of the error `text` fails with
+    fn error(text: &str) -> (String, usize) {
+        let err = parse(text).unwrap_err();
+        (err.message, err.pos)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("tcp || modbus && ip.src").unwrap(),
+            Expr::Or(
+                exists("tcp"),
+                Box::new(Expr::And(exists("mbtcp"), exists("ip.src")))
+            )
+        );
+        assert_eq!(
+            parse("(tcp || modbus) && ip.src").unwrap(),
+            Expr::And(
+                Box::new(Expr::Or(exists("tcp"), exists("mbtcp"))),
+                exists("ip.src")
+            )
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(
+            parse("!tcp && modbus").unwrap(),
+            Expr::And(Box::new(Expr::Not(exists("tcp"))), exists("mbtcp"))
+        );
+        assert_eq!(
+            parse("not not tcp").unwrap(),
+            Expr::Not(Box::new(Expr::Not(exists("tcp"))))
+        );
+    }
+
+    #[test]
+    fn operators_chain_to_the_left() {
+        assert_eq!(
+            parse("tcp and modbus and ip.src").unwrap(),
+            Expr::And(
+                Box::new(Expr::And(exists("tcp"), exists("mbtcp"))),
+                exists("ip.src")
+            )
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            parse("tcp.port >= 0x1f6").unwrap(),
+            uint("tcp.port", CmpOp::Ge, 502)
+        );
+        assert_eq!(
+            parse("modbus.exception == true").unwrap(),
+            Expr::Compare(
+                "modbus.exception".to_string(),
+                CmpOp::Eq,
+                Literal::Value(FieldValue::Bool(true))
+            )
+        );
+        assert_eq!(
+            parse("eth.src == 00:11:22:33:44:55").unwrap(),
+            Expr::Compare(
+                "eth.src".to_string(),
+                CmpOp::Eq,
+                Literal::Value(FieldValue::Ether([0, 0x11, 0x22, 0x33, 0x44, 0x55]))
+            )
+        );
+    }
+
+    #[test]
+    fn protocol_aliases_become_the_layer_name() {
+        assert_eq!(parse("modbus").unwrap(), Expr::Exists("mbtcp".to_string()));
+    }
+
+    #[test]
+    fn sets_hold_values_and_ranges() {
+        assert_eq!(
+            parse("tcp.port in {502, 20000..20010 0x10}").unwrap(),
+            Expr::In(
+                "tcp.port".to_string(),
+                vec![
+                    SetItem::One(Literal::Value(FieldValue::UInt(502))),
+                    SetItem::Range(20000, 20010),
+                    SetItem::One(Literal::Value(FieldValue::UInt(16))),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn ranges_must_be_ordered_and_numeric() {
+        assert_eq!(
+            error("tcp.port in {10..5}"),
+            ("\"10..5\" is not a valid range".to_string(), 13)
+        );
+        assert_eq!(
+            error("tcp.port in {1..x}"),
+            ("\"1..x\" is not a valid range".to_string(), 13)
+        );
+        assert_eq!(error("tcp.port in {}"), ("empty set".to_string(), 14));
+        assert_eq!(error("tcp.port in {1"), ("expected '}'".to_string(), 14));
+    }
+
+    #[test]
+    fn cidr_literals() {
+        assert_eq!(
+            parse("ip.src == 10.0.0.0/8").unwrap(),
+            Expr::Compare(
+                "ip.src".to_string(),
+                CmpOp::Eq,
+                Literal::Ipv4Net(Ipv4Addr::new(10, 0, 0, 0), 8)
+            )
+        );
+        assert_eq!(
+            parse("ipv6.src == fe80::/10").unwrap(),
+            Expr::Compare(
+                "ipv6.src".to_string(),
+                CmpOp::Eq,
+                Literal::Ipv6Net("fe80::".parse().unwrap(), 10)
+            )
+        );
+    }
+
+    #[test]
+    fn cidr_prefixes_are_limited_to_the_address_length() {
+        assert!(parse("ip.src == 10.0.0.0/32").is_ok());
+        assert_eq!(
+            error("ip.src == 10.0.0.0/33"),
+            (
+                "\"10.0.0.0/33\" is not a valid IPv4 address".to_string(),
+                10
+            )
+        );
+        assert!(parse("ipv6.src == ::/128").is_ok());
+        assert_eq!(
+            error("ipv6.src == ::/129"),
+            ("\"::/129\" is not a valid IPv6 address".to_string(), 12)
+        );
+    }
+
+    #[test]
+    fn quoted_strings_match_byte_fields() {
+        assert_eq!(
+            parse(r#"modbus.data contains "AB""#).unwrap(),
+            Expr::Compare(
+                "modbus.data".to_string(),
+                CmpOp::Contains,
+                Literal::Value(FieldValue::Bytes(b"AB".to_vec()))
+            )
+        );
+        assert_eq!(
+            parse("modbus.data contains 41:42").unwrap(),
+            Expr::Compare(
+                "modbus.data".to_string(),
+                CmpOp::Contains,
+                Literal::Value(FieldValue::Bytes(b"AB".to_vec()))
+            )
+        );
+        assert_eq!(
+            parse(r#"modbus.object_str_value == "tui shark""#).unwrap(),
+            Expr::Compare(
+                "modbus.object_str_value".to_string(),
+                CmpOp::Eq,
+                Literal::Value(FieldValue::Str("tui shark".to_string()))
+            )
+        );
+    }
+
+    #[test]
+    fn quoted_strings_do_not_match_other_fields() {
+        assert_eq!(
+            error(r#"tcp.port == "502""#),
+            ("\"502\" is not a valid unsigned integer".to_string(), 12)
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(
+            error("tcp && foo.bar"),
+            (
+                "\"foo.bar\" is neither a field nor a protocol name".to_string(),
+                7
+            )
+        );
+        assert_eq!(
+            error("tcp == 1"),
+            (
+                "\"tcp\" is a protocol and cannot be compared".to_string(),
+                0
+            )
+        );
+        assert_eq!(
+            error("tcp.port > x"),
+            ("\"x\" is not a valid unsigned integer".to_string(), 11)
+        );
+        assert_eq!(
+            error("modbus.exception < true"),
+            (
+                "\"modbus.exception\" is a boolean and cannot be ordered".to_string(),
+                0
+            )
+        );
+        assert_eq!(
+            error("tcp.port contains 1"),
+            (
+                "\"tcp.port\" is a unsigned integer; contains needs a string or bytes".to_string(),
+                0
+            )
+        );
+        assert_eq!(error("(tcp"), ("expected ')'".to_string(), 4));
+        assert_eq!(
+            error("tcp modbus"),
+            ("unexpected token after expression".to_string(), 4)
+        );
+        assert_eq!(error("tcp &&"), ("expected a field name".to_string(), 6));
+        assert_eq!(error("tcp.port =="), ("expected a value".to_string(), 11));
+        assert_eq!(error(""), ("empty filter".to_string(), 0));
+    }
+}
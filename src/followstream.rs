@@ -0,0 +1,223 @@
+// Follow Stream pane state. The stream's payload is shown as one conversation in one of several
+//   formats, with what the client and the server sent in different colors.
+
+use core::fmt;
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+
+use crate::pkt::follow::{Stream, StreamChunk, StreamId};
+
+/// How the payload is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowFormat {
+    /// Printable text, with everything else as dots
+    Ascii,
+    /// Offset, hex and ASCII columns, 16 bytes a line
+    HexDump,
+    /// One C array per packet, ready to paste into source code
+    CArray,
+    /// Unbroken hex digits
+    Raw,
+}
+
+impl fmt::Display for FollowFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FollowFormat::Ascii => write!(f, "ASCII"),
+            FollowFormat::HexDump => write!(f, "Hex Dump"),
+            FollowFormat::CArray => write!(f, "C Arrays"),
+            FollowFormat::Raw => write!(f, "Raw"),
+        }
+    }
+}
+
+/// Which side's data is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowDirection {
+    Both,
+    ClientToServer,
+    ServerToClient,
+}
+
+impl fmt::Display for FollowDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FollowDirection::Both => write!(f, "both directions"),
+            FollowDirection::ClientToServer => write!(f, "client to server only"),
+            FollowDirection::ServerToClient => write!(f, "server to client only"),
+        }
+    }
+}
+
+impl FollowDirection {
+    pub fn next(self) -> Self {
+        match self {
+            FollowDirection::Both => FollowDirection::ClientToServer,
+            FollowDirection::ClientToServer => FollowDirection::ServerToClient,
+            FollowDirection::ServerToClient => FollowDirection::Both,
+        }
+    }
+
+    fn shows(self, chunk: &StreamChunk) -> bool {
+        match self {
+            FollowDirection::Both => true,
+            FollowDirection::ClientToServer => chunk.from_client,
+            FollowDirection::ServerToClient => !chunk.from_client,
+        }
+    }
+}
+
+/// Bytes per line of the hex dump and raw formats
+const DUMP_WIDTH: usize = 16;
+const RAW_WIDTH: usize = 32;
+/// Bytes per line of a C array
+const C_ARRAY_WIDTH: usize = 8;
+
+pub fn client_style() -> Style {
+    Style::default().fg(Color::LightRed)
+}
+
+pub fn server_style() -> Style {
+    Style::default().fg(Color::LightBlue)
+}
+
+fn printable(byte: u8) -> char {
+    if byte.is_ascii() && !byte.is_ascii_control() {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+pub struct FollowView {
+    pub stream: Stream,
+    /// Every stream of the same protocol in the capture, in order, to step through
+    pub ids: Vec<StreamId>,
+    pub format: FollowFormat,
+    pub direction: FollowDirection,
+    /// First line shown
+    pub scroll: usize,
+}
+
+impl FollowView {
+    pub fn new(stream: Stream, ids: Vec<StreamId>) -> Self {
+        FollowView {
+            stream,
+            ids,
+            format: FollowFormat::Ascii,
+            direction: FollowDirection::Both,
+            scroll: 0,
+        }
+    }
+
+    /// Every line of the payload in the current format, colored by the side that sent it
+    pub fn lines(&self) -> Vec<Spans<'static>> {
+        let mut lines: Vec<Spans<'static>> = vec![];
+        // Hex dump offsets and C array names count each side separately, as in Wireshark
+        let mut offsets = [0, 0];
+        let mut counts = [0, 0];
+        // Text a side sends over several packets runs on where the last one left off
+        let mut open_line: Option<bool> = None;
+        for chunk in self
+            .stream
+            .chunks
+            .iter()
+            .filter(|c| self.direction.shows(c))
+        {
+            let side = if chunk.from_client { 0 } else { 1 };
+            let style = if chunk.from_client {
+                client_style()
+            } else {
+                server_style()
+            };
+            if chunk.missing > 0 {
+                let text = format!("[{} bytes missing in capture]", chunk.missing);
+                lines.push(Spans::from(Span::styled(text, style)));
+                open_line = None;
+                continue;
+            }
+            let mut texts = match self.format {
+                FollowFormat::Ascii => ascii_lines(&chunk.bytes),
+                FollowFormat::HexDump => hexdump_lines(&chunk.bytes, offsets[side], side == 1),
+                FollowFormat::CArray => c_array_lines(chunk, side, counts[side]),
+                FollowFormat::Raw => chunk
+                    .bytes
+                    .chunks(RAW_WIDTH)
+                    .map(|line| line.iter().map(|b| format!("{:02x}", b)).collect())
+                    .collect(),
+            };
+            offsets[side] += chunk.bytes.len();
+            counts[side] += 1;
+            if open_line == Some(chunk.from_client) && !texts.is_empty() {
+                if let Some(last) = lines.last_mut() {
+                    last.0.push(Span::styled(texts.remove(0), style));
+                }
+            }
+            open_line = match self.format {
+                FollowFormat::Ascii if !chunk.bytes.ends_with(b"\n") => Some(chunk.from_client),
+                _ => None,
+            };
+            lines.extend(
+                texts
+                    .into_iter()
+                    .map(|text| Spans::from(Span::styled(text, style))),
+            );
+        }
+        lines
+    }
+}
+
+/// Text split at line breaks, dropping the carriage returns before them
+fn ascii_lines(bytes: &[u8]) -> Vec<String> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes
+        .split(|&b| b == b'\n')
+        .map(|line| {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            line.iter().map(|&b| printable(b)).collect()
+        })
+        .collect()
+}
+
+/// Hex dump lines, with the server's indented so the two sides stand apart without color
+fn hexdump_lines(bytes: &[u8], start: usize, indent: bool) -> Vec<String> {
+    bytes
+        .chunks(DUMP_WIDTH)
+        .enumerate()
+        .map(|(i, line)| {
+            let mut text = if indent {
+                "    ".to_string()
+            } else {
+                String::new()
+            };
+            text += &format!("{:08x}  ", start + i * DUMP_WIDTH);
+            for col in 0..DUMP_WIDTH {
+                match line.get(col) {
+                    Some(byte) => text += &format!("{:02x} ", byte),
+                    None => text += "   ",
+                }
+                if col == DUMP_WIDTH / 2 - 1 {
+                    text += " ";
+                }
+            }
+            text += " ";
+            text.extend(line.iter().map(|&b| printable(b)));
+            text
+        })
+        .collect()
+}
+
+/// A C array declaration named after the side and the chunk's number on that side
+fn c_array_lines(chunk: &StreamChunk, side: usize, count: usize) -> Vec<String> {
+    let mut lines = vec![format!(
+        "char peer{}_{}[] = {{ /* Packet {} */",
+        side, count, chunk.frame_num
+    )];
+    let rows: Vec<&[u8]> = chunk.bytes.chunks(C_ARRAY_WIDTH).collect();
+    for (i, row) in rows.iter().enumerate() {
+        let bytes: Vec<String> = row.iter().map(|b| format!("0x{:02x}", b)).collect();
+        let end = if i == rows.len() - 1 { " };" } else { "," };
+        lines.push(bytes.join(", ") + end);
+    }
+    lines
+}
@@ -0,0 +1,237 @@
+// I/O Graph pane state. Packets or bytes per interval of capture time, one series per display
+//   filter, with a cursor on one interval that the packet list can jump to.
+
+use core::fmt;
+use tui::style::Color;
+
+use crate::filter::{Filter, FilterError};
+use crate::pkt::iostat::{self, Bin, INTERVALS};
+use crate::pkt::registry::Registry;
+use crate::pkt::timestamp::{self, Timestamp};
+use crate::pkt::Packet;
+
+/// Most intervals a graph may have, which keeps long captures from going down to 1 ms
+const MAX_BINS: usize = 1_000_000;
+/// Most intervals the graph starts out with
+const INITIAL_BINS: usize = 200;
+/// Series colors, in the order series are added
+const COLORS: [Color; 6] = [
+    Color::LightGreen,
+    Color::LightRed,
+    Color::LightCyan,
+    Color::LightMagenta,
+    Color::LightBlue,
+    Color::White,
+];
+
+/// What the graph plots per interval
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Packets,
+    Bytes,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Packets => write!(f, "Packets"),
+            Unit::Bytes => write!(f, "Bytes"),
+        }
+    }
+}
+
+impl Unit {
+    pub fn value(self, bin: &Bin) -> usize {
+        match self {
+            Unit::Packets => bin.packets,
+            Unit::Bytes => bin.bytes,
+        }
+    }
+}
+
+/// The packets matching one display filter, or all packets without one
+pub struct Series {
+    pub filter: Option<Filter>,
+    /// Indices of the matching packets, so changing the interval doesn't filter again
+    matching: Vec<usize>,
+    pub bins: Vec<Bin>,
+    pub color: Color,
+    pub visible: bool,
+}
+
+impl Series {
+    pub fn name(&self) -> &str {
+        match &self.filter {
+            Some(filter) => &filter.text,
+            None => "All packets",
+        }
+    }
+}
+
+pub struct IoGraphView {
+    pub series: Vec<Series>,
+    /// Series the list cursor is on
+    pub selected: usize,
+    /// Position of the interval length in `INTERVALS`
+    interval: usize,
+    pub unit: Unit,
+    /// Interval the graph cursor is on
+    pub cursor: usize,
+    /// Time of the first packet, which intervals are counted from
+    first: Timestamp,
+    /// Filter being typed for a new series, and its error if it doesn't compile
+    pub input: Option<String>,
+    pub input_error: Option<FilterError>,
+    /// Colors handed out so far
+    colors_used: usize,
+}
+
+impl IoGraphView {
+    /// Starts with all packets and, like Wireshark, the packets TCP analysis flagged
+    pub fn new(pkts: &[Packet], registry: &Registry) -> Self {
+        let interval = (0..INTERVALS.len())
+            .find(|&i| iostat::bin_count(pkts, INTERVALS[i]) <= INITIAL_BINS)
+            .unwrap_or(INTERVALS.len() - 1);
+        let mut view = IoGraphView {
+            series: vec![],
+            selected: 0,
+            interval,
+            unit: Unit::Packets,
+            cursor: 0,
+            first: pkts.first().map(|p| p.timestamp).unwrap_or_default(),
+            input: None,
+            input_error: None,
+            colors_used: 0,
+        };
+        view.push_series(pkts, None);
+        if let Ok(filter) = Filter::compile("tcp.analysis.flags", registry) {
+            view.push_series(pkts, Some(filter));
+        }
+        view
+    }
+
+    fn push_series(&mut self, pkts: &[Packet], filter: Option<Filter>) {
+        let matching: Vec<usize> = match &filter {
+            Some(filter) => pkts
+                .iter()
+                .enumerate()
+                .filter(|(_, pkt)| filter.matches(pkt))
+                .map(|(idx, _)| idx)
+                .collect(),
+            None => (0..pkts.len()).collect(),
+        };
+        let bins = iostat::bins(pkts, &matching, self.interval_nanos());
+        self.series.push(Series {
+            filter,
+            matching,
+            bins,
+            color: COLORS[self.colors_used % COLORS.len()],
+            visible: true,
+        });
+        self.colors_used += 1;
+    }
+
+    /// Adds a series for the filter being typed, if it compiles
+    pub fn add_series(&mut self, pkts: &[Packet], registry: &Registry) -> Result<(), FilterError> {
+        let text = self.input.as_deref().unwrap_or_default().trim();
+        if text.is_empty() {
+            self.input = None;
+            return Ok(());
+        }
+        let filter = Filter::compile(text, registry)?;
+        self.push_series(pkts, Some(filter));
+        self.selected = self.series.len() - 1;
+        self.input = None;
+        self.input_error = None;
+        Ok(())
+    }
+
+    pub fn remove_selected(&mut self) {
+        if self.selected < self.series.len() {
+            self.series.remove(self.selected);
+            self.selected = self.selected.min(self.series.len().saturating_sub(1));
+        }
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(series) = self.series.get_mut(self.selected) {
+            series.visible = !series.visible;
+        }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.series.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn interval_nanos(&self) -> i128 {
+        INTERVALS[self.interval]
+    }
+
+    pub fn bin_count(&self) -> usize {
+        self.series.first().map_or(0, |s| s.bins.len())
+    }
+
+    /// Switches to the next longer or shorter interval, keeping the cursor at the same time.
+    /// Shorter intervals stop where the graph would get too many.
+    pub fn step_interval(&mut self, pkts: &[Packet], longer: bool) {
+        let interval = if longer {
+            (self.interval + 1).min(INTERVALS.len() - 1)
+        } else {
+            self.interval.saturating_sub(1)
+        };
+        if interval == self.interval || iostat::bin_count(pkts, INTERVALS[interval]) > MAX_BINS {
+            return;
+        }
+        let cursor_time = self.cursor as i128 * self.interval_nanos();
+        self.interval = interval;
+        for series in &mut self.series {
+            series.bins = iostat::bins(pkts, &series.matching, INTERVALS[interval]);
+        }
+        self.cursor = (cursor_time / INTERVALS[interval]) as usize;
+        self.move_cursor(0);
+    }
+
+    pub fn toggle_unit(&mut self) {
+        self.unit = match self.unit {
+            Unit::Packets => Unit::Bytes,
+            Unit::Bytes => Unit::Packets,
+        };
+    }
+
+    /// Moves the cursor by `delta` intervals, staying within the graph
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.bin_count().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize).saturating_add(delta).clamp(0, last) as usize;
+    }
+
+    /// Start of the cursor's interval, formatted like relative packet times
+    pub fn cursor_time(&self) -> String {
+        timestamp::format_interval(
+            self.cursor as i128 * self.interval_nanos(),
+            self.first.digits,
+        )
+    }
+
+    /// Start of interval `bin` for the time axis, to the millisecond only for intervals that
+    /// need it
+    pub fn axis_label(&self, bin: usize) -> String {
+        let digits = if self.interval_nanos() < 1_000_000_000 {
+            self.first.digits.min(3)
+        } else {
+            0
+        };
+        timestamp::format_interval(bin as i128 * self.interval_nanos(), digits)
+    }
+
+    /// First packet of the selected series in the cursor's interval, or when it has none there
+    /// the nearest one after it, else before it
+    pub fn cursor_packet(&self) -> Option<usize> {
+        let bins = &self.series.get(self.selected)?.bins;
+        let cursor = self.cursor.min(bins.len());
+        bins[cursor..]
+            .iter()
+            .find_map(|bin| bin.first)
+            .or_else(|| bins[..cursor].iter().rev().find_map(|bin| bin.first))
+    }
+}
@@ -1,20 +1,60 @@
+mod cli;
+use crate::cli::Args;
+
+mod conversations;
+use crate::conversations::ConversationsView;
+
+mod expertinfo;
+use crate::expertinfo::ExpertView;
+
+mod filter;
+use crate::filter::{Filter, FilterError};
+
+mod followstream;
+use crate::followstream::{FollowFormat, FollowView};
+
+mod iograph;
+use crate::iograph::IoGraphView;
+
+mod packetlist;
+use crate::packetlist::{PacketList, PacketRow};
+
 mod pkt;
-use crate::pkt::{legacy_pcap_to_packet, Packet};
+use crate::pkt::capture::{read_capture_file, Interface};
+use crate::pkt::conversation::ConvType;
+use crate::pkt::follow;
+use crate::pkt::iostat;
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{Preferences, Registry};
+use crate::pkt::timestamp::TimeFormat;
+use crate::pkt::Packet;
+
+mod protohierarchy;
+use crate::protohierarchy::HierarchyView;
 
 mod statefultree;
 use crate::statefultree::StatefulTree;
 
+use clap::Parser;
 use core::fmt;
 use std::{
-    io,
+    collections::HashMap,
+    io::{self, Write},
+    net::IpAddr,
+    path::Path,
+    process,
     time::{Duration, Instant},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
-    style::{Modifier, Style},
-    text::Text,
-    widgets::{Block, Borders, Paragraph, Wrap},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans, Text},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Dataset, GraphType, Paragraph, Row, Table, TableState,
+        Tabs, Wrap,
+    },
     Frame, Terminal,
 };
 use tui_tree_widget::Tree;
@@ -25,34 +65,634 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-// High level TODO list
-// - Parse 802.1q Header (Low priority)
-// - Parse IPV4
-// - Somehow cache treeitem construction to minimize slowdown
+/// Smallest share of the screen, in percent, a pane can be shrunk to
+const MIN_PANE_PERCENT: u16 = 10;
+const PANE_RESIZE_STEP: u16 = 5;
+
+/// Panes of the main view, in screen order from top to bottom
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pane {
+    PacketList,
+    Details,
+    Bytes,
+}
+
+impl Pane {
+    fn index(self) -> usize {
+        match self {
+            Pane::PacketList => 0,
+            Pane::Details => 1,
+            Pane::Bytes => 2,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Pane::PacketList => Pane::Details,
+            Pane::Details => Pane::Bytes,
+            Pane::Bytes => Pane::PacketList,
+        }
+    }
+}
 
 struct TuiSharkApp<'a> {
     raw_pkts: Vec<Packet>,
+    interfaces: Vec<Interface>,
+    /// Host names for addresses, from the capture's Name Resolution Blocks
+    names: HashMap<IpAddr, String>,
+    registry: Registry,
+    /// Indices into `raw_pkts` of the packets shown in the packet list
+    displayed: Vec<usize>,
+    pkt_list: PacketList,
+    /// Details of the selected packet, and the tree widget built from them
+    details: Vec<ProtoItem>,
     pkt_tree: StatefulTree<'a>,
+    focus: Pane,
+    /// Heights of the packet list, details and byte panes, in percent
+    pane_sizes: [u16; 3],
+    time_format: TimeFormat,
+    linktype_override: Option<pcap_parser::Linktype>,
+    filter: Option<Filter>,
+    /// Text in the display filter bar, which may differ from the applied filter while editing
+    filter_input: String,
+    editing_filter: bool,
+    /// Error in `filter_input`, if it does not compile
+    filter_error: Option<FilterError>,
+    read_only: bool,
+    /// Follow Stream pane, which takes the place of the packet panes while open
+    follow: Option<FollowView>,
+    /// Expert Info pane, which also takes the place of the packet panes while open
+    expert: Option<ExpertView<'a>>,
+    /// Conversations and Endpoints pane, in place of the packet panes while open
+    conversations: Option<ConversationsView>,
+    /// Protocol Hierarchy pane, in place of the packet panes while open
+    hierarchy: Option<HierarchyView<'a>>,
+    /// I/O Graph pane, in place of the packet panes while open
+    iograph: Option<IoGraphView>,
 }
 
-#[allow(dead_code)]
 impl<'a> TuiSharkApp<'a> {
     fn new() -> Self {
         TuiSharkApp {
             raw_pkts: vec![],
+            interfaces: vec![],
+            names: HashMap::new(),
+            registry: Registry::with_all_dissectors(),
+            displayed: vec![],
+            pkt_list: PacketList::new(),
+            details: vec![],
             pkt_tree: StatefulTree::with_items(vec![]),
+            focus: Pane::PacketList,
+            pane_sizes: [50, 30, 20],
+            time_format: TimeFormat::Relative,
+            linktype_override: None,
+            filter: None,
+            filter_input: String::new(),
+            editing_filter: false,
+            filter_error: None,
+            read_only: false,
+            follow: None,
+            expert: None,
+            conversations: None,
+            hierarchy: None,
+            iograph: None,
         }
     }
 
-    fn load_packets_from_file(&mut self, path: String) {
-        self.raw_pkts = legacy_pcap_to_packet(path);
+    fn from_args(args: &Args) -> Self {
+        let mut app = TuiSharkApp::new();
+        app.linktype_override = args.linktype;
+        app.filter_input = args.filter.clone().unwrap_or_default();
+        app.read_only = args.read_only;
+        app.registry = Registry::with_prefs(Preferences {
+            validate_checksums: !args.no_checksum_validation,
+        });
+        if let Some(time_format) = args.time_format {
+            app.time_format = time_format;
+        }
+        app
+    }
+
+    fn load_packets_from_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let capture = read_capture_file(path)?;
+        self.raw_pkts = capture.packets;
+        self.interfaces = capture.interfaces;
+        self.names = capture.names;
+
+        if let Some(linktype) = self.linktype_override {
+            for interface in &mut self.interfaces {
+                interface.linktype = linktype;
+            }
+            for pkt in &mut self.raw_pkts {
+                pkt.linktype = linktype;
+            }
+        }
+        self.decode_packets();
+
+        Ok(())
+    }
 
+    /// Dissects every packet again from the first, so conversation state is rebuilt in order
+    fn decode_packets(&mut self) {
         for pkt in &mut self.raw_pkts {
-            pkt.decode();
+            pkt.decode(&self.registry);
+        }
+        self.refilter();
+    }
+
+    /// Turns checksum validation on or off and redissects the capture with a fresh registry
+    fn toggle_checksum_validation(&mut self) {
+        let mut prefs = self.registry.prefs.clone();
+        prefs.validate_checksums = !prefs.validate_checksums;
+        self.registry = Registry::with_prefs(prefs);
+        self.decode_packets();
+    }
+
+    /// Compiles the filter bar text and, if it is valid, shows only the packets matching it.
+    /// Empty text clears the filter.
+    fn apply_filter(&mut self) -> Result<(), FilterError> {
+        let text = self.filter_input.trim();
+        self.filter = if text.is_empty() {
+            None
+        } else {
+            match Filter::compile(text, &self.registry) {
+                Ok(filter) => Some(filter),
+                Err(err) => {
+                    self.filter_error = Some(err.clone());
+                    return Err(err);
+                }
+            }
+        };
+        self.filter_error = None;
+        self.refilter();
+        Ok(())
+    }
+
+    fn refilter(&mut self) {
+        self.displayed = match &self.filter {
+            Some(filter) => self
+                .raw_pkts
+                .iter()
+                .enumerate()
+                .filter(|(_, pkt)| filter.matches(pkt))
+                .map(|(idx, _)| idx)
+                .collect(),
+            None => (0..self.raw_pkts.len()).collect(),
+        };
+        self.rebuild_packet_list();
+    }
+
+    fn on_filter_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Enter => {
+                if self.apply_filter().is_ok() {
+                    self.editing_filter = false;
+                }
+                return;
+            }
+            KeyCode::Esc => {
+                self.editing_filter = false;
+                self.filter_input = self
+                    .filter
+                    .as_ref()
+                    .map(|f| f.text.clone())
+                    .unwrap_or_default();
+                self.filter_error = None;
+                return;
+            }
+            KeyCode::Backspace => {
+                self.filter_input.pop();
+            }
+            KeyCode::Char(c) => self.filter_input.push(c),
+            _ => return,
+        }
+        // Validate as the user types, like Wireshark's green/red filter bar
+        let text = self.filter_input.trim();
+        self.filter_error = if text.is_empty() {
+            None
+        } else {
+            Filter::compile(text, &self.registry).err()
+        };
+    }
+
+    /// Formats the capture time of every displayed packet according to the current time format
+    fn packet_times(&self) -> Vec<String> {
+        let first = match self.raw_pkts.first() {
+            Some(pkt) => pkt.timestamp,
+            None => return vec![],
+        };
+        let mut prev_displayed = match self.displayed.first() {
+            Some(&idx) => self.raw_pkts[idx].timestamp,
+            None => return vec![],
+        };
+        self.displayed
+            .iter()
+            .map(|&idx| {
+                let timestamp = self.raw_pkts[idx].timestamp;
+                let time = self.time_format.format(&timestamp, &first, &prev_displayed);
+                prev_displayed = timestamp;
+                time
+            })
+            .collect()
+    }
+
+    /// The name the capture gives the address in an address column, if it gives one
+    fn host_name(&self, address: String) -> String {
+        address
+            .parse::<IpAddr>()
+            .ok()
+            .and_then(|addr| self.names.get(&addr).cloned())
+            .unwrap_or(address)
+    }
+
+    fn rebuild_packet_list(&mut self) {
+        let rows = self
+            .displayed
+            .iter()
+            .zip(self.packet_times())
+            .map(|(&idx, time)| {
+                let pkt = &self.raw_pkts[idx];
+                let (source, destination, protocol, info) = pkt.columns();
+                PacketRow {
+                    pkt_idx: idx,
+                    num: pkt.num.to_string(),
+                    time,
+                    source: self.host_name(source),
+                    destination: self.host_name(destination),
+                    protocol,
+                    length: pkt.caplen().to_string(),
+                    info,
+                }
+            })
+            .collect();
+        self.pkt_list.set_rows(rows);
+        self.rebuild_details();
+    }
+
+    /// Rebuilds the details tree for the selected packet, keeping expanded subtrees open
+    fn rebuild_details(&mut self) {
+        self.details = match self.pkt_list.selected_packet() {
+            Some(idx) => {
+                let pkt = &self.raw_pkts[idx];
+                let first = self.raw_pkts[0].timestamp;
+                let time = TimeFormat::Absolute.format(&pkt.timestamp, &first, &first);
+                pkt.detail_items(&time)
+            }
+            None => vec![],
+        };
+        self.pkt_tree.items = self.details.iter().map(|d| d.to_tree_item()).collect();
+        self.pkt_tree.state.select(Vec::<usize>::new());
+    }
+
+    fn cycle_time_format(&mut self) {
+        self.time_format = self.time_format.next();
+        self.rebuild_packet_list();
+    }
+
+    fn select_packet(&mut self, idx: usize) {
+        self.pkt_list.select_packet(idx);
+        self.rebuild_details();
+    }
+
+    /// Grows the focused pane, or shrinks it when `grow` is false, trading space with its
+    /// neighbour
+    fn resize_focused_pane(&mut self, grow: bool) {
+        let focused = self.focus.index();
+        let neighbour = if focused == self.pane_sizes.len() - 1 {
+            focused - 1
+        } else {
+            focused + 1
+        };
+        let (gainer, loser) = if grow {
+            (focused, neighbour)
+        } else {
+            (neighbour, focused)
+        };
+        if self.pane_sizes[loser] >= MIN_PANE_PERCENT + PANE_RESIZE_STEP {
+            self.pane_sizes[loser] -= PANE_RESIZE_STEP;
+            self.pane_sizes[gainer] += PANE_RESIZE_STEP;
+        }
+    }
+
+    /// Opens the Follow Stream pane on the TCP or UDP stream of the selected packet
+    fn open_follow_stream(&mut self) {
+        let id = match self.pkt_list.selected_packet() {
+            Some(idx) => follow::stream_id(&self.raw_pkts[idx]),
+            None => None,
+        };
+        if let Some(id) = id {
+            let ids = follow::stream_ids(&self.raw_pkts, id.protocol);
+            let index = ids
+                .iter()
+                .position(|other| *other == id)
+                .unwrap_or_default();
+            let stream = follow::follow(&self.raw_pkts, &id, index);
+            self.follow = Some(FollowView::new(stream, ids));
+        }
+    }
+
+    /// Moves the Follow Stream pane to the next or previous stream of the same protocol,
+    /// keeping its format and direction
+    fn step_follow_stream(&mut self, forward: bool) {
+        let view = match &mut self.follow {
+            Some(view) => view,
+            None => return,
+        };
+        let target = if forward {
+            view.stream.index + 1
+        } else {
+            match view.stream.index.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            }
+        };
+        if let Some(id) = view.ids.get(target) {
+            view.stream = follow::follow(&self.raw_pkts, id, target);
+            view.scroll = 0;
+        }
+    }
+
+    fn on_follow_key(&mut self, code: KeyCode) {
+        let view = match &mut self.follow {
+            Some(view) => view,
+            None => return,
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('f') => self.follow = None,
+            KeyCode::Char('a') => view.format = FollowFormat::Ascii,
+            KeyCode::Char('x') => view.format = FollowFormat::HexDump,
+            KeyCode::Char('c') => view.format = FollowFormat::CArray,
+            KeyCode::Char('r') => view.format = FollowFormat::Raw,
+            KeyCode::Char('d') => {
+                view.direction = view.direction.next();
+                view.scroll = 0;
+            }
+            KeyCode::Char('n') => self.step_follow_stream(true),
+            KeyCode::Char('p') => self.step_follow_stream(false),
+            KeyCode::Down => view.scroll += 1,
+            KeyCode::Up => view.scroll = view.scroll.saturating_sub(1),
+            KeyCode::PageDown => view.scroll += 20,
+            KeyCode::PageUp => view.scroll = view.scroll.saturating_sub(20),
+            KeyCode::Home => view.scroll = 0,
+            // Drawing clamps this to the last screenful
+            KeyCode::End => view.scroll = usize::MAX,
+            _ => {}
+        }
+    }
+
+    fn on_expert_key(&mut self, code: KeyCode) {
+        let view = match &mut self.expert {
+            Some(view) => view,
+            None => return,
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('e') => self.expert = None,
+            KeyCode::Left => view.tree.left(),
+            KeyCode::Right => view.tree.right(),
+            KeyCode::Down => view.tree.down(),
+            KeyCode::Up => view.tree.up(),
+            KeyCode::Home => view.tree.first(),
+            KeyCode::End => view.tree.last(),
+            // Enter on a packet goes to it in the packet list, and on a group opens it
+            KeyCode::Enter => match view.selected_packet() {
+                Some(idx) => {
+                    self.expert = None;
+                    self.focus = Pane::PacketList;
+                    self.select_packet(idx);
+                }
+                None => view.tree.toggle(),
+            },
+            _ => {}
+        }
+    }
+
+    fn on_conversations_key(&mut self, code: KeyCode) {
+        let view = match &mut self.conversations {
+            Some(view) => view,
+            None => return,
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('c') => self.conversations = None,
+            KeyCode::Right => view.step_type(true),
+            KeyCode::Left => view.step_type(false),
+            KeyCode::Char('e') => view.toggle_kind(),
+            KeyCode::Char('s') => view.next_sort(),
+            KeyCode::Char('r') => view.reverse_sort(),
+            KeyCode::Down => view.move_selection(1),
+            KeyCode::Up => view.move_selection(-1),
+            KeyCode::PageDown => view.move_selection(20),
+            KeyCode::PageUp => view.move_selection(-20),
+            KeyCode::Home => view.move_selection(isize::MIN / 2),
+            KeyCode::End => view.move_selection(isize::MAX / 2),
+            // Read-only sessions keep the display filter they were started with
+            KeyCode::Enter if !self.read_only => {
+                if let Some(filter) = view.selected_filter() {
+                    self.conversations = None;
+                    self.focus = Pane::PacketList;
+                    self.filter_input = filter;
+                    // Filters built from the table always compile
+                    let _ = self.apply_filter();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_hierarchy_key(&mut self, code: KeyCode) {
+        let view = match &mut self.hierarchy {
+            Some(view) => view,
+            None => return,
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('p') => self.hierarchy = None,
+            KeyCode::Left => view.tree.left(),
+            KeyCode::Right => view.tree.right(),
+            KeyCode::Down => view.tree.down(),
+            KeyCode::Up => view.tree.up(),
+            KeyCode::Home => view.tree.first(),
+            KeyCode::End => view.tree.last(),
+            KeyCode::Enter => view.tree.toggle(),
+            _ => {}
+        }
+    }
+
+    fn on_iograph_key(&mut self, code: KeyCode) {
+        let view = match &mut self.iograph {
+            Some(view) => view,
+            None => return,
+        };
+        // Typing the filter of a new series
+        if let Some(input) = &mut view.input {
+            match code {
+                KeyCode::Enter => {
+                    if let Err(err) = view.add_series(&self.raw_pkts, &self.registry) {
+                        view.input_error = Some(err);
+                    }
+                    return;
+                }
+                KeyCode::Esc => {
+                    view.input = None;
+                    view.input_error = None;
+                    return;
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => return,
+            }
+            let text = input.trim();
+            view.input_error = if text.is_empty() {
+                None
+            } else {
+                Filter::compile(text, &self.registry).err()
+            };
+            return;
+        }
+        match code {
+            KeyCode::Esc | KeyCode::Char('i') => self.iograph = None,
+            KeyCode::Right => view.move_cursor(1),
+            KeyCode::Left => view.move_cursor(-1),
+            KeyCode::PageDown => view.move_cursor(20),
+            KeyCode::PageUp => view.move_cursor(-20),
+            KeyCode::Home => view.move_cursor(isize::MIN),
+            KeyCode::End => view.move_cursor(isize::MAX),
+            KeyCode::Char('+') => view.step_interval(&self.raw_pkts, true),
+            KeyCode::Char('-') => view.step_interval(&self.raw_pkts, false),
+            KeyCode::Char('u') => view.toggle_unit(),
+            KeyCode::Down => view.move_selection(1),
+            KeyCode::Up => view.move_selection(-1),
+            KeyCode::Char('a') => view.input = Some(String::new()),
+            KeyCode::Char('d') => view.remove_selected(),
+            KeyCode::Char(' ') => view.toggle_selected(),
+            // Go to the interval's first packet, or the next one the display filter shows
+            KeyCode::Enter => {
+                if let Some(target) = view.cursor_packet() {
+                    self.iograph = None;
+                    self.focus = Pane::PacketList;
+                    let idx = self
+                        .displayed
+                        .iter()
+                        .copied()
+                        .find(|&idx| idx >= target)
+                        .or_else(|| self.displayed.last().copied());
+                    if let Some(idx) = idx {
+                        self.select_packet(idx);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether keys are going into a text input, where q doesn't quit
+    fn typing(&self) -> bool {
+        self.editing_filter
+            || self
+                .iograph
+                .as_ref()
+                .is_some_and(|view| view.input.is_some())
+    }
+
+    fn on_key(&mut self, code: KeyCode) {
+        if self.editing_filter {
+            self.on_filter_key(code);
+            return;
+        }
+        if self.follow.is_some() {
+            self.on_follow_key(code);
+            return;
+        }
+        if self.expert.is_some() {
+            self.on_expert_key(code);
+            return;
+        }
+        if self.conversations.is_some() {
+            self.on_conversations_key(code);
+            return;
+        }
+        if self.hierarchy.is_some() {
+            self.on_hierarchy_key(code);
+            return;
+        }
+        if self.iograph.is_some() {
+            self.on_iograph_key(code);
+            return;
+        }
+
+        match (self.focus, code) {
+            (_, KeyCode::Char('/')) if !self.read_only => self.editing_filter = true,
+            (_, KeyCode::Tab) => self.focus = self.focus.next(),
+            (_, KeyCode::Char('t')) => self.cycle_time_format(),
+            (_, KeyCode::Char('f')) => self.open_follow_stream(),
+            (_, KeyCode::Char('c')) => {
+                self.conversations = Some(ConversationsView::new(&self.raw_pkts))
+            }
+            (_, KeyCode::Char('p')) => {
+                self.hierarchy = Some(HierarchyView::new(&self.raw_pkts, &self.displayed))
+            }
+            (_, KeyCode::Char('i')) => {
+                self.iograph = Some(IoGraphView::new(&self.raw_pkts, &self.registry))
+            }
+            (_, KeyCode::Char('e')) => {
+                self.expert = Some(ExpertView::new(&self.raw_pkts, &self.displayed))
+            }
+            (_, KeyCode::Char('C')) if !self.read_only => self.toggle_checksum_validation(),
+            (_, KeyCode::Char('+')) => self.resize_focused_pane(true),
+            (_, KeyCode::Char('-')) => self.resize_focused_pane(false),
+
+            (Pane::PacketList, KeyCode::Down) => self.pkt_list.down(1),
+            (Pane::PacketList, KeyCode::Up) => self.pkt_list.up(1),
+            (Pane::PacketList, KeyCode::PageDown) => self.pkt_list.down(20),
+            (Pane::PacketList, KeyCode::PageUp) => self.pkt_list.up(20),
+            (Pane::PacketList, KeyCode::Home) => self.pkt_list.first(),
+            (Pane::PacketList, KeyCode::End) => self.pkt_list.last(),
+
+            (Pane::Details, KeyCode::Left) => self.pkt_tree.left(),
+            (Pane::Details, KeyCode::Right) => self.pkt_tree.right(),
+            (Pane::Details, KeyCode::Down) => self.pkt_tree.down(),
+            (Pane::Details, KeyCode::Up) => self.pkt_tree.up(),
+            (Pane::Details, KeyCode::Enter) => self.pkt_tree.toggle(),
+            _ => {}
+        }
+
+        if self.focus == Pane::PacketList
+            && matches!(
+                code,
+                KeyCode::Down
+                    | KeyCode::Up
+                    | KeyCode::PageDown
+                    | KeyCode::PageUp
+                    | KeyCode::Home
+                    | KeyCode::End
+            )
+        {
+            self.rebuild_details();
         }
+    }
 
-        self.pkt_tree =
-            StatefulTree::with_items(self.raw_pkts.iter().map(|p| p.to_tree_item()).collect());
+    fn print_packets(&self) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        for (id, interface) in self.interfaces.iter().enumerate() {
+            writeln!(
+                out,
+                "Interface {}: {} ({})",
+                id,
+                interface.name.as_deref().unwrap_or("unnamed"),
+                interface.linktype
+            )?;
+        }
+        for (&idx, time) in self.displayed.iter().zip(self.packet_times()) {
+            let pkt = &self.raw_pkts[idx];
+            writeln!(out, "{}", pkt.summary(&time))?;
+            for block in &pkt.custom_blocks {
+                writeln!(out, "    {}", block)?;
+            }
+            for layer in &pkt.layers {
+                writeln!(out, "    {}", layer)?;
+            }
+        }
+        out.flush()
     }
 }
 
@@ -62,46 +702,568 @@ impl fmt::Display for TuiSharkApp<'_> {
     }
 }
 
-use tui_tree_widget::TreeItem;
+fn pane_block(title: String, focused: bool) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if focused {
+        block.border_style(Style::default().fg(Color::LightGreen))
+    } else {
+        block
+    }
+}
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &mut TuiSharkApp, tree_obj: Vec<TreeItem>) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
-        .split(f.size());
+fn highlight_style() -> Style {
+    Style::default()
+        .fg(Color::Black)
+        .bg(Color::LightGreen)
+        .add_modifier(Modifier::BOLD)
+}
 
-    let packet_view = Tree::new(tree_obj)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(format!("Packet View {:?}", app.pkt_tree.state)),
-        )
-        .highlight_style(
-            Style::default()
-                .fg(tui::style::Color::Black)
-                .bg(tui::style::Color::LightGreen)
-                .add_modifier(Modifier::BOLD),
-        )
+fn draw_packet_list<B: Backend>(f: &mut Frame<B>, app: &mut TuiSharkApp, area: Rect) {
+    let mut title = match &app.filter {
+        Some(_) => format!(
+            "Packet List [{}] ({} of {} packets displayed)",
+            app.time_format,
+            app.displayed.len(),
+            app.raw_pkts.len()
+        ),
+        None => format!(
+            "Packet List [{}] ({} packets)",
+            app.time_format,
+            app.raw_pkts.len()
+        ),
+    };
+    if !app.registry.prefs.validate_checksums {
+        title += " [checksums not validated, C to validate]";
+    }
+
+    // Two border rows and one header row
+    let height = area.height.saturating_sub(3) as usize;
+    let rows: Vec<Row> = app
+        .pkt_list
+        .visible(height)
+        .iter()
+        .map(|r| {
+            Row::new(vec![
+                Cell::from(r.num.clone()),
+                Cell::from(r.time.clone()),
+                Cell::from(r.source.clone()),
+                Cell::from(r.destination.clone()),
+                Cell::from(r.protocol.clone()),
+                Cell::from(r.length.clone()),
+                Cell::from(r.info.clone()),
+            ])
+        })
+        .collect();
+
+    let header = Row::new(vec![
+        "No.",
+        "Time",
+        "Source",
+        "Destination",
+        "Protocol",
+        "Length",
+        "Info",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let time_width = match app.time_format {
+        TimeFormat::Absolute | TimeFormat::Utc => 26,
+        _ => 18,
+    };
+    let fixed_widths = [7, time_width, 22, 22, 10, 7];
+    // Info takes whatever the fixed columns, their spacing and the borders leave over
+    let used = fixed_widths.iter().sum::<u16>() + fixed_widths.len() as u16 + 2;
+    let mut widths: Vec<Constraint> = fixed_widths
+        .iter()
+        .map(|&w| Constraint::Length(w))
+        .collect();
+    widths.push(Constraint::Length(area.width.saturating_sub(used)));
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(pane_block(title, app.focus == Pane::PacketList))
+        .widths(&widths)
+        .highlight_style(highlight_style());
+
+    let mut state = TableState::default();
+    state.select(app.pkt_list.selected.map(|s| s - app.pkt_list.offset));
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_details<B: Backend>(f: &mut Frame<B>, app: &mut TuiSharkApp, area: Rect) {
+    let details = Tree::new(app.pkt_tree.items.clone())
+        .block(pane_block(
+            "Packet Details".to_string(),
+            app.focus == Pane::Details,
+        ))
+        .highlight_style(highlight_style())
         .highlight_symbol(">> ");
 
-    f.render_stateful_widget(packet_view, chunks[0], &mut app.pkt_tree.state);
+    f.render_stateful_widget(details, area, &mut app.pkt_tree.state);
+}
 
-    let byte_text = if app.pkt_tree.state.selected().is_empty() {
-        Text::from("")
-    } else {
-        let idx = app.pkt_tree.state.selected()[0];
-        let bytepool = &app.raw_pkts[idx].bytepool;
-        let window_width = chunks[1].width - 2;
-        Text::from(bytepool.hexdump(window_width as usize))
+fn draw_bytes<B: Backend>(f: &mut Frame<B>, app: &mut TuiSharkApp, area: Rect) {
+    let mut title = "Byte View".to_string();
+    let byte_text = match app.pkt_list.selected_packet() {
+        Some(idx) => {
+            // Show whichever bytes the selected item was dissected from
+            let selected = ProtoItem::item_at(&app.details, &app.pkt_tree.state.selected());
+            let source = selected.map_or(0, |item| item.source);
+            match app.raw_pkts[idx].data_source(source) {
+                Some((name, bytepool)) => {
+                    if source != 0 {
+                        title = format!("Byte View: {} ({} bytes)", name, bytepool.len());
+                    }
+                    let window_width = area.width.saturating_sub(2);
+                    let highlight = selected.and_then(|item| item.range());
+                    Text::from(bytepool.hexdump(window_width as usize, highlight))
+                }
+                None => Text::from(""),
+            }
+        }
+        None => Text::from(""),
     };
 
     let bytes_paragraph = Paragraph::new(byte_text)
-        .block(Block::default().title("Byte View").borders(Borders::ALL))
+        .block(pane_block(title, app.focus == Pane::Bytes))
         .style(Style::default())
         .alignment(tui::layout::Alignment::Left)
         .wrap(Wrap { trim: false });
 
-    f.render_widget(bytes_paragraph, chunks[1]);
+    f.render_widget(bytes_paragraph, area);
+}
+
+fn draw_follow_stream<B: Backend>(f: &mut Frame<B>, view: &mut FollowView, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(4)].as_ref())
+        .split(area);
+
+    let stream = &view.stream;
+    let title = format!(
+        "Follow {} Stream {}: {} \u{2194} {} [{}, {}]",
+        stream.id.protocol, stream.index, stream.client, stream.server, view.format, view.direction
+    );
+    // Long lines wrap, so count the rows they take up to know how far down scrolling can go
+    let lines = view.lines();
+    let width = chunks[0].width.saturating_sub(2).max(1) as usize;
+    let height = chunks[0].height.saturating_sub(2) as usize;
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().max(1).div_ceil(width))
+        .sum();
+    view.scroll = view.scroll.min(rows.saturating_sub(height));
+
+    let text = Paragraph::new(Text::from(lines))
+        .block(pane_block(title, true))
+        .wrap(Wrap { trim: false })
+        .scroll((view.scroll.min(u16::MAX as usize) as u16, 0));
+    f.render_widget(text, chunks[0]);
+
+    let (client_bytes, server_bytes) = stream.byte_counts();
+    let status = vec![
+        Spans::from(vec![
+            Span::styled(
+                format!(
+                    "{} \u{2192} {} ({} bytes)",
+                    stream.client, stream.server, client_bytes
+                ),
+                followstream::client_style(),
+            ),
+            Span::raw("   "),
+            Span::styled(
+                format!(
+                    "{} \u{2192} {} ({} bytes)",
+                    stream.server, stream.client, server_bytes
+                ),
+                followstream::server_style(),
+            ),
+        ]),
+        Spans::from(
+            "a ASCII, x hex dump, c C arrays, r raw, d direction, n/p next/previous stream, Esc close",
+        ),
+    ];
+    let bar = Paragraph::new(status).block(Block::default().borders(Borders::ALL));
+    f.render_widget(bar, chunks[1]);
+}
+
+fn draw_expert_info<B: Backend>(f: &mut Frame<B>, view: &mut ExpertView, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(area);
+
+    let title = if view.is_empty() {
+        "Expert Information: none in the displayed packets".to_string()
+    } else {
+        "Expert Information (severity, group, protocol, summary, count)".to_string()
+    };
+    let tree = Tree::new(view.tree.items.clone())
+        .block(pane_block(title, true))
+        .highlight_style(highlight_style())
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(tree, chunks[0], &mut view.tree.state);
+
+    let help = Paragraph::new("Enter open group or go to packet, Esc close")
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, chunks[1]);
+}
+
+fn draw_conversations<B: Backend>(
+    f: &mut Frame<B>,
+    view: &mut ConversationsView,
+    read_only: bool,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let titles: Vec<Spans> = ConvType::ALL
+        .iter()
+        .map(|t| Spans::from(format!("{} \u{b7} {}", t, view.count(*t))))
+        .collect();
+    let selected = ConvType::ALL
+        .iter()
+        .position(|t| *t == view.conv_type)
+        .unwrap_or(0);
+    let tabs = Tabs::new(titles)
+        .block(pane_block(view.kind.to_string(), true))
+        .select(selected)
+        .highlight_style(highlight_style());
+    f.render_widget(tabs, chunks[0]);
+
+    let headers: Vec<String> = view
+        .headers()
+        .iter()
+        .enumerate()
+        .map(|(i, header)| match view.sort {
+            Some((column, true)) if column == i => format!("{} \u{25bc}", header),
+            Some((column, false)) if column == i => format!("{} \u{25b2}", header),
+            _ => header.to_string(),
+        })
+        .collect();
+    let rows = view.rows();
+    // Columns as wide as their widest cell, so addresses aren't cut short
+    let widths: Vec<Constraint> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            let widest = rows
+                .iter()
+                .map(|row| row.cells[i].chars().count())
+                .chain([header.chars().count()])
+                .max()
+                .unwrap_or_default();
+            Constraint::Length(widest as u16)
+        })
+        .collect();
+    let header = Row::new(headers).style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD),
+    );
+    let table = Table::new(rows.into_iter().map(|row| Row::new(row.cells)))
+        .header(header)
+        .block(Block::default().borders(Borders::ALL))
+        .column_spacing(2)
+        .widths(&widths)
+        .highlight_style(highlight_style());
+    f.render_stateful_widget(table, chunks[1], &mut view.state);
+
+    let help = if read_only {
+        "\u{2190}/\u{2192} layer, e conversations/endpoints, s sort column, r reverse, Esc close"
+    } else {
+        "\u{2190}/\u{2192} layer, e conversations/endpoints, s sort column, r reverse, Enter filter, Esc close"
+    };
+    let help = Paragraph::new(help).block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, chunks[2]);
+}
+
+fn draw_hierarchy<B: Backend>(f: &mut Frame<B>, view: &mut HierarchyView, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(area);
+
+    let block = pane_block("Protocol Hierarchy Statistics".to_string(), true);
+    let inner = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(inner);
+
+    // The highlight symbol and the open/closed marker come before each row's text
+    let header = Paragraph::new(format!("     {}", view.header())).style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_widget(header, rows[0]);
+    let tree = Tree::new(view.tree.items.clone())
+        .highlight_style(highlight_style())
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(tree, rows[1], &mut view.tree.state);
+
+    let help = Paragraph::new("Enter open or close, Esc close")
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, chunks[1]);
+}
+
+fn draw_io_graph<B: Backend>(f: &mut Frame<B>, view: &mut IoGraphView, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(view.series.len().max(1) as u16 + 2),
+                Constraint::Length(3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let unit = view.unit;
+    let points: Vec<Vec<(f64, f64)>> = view
+        .series
+        .iter()
+        .map(|series| {
+            series
+                .bins
+                .iter()
+                .enumerate()
+                .map(|(i, bin)| (i as f64, unit.value(bin) as f64))
+                .collect()
+        })
+        .collect();
+    let highest = view
+        .series
+        .iter()
+        .filter(|series| series.visible)
+        .flat_map(|series| series.bins.iter().map(|bin| unit.value(bin)))
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let last = view.bin_count().saturating_sub(1).max(1);
+    let cursor = [
+        (view.cursor as f64, 0.0),
+        (view.cursor as f64, highest as f64),
+    ];
+    let mut datasets: Vec<Dataset> = view
+        .series
+        .iter()
+        .zip(&points)
+        .filter(|(series, _)| series.visible)
+        .map(|(series, points)| {
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(series.color))
+                .data(points)
+        })
+        .collect();
+    datasets.push(
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(&cursor),
+    );
+
+    let title = format!(
+        "I/O Graph: {} per {}, cursor at {} s",
+        view.unit,
+        iostat::format_interval_length(view.interval_nanos()),
+        view.cursor_time()
+    );
+    let axis_style = Style::default().fg(Color::Gray);
+    let chart = Chart::new(datasets)
+        .block(pane_block(title, true))
+        // The series list below says which color is which
+        .hidden_legend_constraints((Constraint::Length(0), Constraint::Length(0)))
+        .x_axis(
+            Axis::default()
+                .title("Time (s)")
+                .style(axis_style)
+                .bounds([0.0, last as f64])
+                .labels(
+                    [0, last / 2, last]
+                        .iter()
+                        .map(|&bin| Span::raw(view.axis_label(bin)))
+                        .collect(),
+                ),
+        )
+        .y_axis(
+            Axis::default()
+                .title(view.unit.to_string())
+                .style(axis_style)
+                .bounds([0.0, highest as f64])
+                .labels(if highest > 1 {
+                    vec![
+                        Span::raw("0"),
+                        Span::raw((highest / 2).to_string()),
+                        Span::raw(highest.to_string()),
+                    ]
+                } else {
+                    vec![Span::raw("0"), Span::raw("1")]
+                }),
+        );
+    f.render_widget(chart, chunks[0]);
+
+    // One line per series, with what it counts in the cursor's interval
+    let lines: Vec<Spans> = view
+        .series
+        .iter()
+        .enumerate()
+        .map(|(i, series)| {
+            let bin = series.bins.get(view.cursor).copied().unwrap_or_default();
+            let text = format!(
+                "[{}] {}: {} packets, {} bytes",
+                if series.visible { 'x' } else { ' ' },
+                series.name(),
+                bin.packets,
+                bin.bytes
+            );
+            let style = if i == view.selected {
+                highlight_style()
+            } else {
+                Style::default()
+            };
+            Spans::from(vec![
+                Span::styled("\u{25a0} ", Style::default().fg(series.color)),
+                Span::styled(text, style),
+            ])
+        })
+        .collect();
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(format!(
+        "Series (interval starting at {} s)",
+        view.cursor_time()
+    )));
+    f.render_widget(list, chunks[1]);
+
+    match &view.input {
+        Some(input) => {
+            let title = match &view.input_error {
+                Some(err) => format!("Series Filter: {}", err),
+                None => "Series Filter (Enter to add, Esc to cancel)".to_string(),
+            };
+            let style = match (&view.input_error, input.trim().is_empty()) {
+                (Some(_), _) => Style::default().fg(Color::Black).bg(Color::LightRed),
+                (None, false) => Style::default().fg(Color::Black).bg(Color::LightGreen),
+                (None, true) => Style::default(),
+            };
+            let bar = Paragraph::new(input.clone())
+                .style(style)
+                .block(pane_block(title, true));
+            f.render_widget(bar, chunks[2]);
+            let cursor_x = chunks[2].x + 1 + input.chars().count() as u16;
+            f.set_cursor(
+                cursor_x.min(chunks[2].right().saturating_sub(2)),
+                chunks[2].y + 1,
+            );
+        }
+        None => {
+            let help = Paragraph::new(
+                "\u{2190}/\u{2192} interval, +/- interval length, u packets/bytes, \u{2191}/\u{2193} series, a add, d delete, Space show/hide, Enter go to packet, Esc close",
+            )
+            .block(Block::default().borders(Borders::ALL));
+            f.render_widget(help, chunks[2]);
+        }
+    }
+}
+
+fn draw_filter_bar<B: Backend>(f: &mut Frame<B>, app: &TuiSharkApp, area: Rect) {
+    let title = match &app.filter_error {
+        Some(err) => format!("Display Filter: {}", err),
+        None if app.editing_filter => {
+            // Describe the field name just typed, if it is one
+            let word = app
+                .filter_input
+                .rsplit(|c: char| !(c.is_alphanumeric() || c == '.' || c == '_'))
+                .next()
+                .unwrap_or_default();
+            match app.registry.field_info(word) {
+                Some(field) => format!(
+                    "Display Filter: {} ({}) (Enter to apply, Esc to cancel)",
+                    field.description, field.kind
+                ),
+                None => "Display Filter (Enter to apply, Esc to cancel)".to_string(),
+            }
+        }
+        None if app.read_only => "Display Filter (read-only)".to_string(),
+        None => "Display Filter (/ to edit)".to_string(),
+    };
+
+    let style = match (&app.filter_error, app.filter_input.trim().is_empty()) {
+        (Some(_), _) => Style::default().fg(Color::Black).bg(Color::LightRed),
+        (None, false) => Style::default().fg(Color::Black).bg(Color::LightGreen),
+        (None, true) => Style::default(),
+    };
+
+    let bar = Paragraph::new(app.filter_input.clone())
+        .style(style)
+        .block(pane_block(title, app.editing_filter));
+    f.render_widget(bar, area);
+
+    if app.editing_filter {
+        let cursor_x = area.x + 1 + app.filter_input.chars().count() as u16;
+        f.set_cursor(cursor_x.min(area.right().saturating_sub(2)), area.y + 1);
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut TuiSharkApp) {
+    let outer = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(f.size());
+
+    draw_filter_bar(f, app, outer[0]);
+
+    if let Some(view) = &mut app.follow {
+        draw_follow_stream(f, view, outer[1]);
+        return;
+    }
+    if let Some(view) = &mut app.expert {
+        draw_expert_info(f, view, outer[1]);
+        return;
+    }
+    if let Some(view) = &mut app.conversations {
+        draw_conversations(f, view, app.read_only, outer[1]);
+        return;
+    }
+    if let Some(view) = &mut app.hierarchy {
+        draw_hierarchy(f, view, outer[1]);
+        return;
+    }
+    if let Some(view) = &mut app.iograph {
+        draw_io_graph(f, view, outer[1]);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            app.pane_sizes
+                .iter()
+                .map(|&p| Constraint::Percentage(p))
+                .collect::<Vec<Constraint>>(),
+        )
+        .split(outer[1]);
+
+    draw_packet_list(f, app, chunks[0]);
+    draw_details(f, app, chunks[1]);
+    draw_bytes(f, app, chunks[2]);
 }
 
 fn run_app<B: Backend>(
@@ -114,8 +1276,7 @@ fn run_app<B: Backend>(
     let mut state_changed = true;
     loop {
         if state_changed {
-            let treeobject = app.pkt_tree.items.clone();
-            terminal.draw(|f| ui(f, &mut app, treeobject))?;
+            terminal.draw(|f| ui(f, &mut app))?;
             state_changed = false;
         }
 
@@ -125,15 +1286,10 @@ fn run_app<B: Backend>(
         if crossterm::event::poll(timeout)? {
             match event::read()? {
                 Event::Key(key) => {
-                    match key.code {
-                        KeyCode::Left => app.pkt_tree.left(),
-                        KeyCode::Right => app.pkt_tree.right(),
-                        KeyCode::Down => app.pkt_tree.down(),
-                        KeyCode::Up => app.pkt_tree.up(),
-
-                        KeyCode::Char('q') => return Ok(()),
-                        _ => {}
+                    if key.code == KeyCode::Char('q') && !app.typing() {
+                        return Ok(());
                     }
+                    app.on_key(key.code);
                     state_changed = true;
                 }
                 Event::Resize(_, _) => {
@@ -149,20 +1305,61 @@ fn run_app<B: Backend>(
     }
 }
 
-#[cfg(target_os = "linux")]
-fn get_hardcoded_path() -> &'static str {
-    "/home/sl/references/ICS-Security-Tools/pcaps/ModbusTCP/ModbusTCP.pcap"
-}
-#[cfg(target_os = "macos")]
-fn get_hardcoded_path() -> &'static str {
-    "/Users/slitchfield3/reference/ICS-Security-Tools/pcaps/ModbusTCP/ModbusTCP_trunc.pcap"
-}
-#[cfg(target_os = "windows")]
-fn get_hardcoded_path() -> &'static str {
-    "C:/Users/samue/Documents/Github/ICS-Security-Tools/pcaps/ModbusTCP/ModbusTCP_trunc.pcap"
-}
-
 fn main() -> Result<(), io::Error> {
+    let args = Args::parse();
+
+    if !args.path.is_file() {
+        eprintln!(
+            "tuishark: capture file '{}' does not exist or is not a regular file",
+            args.path.display()
+        );
+        process::exit(1);
+    }
+
+    let mut app = TuiSharkApp::from_args(&args);
+    if let Err(err) = app.load_packets_from_file(&args.path) {
+        eprintln!(
+            "tuishark: could not read '{}': {}",
+            args.path.display(),
+            err
+        );
+        process::exit(1);
+    }
+
+    if let Err(err) = app.apply_filter() {
+        eprintln!("tuishark: invalid display filter: {}", err);
+        process::exit(1);
+    }
+
+    if let Some(num) = args.start_packet {
+        if num >= app.raw_pkts.len() {
+            eprintln!(
+                "tuishark: start packet {} is past the end of the capture ({} packets)",
+                num,
+                app.raw_pkts.len()
+            );
+            process::exit(1);
+        }
+        if !app.displayed.contains(&num) {
+            eprintln!(
+                "tuishark: start packet {} is hidden by the display filter",
+                num
+            );
+            process::exit(1);
+        }
+    }
+
+    if args.headless {
+        // Output piped into something like `head` may stop being read at any point
+        return match app.print_packets() {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            res => res,
+        };
+    }
+    if let Some(num) = args.start_packet {
+        app.select_packet(num);
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -170,9 +1367,6 @@ fn main() -> Result<(), io::Error> {
     let mut terminal = Terminal::new(backend)?;
 
     let tick_rate = Duration::from_millis(20);
-    let mut app = TuiSharkApp::new();
-    let path = get_hardcoded_path();
-    app.load_packets_from_file(path.to_string());
     let res = run_app(&mut terminal, app, tick_rate);
 
     disable_raw_mode()?;
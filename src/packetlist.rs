@@ -0,0 +1,101 @@
+// Scrollable packet list state. Only the rows inside the visible window are handed to the
+//   table widget, so large captures stay cheap to draw.
+
+/// Column text of one packet list row
+#[derive(Clone, Debug)]
+pub struct PacketRow {
+    /// Index into the app's packet vector
+    pub pkt_idx: usize,
+    pub num: String,
+    pub time: String,
+    pub source: String,
+    pub destination: String,
+    pub protocol: String,
+    pub length: String,
+    pub info: String,
+}
+
+pub struct PacketList {
+    pub rows: Vec<PacketRow>,
+    /// Selected row, if any rows exist
+    pub selected: Option<usize>,
+    /// First row inside the visible window
+    pub offset: usize,
+}
+
+impl PacketList {
+    pub fn new() -> Self {
+        PacketList {
+            rows: vec![],
+            selected: None,
+            offset: 0,
+        }
+    }
+
+    /// Replaces the rows, keeping the selection on the same packet when it is still listed
+    pub fn set_rows(&mut self, rows: Vec<PacketRow>) {
+        let selected_pkt = self.selected_packet();
+        self.rows = rows;
+        self.selected = match selected_pkt {
+            Some(pkt_idx) => self
+                .rows
+                .iter()
+                .position(|r| r.pkt_idx == pkt_idx)
+                .or(if self.rows.is_empty() { None } else { Some(0) }),
+            None if !self.rows.is_empty() => Some(0),
+            None => None,
+        };
+    }
+
+    /// Index into the app's packet vector of the selected row
+    pub fn selected_packet(&self) -> Option<usize> {
+        self.selected.map(|i| self.rows[i].pkt_idx)
+    }
+
+    pub fn select(&mut self, row: usize) {
+        if !self.rows.is_empty() {
+            self.selected = Some(row.min(self.rows.len() - 1));
+        }
+    }
+
+    /// Selects the row showing packet `pkt_idx`, if it is listed
+    pub fn select_packet(&mut self, pkt_idx: usize) {
+        if let Some(row) = self.rows.iter().position(|r| r.pkt_idx == pkt_idx) {
+            self.selected = Some(row);
+        }
+    }
+
+    pub fn down(&mut self, count: usize) {
+        if let Some(selected) = self.selected {
+            self.select(selected.saturating_add(count));
+        }
+    }
+
+    pub fn up(&mut self, count: usize) {
+        if let Some(selected) = self.selected {
+            self.select(selected.saturating_sub(count));
+        }
+    }
+
+    pub fn first(&mut self) {
+        self.select(0);
+    }
+
+    pub fn last(&mut self) {
+        self.select(self.rows.len().saturating_sub(1));
+    }
+
+    /// Scrolls so the selection is inside a window of `height` rows, and returns that window
+    pub fn visible(&mut self, height: usize) -> &[PacketRow] {
+        if let Some(selected) = self.selected {
+            if selected < self.offset {
+                self.offset = selected;
+            } else if height > 0 && selected >= self.offset + height {
+                self.offset = selected + 1 - height;
+            }
+        }
+        self.offset = self.offset.min(self.rows.len().saturating_sub(1));
+        let end = (self.offset + height).min(self.rows.len());
+        &self.rows[self.offset..end]
+    }
+}
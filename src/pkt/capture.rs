@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use pcap_parser::pcapng::{Block, NameRecordType, OptionCode};
+use pcap_parser::traits::PcapNGPacketBlock;
+use pcap_parser::{Linktype, PcapBlockOwned, PcapError};
+
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::timestamp::Timestamp;
+use crate::pkt::Packet;
+
+/// Timestamp resolutions of legacy pcap files, in units per second
+const USEC_RESOLUTION: u64 = 1_000_000;
+const NSEC_RESOLUTION: u64 = 1_000_000_000;
+
+/// pcapng `if_name` option code
+const IF_NAME: OptionCode = OptionCode(2);
+
+/// A capture interface, as described by a pcapng Interface Description Block. Legacy pcap files
+/// have a single implicit interface built from the file header.
+#[derive(Clone, Debug)]
+pub struct Interface {
+    pub name: Option<String>,
+    pub linktype: Linktype,
+    pub snaplen: u32,
+    /// Timestamp units per second
+    pub ts_resolution: u64,
+    /// Seconds added to every timestamp on this interface
+    pub ts_offset: u64,
+}
+
+impl Interface {
+    fn legacy(header: &pcap_parser::PcapHeader) -> Self {
+        Interface {
+            name: None,
+            linktype: header.network,
+            snaplen: header.snaplen,
+            ts_resolution: if header.is_nanosecond_precision() {
+                NSEC_RESOLUTION
+            } else {
+                USEC_RESOLUTION
+            },
+            ts_offset: 0,
+        }
+    }
+
+    fn timestamp(&self, units: u64) -> Timestamp {
+        Timestamp::from_units(units, self.ts_resolution, self.ts_offset)
+    }
+}
+
+/// A pcapng Custom Block, kept verbatim
+#[derive(Clone, Debug)]
+pub struct CustomBlock {
+    /// Private Enterprise Number of the organization that defined the block
+    pub pen: u32,
+    pub data: Vec<u8>,
+    /// Whether tools rewriting the file may copy the block over
+    pub copyable: bool,
+}
+
+impl fmt::Display for CustomBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pcapng Custom Block, PEN: {}, {} bytes",
+            self.pen,
+            self.data.len()
+        )
+    }
+}
+
+impl CustomBlock {
+    /// Details tree of the block, for the frame it was read before
+    pub fn to_proto_item(&self) -> ProtoItem {
+        let hex: Vec<String> = self.data.iter().map(|b| format!("{:02x}", b)).collect();
+        ProtoItem::new(
+            self.to_string(),
+            0,
+            0,
+            vec![
+                ProtoItem::new_leaf(format!("Private Enterprise Number: {}", self.pen), 0, 0),
+                ProtoItem::new_leaf(
+                    format!("Copyable: {}", if self.copyable { "Yes" } else { "No" }),
+                    0,
+                    0,
+                ),
+                ProtoItem::new_leaf(format!("Custom Data: {}", hex.join(":")), 0, 0),
+            ],
+        )
+    }
+}
+
+/// Everything read out of a capture file
+#[derive(Clone, Debug, Default)]
+pub struct Capture {
+    pub packets: Vec<Packet>,
+    /// Interfaces, indexed by the interface id stored on each packet. pcapng interface ids are
+    /// per-section, so interfaces from later sections are appended after earlier ones.
+    pub interfaces: Vec<Interface>,
+    /// Addresses resolved by Name Resolution Blocks
+    pub names: HashMap<IpAddr, String>,
+    /// Custom Blocks read since the last packet, which go with the next one
+    custom_blocks: Vec<CustomBlock>,
+    /// Whether a packet with a timestamp of its own has been read yet
+    timestamped: bool,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn option_string(options: &[pcap_parser::PcapNGOption], code: OptionCode) -> Option<String> {
+    options.iter().find(|o| o.code == code).map(|o| {
+        let len = (o.len as usize).min(o.value.len());
+        String::from_utf8_lossy(&o.value[..len])
+            .trim_end_matches('\0')
+            .to_string()
+    })
+}
+
+/// Parses a name record value into its address and first name. Values are an address followed
+/// by one or more NUL-terminated names.
+fn parse_name_record(record_type: NameRecordType, value: &[u8]) -> Option<(IpAddr, String)> {
+    let (addr, rest): (IpAddr, &[u8]) = match record_type {
+        NameRecordType::Ipv4 if value.len() >= 4 => {
+            let mut octets = [0u8; 4];
+            octets.copy_from_slice(&value[..4]);
+            (IpAddr::V4(Ipv4Addr::from(octets)), &value[4..])
+        }
+        NameRecordType::Ipv6 if value.len() >= 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[..16]);
+            (IpAddr::V6(Ipv6Addr::from(octets)), &value[16..])
+        }
+        _ => return None,
+    };
+    let name = rest.split(|b| *b == 0).find(|n| !n.is_empty())?;
+    Some((addr, String::from_utf8_lossy(name).to_string()))
+}
+
+impl Capture {
+    /// Adds a packet. Packets without a `timestamp` take the previous packet's, or the next
+    /// timestamped one's when they come first, so they never move the start of the capture.
+    fn push_packet(&mut self, interface_id: usize, timestamp: Option<Timestamp>, data: &[u8]) {
+        let timestamp = match timestamp {
+            Some(timestamp) => {
+                if !self.timestamped {
+                    for pkt in &mut self.packets {
+                        pkt.timestamp = timestamp;
+                    }
+                    self.timestamped = true;
+                }
+                timestamp
+            }
+            None => self.packets.last().map(|p| p.timestamp).unwrap_or_default(),
+        };
+        let mut pkt = Packet::new();
+        pkt.num = self.packets.len();
+        pkt.bytepool.bytes.extend_from_slice(data);
+        pkt.interface_id = interface_id;
+        pkt.timestamp = timestamp;
+        pkt.custom_blocks = std::mem::take(&mut self.custom_blocks);
+        pkt.linktype = self
+            .interfaces
+            .get(interface_id)
+            .map(|i| i.linktype)
+            .unwrap_or(Linktype::NULL);
+        self.packets.push(pkt);
+    }
+
+    fn handle_ng_block(&mut self, block: Block, section_start: &mut usize) {
+        match block {
+            Block::SectionHeader(_) => {
+                // Interface ids restart at 0 in each section
+                *section_start = self.interfaces.len();
+            }
+            Block::InterfaceDescription(idb) => {
+                self.interfaces.push(Interface {
+                    name: option_string(&idb.options, IF_NAME),
+                    linktype: idb.linktype,
+                    snaplen: idb.snaplen,
+                    ts_resolution: idb.ts_resolution().unwrap_or(USEC_RESOLUTION),
+                    ts_offset: idb.ts_offset(),
+                });
+            }
+            Block::EnhancedPacket(epb) => {
+                let interface_id = *section_start + epb.if_id as usize;
+                let units = ((epb.ts_high as u64) << 32) | epb.ts_low as u64;
+                let timestamp = match self.interfaces.get(interface_id) {
+                    Some(interface) => interface.timestamp(units),
+                    None => Timestamp::from_units(units, USEC_RESOLUTION, 0),
+                };
+                self.push_packet(interface_id, Some(timestamp), epb.packet_data());
+            }
+            Block::SimplePacket(spb) => {
+                // Simple packets always belong to the first interface of the section, and are
+                // truncated to its snaplen. They carry no timestamp.
+                let interface_id = *section_start;
+                let mut data = spb.packet_data();
+                if let Some(interface) = self.interfaces.get(interface_id) {
+                    if interface.snaplen != 0 && data.len() > interface.snaplen as usize {
+                        data = &data[..interface.snaplen as usize];
+                    }
+                }
+                self.push_packet(interface_id, None, data);
+            }
+            Block::NameResolution(nrb) => {
+                for record in &nrb.nr {
+                    if let Some((addr, name)) =
+                        parse_name_record(record.record_type, record.record_value)
+                    {
+                        self.names.entry(addr).or_insert(name);
+                    }
+                }
+            }
+            Block::Custom(cb) => {
+                self.custom_blocks.push(CustomBlock {
+                    pen: cb.pen,
+                    data: cb.data.to_vec(),
+                    copyable: !cb.do_not_copy(),
+                });
+            }
+            // Statistics, journal export and decryption secrets blocks carry nothing we display
+            _ => {}
+        }
+    }
+}
+
+/// Reads every block of a pcap or pcapng file
+pub fn read_capture_file<P: AsRef<Path>>(path: P) -> io::Result<Capture> {
+    let file = File::open(path)?;
+    let mut reader = pcap_parser::create_reader(65536, file)
+        .map_err(|e| invalid_data(format!("not a pcap or pcapng file: {:?}", e)))?;
+
+    let mut capture = Capture::default();
+    let mut section_start: usize = 0;
+
+    loop {
+        match reader.next() {
+            Ok((offset, block)) => {
+                match block {
+                    PcapBlockOwned::Legacy(legacyblock) => {
+                        // ts_usec holds nanoseconds in nanosecond-resolution files
+                        let timestamp = match capture.interfaces.first() {
+                            Some(interface) => interface.timestamp(
+                                legacyblock.ts_sec as u64 * interface.ts_resolution
+                                    + legacyblock.ts_usec as u64,
+                            ),
+                            None => Timestamp::default(),
+                        };
+                        capture.push_packet(0, Some(timestamp), legacyblock.data);
+                    }
+                    PcapBlockOwned::LegacyHeader(legacyheader) => {
+                        capture.interfaces = vec![Interface::legacy(&legacyheader)];
+                    }
+                    PcapBlockOwned::NG(block) => {
+                        capture.handle_ng_block(block, &mut section_start);
+                    }
+                }
+                reader.consume(offset);
+            }
+            Err(PcapError::Eof) => break,
+            Err(PcapError::Incomplete) => {
+                if reader.reader_exhausted() {
+                    // The capture was cut short in the middle of a block; keep what we have
+                    break;
+                }
+                reader
+                    .refill()
+                    .map_err(|e| invalid_data(format!("error while reading: {:?}", e)))?;
+            }
+            Err(e) => return Err(invalid_data(format!("error while reading: {:?}", e))),
+        }
+    }
+
+    // Custom Blocks after the last packet go with it instead
+    if let Some(last) = capture.packets.last_mut() {
+        last.custom_blocks.append(&mut capture.custom_blocks);
+    }
+    Ok(capture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A pcapng block of type `block_type`, padding `body` to 32 bits
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize(body.len().next_multiple_of(4), 0);
+        let len = (12 + body.len()) as u32;
+        let mut bytes = vec![];
+        bytes.extend(block_type.to_le_bytes());
+        bytes.extend(len.to_le_bytes());
+        bytes.extend(body);
+        bytes.extend(len.to_le_bytes());
+        bytes
+    }
+
+    fn section_header() -> Vec<u8> {
+        let mut body = vec![];
+        body.extend(0x1A2B3C4Du32.to_le_bytes());
+        body.extend([1, 0, 0, 0]);
+        body.extend((-1i64).to_le_bytes());
+        block(0x0A0D0D0A, &body)
+    }
+
+    /// An Ethernet interface with microsecond timestamps
+    fn interface() -> Vec<u8> {
+        block(1, &[1, 0, 0, 0, 0, 0, 0, 0])
+    }
+
+    fn enhanced_packet(micros: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![0; 4];
+        body.extend(((micros >> 32) as u32).to_le_bytes());
+        body.extend((micros as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend(data);
+        block(6, &body)
+    }
+
+    fn simple_packet(data: &[u8]) -> Vec<u8> {
+        let mut body = (data.len() as u32).to_le_bytes().to_vec();
+        body.extend(data);
+        block(3, &body)
+    }
+
+    fn name_resolution(records: &[([u8; 4], &str)]) -> Vec<u8> {
+        let mut body = vec![];
+        for (addr, name) in records {
+            let mut value = addr.to_vec();
+            value.extend(name.as_bytes());
+            value.push(0);
+            body.extend(1u16.to_le_bytes());
+            body.extend((value.len() as u16).to_le_bytes());
+            body.extend(&value);
+            body.resize(body.len().next_multiple_of(4), 0);
+        }
+        body.extend([0; 4]);
+        block(4, &body)
+    }
+
+    fn custom(copyable: bool, data: &[u8]) -> Vec<u8> {
+        let mut body = 32473u32.to_le_bytes().to_vec();
+        body.extend(data);
+        block(if copyable { 0x0BAD } else { 0x4000_0BAD }, &body)
+    }
+
+    /// Reads `blocks` back as a capture file
+    fn read(name: &str, blocks: &[Vec<u8>]) -> Capture {
+        let path =
+            std::env::temp_dir().join(format!("tuishark-{}-{}.pcapng", std::process::id(), name));
+        File::create(&path)
+            .unwrap()
+            .write_all(&blocks.concat())
+            .unwrap();
+        let capture = read_capture_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        capture.unwrap()
+    }
+
+    #[test]
+    fn name_resolution_records_are_kept() {
+        let capture = read(
+            "names",
+            &[
+                section_header(),
+                interface(),
+                name_resolution(&[([10, 0, 0, 5], "plc1"), ([10, 0, 0, 1], "hmi")]),
+                enhanced_packet(0, &[0; 14]),
+            ],
+        );
+        assert_eq!(capture.names.len(), 2);
+        assert_eq!(capture.names[&"10.0.0.5".parse().unwrap()], "plc1");
+        assert_eq!(capture.names[&"10.0.0.1".parse().unwrap()], "hmi");
+    }
+
+    #[test]
+    fn custom_blocks_go_with_the_next_packet_or_else_the_last() {
+        let capture = read(
+            "custom",
+            &[
+                section_header(),
+                custom(true, b"abcd"),
+                interface(),
+                enhanced_packet(0, &[0; 14]),
+                enhanced_packet(1, &[0; 14]),
+                custom(false, b"efgh"),
+            ],
+        );
+        let blocks: Vec<Vec<(u32, &[u8], bool)>> = capture
+            .packets
+            .iter()
+            .map(|pkt| {
+                pkt.custom_blocks
+                    .iter()
+                    .map(|b| (b.pen, b.data.as_slice(), b.copyable))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                vec![(32473, &b"abcd"[..], true)],
+                vec![(32473, &b"efgh"[..], false)]
+            ]
+        );
+    }
+
+    fn secs(capture: &Capture) -> Vec<i64> {
+        capture.packets.iter().map(|p| p.timestamp.secs).collect()
+    }
+
+    #[test]
+    fn simple_packets_take_the_previous_timestamp() {
+        let capture = read(
+            "spb-after",
+            &[
+                section_header(),
+                interface(),
+                enhanced_packet(1_700_000_000_000_000, &[0; 14]),
+                simple_packet(&[0; 14]),
+                enhanced_packet(1_700_000_002_000_000, &[0; 14]),
+            ],
+        );
+        assert_eq!(
+            secs(&capture),
+            vec![1_700_000_000, 1_700_000_000, 1_700_000_002]
+        );
+    }
+
+    #[test]
+    fn leading_simple_packets_take_the_first_timestamp() {
+        let capture = read(
+            "spb-first",
+            &[
+                section_header(),
+                interface(),
+                simple_packet(&[0; 14]),
+                simple_packet(&[0; 14]),
+                enhanced_packet(1_700_000_000_000_000, &[0; 14]),
+                enhanced_packet(1_700_000_002_000_000, &[0; 14]),
+            ],
+        );
+        assert_eq!(
+            secs(&capture),
+            vec![1_700_000_000, 1_700_000_000, 1_700_000_000, 1_700_000_002]
+        );
+        assert_eq!(capture.packets[0].timestamp.digits, 6);
+    }
+}
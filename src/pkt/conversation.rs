@@ -0,0 +1,281 @@
+// Conversation and endpoint statistics: the traffic between each pair of addresses, and to and
+//   from each address, at the Ethernet, IP and transport layers. Each packet counts once per
+//   layer, at the first layer of that kind, and whatever an ICMP error quotes isn't counted.
+
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::pkt::dissectors::tcp::Tcp;
+use crate::pkt::dissectors::udp::Udp;
+use crate::pkt::timestamp::Timestamp;
+use crate::pkt::{Layer, Packet};
+
+/// Layers conversations are tracked at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConvType {
+    Ethernet,
+    Ipv4,
+    Ipv6,
+    Tcp,
+    Udp,
+}
+
+impl ConvType {
+    pub const ALL: [ConvType; 5] = [
+        ConvType::Ethernet,
+        ConvType::Ipv4,
+        ConvType::Ipv6,
+        ConvType::Tcp,
+        ConvType::Udp,
+    ];
+
+    /// Whether endpoints are told apart by port as well as address
+    pub fn has_ports(self) -> bool {
+        matches!(self, ConvType::Tcp | ConvType::Udp)
+    }
+
+    /// Filter field matching either address of a packet at this layer, given one of them
+    fn address_field(self, address: &str) -> &'static str {
+        match self {
+            ConvType::Ethernet => "eth.addr",
+            ConvType::Ipv4 => "ip.addr",
+            ConvType::Ipv6 => "ipv6.addr",
+            // Transport endpoints sit on either IP version
+            ConvType::Tcp | ConvType::Udp if address.contains(':') => "ipv6.addr",
+            ConvType::Tcp | ConvType::Udp => "ip.addr",
+        }
+    }
+
+    fn port_field(self) -> &'static str {
+        match self {
+            ConvType::Udp => "udp.port",
+            _ => "tcp.port",
+        }
+    }
+}
+
+impl fmt::Display for ConvType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvType::Ethernet => write!(f, "Ethernet"),
+            ConvType::Ipv4 => write!(f, "IPv4"),
+            ConvType::Ipv6 => write!(f, "IPv6"),
+            ConvType::Tcp => write!(f, "TCP"),
+            ConvType::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+/// An address, and for TCP and UDP a port
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Endpoint {
+    pub address: String,
+    pub port: Option<u16>,
+}
+
+impl Endpoint {
+    /// Filter matching packets to or from the endpoint
+    fn filter(&self, conv_type: ConvType) -> String {
+        let address = format!(
+            "{} == {}",
+            conv_type.address_field(&self.address),
+            self.address
+        );
+        match self.port {
+            Some(port) => format!("{} && {} == {}", address, conv_type.port_field(), port),
+            None => address,
+        }
+    }
+}
+
+/// Packets and bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub packets: usize,
+    pub bytes: usize,
+}
+
+impl Traffic {
+    fn add(&mut self, bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes;
+    }
+
+    pub fn total(self, other: Traffic) -> Traffic {
+        Traffic {
+            packets: self.packets + other.packets,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+/// Traffic between two endpoints, the lower one first
+#[derive(Clone, Debug)]
+pub struct Conversation {
+    pub a: Endpoint,
+    pub b: Endpoint,
+    pub a_to_b: Traffic,
+    pub b_to_a: Traffic,
+    /// Capture times of the first and last packets
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+impl Conversation {
+    pub fn total(&self) -> Traffic {
+        self.a_to_b.total(self.b_to_a)
+    }
+
+    /// Filter matching the conversation's packets
+    pub fn filter(&self, conv_type: ConvType) -> String {
+        format!(
+            "{} && {}",
+            self.a.filter(conv_type),
+            self.b.filter(conv_type)
+        )
+    }
+}
+
+/// Traffic to and from one endpoint
+#[derive(Clone, Debug)]
+pub struct EndpointStats {
+    pub endpoint: Endpoint,
+    pub tx: Traffic,
+    pub rx: Traffic,
+}
+
+impl EndpointStats {
+    pub fn total(&self) -> Traffic {
+        self.tx.total(self.rx)
+    }
+
+    pub fn filter(&self, conv_type: ConvType) -> String {
+        self.endpoint.filter(conv_type)
+    }
+}
+
+/// Source and destination of `pkt` at each layer conversations are tracked at
+fn packet_endpoints(pkt: &Packet) -> Vec<(ConvType, Endpoint, Endpoint)> {
+    let mut found: Vec<(ConvType, Endpoint, Endpoint)> = vec![];
+    let mut addresses: Option<(String, String)> = None;
+    for layer in &pkt.layers {
+        let inner = match layer {
+            Layer::Protocol(inner) => inner,
+            _ => break,
+        };
+        let conv_type = match inner.name() {
+            "eth" => ConvType::Ethernet,
+            "ip" => ConvType::Ipv4,
+            "ipv6" => ConvType::Ipv6,
+            "tcp" => ConvType::Tcp,
+            "udp" => ConvType::Udp,
+            // What follows is the packet that caused the error, not this one
+            "icmp" | "icmpv6" => break,
+            _ => continue,
+        };
+        let (src, dst) = if conv_type.has_ports() {
+            let (sport, dport) = match (layer.downcast::<Tcp>(), layer.downcast::<Udp>()) {
+                (Some(tcp), _) => tcp.ports(),
+                (_, Some(udp)) => udp.ports(),
+                _ => continue,
+            };
+            let (src, dst) = match &addresses {
+                Some(addresses) => addresses.clone(),
+                None => continue,
+            };
+            (
+                Endpoint {
+                    address: src,
+                    port: Some(sport),
+                },
+                Endpoint {
+                    address: dst,
+                    port: Some(dport),
+                },
+            )
+        } else {
+            let (src, dst) = match inner.addresses() {
+                Some(addresses) => addresses,
+                None => continue,
+            };
+            if conv_type != ConvType::Ethernet {
+                addresses = Some((src.clone(), dst.clone()));
+            }
+            (
+                Endpoint {
+                    address: src,
+                    port: None,
+                },
+                Endpoint {
+                    address: dst,
+                    port: None,
+                },
+            )
+        };
+        if !found.iter().any(|(seen, ..)| *seen == conv_type) {
+            found.push((conv_type, src, dst));
+        }
+    }
+    found
+}
+
+/// Every conversation in `pkts` at each layer, in order of their first packet
+pub fn conversations(pkts: &[Packet]) -> HashMap<ConvType, Vec<Conversation>> {
+    let mut tables: HashMap<ConvType, Vec<Conversation>> = HashMap::new();
+    // Position of each conversation in its table
+    let mut index: HashMap<(ConvType, Endpoint, Endpoint), usize> = HashMap::new();
+    for pkt in pkts {
+        let bytes = pkt.caplen();
+        for (conv_type, src, dst) in packet_endpoints(pkt) {
+            let forward = src <= dst;
+            let (a, b) = if forward { (src, dst) } else { (dst, src) };
+            let table = tables.entry(conv_type).or_default();
+            let idx = *index
+                .entry((conv_type, a.clone(), b.clone()))
+                .or_insert_with(|| {
+                    table.push(Conversation {
+                        a,
+                        b,
+                        a_to_b: Traffic::default(),
+                        b_to_a: Traffic::default(),
+                        start: pkt.timestamp,
+                        end: pkt.timestamp,
+                    });
+                    table.len() - 1
+                });
+            let conversation = &mut table[idx];
+            if forward {
+                conversation.a_to_b.add(bytes);
+            } else {
+                conversation.b_to_a.add(bytes);
+            }
+            conversation.start = conversation.start.min(pkt.timestamp);
+            conversation.end = conversation.end.max(pkt.timestamp);
+        }
+    }
+    tables
+}
+
+/// Every endpoint of `conversations`, in order of their first conversation
+pub fn endpoints(conversations: &[Conversation]) -> Vec<EndpointStats> {
+    let mut stats: Vec<EndpointStats> = vec![];
+    let mut index: HashMap<Endpoint, usize> = HashMap::new();
+    for conversation in conversations {
+        for (endpoint, tx, rx) in [
+            (&conversation.a, conversation.a_to_b, conversation.b_to_a),
+            (&conversation.b, conversation.b_to_a, conversation.a_to_b),
+        ] {
+            let idx = *index.entry(endpoint.clone()).or_insert_with(|| {
+                stats.push(EndpointStats {
+                    endpoint: endpoint.clone(),
+                    tx: Traffic::default(),
+                    rx: Traffic::default(),
+                });
+                stats.len() - 1
+            });
+            stats[idx].tx = stats[idx].tx.total(tx);
+            stats[idx].rx = stats[idx].rx.total(rx);
+        }
+    }
+    stats
+}
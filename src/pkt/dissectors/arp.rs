@@ -0,0 +1,517 @@
+// ARP and RARP. Besides decoding each packet, the dissector remembers which hardware address
+//   every protocol address was first claimed by, so spoofed or duplicate addresses stand out.
+
+use core::fmt;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::ethernet::{mac_to_string, Ethertype};
+use crate::pkt::dissectors::util;
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::expert::{ExpertInfo, Severity};
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+const FIXED_LEN: usize = 8;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("arp.hw.type", FieldKind::UInt, "Hardware type"),
+    FieldInfo::new("arp.proto.type", FieldKind::UInt, "Protocol type"),
+    FieldInfo::new("arp.hw.size", FieldKind::UInt, "Hardware size"),
+    FieldInfo::new("arp.proto.size", FieldKind::UInt, "Protocol size"),
+    FieldInfo::new("arp.opcode", FieldKind::UInt, "Opcode"),
+    FieldInfo::new("arp.src.hw_mac", FieldKind::Ether, "Sender MAC address"),
+    FieldInfo::new("arp.src.proto_ipv4", FieldKind::Ipv4, "Sender IP address"),
+    FieldInfo::new("arp.dst.hw_mac", FieldKind::Ether, "Target MAC address"),
+    FieldInfo::new("arp.dst.proto_ipv4", FieldKind::Ipv4, "Target IP address"),
+    FieldInfo::new("arp.isgratuitous", FieldKind::Bool, "Is gratuitous"),
+    FieldInfo::new("arp.isprobe", FieldKind::Bool, "Is probe"),
+    FieldInfo::new(
+        "arp.duplicate-address-detected",
+        FieldKind::Bool,
+        "Duplicate IP address detected",
+    ),
+    FieldInfo::new(
+        "arp.duplicate-address-frame",
+        FieldKind::UInt,
+        "Frame showing earlier use of IP address",
+    ),
+];
+
+fn opcode_name(opcode: u16) -> &'static str {
+    match opcode {
+        1 => "request",
+        2 => "reply",
+        3 => "reverse request",
+        4 => "reverse reply",
+        8 => "inverse request",
+        9 => "inverse reply",
+        _ => "unknown",
+    }
+}
+
+fn hw_type_name(hw_type: u16) -> &'static str {
+    match hw_type {
+        1 => "Ethernet",
+        6 => "IEEE 802",
+        15 => "Frame Relay",
+        16 => "ATM",
+        20 => "Serial Line",
+        24 => "IEEE 1394",
+        32 => "InfiniBand",
+        _ => "Unknown",
+    }
+}
+
+/// Where a protocol address was first claimed, when a later packet claims it for another
+/// hardware address
+#[derive(Clone, Debug)]
+pub struct AddressConflict {
+    pub earlier_hw: Vec<u8>,
+    pub earlier_frame: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arp {
+    /// Offset of the header in the packet
+    offset: usize,
+    hw_type: u16,
+    proto_type: u16,
+    hw_size: u8,
+    proto_size: u8,
+    opcode: u16,
+    sender_hw: Vec<u8>,
+    sender_proto: Vec<u8>,
+    target_hw: Vec<u8>,
+    target_proto: Vec<u8>,
+    /// Announces the sender's own address rather than asking for another
+    is_gratuitous: bool,
+    /// RFC 5227 probe checking whether an address is free before claiming it
+    is_probe: bool,
+    conflict: Option<AddressConflict>,
+}
+
+impl Arp {
+    pub fn from_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < FIXED_LEN {
+            return Err(DissectError::truncated("ARP", FIXED_LEN, bytes.len()));
+        }
+        let hw_type = util::two_bytes_to_u16(&bytes[0..2]);
+        let proto_type = util::two_bytes_to_u16(&bytes[2..4]);
+        let hw_size = bytes[4];
+        let proto_size = bytes[5];
+        let opcode = util::two_bytes_to_u16(&bytes[6..8]);
+
+        let (hs, ps) = (hw_size as usize, proto_size as usize);
+        let total_len = FIXED_LEN + 2 * (hs + ps);
+        if bytes.len() < total_len {
+            return Err(DissectError::truncated("ARP", total_len, bytes.len()));
+        }
+        let mut pos = FIXED_LEN;
+        let mut take = |len: usize| {
+            let field = bytes[pos..pos + len].to_vec();
+            pos += len;
+            field
+        };
+        let sender_hw = take(hs);
+        let sender_proto = take(ps);
+        let target_hw = take(hs);
+        let target_proto = take(ps);
+
+        let is_request = opcode == 1;
+        let is_probe =
+            is_request && sender_proto.iter().all(|b| *b == 0) && target_hw.iter().all(|b| *b == 0);
+        let is_gratuitous = !is_probe && matches!(opcode, 1 | 2) && sender_proto == target_proto;
+
+        let arp_layer = Arp {
+            offset: next_byte,
+            hw_type,
+            proto_type,
+            hw_size,
+            proto_size,
+            opcode,
+            sender_hw,
+            sender_proto,
+            target_hw,
+            target_proto,
+            is_gratuitous,
+            is_probe,
+            conflict: None,
+        };
+
+        Ok((arp_layer, next_byte + total_len, NextLayer::Undecoded))
+    }
+
+    /// Whether the addresses are the usual Ethernet MACs and IPv4 addresses
+    fn is_ether_ipv4(&self) -> bool {
+        self.hw_size == 6 && self.proto_size == 4 && self.proto_type == 0x0800
+    }
+
+    fn hw_to_string(&self, addr: &[u8]) -> String {
+        match <[u8; 6]>::try_from(addr) {
+            Ok(mac) => mac_to_string(&mac),
+            Err(_) => hex_string(addr),
+        }
+    }
+
+    fn proto_to_string(&self, addr: &[u8]) -> String {
+        if self.proto_type == 0x0800 && addr.len() == 4 {
+            format!("{}.{}.{}.{}", addr[0], addr[1], addr[2], addr[3])
+        } else {
+            hex_string(addr)
+        }
+    }
+
+    fn is_reverse(&self) -> bool {
+        matches!(self.opcode, 3 | 4)
+    }
+
+    fn summary(&self) -> String {
+        let sender_proto = self.proto_to_string(&self.sender_proto);
+        let target_proto = self.proto_to_string(&self.target_proto);
+        let mut summary = match self.opcode {
+            _ if self.is_probe => format!("Who has {}? (ARP Probe)", target_proto),
+            1 if self.is_gratuitous => format!("ARP Announcement for {}", sender_proto),
+            2 if self.is_gratuitous => format!("Gratuitous ARP for {} (Reply)", sender_proto),
+            1 => format!("Who has {}? Tell {}", target_proto, sender_proto),
+            2 => format!(
+                "{} is at {}",
+                sender_proto,
+                self.hw_to_string(&self.sender_hw)
+            ),
+            3 => format!(
+                "Who is {}? Tell {}",
+                self.hw_to_string(&self.target_hw),
+                self.hw_to_string(&self.sender_hw)
+            ),
+            4 => format!(
+                "{} is at {}",
+                self.hw_to_string(&self.target_hw),
+                target_proto
+            ),
+            opcode => format!("{} ({})", opcode_name(opcode), opcode),
+        };
+        if self.conflict.is_some() {
+            summary += &format!(" (duplicate use of {} detected!)", sender_proto);
+        }
+        summary
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+impl fmt::Display for Arp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = if self.is_reverse() {
+            "Reverse Address Resolution Protocol"
+        } else {
+            "Address Resolution Protocol"
+        };
+        if self.is_probe {
+            return write!(f, "{} (ARP Probe)", name);
+        }
+        let kind = if self.is_gratuitous {
+            "/gratuitous ARP"
+        } else {
+            ""
+        };
+        write!(f, "{} ({}{})", name, opcode_name(self.opcode), kind)
+    }
+}
+
+impl ProtocolLayer for Arp {
+    fn name(&self) -> &'static str {
+        "arp"
+    }
+
+    fn label(&self) -> String {
+        if self.is_reverse() { "RARP" } else { "ARP" }.to_string()
+    }
+
+    fn info(&self) -> String {
+        self.summary()
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("arp.hw.type", self.hw_type.into()),
+            ("arp.proto.type", self.proto_type.into()),
+            ("arp.hw.size", self.hw_size.into()),
+            ("arp.proto.size", self.proto_size.into()),
+            ("arp.opcode", self.opcode.into()),
+            ("arp.isgratuitous", self.is_gratuitous.into()),
+            ("arp.isprobe", self.is_probe.into()),
+        ];
+        if self.is_ether_ipv4() {
+            let as_mac = |addr: &[u8]| <[u8; 6]>::try_from(addr).unwrap_or_default();
+            let as_ipv4 = |addr: &[u8]| <[u8; 4]>::try_from(addr).unwrap_or_default();
+            fields.extend([
+                ("arp.src.hw_mac", as_mac(&self.sender_hw).into()),
+                ("arp.src.proto_ipv4", as_ipv4(&self.sender_proto).into()),
+                ("arp.dst.hw_mac", as_mac(&self.target_hw).into()),
+                ("arp.dst.proto_ipv4", as_ipv4(&self.target_proto).into()),
+            ]);
+        }
+        if let Some(conflict) = &self.conflict {
+            fields.push(("arp.duplicate-address-detected", true.into()));
+            fields.push(("arp.duplicate-address-frame", conflict.earlier_frame.into()));
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let (hs, ps) = (self.hw_size as usize, self.proto_size as usize);
+        let (hw_label, proto_label) = if self.is_ether_ipv4() {
+            ("MAC", "IP")
+        } else {
+            ("hardware", "protocol")
+        };
+        let mut children = vec![
+            ProtoItem::new_leaf(
+                format!(
+                    "Hardware type: {} ({})",
+                    hw_type_name(self.hw_type),
+                    self.hw_type
+                ),
+                off,
+                2,
+            ),
+            ProtoItem::new_leaf(
+                format!(
+                    "Protocol type: {} ({:#06x})",
+                    Ethertype::from(self.proto_type),
+                    self.proto_type
+                ),
+                off + 2,
+                2,
+            ),
+            ProtoItem::new_leaf(format!("Hardware size: {}", self.hw_size), off + 4, 1),
+            ProtoItem::new_leaf(format!("Protocol size: {}", self.proto_size), off + 5, 1),
+            ProtoItem::new_leaf(
+                format!("Opcode: {} ({})", opcode_name(self.opcode), self.opcode),
+                off + 6,
+                2,
+            ),
+        ];
+        if self.is_gratuitous {
+            children.push(ProtoItem::new_leaf("[Is gratuitous: True]", 0, 0));
+        }
+        if self.is_probe {
+            children.push(ProtoItem::new_leaf("[Is probe: True]", 0, 0));
+        }
+        let mut pos = off + FIXED_LEN;
+        for (label, text, len) in [
+            (
+                format!("Sender {} address", hw_label),
+                self.hw_to_string(&self.sender_hw),
+                hs,
+            ),
+            (
+                format!("Sender {} address", proto_label),
+                self.proto_to_string(&self.sender_proto),
+                ps,
+            ),
+            (
+                format!("Target {} address", hw_label),
+                self.hw_to_string(&self.target_hw),
+                hs,
+            ),
+            (
+                format!("Target {} address", proto_label),
+                self.proto_to_string(&self.target_proto),
+                ps,
+            ),
+        ] {
+            children.push(ProtoItem::new_leaf(
+                format!("{}: {}", label, text),
+                pos,
+                len,
+            ));
+            pos += len;
+        }
+        if let Some(conflict) = &self.conflict {
+            children.push(
+                ProtoItem::new_leaf(
+                    format!(
+                        "[Duplicate IP address detected for {} ({}) - also in use by {} (frame {})]",
+                        self.proto_to_string(&self.sender_proto),
+                        self.hw_to_string(&self.sender_hw),
+                        self.hw_to_string(&conflict.earlier_hw),
+                        conflict.earlier_frame
+                    ),
+                    off + FIXED_LEN,
+                    hs + ps,
+                )
+                .style(Style::default().fg(Color::White).bg(Color::Red)),
+            );
+        }
+
+        ProtoItem::new(self.to_string(), off, FIXED_LEN + 2 * (hs + ps), children)
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        match &self.conflict {
+            Some(_) => vec![ExpertInfo::new(
+                Severity::Warning,
+                "Sequence",
+                &self.label(),
+                format!(
+                    "Duplicate IP address configured ({})",
+                    self.proto_to_string(&self.sender_proto)
+                ),
+            )],
+            None => vec![],
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Hardware address a protocol address was claimed for, and the frame that claimed it
+type Claim = (Vec<u8>, usize);
+
+#[derive(Default)]
+pub struct ArpDissector {
+    /// First claim seen for each protocol address
+    claims: RefCell<HashMap<Vec<u8>, Claim>>,
+}
+
+impl Dissector for ArpDissector {
+    fn name(&self) -> &'static str {
+        "arp"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (mut layer, next_byte, next_layer) = Arp::from_bytes(next_byte, bytes)?;
+
+        // Anything after the addresses is link layer padding
+        ctx.payload_end = Some(next_byte);
+
+        // Probes claim nothing, and reverse ARP resolves the other way around
+        let claims_address = layer.sender_proto.iter().any(|b| *b != 0);
+        if claims_address && !layer.is_probe && matches!(layer.opcode, 1 | 2) {
+            let mut claims = self.claims.borrow_mut();
+            match claims.get(&layer.sender_proto) {
+                Some((hw, frame)) if *hw != layer.sender_hw => {
+                    layer.conflict = Some(AddressConflict {
+                        earlier_hw: hw.clone(),
+                        earlier_frame: *frame,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    claims.insert(
+                        layer.sender_proto.clone(),
+                        (layer.sender_hw.clone(), ctx.frame_num),
+                    );
+                }
+            }
+        }
+
+        Ok(Dissection::new(layer, next_byte, next_layer))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(ArpDissector::default());
+    registry.add_to_table(Table::EtherType, 0x0806, "arp");
+    registry.add_to_table(Table::EtherType, 0x8035, "arp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ethernet/IPv4 reply claiming `ip` for `mac`
+    fn reply(mac: [u8; 6], ip: [u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0, 1, 0x08, 0, 6, 4, 0, 2];
+        bytes.extend(mac);
+        bytes.extend(ip);
+        bytes.extend([0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 10, 0, 0, 9]);
+        bytes
+    }
+
+    fn dissect(dissector: &ArpDissector, frame_num: usize, bytes: &[u8]) -> Arp {
+        let registry = Registry::default();
+        let mut ctx = DissectCtx::new(&registry, frame_num, Default::default());
+        let dissection = dissector.dissect(&mut ctx, 0, bytes).unwrap();
+        dissection
+            .layer
+            .as_any()
+            .downcast_ref::<Arp>()
+            .unwrap()
+            .clone()
+    }
+
+    const FIRST: [u8; 6] = [0, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const SECOND: [u8; 6] = [0, 0x11, 0x22, 0x33, 0x44, 0x66];
+
+    #[test]
+    fn address_claimed_by_another_host_is_a_conflict() {
+        let dissector = ArpDissector::default();
+        let first = dissect(&dissector, 0, &reply(FIRST, [10, 0, 0, 1]));
+        let again = dissect(&dissector, 1, &reply(FIRST, [10, 0, 0, 1]));
+        let other = dissect(&dissector, 2, &reply(SECOND, [10, 0, 0, 2]));
+        assert!(first.expert_infos().is_empty());
+        assert!(again.expert_infos().is_empty());
+        assert!(other.expert_infos().is_empty());
+
+        let spoofed = dissect(&dissector, 3, &reply(SECOND, [10, 0, 0, 1]));
+        assert!(matches!(
+            &spoofed.conflict,
+            Some(AddressConflict { earlier_hw, earlier_frame: 0 }) if earlier_hw == &FIRST
+        ));
+        let infos = spoofed.expert_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].severity, Severity::Warning);
+        assert_eq!(
+            infos[0].summary,
+            "Duplicate IP address configured (10.0.0.1)"
+        );
+    }
+
+    #[test]
+    fn probes_claim_nothing() {
+        let dissector = ArpDissector::default();
+        // A probe has no sender address, so another host may still take 10.0.0.1 first
+        let mut probe = reply(FIRST, [0; 4]);
+        probe[7] = 1;
+        probe[24..28].copy_from_slice(&[10, 0, 0, 1]);
+        dissect(&dissector, 0, &probe);
+        let claim = dissect(&dissector, 1, &reply(SECOND, [10, 0, 0, 1]));
+        assert!(claim.conflict.is_none());
+    }
+
+    #[test]
+    fn addresses_past_the_capture_are_an_error() {
+        let err = Arp::from_bytes(0, &reply(FIRST, [10, 0, 0, 1])[..27]).unwrap_err();
+        assert_eq!(err.reason, "ARP header needs 28 bytes, only 27 captured");
+    }
+}
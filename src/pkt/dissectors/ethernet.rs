@@ -1,20 +1,55 @@
-use crate::pkt::LayerHint;
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
 use core::fmt;
+use std::any::Any;
 use tui::style::{Color, Style};
-use tui_tree_widget::TreeItem;
+
+use crate::pkt::prototree::ProtoItem;
 
 #[derive(Clone, Debug)]
 pub enum Ethertype {
     IPV4,
+    IPV6,
+    Arp,
+    Rarp,
+    Vlan,
     Unidentified,
 }
 
+impl From<u16> for Ethertype {
+    fn from(ether_type: u16) -> Self {
+        match ether_type {
+            0x0800 => Ethertype::IPV4,
+            0x86dd => Ethertype::IPV6,
+            0x0806 => Ethertype::Arp,
+            0x8035 => Ethertype::Rarp,
+            t if VLAN_TPIDS.contains(&t) => Ethertype::Vlan,
+            _ => Ethertype::Unidentified,
+        }
+    }
+}
+
 impl fmt::Display for Ethertype {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::IPV4 => {
                 write!(f, "IPV4")
             }
+            Self::IPV6 => {
+                write!(f, "IPV6")
+            }
+            Self::Arp => {
+                write!(f, "ARP")
+            }
+            Self::Rarp => {
+                write!(f, "RARP")
+            }
+            Self::Vlan => {
+                write!(f, "802.1Q Virtual LAN")
+            }
             Self::Unidentified => {
                 write!(f, "Unidentified")
             }
@@ -22,7 +57,83 @@ impl fmt::Display for Ethertype {
     }
 }
 
-fn mac_to_string(mac_addr_in: &[u8; 6]) -> String {
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("eth.dst", FieldKind::Ether, "Destination"),
+    FieldInfo::new("eth.src", FieldKind::Ether, "Source"),
+    FieldInfo::new(
+        "eth.addr",
+        FieldKind::Ether,
+        "Source or Destination Address",
+    ),
+    FieldInfo::new("eth.type", FieldKind::UInt, "Type"),
+    FieldInfo::new("vlan.tpid", FieldKind::UInt, "VLAN Tag Protocol Identifier"),
+    FieldInfo::new("vlan.priority", FieldKind::UInt, "VLAN Priority"),
+    FieldInfo::new("vlan.dei", FieldKind::Bool, "VLAN Drop Eligible Indicator"),
+    FieldInfo::new("vlan.id", FieldKind::UInt, "VLAN ID"),
+    FieldInfo::new("vlan.etype", FieldKind::UInt, "VLAN Encapsulated Type"),
+];
+
+/// Tag protocol identifiers of 802.1Q customer tags, 802.1ad service tags and the pre-standard
+/// QinQ tag some switches still send
+const VLAN_TPIDS: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+const VLAN_TAG_LEN: usize = 4;
+
+/// One 802.1Q tag. Stacked (QinQ) tags appear outermost first.
+#[derive(Clone, Debug)]
+pub struct VlanTag {
+    /// Offset of the tag protocol identifier in the packet
+    pub offset: usize,
+    pub tpid: u16,
+    /// Priority code point
+    pub pcp: u8,
+    /// Drop eligible indicator
+    pub dei: bool,
+    pub vid: u16,
+    /// Ethertype following this tag
+    pub ether_type: u16,
+}
+
+impl VlanTag {
+    fn tpid_name(&self) -> &'static str {
+        match self.tpid {
+            0x88a8 => "802.1ad",
+            0x9100 => "QinQ",
+            _ => "802.1Q",
+        }
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        ProtoItem::new(
+            format!(
+                "{} Virtual LAN, PRI: {}, DEI: {}, ID: {}",
+                self.tpid_name(),
+                self.pcp,
+                self.dei as u8,
+                self.vid
+            ),
+            off,
+            VLAN_TAG_LEN,
+            vec![
+                ProtoItem::new_leaf(
+                    format!("Tag Protocol Identifier: {:#06x}", self.tpid),
+                    off,
+                    2,
+                ),
+                ProtoItem::new_leaf(format!("Priority: {}", self.pcp), off + 2, 1),
+                ProtoItem::new_leaf(format!("DEI: {}", self.dei as u8), off + 2, 1),
+                ProtoItem::new_leaf(format!("ID: {}", self.vid), off + 2, 2),
+                ProtoItem::new_leaf(
+                    format!("Type: {:#06x}", self.ether_type),
+                    off + VLAN_TAG_LEN,
+                    2,
+                ),
+            ],
+        )
+    }
+}
+
+pub fn mac_to_string(mac_addr_in: &[u8; 6]) -> String {
     format!(
         "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
         mac_addr_in[0],
@@ -36,49 +147,23 @@ fn mac_to_string(mac_addr_in: &[u8; 6]) -> String {
 
 #[derive(Clone, Debug)]
 pub struct Ethernet {
+    /// Offset of the header in the packet
+    pub offset: usize,
     pub destination_mac: [u8; 6],
     pub source_mac: [u8; 6],
     pub ether_type_raw: [u8; 2],
     pub ether_type: Ethertype,
+    pub vlan_tags: Vec<VlanTag>,
 }
 
-#[allow(dead_code)]
 impl Ethernet {
-    pub fn new() -> Self {
-        Ethernet {
-            destination_mac: [0; 6],
-            source_mac: [0; 6],
-            ether_type_raw: [0; 2],
-            ether_type: Ethertype::Unidentified,
+    pub fn from_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < 14 {
+            return Err(DissectError::truncated("Ethernet", 14, bytes.len()));
         }
-    }
-
-    #[allow(dead_code)]
-    pub fn to_tree_item_verbose<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new(
-            self.to_string(),
-            vec![
-                TreeItem::new_leaf(format!(
-                    "Destination Mac Address: {}",
-                    mac_to_string(&self.destination_mac)
-                )),
-                TreeItem::new_leaf(format!(
-                    "Source Mac Address:      {}",
-                    mac_to_string(&self.source_mac)
-                )),
-                TreeItem::new_leaf(format!("Ethertype: {}", self.ether_type)),
-            ],
-        )
-        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-    }
-
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new_leaf(self.to_string())
-            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-    }
-
-    pub fn from_bytes(_next_byte: usize, bytes: &[u8]) -> (Self, usize, LayerHint) {
-        assert!(bytes.len() >= 14usize);
 
         let mut destination_mac: [u8; 6] = [0u8; 6];
         destination_mac.clone_from_slice(&bytes[0..6]);
@@ -89,28 +174,59 @@ impl Ethernet {
         let mut ether_type_raw: [u8; 2] = [0u8; 2];
         ether_type_raw.clone_from_slice(&bytes[12..14]);
 
-        // TODO: Handle 802.1q tag value of ethertype. Requires consumption of more bytes
-        let ethtypetmp: u32 = (ether_type_raw[0] as u32) * 256 + (ether_type_raw[1] as u32);
-        let ether_type = match ethtypetmp {
-            0x0800 => Ethertype::IPV4,
-            _ => Ethertype::Unidentified,
-        };
+        let ethtypetmp = u16::from_be_bytes(ether_type_raw);
+        let ether_type = Ethertype::from(ethtypetmp);
+
+        // Peel off VLAN tags, however deeply stacked, down to the payload's ethertype
+        let mut vlan_tags = vec![];
+        let mut payload_type = ethtypetmp;
+        let mut header_len = 14usize;
+        while VLAN_TPIDS.contains(&payload_type) {
+            if bytes.len() < header_len + VLAN_TAG_LEN {
+                return Err(DissectError::truncated(
+                    "802.1Q tag",
+                    header_len + VLAN_TAG_LEN,
+                    bytes.len(),
+                ));
+            }
+            let tci = u16::from_be_bytes([bytes[header_len], bytes[header_len + 1]]);
+            let tag = VlanTag {
+                offset: next_byte + header_len - 2,
+                tpid: payload_type,
+                pcp: (tci >> 13) as u8,
+                dei: tci & 0x1000 != 0,
+                vid: tci & 0x0fff,
+                ether_type: u16::from_be_bytes([bytes[header_len + 2], bytes[header_len + 3]]),
+            };
+            payload_type = tag.ether_type;
+            header_len += VLAN_TAG_LEN;
+            vlan_tags.push(tag);
+        }
 
         let ethlayer = Ethernet {
+            offset: next_byte,
             destination_mac,
             source_mac,
             ether_type_raw,
             ether_type,
+            vlan_tags,
         };
 
-        let next_byte = 14usize;
+        let next_byte = next_byte + header_len;
+        let next_layer = NextLayer::Table(Table::EtherType, payload_type as u32);
 
-        let layer_hint = match ethlayer.ether_type {
-            Ethertype::IPV4 => LayerHint::IPv4,
-            _ => LayerHint::Undecoded,
-        };
+        Ok((ethlayer, next_byte, next_layer))
+    }
+
+    /// Ethertype of the payload, after any VLAN tags
+    pub fn payload_type(&self) -> u16 {
+        self.vlan_tags
+            .last()
+            .map_or(u16::from_be_bytes(self.ether_type_raw), |t| t.ether_type)
+    }
 
-        (ethlayer, next_byte, layer_hint)
+    fn header_len(&self) -> usize {
+        14 + VLAN_TAG_LEN * self.vlan_tags.len()
     }
 }
 
@@ -118,10 +234,125 @@ impl fmt::Display for Ethernet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Ethernet Data [Destination: {} | Source: {} | Type: {}]",
+            "Ethernet Data [Destination: {} | Source: {} | Type: {}",
             mac_to_string(&self.destination_mac),
             mac_to_string(&self.source_mac),
-            self.ether_type
+            Ethertype::from(self.payload_type())
+        )?;
+        if !self.vlan_tags.is_empty() {
+            let ids: Vec<String> = self.vlan_tags.iter().map(|t| t.vid.to_string()).collect();
+            write!(f, " | VLAN: {}", ids.join("/"))?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl ProtocolLayer for Ethernet {
+    fn name(&self) -> &'static str {
+        "eth"
+    }
+
+    fn label(&self) -> String {
+        "Ethernet".to_string()
+    }
+
+    fn info(&self) -> String {
+        format!("Ethertype {}", Ethertype::from(self.payload_type()))
+    }
+
+    fn addresses(&self) -> Option<(String, String)> {
+        Some((
+            mac_to_string(&self.source_mac),
+            mac_to_string(&self.destination_mac),
+        ))
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("eth.dst", self.destination_mac.into()),
+            ("eth.src", self.source_mac.into()),
+            ("eth.addr", self.destination_mac.into()),
+            ("eth.addr", self.source_mac.into()),
+            ("eth.type", u16::from_be_bytes(self.ether_type_raw).into()),
+        ];
+        for tag in &self.vlan_tags {
+            fields.extend([
+                ("vlan.tpid", tag.tpid.into()),
+                ("vlan.priority", tag.pcp.into()),
+                ("vlan.dei", tag.dei.into()),
+                ("vlan.id", tag.vid.into()),
+                ("vlan.etype", tag.ether_type.into()),
+            ]);
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let mut item = ProtoItem::new(
+            self.to_string(),
+            off,
+            self.header_len(),
+            vec![
+                ProtoItem::new_leaf(
+                    format!("Destination: {}", mac_to_string(&self.destination_mac)),
+                    off,
+                    6,
+                ),
+                ProtoItem::new_leaf(
+                    format!("Source: {}", mac_to_string(&self.source_mac)),
+                    off + 6,
+                    6,
+                ),
+                ProtoItem::new_leaf(
+                    format!(
+                        "Type: {} (0x{:02x}{:02x})",
+                        self.ether_type, self.ether_type_raw[0], self.ether_type_raw[1]
+                    ),
+                    off + 12,
+                    2,
+                ),
+            ],
         )
+        .style(Style::default().fg(Color::Black).bg(Color::LightYellow));
+        for tag in &self.vlan_tags {
+            item.add_child(tag.to_proto_item());
+        }
+        item
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct EthernetDissector;
+
+impl Dissector for EthernetDissector {
+    fn name(&self) -> &'static str {
+        "eth"
+    }
+
+    fn dissect(
+        &self,
+        _ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (layer, next_byte, next_layer) = Ethernet::from_bytes(next_byte, bytes)?;
+        Ok(Dissection::new(layer, next_byte, next_layer))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(EthernetDissector);
+    registry.add_to_table(
+        Table::LinkType,
+        pcap_parser::Linktype::ETHERNET.0 as u32,
+        "eth",
+    );
 }
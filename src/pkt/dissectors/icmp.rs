@@ -0,0 +1,927 @@
+// ICMP (RFC 792) and ICMPv6 (RFC 4443), which share a header layout. Echo requests and replies
+//   are paired by identifier and sequence number; error messages quote the start of the
+//   datagram that caused them, which is dissected again as a nested subtree.
+
+use core::fmt;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::util::{self, ChecksumStatus, Pairing};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::expert::{ExpertInfo, Severity};
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+use crate::pkt::timestamp::Timestamp;
+use crate::pkt::{dissect_layers, Layer};
+
+/// Type, code, checksum and the 4 type-specific bytes every message starts with
+const HEADER_LEN: usize = 8;
+
+const ICMP_FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("icmp.type", FieldKind::UInt, "Type"),
+    FieldInfo::new("icmp.code", FieldKind::UInt, "Code"),
+    FieldInfo::new("icmp.checksum", FieldKind::UInt, "Checksum"),
+    FieldInfo::new(
+        "icmp.checksum.status",
+        FieldKind::UInt,
+        "Checksum Status (0 bad, 1 good, 2 unverified)",
+    ),
+    FieldInfo::new("icmp.ident", FieldKind::UInt, "Identifier"),
+    FieldInfo::new("icmp.seq", FieldKind::UInt, "Sequence Number"),
+    FieldInfo::new("icmp.resp_in", FieldKind::UInt, "Response frame"),
+    FieldInfo::new("icmp.resp_to", FieldKind::UInt, "Request frame"),
+    FieldInfo::new("icmp.no_resp", FieldKind::Bool, "No response seen"),
+    FieldInfo::new("icmp.mtu", FieldKind::UInt, "MTU of next hop"),
+    FieldInfo::new("icmp.redir_gw", FieldKind::Ipv4, "Gateway Address"),
+    FieldInfo::new("icmp.pointer", FieldKind::UInt, "Pointer"),
+];
+
+const ICMPV6_FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("icmpv6.type", FieldKind::UInt, "Type"),
+    FieldInfo::new("icmpv6.code", FieldKind::UInt, "Code"),
+    FieldInfo::new("icmpv6.checksum", FieldKind::UInt, "Checksum"),
+    FieldInfo::new(
+        "icmpv6.checksum.status",
+        FieldKind::UInt,
+        "Checksum Status (0 bad, 1 good, 2 unverified)",
+    ),
+    FieldInfo::new("icmpv6.echo.identifier", FieldKind::UInt, "Identifier"),
+    FieldInfo::new(
+        "icmpv6.echo.sequence_number",
+        FieldKind::UInt,
+        "Sequence Number",
+    ),
+    FieldInfo::new("icmpv6.resp_in", FieldKind::UInt, "Response In"),
+    FieldInfo::new("icmpv6.resp_to", FieldKind::UInt, "Response To"),
+    FieldInfo::new("icmpv6.no_resp", FieldKind::Bool, "No response seen"),
+    FieldInfo::new("icmpv6.mtu", FieldKind::UInt, "MTU"),
+    FieldInfo::new("icmpv6.pointer", FieldKind::UInt, "Pointer"),
+    FieldInfo::new(
+        "icmpv6.nd.ns.target_address",
+        FieldKind::Ipv6,
+        "Target Address",
+    ),
+    FieldInfo::new(
+        "icmpv6.nd.na.target_address",
+        FieldKind::Ipv6,
+        "Target Address",
+    ),
+    FieldInfo::new("icmpv6.nd.na.flag.r", FieldKind::Bool, "Router"),
+    FieldInfo::new("icmpv6.nd.na.flag.s", FieldKind::Bool, "Solicited"),
+    FieldInfo::new("icmpv6.nd.na.flag.o", FieldKind::Bool, "Override"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IcmpVersion {
+    V4,
+    V6,
+}
+
+impl IcmpVersion {
+    fn is_echo_request(self, icmp_type: u8) -> bool {
+        match self {
+            Self::V4 => icmp_type == 8,
+            Self::V6 => icmp_type == 128,
+        }
+    }
+
+    fn is_echo_reply(self, icmp_type: u8) -> bool {
+        match self {
+            Self::V4 => icmp_type == 0,
+            Self::V6 => icmp_type == 129,
+        }
+    }
+
+    /// Error messages quote as much of the offending datagram as fits
+    fn is_error(self, icmp_type: u8) -> bool {
+        match self {
+            Self::V4 => matches!(icmp_type, 3 | 4 | 5 | 11 | 12),
+            Self::V6 => matches!(icmp_type, 1..=4),
+        }
+    }
+
+    /// Dissector for the datagram an error message quotes
+    fn quoted_dissector(self) -> &'static str {
+        match self {
+            Self::V4 => "ip",
+            Self::V6 => "ipv6",
+        }
+    }
+
+    /// Picks between the ICMP and ICMPv6 spelling of a field name
+    fn field(self, v4: &'static str, v6: &'static str) -> &'static str {
+        match self {
+            Self::V4 => v4,
+            Self::V6 => v6,
+        }
+    }
+}
+
+fn type_name(version: IcmpVersion, icmp_type: u8) -> &'static str {
+    match (version, icmp_type) {
+        (IcmpVersion::V4, 0) => "Echo (ping) reply",
+        (IcmpVersion::V4, 3) => "Destination unreachable",
+        (IcmpVersion::V4, 4) => "Source quench (flow control)",
+        (IcmpVersion::V4, 5) => "Redirect",
+        (IcmpVersion::V4, 8) => "Echo (ping) request",
+        (IcmpVersion::V4, 9) => "Router advertisement",
+        (IcmpVersion::V4, 10) => "Router solicitation",
+        (IcmpVersion::V4, 11) => "Time-to-live exceeded",
+        (IcmpVersion::V4, 12) => "Parameter problem",
+        (IcmpVersion::V4, 13) => "Timestamp request",
+        (IcmpVersion::V4, 14) => "Timestamp reply",
+        (IcmpVersion::V4, 17) => "Address mask request",
+        (IcmpVersion::V4, 18) => "Address mask reply",
+        (IcmpVersion::V6, 1) => "Destination Unreachable",
+        (IcmpVersion::V6, 2) => "Packet Too Big",
+        (IcmpVersion::V6, 3) => "Time Exceeded",
+        (IcmpVersion::V6, 4) => "Parameter Problem",
+        (IcmpVersion::V6, 128) => "Echo (ping) request",
+        (IcmpVersion::V6, 129) => "Echo (ping) reply",
+        (IcmpVersion::V6, 130) => "Multicast Listener Query",
+        (IcmpVersion::V6, 131) => "Multicast Listener Report",
+        (IcmpVersion::V6, 132) => "Multicast Listener Done",
+        (IcmpVersion::V6, 133) => "Router Solicitation",
+        (IcmpVersion::V6, 134) => "Router Advertisement",
+        (IcmpVersion::V6, 135) => "Neighbor Solicitation",
+        (IcmpVersion::V6, 136) => "Neighbor Advertisement",
+        (IcmpVersion::V6, 137) => "Redirect",
+        (IcmpVersion::V6, 143) => "Multicast Listener Report Message v2",
+        _ => "Unknown",
+    }
+}
+
+/// Name of `code`, for the types that give their codes a meaning
+fn code_name(version: IcmpVersion, icmp_type: u8, code: u8) -> Option<&'static str> {
+    let name = match (version, icmp_type, code) {
+        (IcmpVersion::V4, 3, 0) => "Network unreachable",
+        (IcmpVersion::V4, 3, 1) => "Host unreachable",
+        (IcmpVersion::V4, 3, 2) => "Protocol unreachable",
+        (IcmpVersion::V4, 3, 3) => "Port unreachable",
+        (IcmpVersion::V4, 3, 4) => "Fragmentation needed",
+        (IcmpVersion::V4, 3, 5) => "Source route failed",
+        (IcmpVersion::V4, 3, 6) => "Destination network unknown",
+        (IcmpVersion::V4, 3, 7) => "Destination host unknown",
+        (IcmpVersion::V4, 3, 8) => "Source host isolated",
+        (IcmpVersion::V4, 3, 9) => "Network administratively prohibited",
+        (IcmpVersion::V4, 3, 10) => "Host administratively prohibited",
+        (IcmpVersion::V4, 3, 11) => "Network unreachable for TOS",
+        (IcmpVersion::V4, 3, 12) => "Host unreachable for TOS",
+        (IcmpVersion::V4, 3, 13) => "Communication administratively filtered",
+        (IcmpVersion::V4, 3, 14) => "Host precedence violation",
+        (IcmpVersion::V4, 3, 15) => "Precedence cutoff in effect",
+        (IcmpVersion::V4, 5, 0) => "Redirect for network",
+        (IcmpVersion::V4, 5, 1) => "Redirect for host",
+        (IcmpVersion::V4, 5, 2) => "Redirect for TOS and network",
+        (IcmpVersion::V4, 5, 3) => "Redirect for TOS and host",
+        (IcmpVersion::V4, 11, 0) => "Time to live exceeded in transit",
+        (IcmpVersion::V4, 11, 1) => "Fragment reassembly time exceeded",
+        (IcmpVersion::V4, 12, 0) => "Pointer indicates the error",
+        (IcmpVersion::V4, 12, 1) => "Required option missing",
+        (IcmpVersion::V4, 12, 2) => "Bad length",
+        (IcmpVersion::V6, 1, 0) => "no route to destination",
+        (IcmpVersion::V6, 1, 1) => "communication with destination administratively prohibited",
+        (IcmpVersion::V6, 1, 2) => "beyond scope of source address",
+        (IcmpVersion::V6, 1, 3) => "address unreachable",
+        (IcmpVersion::V6, 1, 4) => "port unreachable",
+        (IcmpVersion::V6, 1, 5) => "source address failed ingress/egress policy",
+        (IcmpVersion::V6, 1, 6) => "reject route to destination",
+        (IcmpVersion::V6, 3, 0) => "hop limit exceeded in transit",
+        (IcmpVersion::V6, 3, 1) => "fragment reassembly time exceeded",
+        (IcmpVersion::V6, 4, 0) => "erroneous header field encountered",
+        (IcmpVersion::V6, 4, 1) => "unrecognized Next Header type encountered",
+        (IcmpVersion::V6, 4, 2) => "unrecognized IPv6 option encountered",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[derive(Clone, Debug)]
+pub struct Icmp {
+    /// Offset of the header in the packet
+    offset: usize,
+    version: IcmpVersion,
+    icmp_type: u8,
+    code: u8,
+    checksum: u16,
+    xsum_status: ChecksumStatus,
+    /// The 4 bytes after the checksum, whose meaning depends on the type
+    rest_of_header: [u8; 4],
+    /// Length of the whole message, header included
+    length: usize,
+    /// Echo identifier and sequence number, with where the other half of the exchange is
+    echo: Option<(u16, u16, Pairing)>,
+    /// Address a Neighbor Solicitation or Advertisement is about
+    target: Option<Ipv6Addr>,
+    /// Layers dissected from the datagram an error message quotes
+    quoted: Vec<Layer>,
+}
+
+impl Icmp {
+    pub fn from_bytes(
+        ctx: &DissectCtx,
+        version: IcmpVersion,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Self, DissectError> {
+        let protocol = match version {
+            IcmpVersion::V4 => "ICMP",
+            IcmpVersion::V6 => "ICMPv6",
+        };
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated(protocol, HEADER_LEN, bytes.len()));
+        }
+        let icmp_type = bytes[0];
+        let code = bytes[1];
+        let checksum = util::two_bytes_to_u16(&bytes[2..4]);
+        let mut rest_of_header = [0; 4];
+        rest_of_header.copy_from_slice(&bytes[4..8]);
+
+        // The checksum covers the whole message, which the IP layer bounds `bytes` to only
+        //   when it was captured in full
+        let whole_message = ctx.payload_end == Some(next_byte + bytes.len());
+        let xsum_status = match (version, ctx.net_src, ctx.net_dst) {
+            _ if !whole_message || ctx.in_error_packet => ChecksumStatus::Unverified,
+            _ if !ctx.registry.prefs.validate_checksums => ChecksumStatus::Offloaded,
+            (IcmpVersion::V4, _, _) => match util::internet_checksum(&[bytes]) {
+                0 => ChecksumStatus::Correct,
+                _ => {
+                    ChecksumStatus::Incorrect(util::internet_checksum(&[&bytes[0..2], &bytes[4..]]))
+                }
+            },
+            (IcmpVersion::V6, Some(src), Some(dst)) => {
+                let pseudo = util::pseudo_header(src, dst, 58, bytes.len());
+                match util::internet_checksum(&[&pseudo, bytes]) {
+                    0 => ChecksumStatus::Correct,
+                    _ => ChecksumStatus::Incorrect(util::internet_checksum(&[
+                        &pseudo,
+                        &bytes[0..2],
+                        &bytes[4..],
+                    ])),
+                }
+            }
+            (IcmpVersion::V6, _, _) => ChecksumStatus::Unverified,
+        };
+
+        let target = match (version, icmp_type) {
+            (IcmpVersion::V6, 135 | 136) if bytes.len() >= HEADER_LEN + 16 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&bytes[HEADER_LEN..HEADER_LEN + 16]);
+                Some(Ipv6Addr::from(octets))
+            }
+            _ => None,
+        };
+
+        let quoted = if version.is_error(icmp_type) && bytes.len() > HEADER_LEN {
+            let mut quoted_ctx = ctx.for_error_packet();
+            dissect_layers(
+                &mut quoted_ctx,
+                NextLayer::Named(version.quoted_dissector()),
+                next_byte + HEADER_LEN,
+                &bytes[HEADER_LEN..],
+            )
+        } else {
+            vec![]
+        };
+
+        Ok(Icmp {
+            offset: next_byte,
+            version,
+            icmp_type,
+            code,
+            checksum,
+            xsum_status,
+            rest_of_header,
+            length: bytes.len(),
+            echo: None,
+            target,
+            quoted,
+        })
+    }
+
+    fn is_echo(&self) -> bool {
+        self.version.is_echo_request(self.icmp_type) || self.version.is_echo_reply(self.icmp_type)
+    }
+
+    fn identifier(&self) -> u16 {
+        util::two_bytes_to_u16(&self.rest_of_header[0..2])
+    }
+
+    fn sequence_num(&self) -> u16 {
+        util::two_bytes_to_u16(&self.rest_of_header[2..4])
+    }
+
+    fn rest_of_header_u32(&self) -> u32 {
+        util::four_bytes_to_u32(&self.rest_of_header)
+    }
+
+    /// Next-hop MTU of a "fragmentation needed" or "packet too big" error
+    fn mtu(&self) -> Option<u32> {
+        match (self.version, self.icmp_type, self.code) {
+            (IcmpVersion::V4, 3, 4) => {
+                Some(util::two_bytes_to_u16(&self.rest_of_header[2..4]) as u32)
+            }
+            (IcmpVersion::V6, 2, _) => Some(self.rest_of_header_u32()),
+            _ => None,
+        }
+    }
+
+    /// Offset of the octet a parameter problem error points at
+    fn pointer(&self) -> Option<u32> {
+        match (self.version, self.icmp_type) {
+            (IcmpVersion::V4, 12) => Some(self.rest_of_header[0] as u32),
+            (IcmpVersion::V6, 4) => Some(self.rest_of_header_u32()),
+            _ => None,
+        }
+    }
+
+    fn type_and_code(&self) -> String {
+        match code_name(self.version, self.icmp_type, self.code) {
+            Some(code) => format!("{} ({})", type_name(self.version, self.icmp_type), code),
+            None => type_name(self.version, self.icmp_type).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Icmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            IcmpVersion::V4 => write!(f, "Internet Control Message Protocol"),
+            IcmpVersion::V6 => write!(f, "Internet Control Message Protocol v6"),
+        }
+    }
+}
+
+impl ProtocolLayer for Icmp {
+    fn name(&self) -> &'static str {
+        match self.version {
+            IcmpVersion::V4 => "icmp",
+            IcmpVersion::V6 => "icmpv6",
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.version {
+            IcmpVersion::V4 => "ICMP".to_string(),
+            IcmpVersion::V6 => "ICMPv6".to_string(),
+        }
+    }
+
+    fn info(&self) -> String {
+        let mut info = self.type_and_code();
+        if let Some((id, seq, pairing)) = &self.echo {
+            info += &format!("  id={:#06x}, seq={}/{}", id, seq, seq.swap_bytes());
+            match pairing {
+                Pairing::Request(cell) => match cell.get() {
+                    Some(frame) => info += &format!(" (reply in {})", frame),
+                    None => info += " (no response found!)",
+                },
+                Pairing::Response(Some((frame, _))) => info += &format!(" (request in {})", frame),
+                Pairing::Response(None) => {}
+            }
+        }
+        if let Some(target) = self.target {
+            info += &format!(" for {}", target);
+        }
+        if let Some(mtu) = self.mtu() {
+            info += &format!(", MTU = {}", mtu);
+        }
+        info
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let v = self.version;
+        let mut fields: Vec<Field> = vec![
+            (v.field("icmp.type", "icmpv6.type"), self.icmp_type.into()),
+            (v.field("icmp.code", "icmpv6.code"), self.code.into()),
+            (
+                v.field("icmp.checksum", "icmpv6.checksum"),
+                self.checksum.into(),
+            ),
+            (
+                v.field("icmp.checksum.status", "icmpv6.checksum.status"),
+                self.xsum_status.code().into(),
+            ),
+        ];
+        if let Some((id, seq, pairing)) = &self.echo {
+            fields.push((
+                v.field("icmp.ident", "icmpv6.echo.identifier"),
+                (*id).into(),
+            ));
+            fields.push((
+                v.field("icmp.seq", "icmpv6.echo.sequence_number"),
+                (*seq).into(),
+            ));
+            match pairing {
+                Pairing::Request(cell) => match cell.get() {
+                    Some(frame) => {
+                        fields.push((v.field("icmp.resp_in", "icmpv6.resp_in"), frame.into()))
+                    }
+                    None => fields.push((v.field("icmp.no_resp", "icmpv6.no_resp"), true.into())),
+                },
+                Pairing::Response(Some((frame, _))) => {
+                    fields.push((v.field("icmp.resp_to", "icmpv6.resp_to"), (*frame).into()))
+                }
+                Pairing::Response(None) => {}
+            }
+        }
+        if let Some(mtu) = self.mtu() {
+            fields.push((v.field("icmp.mtu", "icmpv6.mtu"), mtu.into()));
+        }
+        if let Some(pointer) = self.pointer() {
+            fields.push((v.field("icmp.pointer", "icmpv6.pointer"), pointer.into()));
+        }
+        if (v, self.icmp_type) == (IcmpVersion::V4, 5) {
+            fields.push(("icmp.redir_gw", self.rest_of_header.into()));
+        }
+        if let Some(target) = self.target {
+            if self.icmp_type == 135 {
+                fields.push(("icmpv6.nd.ns.target_address", target.octets().into()));
+            } else {
+                fields.push(("icmpv6.nd.na.target_address", target.octets().into()));
+                let flags = self.rest_of_header[0];
+                fields.push(("icmpv6.nd.na.flag.r", (flags & 0x80 != 0).into()));
+                fields.push(("icmpv6.nd.na.flag.s", (flags & 0x40 != 0).into()));
+                fields.push(("icmpv6.nd.na.flag.o", (flags & 0x20 != 0).into()));
+            }
+        }
+        // Filters on the quoted headers match too, as in Wireshark
+        for layer in &self.quoted {
+            if let Layer::Protocol(layer) = layer {
+                fields.extend(layer.fields());
+            }
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let mut children = vec![
+            ProtoItem::new_leaf(
+                format!(
+                    "Type: {} ({})",
+                    self.icmp_type,
+                    type_name(self.version, self.icmp_type)
+                ),
+                off,
+                1,
+            ),
+            ProtoItem::new_leaf(
+                match code_name(self.version, self.icmp_type, self.code) {
+                    Some(name) => format!("Code: {} ({})", self.code, name),
+                    None => format!("Code: {}", self.code),
+                },
+                off + 1,
+                1,
+            ),
+            self.xsum_status
+                .to_proto_item("Checksum", self.checksum, off + 2),
+        ];
+        let mut body_start = HEADER_LEN;
+
+        if let Some((id, seq, pairing)) = &self.echo {
+            children.push(ProtoItem::new_leaf(
+                format!("Identifier: {} ({:#06x})", id, id),
+                off + 4,
+                2,
+            ));
+            children.push(ProtoItem::new_leaf(
+                format!("Sequence Number: {} ({:#06x})", seq, seq),
+                off + 6,
+                2,
+            ));
+            match pairing {
+                Pairing::Request(cell) => match cell.get() {
+                    Some(frame) => children.push(ProtoItem::new_leaf(
+                        format!("[Response frame: {}]", frame),
+                        0,
+                        0,
+                    )),
+                    None => children.push(
+                        ProtoItem::new_leaf("[No response seen]", 0, 0)
+                            .style(Style::default().fg(Color::White).bg(Color::Red)),
+                    ),
+                },
+                Pairing::Response(Some((frame, nanos))) => {
+                    children.push(ProtoItem::new_leaf(
+                        format!("[Request frame: {}]", frame),
+                        0,
+                        0,
+                    ));
+                    children.push(ProtoItem::new_leaf(
+                        format!("[Response time: {:.3} ms]", *nanos as f64 / 1e6),
+                        0,
+                        0,
+                    ));
+                }
+                Pairing::Response(None) => {}
+            }
+        } else if let Some(mtu) = self.mtu() {
+            children.push(match self.version {
+                IcmpVersion::V4 => {
+                    ProtoItem::new_leaf(format!("MTU of next hop: {}", mtu), off + 6, 2)
+                }
+                IcmpVersion::V6 => ProtoItem::new_leaf(format!("MTU: {}", mtu), off + 4, 4),
+            });
+        } else if let Some(pointer) = self.pointer() {
+            children.push(match self.version {
+                IcmpVersion::V4 => ProtoItem::new_leaf(format!("Pointer: {}", pointer), off + 4, 1),
+                IcmpVersion::V6 => ProtoItem::new_leaf(format!("Pointer: {}", pointer), off + 4, 4),
+            });
+        } else if (self.version, self.icmp_type) == (IcmpVersion::V4, 5) {
+            children.push(ProtoItem::new_leaf(
+                format!("Gateway Address: {}", Ipv4Addr::from(self.rest_of_header)),
+                off + 4,
+                4,
+            ));
+        } else if let Some(target) = self.target {
+            if self.icmp_type == 136 {
+                let flags = self.rest_of_header[0];
+                let bit = |mask: u8, name: &str| {
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{}: {}",
+                            name,
+                            if flags & mask != 0 { "Set" } else { "Not set" }
+                        ),
+                        off + 4,
+                        1,
+                    )
+                };
+                children.push(ProtoItem::new(
+                    format!("Flags: {:#010x}", self.rest_of_header_u32()),
+                    off + 4,
+                    4,
+                    vec![
+                        bit(0x80, "Router"),
+                        bit(0x40, "Solicited"),
+                        bit(0x20, "Override"),
+                    ],
+                ));
+            }
+            children.push(ProtoItem::new_leaf(
+                format!("Target Address: {}", target),
+                off + HEADER_LEN,
+                16,
+            ));
+            body_start += 16;
+        }
+
+        if !self.quoted.is_empty() {
+            children.extend(self.quoted.iter().map(|layer| layer.to_proto_item()));
+        } else if self.length > body_start {
+            children.push(ProtoItem::new_leaf(
+                format!("Data ({} bytes)", self.length - body_start),
+                off + body_start,
+                self.length - body_start,
+            ));
+        }
+
+        ProtoItem::new(self.to_string(), off, self.length, children)
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        let mut infos: Vec<ExpertInfo> = self
+            .xsum_status
+            .expert_info(&self.label())
+            .into_iter()
+            .collect();
+        if let Some((_, _, Pairing::Request(cell))) = &self.echo {
+            if cell.get().is_none() {
+                infos.push(ExpertInfo::new(
+                    Severity::Warning,
+                    "Sequence",
+                    &self.label(),
+                    "No response seen to ICMP request",
+                ));
+            }
+        }
+        infos
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Key pairing an echo request with its reply: requester, responder, identifier, sequence number
+type EchoKey = (Option<IpAddr>, Option<IpAddr>, u16, u16);
+
+/// An echo request still waiting for its reply
+#[derive(Clone, Debug)]
+struct PendingEcho {
+    frame: usize,
+    timestamp: Timestamp,
+    /// Shared with the request's layer, filled in once the reply is seen
+    response_in: Rc<Cell<Option<usize>>>,
+}
+
+pub struct IcmpDissector {
+    version: IcmpVersion,
+    /// Echo requests seen so far that no reply has answered yet
+    pending: RefCell<HashMap<EchoKey, PendingEcho>>,
+}
+
+impl IcmpDissector {
+    pub fn new(version: IcmpVersion) -> Self {
+        IcmpDissector {
+            version,
+            pending: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Dissector for IcmpDissector {
+    fn name(&self) -> &'static str {
+        match self.version {
+            IcmpVersion::V4 => "icmp",
+            IcmpVersion::V6 => "icmpv6",
+        }
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let mut layer = Icmp::from_bytes(ctx, self.version, next_byte, bytes)?;
+
+        if layer.is_echo() {
+            let (id, seq) = (layer.identifier(), layer.sequence_num());
+            let pairing = if self.version.is_echo_request(layer.icmp_type) {
+                let response_in = Rc::new(Cell::new(None));
+                if !ctx.in_error_packet {
+                    let request = PendingEcho {
+                        frame: ctx.frame_num,
+                        timestamp: ctx.timestamp,
+                        response_in: response_in.clone(),
+                    };
+                    let key = (ctx.net_src, ctx.net_dst, id, seq);
+                    self.pending.borrow_mut().insert(key, request);
+                }
+                Pairing::Request(response_in)
+            } else {
+                let request = if ctx.in_error_packet {
+                    None
+                } else {
+                    let key = (ctx.net_dst, ctx.net_src, id, seq);
+                    self.pending.borrow_mut().remove(&key)
+                };
+                if let Some(request) = &request {
+                    request.response_in.set(Some(ctx.frame_num));
+                }
+                Pairing::Response(
+                    request.map(|r| (r.frame, ctx.timestamp.nanos_since(&r.timestamp))),
+                )
+            };
+            layer.echo = Some((id, seq, pairing));
+        }
+
+        let ret_next_byte = next_byte + bytes.len();
+        Ok(Dissection::new(layer, ret_next_byte, NextLayer::Undecoded))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        match self.version {
+            IcmpVersion::V4 => ICMP_FIELDS,
+            IcmpVersion::V6 => ICMPV6_FIELDS,
+        }
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(IcmpDissector::new(IcmpVersion::V4));
+    registry.register(IcmpDissector::new(IcmpVersion::V6));
+    registry.add_to_table(Table::IpProto, 1, "icmp");
+    registry.add_to_table(Table::IpProto, 58, "icmpv6");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ICMP message with its checksum filled in
+    fn message(icmp_type: u8, code: u8, rest_of_header: [u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![icmp_type, code, 0, 0];
+        bytes.extend(rest_of_header);
+        bytes.extend(body);
+        let checksum = util::internet_checksum(&[&bytes]);
+        bytes[2..4].copy_from_slice(&checksum.to_be_bytes());
+        bytes
+    }
+
+    fn echo(icmp_type: u8, id: u16, seq: u16) -> Vec<u8> {
+        let mut rest_of_header = [0; 4];
+        rest_of_header[0..2].copy_from_slice(&id.to_be_bytes());
+        rest_of_header[2..4].copy_from_slice(&seq.to_be_bytes());
+        message(icmp_type, 0, rest_of_header, b"ping")
+    }
+
+    /// Runs the dissector over `bytes` as the whole payload of frame `frame_num`, captured
+    /// `millis` into the capture
+    fn dissect(
+        dissector: &IcmpDissector,
+        registry: &Registry,
+        (frame_num, millis): (usize, u32),
+        (src, dst): (&str, &str),
+        bytes: &[u8],
+    ) -> Icmp {
+        let timestamp = Timestamp {
+            nanos: millis * 1_000_000,
+            ..Timestamp::default()
+        };
+        let mut ctx = DissectCtx::new(registry, frame_num, timestamp);
+        ctx.net_src = Some(src.parse().unwrap());
+        ctx.net_dst = Some(dst.parse().unwrap());
+        ctx.payload_end = Some(bytes.len());
+        let dissection = dissector.dissect(&mut ctx, 0, bytes).unwrap();
+        assert_eq!(dissection.next_byte, bytes.len());
+        dissection
+            .layer
+            .as_any()
+            .downcast_ref::<Icmp>()
+            .unwrap()
+            .clone()
+    }
+
+    const HOST: (&str, &str) = ("10.0.0.1", "10.0.0.5");
+    const REPLY: (&str, &str) = ("10.0.0.5", "10.0.0.1");
+
+    #[test]
+    fn echo_reply_pairs_with_its_request() {
+        let registry = Registry::default();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        let request = dissect(&dissector, &registry, (0, 0), HOST, &echo(8, 1, 7));
+        let reply = dissect(&dissector, &registry, (1, 3), REPLY, &echo(0, 1, 7));
+
+        assert_eq!(
+            request.info(),
+            "Echo (ping) request  id=0x0001, seq=7/1792 (reply in 1)"
+        );
+        assert!(request.fields().contains(&("icmp.resp_in", 1usize.into())));
+        assert!(matches!(
+            reply.echo,
+            Some((1, 7, Pairing::Response(Some((0, 3_000_000)))))
+        ));
+        assert!(reply.fields().contains(&("icmp.resp_to", 0usize.into())));
+    }
+
+    #[test]
+    fn unanswered_requests_are_flagged() {
+        let registry = Registry::default();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        let request = dissect(&dissector, &registry, (0, 0), HOST, &echo(8, 1, 7));
+        // Another sequence number, then the right one from a host that wasn't asked
+        let other = dissect(&dissector, &registry, (1, 1), REPLY, &echo(0, 1, 8));
+        let stranger = dissect(
+            &dissector,
+            &registry,
+            (2, 2),
+            ("10.0.0.9", "10.0.0.1"),
+            &echo(0, 1, 7),
+        );
+
+        assert!(request.info().ends_with("(no response found!)"));
+        assert!(request.fields().contains(&("icmp.no_resp", true.into())));
+        assert!(matches!(other.echo, Some((_, _, Pairing::Response(None)))));
+        assert!(matches!(
+            stranger.echo,
+            Some((_, _, Pairing::Response(None)))
+        ));
+    }
+
+    #[test]
+    fn checksum_covers_the_whole_message() {
+        let registry = Registry::default();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        let bytes = [8, 0, 0xf7, 0xfd, 0, 1, 0, 1];
+        let icmp = dissect(&dissector, &registry, (0, 0), HOST, &bytes);
+        assert_eq!(icmp.xsum_status, ChecksumStatus::Correct);
+        let bytes = [8, 0, 0xf7, 0xfe, 0, 1, 0, 1];
+        let icmp = dissect(&dissector, &registry, (1, 0), HOST, &bytes);
+        assert_eq!(icmp.xsum_status, ChecksumStatus::Incorrect(0xf7fd));
+
+        // Without the IP layer's length the message may be cut short
+        let ctx = DissectCtx::new(&registry, 2, Timestamp::default());
+        let icmp = Icmp::from_bytes(&ctx, IcmpVersion::V4, 0, &bytes).unwrap();
+        assert_eq!(icmp.xsum_status, ChecksumStatus::Unverified);
+    }
+
+    #[test]
+    fn error_messages_dissect_the_quoted_datagram() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        // The first 8 bytes of a UDP datagram to port 53, which was 30 bytes long
+        let mut quoted = vec![0x45, 0, 0, 30, 0, 1, 0, 0, 64, 17, 0, 0];
+        quoted.extend([10, 0, 0, 5, 10, 0, 0, 1]);
+        quoted.extend([0x04, 0xd2, 0, 53, 0, 10, 0, 0]);
+        let bytes = message(3, 3, [0; 4], &quoted);
+        let icmp = dissect(&dissector, &registry, (0, 0), REPLY, &bytes);
+
+        let names: Vec<&str> = icmp.quoted.iter().map(Layer::name).collect();
+        assert_eq!(names, vec!["ip", "udp"]);
+        assert_eq!(
+            icmp.type_and_code(),
+            "Destination unreachable (Port unreachable)"
+        );
+        assert!(icmp.fields().contains(&("udp.dstport", 53u16.into())));
+        let item = icmp.to_proto_item();
+        assert_eq!(item.children.len(), 5);
+        assert_eq!(item.children[4].start, HEADER_LEN + 20);
+    }
+
+    #[test]
+    fn quoted_echo_requests_are_not_waited_on() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        let mut quoted = vec![0x45, 0, 0, 32, 0, 1, 0, 0, 1, 1, 0, 0];
+        quoted.extend([10, 0, 0, 1, 10, 0, 0, 5]);
+        quoted.extend(echo(8, 1, 7));
+        let bytes = message(11, 0, [0; 4], &quoted);
+        let icmp = dissect(
+            &dissector,
+            &registry,
+            (0, 0),
+            ("10.0.0.9", "10.0.0.1"),
+            &bytes,
+        );
+        assert_eq!(icmp.quoted.len(), 2);
+
+        // The quoted request went through the registry's own ICMP dissector, which must not
+        //   have taken it as sent
+        let icmp_dissector = registry.get("icmp").unwrap();
+        let mut ctx = DissectCtx::new(&registry, 1, Timestamp::default());
+        ctx.net_src = Some("10.0.0.5".parse().unwrap());
+        ctx.net_dst = Some("10.0.0.1".parse().unwrap());
+        let reply = icmp_dissector.dissect(&mut ctx, 0, &echo(0, 1, 7)).unwrap();
+        let reply = reply.layer.as_any().downcast_ref::<Icmp>().unwrap();
+        assert!(matches!(reply.echo, Some((_, _, Pairing::Response(None)))));
+    }
+
+    #[test]
+    fn short_quotes_and_headers() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        // Too little of the datagram to make out its header
+        let bytes = message(3, 1, [0; 4], &[0x45, 0, 0, 30]);
+        let icmp = dissect(&dissector, &registry, (0, 0), REPLY, &bytes);
+        assert!(matches!(icmp.quoted[..], [Layer::Malformed(_)]));
+
+        let ctx = DissectCtx::new(&registry, 1, Timestamp::default());
+        let err = Icmp::from_bytes(&ctx, IcmpVersion::V6, 0, &[128, 0, 0, 0, 0]).unwrap_err();
+        assert_eq!(err.reason, "ICMPv6 header needs 8 bytes, only 5 captured");
+    }
+
+    #[test]
+    fn icmpv6_packet_too_big_shows_the_mtu() {
+        let registry = Registry::default();
+        let dissector = IcmpDissector::new(IcmpVersion::V6);
+        let mut bytes = vec![2, 0, 0, 0];
+        bytes.extend(1280u32.to_be_bytes());
+        let icmp = dissect(
+            &dissector,
+            &registry,
+            (0, 0),
+            ("2001:db8::1", "2001:db8::2"),
+            &bytes,
+        );
+        assert_eq!(icmp.info(), "Packet Too Big, MTU = 1280");
+        assert!(icmp.fields().contains(&("icmpv6.mtu", 1280u32.into())));
+        // The checksum covers the IPv6 pseudo-header, so a zero one is wrong
+        assert!(matches!(icmp.xsum_status, ChecksumStatus::Incorrect(_)));
+    }
+
+    #[test]
+    fn unanswered_requests_and_bad_checksums_are_expert_info() {
+        let registry = Registry::default();
+        let dissector = IcmpDissector::new(IcmpVersion::V4);
+        let request = dissect(&dissector, &registry, (0, 0), HOST, &echo(8, 1, 7));
+        let infos = request.expert_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(
+            (infos[0].severity, infos[0].summary.as_str()),
+            (Severity::Warning, "No response seen to ICMP request")
+        );
+
+        let mut bytes = echo(0, 1, 7);
+        bytes[2] ^= 0xff;
+        let reply = dissect(&dissector, &registry, (1, 1), REPLY, &bytes);
+        // The request got its reply, bad checksum or not
+        assert!(request.expert_infos().is_empty());
+        let infos = reply.expert_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(
+            (infos[0].group, infos[0].protocol.as_str()),
+            ("Checksum", "ICMP")
+        );
+    }
+}
@@ -1,12 +1,31 @@
 use core::fmt;
+use std::any::Any;
+use std::cell::Cell;
+use std::net::Ipv4Addr;
+use std::rc::Rc;
 use tui::style::{Color, Style};
-use tui_tree_widget::TreeItem;
 
-use crate::pkt::LayerHint;
+use crate::pkt::dissectors::reassembly::{FragmentTable, Reassembly};
+use crate::pkt::dissectors::util::{self, ChecksumStatus};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::expert::{ExpertInfo, Severity};
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+/// Length of the header without options
+const HEADER_LEN: usize = 20;
+
+const FLAG_RESERVED: u8 = 0x4;
+const FLAG_DONT_FRAGMENT: u8 = 0x2;
+const FLAG_MORE_FRAGMENTS: u8 = 0x1;
 
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub struct IPv4 {
+    /// Offset of the header in the packet
+    offset: usize,
     version: u8,
     header_len: u8,
     diffserv: u8,
@@ -14,108 +33,514 @@ pub struct IPv4 {
     total_length: u16,
     identification: u16,
     flags: u8,
+    /// In units of 8 bytes
     fragment_offset: u16,
     ttl: u8,
     protocol: u8,
     header_xsum: u16,
+    xsum_status: ChecksumStatus,
     source_addr: [u8; 4],
     dest_addr: [u8; 4],
+    options: Vec<Ipv4Option>,
+    /// Why option decoding stopped early, if it did
+    options_error: Option<String>,
+    /// For fragments, the frame the datagram was reassembled in once that has happened
+    reassembled_in: Option<Rc<Cell<Option<usize>>>>,
+    /// The datagram, if this fragment completed it
+    reassembly: Option<Reassembly>,
 }
 
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("ip.version", FieldKind::UInt, "Version"),
+    FieldInfo::new("ip.hdr_len", FieldKind::UInt, "Header Length"),
+    FieldInfo::new(
+        "ip.dsfield",
+        FieldKind::UInt,
+        "Differentiated Services Field",
+    ),
+    FieldInfo::new(
+        "ip.dsfield.dscp",
+        FieldKind::UInt,
+        "Differentiated Services Codepoint",
+    ),
+    FieldInfo::new(
+        "ip.dsfield.ecn",
+        FieldKind::UInt,
+        "Explicit Congestion Notification",
+    ),
+    FieldInfo::new("ip.len", FieldKind::UInt, "Total Length"),
+    FieldInfo::new("ip.id", FieldKind::UInt, "Identification"),
+    FieldInfo::new("ip.flags", FieldKind::UInt, "Flags"),
+    FieldInfo::new("ip.flags.rb", FieldKind::Bool, "Reserved bit"),
+    FieldInfo::new("ip.flags.df", FieldKind::Bool, "Don't fragment"),
+    FieldInfo::new("ip.flags.mf", FieldKind::Bool, "More fragments"),
+    FieldInfo::new(
+        "ip.frag_offset",
+        FieldKind::UInt,
+        "Fragment Offset, in bytes",
+    ),
+    FieldInfo::new("ip.ttl", FieldKind::UInt, "Time to Live"),
+    FieldInfo::new("ip.proto", FieldKind::UInt, "Protocol"),
+    FieldInfo::new("ip.checksum", FieldKind::UInt, "Header Checksum"),
+    FieldInfo::new(
+        "ip.checksum.status",
+        FieldKind::UInt,
+        "Header Checksum Status (0 bad, 1 good, 2 unverified)",
+    ),
+    FieldInfo::new("ip.src", FieldKind::Ipv4, "Source Address"),
+    FieldInfo::new("ip.dst", FieldKind::Ipv4, "Destination Address"),
+    FieldInfo::new("ip.addr", FieldKind::Ipv4, "Source or Destination Address"),
+    FieldInfo::new("ip.opt.type", FieldKind::UInt, "Option Type"),
+    FieldInfo::new("ip.opt.len", FieldKind::UInt, "Option Length"),
+    FieldInfo::new("ip.rec_rt", FieldKind::Ipv4, "Recorded Route"),
+    FieldInfo::new("ip.src_rt", FieldKind::Ipv4, "Source Route"),
+    FieldInfo::new("ip.opt.time_stamp", FieldKind::UInt, "Timestamp"),
+    FieldInfo::new(
+        "ip.opt.time_stamp_addr",
+        FieldKind::Ipv4,
+        "Timestamp Address",
+    ),
+    FieldInfo::new("ip.opt.ra", FieldKind::UInt, "Router Alert"),
+    FieldInfo::new("ip.opt.sec_cl", FieldKind::UInt, "Security Classification"),
+    FieldInfo::new("ip.fragment", FieldKind::UInt, "IPv4 Fragment"),
+    FieldInfo::new("ip.fragment.count", FieldKind::UInt, "Fragment count"),
+    FieldInfo::new(
+        "ip.reassembled_in",
+        FieldKind::UInt,
+        "Reassembled IPv4 in frame",
+    ),
+    FieldInfo::new(
+        "ip.reassembled.length",
+        FieldKind::UInt,
+        "Reassembled IPv4 length",
+    ),
+];
+
 fn ipaddr_to_string(bytes: &[u8; 4]) -> String {
     format!("{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
 }
 
-#[allow(dead_code)]
-impl IPv4 {
-    pub fn new() -> Self {
-        IPv4 {
-            version: 0,
-            header_len: 0,
-            diffserv: 0,
-            congestion_notification: 0,
-            total_length: 0,
-            identification: 0,
-            flags: 0,
-            fragment_offset: 0,
-            ttl: 0,
-            protocol: 0,
-            header_xsum: 0,
-            source_addr: [0; 4],
-            dest_addr: [0; 4],
-        }
-    }
-
-    #[allow(dead_code)]
-    pub fn to_tree_item_verbose<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new(
-            self.to_string(),
-            vec![
-                TreeItem::new_leaf(format!("Version: {}", self.version)),
-                TreeItem::new_leaf(format!(
-                    "Header Length: {} bytes ({})",
-                    4 * self.header_len,
-                    self.header_len
-                )),
-            ],
-        )
-        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+fn option_name(option_type: u8) -> &'static str {
+    match option_type {
+        0 => "End of Options List (EOL)",
+        1 => "No Operation (NOP)",
+        7 => "Record Route",
+        68 => "Time Stamp",
+        82 => "Traceroute",
+        130 => "Security",
+        131 => "Loose Source Route",
+        133 => "Extended Security",
+        134 => "Commercial IP Security",
+        136 => "Stream ID",
+        137 => "Strict Source Route",
+        148 => "Router Alert",
+        _ => "Unknown",
     }
+}
 
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
-        //TreeItem::new_leaf(self.to_string())
-        //    .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-        TreeItem::new(
-            self.to_string(),
-            vec![
-                TreeItem::new_leaf(format!("Version: {}", self.version)),
-                TreeItem::new_leaf(format!(
-                    "Header Length: {} bytes ({})",
-                    4 * self.header_len,
-                    self.header_len
-                )),
-                TreeItem::new_leaf(format!(
-                    "Differentiated Services Field: {:#04X}",
-                    self.diffserv
-                )),
-                TreeItem::new_leaf(format!("Total Length: {}", self.total_length)),
-                TreeItem::new_leaf(format!("Identification: {:#06x}", self.identification)),
-                TreeItem::new_leaf(format!("Flags: {:#06x}", self.flags)),
-                TreeItem::new_leaf(format!("Time to live: {}", self.ttl)),
-                TreeItem::new_leaf(format!("Protocol: {}", self.protocol)),
-                TreeItem::new_leaf(format!("Header checksum: {:#06x}", self.header_xsum)),
-                TreeItem::new_leaf(format!("Source: {}", ipaddr_to_string(&self.source_addr))),
-                TreeItem::new_leaf(format!(
-                    "Destination: {}",
-                    ipaddr_to_string(&self.dest_addr)
+/// Classification levels of the RFC 1108 security option
+fn classification_name(level: u8) -> &'static str {
+    match level {
+        0x01 => "Reserved 4",
+        0x3d => "Top Secret",
+        0x5a => "Secret",
+        0x96 => "Confidential",
+        0x66 => "Reserved 3",
+        0xcc => "Reserved 2",
+        0xab => "Unclassified",
+        0xf1 => "Reserved 1",
+        _ => "Unknown",
+    }
+}
+
+fn timestamp_flag_name(flag: u8) -> &'static str {
+    match flag {
+        0 => "Time stamps only",
+        1 => "Time stamp and address",
+        3 => "Time stamps for prespecified addresses",
+        _ => "Unknown",
+    }
+}
+
+/// What a particular option carries beyond its type and length
+#[derive(Clone, Debug)]
+enum Ipv4OptionKind {
+    EndOfList,
+    Nop,
+    /// Record Route, Loose Source Route and Strict Source Route share a layout: a pointer to
+    ///   the next free slot followed by the route's addresses
+    Route {
+        pointer: u8,
+        addresses: Vec<[u8; 4]>,
+    },
+    Timestamp {
+        pointer: u8,
+        overflow: u8,
+        flag: u8,
+        /// Each timestamp, with the address that recorded it when the flag asks for one
+        entries: Vec<(Option<[u8; 4]>, u32)>,
+    },
+    RouterAlert(u16),
+    Security {
+        classification: u8,
+        authority: Vec<u8>,
+    },
+    Other,
+}
+
+#[derive(Clone, Debug)]
+struct Ipv4Option {
+    /// Offset of the option in the packet
+    offset: usize,
+    option_type: u8,
+    length: usize,
+    kind: Ipv4OptionKind,
+}
+
+impl Ipv4Option {
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let name = option_name(self.option_type);
+        let mut children = vec![];
+        if self.length > 1 {
+            children.push(ProtoItem::new(
+                format!("Type: {}", self.option_type),
+                off,
+                1,
+                vec![
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{} = Copy on fragmentation: {}",
+                            util::bit_pattern(self.option_type as u32, 0x80, 8),
+                            if self.option_type & 0x80 != 0 {
+                                "Yes"
+                            } else {
+                                "No"
+                            }
+                        ),
+                        off,
+                        1,
+                    ),
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{} = Class: {}",
+                            util::bit_pattern(self.option_type as u32, 0x60, 8),
+                            match (self.option_type >> 5) & 0x03 {
+                                0 => "Control (0)",
+                                1 => "Reserved for future use (1)",
+                                2 => "Debugging and measurement (2)",
+                                _ => "Reserved for future use (3)",
+                            }
+                        ),
+                        off,
+                        1,
+                    ),
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{} = Number: {}",
+                            util::bit_pattern(self.option_type as u32, 0x1f, 8),
+                            self.option_type & 0x1f
+                        ),
+                        off,
+                        1,
+                    ),
+                ],
+            ));
+            children.push(ProtoItem::new_leaf(
+                format!("Length: {}", self.length),
+                off + 1,
+                1,
+            ));
+        }
+
+        let text = match &self.kind {
+            Ipv4OptionKind::EndOfList | Ipv4OptionKind::Nop | Ipv4OptionKind::Other => {
+                format!("IP Option - {}", name)
+            }
+            Ipv4OptionKind::Route { pointer, addresses } => {
+                children.push(ProtoItem::new_leaf(
+                    format!("Pointer: {}", pointer),
+                    off + 2,
+                    1,
+                ));
+                // The pointer is 1-based from the start of the option and names the next slot
+                let next_slot = (*pointer as usize).saturating_sub(4) / 4;
+                for (i, addr) in addresses.iter().enumerate() {
+                    let marker = if i == next_slot { " <- (next)" } else { "" };
+                    children.push(ProtoItem::new_leaf(
+                        format!("{}{}", ipaddr_to_string(addr), marker),
+                        off + 3 + 4 * i,
+                        4,
+                    ));
+                }
+                format!("IP Option - {} ({} bytes)", name, self.length)
+            }
+            Ipv4OptionKind::Timestamp {
+                pointer,
+                overflow,
+                flag,
+                entries,
+            } => {
+                children.push(ProtoItem::new_leaf(
+                    format!("Pointer: {}", pointer),
+                    off + 2,
+                    1,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Overflow: {}", overflow),
+                    off + 3,
+                    1,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Flag: {} ({})", timestamp_flag_name(*flag), flag),
+                    off + 3,
+                    1,
+                ));
+                let entry_len = if *flag == 0 { 4 } else { 8 };
+                for (i, (addr, timestamp)) in entries.iter().enumerate() {
+                    let entry_off = off + 4 + entry_len * i;
+                    if let Some(addr) = addr {
+                        children.push(ProtoItem::new_leaf(
+                            format!("Address: {}", ipaddr_to_string(addr)),
+                            entry_off,
+                            4,
+                        ));
+                    }
+                    children.push(ProtoItem::new_leaf(
+                        format!("Time stamp: {}", timestamp),
+                        entry_off + entry_len - 4,
+                        4,
+                    ));
+                }
+                format!("IP Option - {} ({} bytes)", name, self.length)
+            }
+            Ipv4OptionKind::RouterAlert(value) => {
+                let meaning = if *value == 0 {
+                    "Router shall examine packet"
+                } else {
+                    "Reserved"
+                };
+                children.push(ProtoItem::new_leaf(
+                    format!("Router Alert: {} ({})", meaning, value),
+                    off + 2,
+                    2,
+                ));
+                format!("IP Option - {} ({} bytes)", name, self.length)
+            }
+            Ipv4OptionKind::Security {
+                classification,
+                authority,
+            } => {
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "Classification level: {} ({:#04x})",
+                        classification_name(*classification),
+                        classification
+                    ),
+                    off + 2,
+                    1,
+                ));
+                if !authority.is_empty() {
+                    let hex: String = authority.iter().map(|b| format!("{:02x}", b)).collect();
+                    children.push(ProtoItem::new_leaf(
+                        format!("Protection authority flags: {}", hex),
+                        off + 3,
+                        authority.len(),
+                    ));
+                }
+                format!(
+                    "IP Option - {} ({} bytes): {}",
+                    name,
+                    self.length,
+                    classification_name(*classification)
+                )
+            }
+        };
+        ProtoItem::new(text, off, self.length, children)
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![("ip.opt.type", self.option_type.into())];
+        if self.length > 1 {
+            fields.push(("ip.opt.len", self.length.into()));
+        }
+        match &self.kind {
+            Ipv4OptionKind::Route { addresses, .. } => {
+                let name = if self.option_type == 7 {
+                    "ip.rec_rt"
+                } else {
+                    "ip.src_rt"
+                };
+                fields.extend(addresses.iter().map(|a| (name, (*a).into())));
+            }
+            Ipv4OptionKind::Timestamp { entries, .. } => {
+                for (addr, timestamp) in entries {
+                    if let Some(addr) = addr {
+                        fields.push(("ip.opt.time_stamp_addr", (*addr).into()));
+                    }
+                    fields.push(("ip.opt.time_stamp", (*timestamp).into()));
+                }
+            }
+            Ipv4OptionKind::RouterAlert(value) => fields.push(("ip.opt.ra", (*value).into())),
+            Ipv4OptionKind::Security { classification, .. } => {
+                fields.push(("ip.opt.sec_cl", (*classification).into()))
+            }
+            _ => {}
+        }
+        fields
+    }
+}
+
+/// Decodes the options in `bytes`, which start at offset `offset` in the packet. Decoding
+/// stops at the first option whose length doesn't make sense, which is reported alongside the
+/// options before it.
+fn parse_options(offset: usize, bytes: &[u8]) -> (Vec<Ipv4Option>, Option<String>) {
+    let mut options = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let option_type = bytes[pos];
+        if option_type == 0 || option_type == 1 {
+            options.push(Ipv4Option {
+                offset: offset + pos,
+                option_type,
+                length: 1,
+                kind: if option_type == 0 {
+                    Ipv4OptionKind::EndOfList
+                } else {
+                    Ipv4OptionKind::Nop
+                },
+            });
+            pos += 1;
+            // Whatever follows the end of the list is padding
+            if option_type == 0 {
+                break;
+            }
+            continue;
+        }
+
+        let name = option_name(option_type);
+        if pos + 1 >= bytes.len() {
+            return (options, Some(format!("{} option has no length", name)));
+        }
+        let length = bytes[pos + 1] as usize;
+        if length < 2 || pos + length > bytes.len() {
+            return (
+                options,
+                Some(format!(
+                    "{} option length {} is invalid, {} bytes of options remain",
+                    name,
+                    length,
+                    bytes.len() - pos
                 )),
-            ],
-        )
-        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+            );
+        }
+        let value = &bytes[pos + 2..pos + length];
+        let kind = match option_type {
+            7 | 131 | 137 if !value.is_empty() => Ipv4OptionKind::Route {
+                pointer: value[0],
+                addresses: value[1..]
+                    .chunks_exact(4)
+                    .map(|a| [a[0], a[1], a[2], a[3]])
+                    .collect(),
+            },
+            68 if value.len() >= 2 => {
+                let flag = value[1] & 0x0f;
+                let entry_len = if flag == 0 { 4 } else { 8 };
+                let entries = value[2..]
+                    .chunks_exact(entry_len)
+                    .map(|entry| {
+                        let addr = (flag != 0).then(|| [entry[0], entry[1], entry[2], entry[3]]);
+                        (addr, util::four_bytes_to_u32(&entry[entry_len - 4..]))
+                    })
+                    .collect();
+                Ipv4OptionKind::Timestamp {
+                    pointer: value[0],
+                    overflow: value[1] >> 4,
+                    flag,
+                    entries,
+                }
+            }
+            148 if value.len() == 2 => Ipv4OptionKind::RouterAlert(util::two_bytes_to_u16(value)),
+            130 if !value.is_empty() => Ipv4OptionKind::Security {
+                classification: value[0],
+                authority: value[1..].to_vec(),
+            },
+            7 | 131 | 137 | 68 | 148 | 130 => {
+                return (
+                    options,
+                    Some(format!("{} option length {} is invalid", name, length)),
+                );
+            }
+            _ => Ipv4OptionKind::Other,
+        };
+        options.push(Ipv4Option {
+            offset: offset + pos,
+            option_type,
+            length,
+            kind,
+        });
+        pos += length;
     }
+    (options, None)
+}
 
-    pub fn from_bytes(next_byte: usize, bytes: &[u8]) -> (Self, usize, LayerHint) {
-        assert!(bytes.len() >= 20);
+impl IPv4 {
+    pub fn from_bytes(
+        ctx: &DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated("IPv4", HEADER_LEN, bytes.len()));
+        }
 
-        // TODO: move to byte packing func
         let version = (bytes[0] >> 4) & 0x0f;
         let header_len = bytes[0] & 0x0f;
+        if version != 4 {
+            return Err(DissectError::new(format!(
+                "bogus IP version ({}, must be 4)",
+                version
+            )));
+        }
+        if header_len < 5 {
+            return Err(DissectError::new(format!(
+                "bogus IPv4 header length ({} bytes, must be at least 20)",
+                4 * header_len
+            )));
+        }
+        let header_bytes = 4 * header_len as usize;
+        if bytes.len() < header_bytes {
+            return Err(DissectError::truncated("IPv4", header_bytes, bytes.len()));
+        }
         let diffserv = (bytes[1] >> 2) & 0x3f;
         let congestion_notification = bytes[1] & 0x03;
-        let total_length = (256u16 * bytes[2] as u16) + bytes[3] as u16;
-        let identification = (256u16 * bytes[4] as u16) + bytes[5] as u16;
+        let total_length = util::two_bytes_to_u16(&bytes[2..4]);
+        let identification = util::two_bytes_to_u16(&bytes[4..6]);
         let flags = (bytes[6] >> 5) & 0x07;
-        let fragment_offset = (256u16 * (bytes[6] & 0x1f) as u16) + bytes[7] as u16;
+        let fragment_offset = util::two_bytes_to_u16(&bytes[6..8]) & 0x1fff;
         let ttl = bytes[8];
         let protocol = bytes[9];
-        let header_xsum = (256u16 * bytes[10] as u16) + bytes[11] as u16;
+        let header_xsum = util::two_bytes_to_u16(&bytes[10..12]);
         let mut source_addr: [u8; 4] = [0; 4];
         source_addr.clone_from_slice(&bytes[12..16]);
         let mut dest_addr: [u8; 4] = [0; 4];
         dest_addr.clone_from_slice(&bytes[16..20]);
-        //TODO: Parse Options!
+        let (options, options_error) =
+            parse_options(next_byte + HEADER_LEN, &bytes[HEADER_LEN..header_bytes]);
+        let xsum_status = if !ctx.registry.prefs.validate_checksums {
+            ChecksumStatus::Offloaded
+        } else {
+            match util::internet_checksum(&[&bytes[..header_bytes]]) {
+                0 => ChecksumStatus::Correct,
+                _ => ChecksumStatus::Incorrect(util::internet_checksum(&[
+                    &bytes[0..10],
+                    &bytes[12..header_bytes],
+                ])),
+            }
+        };
 
         let ip_layer = IPv4 {
+            offset: next_byte,
             version,
             header_len,
             diffserv,
@@ -127,17 +552,79 @@ impl IPv4 {
             ttl,
             protocol,
             header_xsum,
+            xsum_status,
             source_addr,
             dest_addr,
+            options,
+            options_error,
+            reassembled_in: None,
+            reassembly: None,
         };
 
-        let ret_next_byte = next_byte + 4usize * (ip_layer.header_len as usize);
-        let layer_hint = match ip_layer.protocol {
-            6 => LayerHint::Tcp,
-            _ => LayerHint::Undecoded,
-        };
+        let ret_next_byte = next_byte + header_bytes;
+        let next_layer = NextLayer::Table(Table::IpProto, ip_layer.protocol as u32);
+
+        Ok((ip_layer, ret_next_byte, next_layer))
+    }
+
+    fn header_bytes(&self) -> usize {
+        4 * self.header_len as usize
+    }
+
+    fn dsfield(&self) -> u8 {
+        (self.diffserv << 2) | self.congestion_notification
+    }
 
-        (ip_layer, ret_next_byte, layer_hint)
+    /// Fragment offset in bytes
+    fn fragment_offset_bytes(&self) -> usize {
+        8 * self.fragment_offset as usize
+    }
+
+    /// Whether this datagram only carries part of its payload
+    fn is_fragment(&self) -> bool {
+        self.flags & FLAG_MORE_FRAGMENTS != 0 || self.fragment_offset != 0
+    }
+
+    /// Frame the datagram this fragment belongs to was reassembled in, if it was
+    fn reassembled_in(&self) -> Option<usize> {
+        self.reassembled_in.as_ref().and_then(|link| link.get())
+    }
+
+    fn flags_to_proto_item(&self) -> ProtoItem {
+        let off = self.offset + 6;
+        let bit = |mask: u8, name: &str| {
+            ProtoItem::new_leaf(
+                format!(
+                    "{} = {}: {}",
+                    util::bit_pattern(self.flags as u32, mask as u32, 3),
+                    name,
+                    if self.flags & mask != 0 {
+                        "Set"
+                    } else {
+                        "Not set"
+                    }
+                ),
+                off,
+                1,
+            )
+        };
+        let mut text = format!("Flags: {:#x}", self.flags);
+        if self.flags & FLAG_DONT_FRAGMENT != 0 {
+            text += ", Don't fragment";
+        }
+        if self.flags & FLAG_MORE_FRAGMENTS != 0 {
+            text += ", More fragments";
+        }
+        ProtoItem::new(
+            text,
+            off,
+            1,
+            vec![
+                bit(FLAG_RESERVED, "Reserved bit"),
+                bit(FLAG_DONT_FRAGMENT, "Don't fragment"),
+                bit(FLAG_MORE_FRAGMENTS, "More fragments"),
+            ],
+        )
     }
 }
 
@@ -151,3 +638,548 @@ impl fmt::Display for IPv4 {
         )
     }
 }
+
+impl ProtocolLayer for IPv4 {
+    fn name(&self) -> &'static str {
+        "ip"
+    }
+
+    fn label(&self) -> String {
+        "IPv4".to_string()
+    }
+
+    fn info(&self) -> String {
+        if !self.is_fragment() {
+            return format!(
+                "Protocol {} ({})",
+                util::ip_proto_name(self.protocol),
+                self.protocol
+            );
+        }
+        let mut info = format!(
+            "Fragmented IP protocol (proto={} {}, off={}, ID={:04x})",
+            util::ip_proto_name(self.protocol),
+            self.protocol,
+            self.fragment_offset_bytes(),
+            self.identification
+        );
+        if let Some(frame) = self.reassembled_in() {
+            info += &format!(" [Reassembled in #{}]", frame);
+        }
+        info
+    }
+
+    fn addresses(&self) -> Option<(String, String)> {
+        Some((
+            ipaddr_to_string(&self.source_addr),
+            ipaddr_to_string(&self.dest_addr),
+        ))
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("ip.version", self.version.into()),
+            ("ip.hdr_len", (4 * self.header_len).into()),
+            ("ip.dsfield", self.dsfield().into()),
+            ("ip.dsfield.dscp", self.diffserv.into()),
+            ("ip.dsfield.ecn", self.congestion_notification.into()),
+            ("ip.len", self.total_length.into()),
+            ("ip.id", self.identification.into()),
+            ("ip.flags", self.flags.into()),
+            ("ip.flags.rb", (self.flags & FLAG_RESERVED != 0).into()),
+            ("ip.flags.df", (self.flags & FLAG_DONT_FRAGMENT != 0).into()),
+            (
+                "ip.flags.mf",
+                (self.flags & FLAG_MORE_FRAGMENTS != 0).into(),
+            ),
+            ("ip.frag_offset", self.fragment_offset_bytes().into()),
+            ("ip.ttl", self.ttl.into()),
+            ("ip.proto", self.protocol.into()),
+            ("ip.checksum", self.header_xsum.into()),
+            ("ip.checksum.status", self.xsum_status.code().into()),
+            ("ip.src", self.source_addr.into()),
+            ("ip.dst", self.dest_addr.into()),
+            ("ip.addr", self.source_addr.into()),
+            ("ip.addr", self.dest_addr.into()),
+        ];
+        for option in &self.options {
+            fields.extend(option.fields());
+        }
+        if let Some(reassembly) = &self.reassembly {
+            fields.extend(
+                reassembly
+                    .fragments
+                    .iter()
+                    .map(|f| ("ip.fragment", f.frame_num.into())),
+            );
+            fields.push(("ip.fragment.count", reassembly.fragments.len().into()));
+            fields.push(("ip.reassembled.length", reassembly.bytes.len().into()));
+        } else if let Some(frame) = self.reassembled_in() {
+            fields.push(("ip.reassembled_in", frame.into()));
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let dsfield = self.dsfield();
+        let mut children = vec![
+            ProtoItem::new_leaf(
+                format!(
+                    "{} .... = Version: {}",
+                    util::bit_pattern(self.version as u32, 0xf, 4),
+                    self.version
+                ),
+                off,
+                1,
+            ),
+            ProtoItem::new_leaf(
+                format!(
+                    ".... {} = Header Length: {} bytes ({})",
+                    util::bit_pattern(self.header_len as u32, 0xf, 4),
+                    self.header_bytes(),
+                    self.header_len
+                ),
+                off,
+                1,
+            ),
+            ProtoItem::new(
+                format!(
+                    "Differentiated Services Field: {:#04x} (DSCP: {}, ECN: {})",
+                    dsfield,
+                    util::dscp_name(self.diffserv),
+                    util::ecn_name(self.congestion_notification)
+                ),
+                off + 1,
+                1,
+                vec![
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{} = Differentiated Services Codepoint: {} ({})",
+                            util::bit_pattern(dsfield as u32, 0xfc, 8),
+                            util::dscp_name(self.diffserv),
+                            self.diffserv
+                        ),
+                        off + 1,
+                        1,
+                    ),
+                    ProtoItem::new_leaf(
+                        format!(
+                            "{} = Explicit Congestion Notification: {} ({})",
+                            util::bit_pattern(dsfield as u32, 0x03, 8),
+                            util::ecn_name(self.congestion_notification),
+                            self.congestion_notification
+                        ),
+                        off + 1,
+                        1,
+                    ),
+                ],
+            ),
+            ProtoItem::new_leaf(format!("Total Length: {}", self.total_length), off + 2, 2),
+            ProtoItem::new_leaf(
+                format!(
+                    "Identification: {:#06x} ({})",
+                    self.identification, self.identification
+                ),
+                off + 4,
+                2,
+            ),
+            self.flags_to_proto_item(),
+            ProtoItem::new_leaf(
+                format!(
+                    "{} = Fragment Offset: {} bytes",
+                    util::bit_pattern(self.fragment_offset as u32, 0x1fff, 16),
+                    self.fragment_offset_bytes()
+                ),
+                off + 6,
+                2,
+            ),
+            ProtoItem::new_leaf(format!("Time to live: {}", self.ttl), off + 8, 1),
+            ProtoItem::new_leaf(
+                format!(
+                    "Protocol: {} ({})",
+                    util::ip_proto_name(self.protocol),
+                    self.protocol
+                ),
+                off + 9,
+                1,
+            ),
+            self.xsum_status
+                .to_proto_item("Header Checksum", self.header_xsum, off + 10),
+            ProtoItem::new_leaf(
+                format!("Source: {}", ipaddr_to_string(&self.source_addr)),
+                off + 12,
+                4,
+            ),
+            ProtoItem::new_leaf(
+                format!("Destination: {}", ipaddr_to_string(&self.dest_addr)),
+                off + 16,
+                4,
+            ),
+        ];
+
+        let options_len = self.header_bytes() - HEADER_LEN;
+        if options_len > 0 {
+            let mut option_items: Vec<ProtoItem> =
+                self.options.iter().map(|o| o.to_proto_item()).collect();
+            // Bytes after an End of Options List only pad the header to a multiple of 4
+            let decoded_len: usize = self.options.iter().map(|o| o.length).sum();
+            if self.options_error.is_none() && decoded_len < options_len {
+                option_items.push(ProtoItem::new_leaf(
+                    format!("Padding ({} bytes)", options_len - decoded_len),
+                    off + HEADER_LEN + decoded_len,
+                    options_len - decoded_len,
+                ));
+            }
+            if let Some(err) = &self.options_error {
+                option_items.push(
+                    ProtoItem::new_leaf(format!("[{}]", err), 0, 0)
+                        .style(Style::default().fg(Color::White).bg(Color::Red)),
+                );
+            }
+            let names: Vec<&str> = self
+                .options
+                .iter()
+                .filter(|o| o.length > 1)
+                .map(|o| option_name(o.option_type))
+                .collect();
+            let text = if names.is_empty() {
+                format!("Options: ({} bytes)", options_len)
+            } else {
+                format!("Options: ({} bytes), {}", options_len, names.join(", "))
+            };
+            children.push(ProtoItem::new(
+                text,
+                off + HEADER_LEN,
+                options_len,
+                option_items,
+            ));
+        }
+
+        if let Some(reassembly) = &self.reassembly {
+            children.push(reassembly.to_proto_item("IPv4", "Fragment"));
+        } else if let Some(frame) = self.reassembled_in() {
+            children.push(ProtoItem::new_leaf(
+                format!("[Reassembled in #{}]", frame),
+                0,
+                0,
+            ));
+        }
+
+        ProtoItem::new(self.to_string(), off, self.header_bytes(), children)
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        let mut infos: Vec<ExpertInfo> = self.xsum_status.expert_info("IPv4").into_iter().collect();
+        if let Some(err) = &self.options_error {
+            infos.push(ExpertInfo::new(
+                Severity::Warning,
+                "Protocol",
+                "IPv4",
+                err.clone(),
+            ));
+        }
+        infos
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Identifies the datagram a fragment belongs to: source, destination, protocol and
+/// identification (RFC 791)
+type FragmentKey = ([u8; 4], [u8; 4], u8, u16);
+
+pub struct IPv4Dissector {
+    fragments: FragmentTable<FragmentKey>,
+}
+
+impl IPv4Dissector {
+    pub fn new() -> Self {
+        IPv4Dissector {
+            fragments: FragmentTable::new(),
+        }
+    }
+}
+
+impl Dissector for IPv4Dissector {
+    fn name(&self) -> &'static str {
+        "ip"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (mut layer, next_byte, next_layer) = IPv4::from_bytes(ctx, next_byte, bytes)?;
+        ctx.net_src = Some(Ipv4Addr::from(layer.source_addr).into());
+        ctx.net_dst = Some(Ipv4Addr::from(layer.dest_addr).into());
+        // Ignore a bogus total length rather than cutting the payload short
+        let total_length = layer.total_length as usize;
+        let whole_datagram = total_length >= layer.header_bytes() && total_length <= bytes.len();
+        if whole_datagram {
+            ctx.payload_end = Some(layer.offset + total_length);
+        }
+
+        // An ICMP error quoting the start of a first fragment still shows its transport header
+        if !layer.is_fragment() || (ctx.in_error_packet && layer.fragment_offset == 0) {
+            return Ok(Dissection::new(layer, next_byte, next_layer));
+        }
+        if ctx.in_error_packet || !whole_datagram {
+            return Ok(Dissection::new(layer, next_byte, NextLayer::Undecoded));
+        }
+        let key = (
+            layer.source_addr,
+            layer.dest_addr,
+            layer.protocol,
+            layer.identification,
+        );
+        let (reassembled_in, reassembly) = self.fragments.add(
+            key,
+            ctx.frame_num,
+            ctx.timestamp,
+            layer.fragment_offset_bytes(),
+            &bytes[layer.header_bytes()..total_length],
+            layer.flags & FLAG_MORE_FRAGMENTS == 0,
+        );
+        layer.reassembled_in = Some(reassembled_in);
+        match reassembly {
+            Some(reassembly) => {
+                let datagram = reassembly.bytes.clone();
+                layer.reassembly = Some(reassembly);
+                Ok(Dissection::new(layer, next_byte, next_layer)
+                    .with_reassembled("Reassembled IPv4", datagram))
+            }
+            None => Ok(Dissection::new(layer, next_byte, NextLayer::Undecoded)),
+        }
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(IPv4Dissector::new());
+    registry.add_to_table(Table::EtherType, 0x0800, "ip");
+    registry.add_to_table(Table::LinkType, pcap_parser::Linktype::IPV4.0 as u32, "ip");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pkt::registry::Preferences;
+
+    /// Options starting 20 bytes into the packet, after a header without any
+    fn parse(bytes: &[u8]) -> (Vec<Ipv4Option>, Option<String>) {
+        parse_options(HEADER_LEN, bytes)
+    }
+
+    fn types(options: &[Ipv4Option]) -> Vec<u8> {
+        options.iter().map(|o| o.option_type).collect()
+    }
+
+    #[test]
+    fn route_options_list_their_addresses() {
+        // A NOP, then a record route with room for two addresses, one filled in
+        let bytes = [1, 7, 11, 8, 10, 0, 0, 1, 0, 0, 0, 0];
+        let (options, error) = parse(&bytes);
+        assert_eq!(error, None);
+        assert_eq!(types(&options), vec![1, 7]);
+        let route = &options[1];
+        assert_eq!((route.offset, route.length), (HEADER_LEN + 1, 11));
+        assert!(matches!(
+            &route.kind,
+            Ipv4OptionKind::Route { pointer: 8, addresses } if addresses == &[[10, 0, 0, 1], [0; 4]]
+        ));
+        assert!(route
+            .fields()
+            .contains(&("ip.rec_rt", [10, 0, 0, 1].into())));
+        let item = route.to_proto_item();
+        assert_eq!(item.children.last().unwrap().text, "0.0.0.0 <- (next)");
+
+        let (options, _) = parse(&[131, 7, 4, 192, 0, 2, 1, 0]);
+        assert!(options[0]
+            .fields()
+            .contains(&("ip.src_rt", [192, 0, 2, 1].into())));
+    }
+
+    #[test]
+    fn timestamp_entries_follow_the_flag() {
+        // Time stamps only
+        let mut bytes = vec![68, 12, 13, 0];
+        bytes.extend(1000u32.to_be_bytes());
+        bytes.extend(2000u32.to_be_bytes());
+        let (options, error) = parse(&bytes);
+        assert_eq!(error, None);
+        assert!(matches!(
+            &options[0].kind,
+            Ipv4OptionKind::Timestamp { flag: 0, entries, .. }
+                if entries == &[(None, 1000), (None, 2000)]
+        ));
+
+        // Address and time stamp pairs, with an overflow count of 2
+        let mut bytes = vec![68, 12, 13, 0x21, 10, 0, 0, 1];
+        bytes.extend(3000u32.to_be_bytes());
+        let (options, _) = parse(&bytes);
+        assert!(matches!(
+            &options[0].kind,
+            Ipv4OptionKind::Timestamp { overflow: 2, flag: 1, entries, .. }
+                if entries == &[(Some([10, 0, 0, 1]), 3000)]
+        ));
+        let fields = options[0].fields();
+        assert!(fields.contains(&("ip.opt.time_stamp_addr", [10, 0, 0, 1].into())));
+        assert!(fields.contains(&("ip.opt.time_stamp", 3000u32.into())));
+    }
+
+    #[test]
+    fn security_and_router_alert() {
+        let (options, error) = parse(&[130, 4, 0x5a, 0x80, 148, 4, 0, 0, 0]);
+        assert_eq!(error, None);
+        assert_eq!(types(&options), vec![130, 148, 0]);
+        assert!(matches!(
+            &options[0].kind,
+            Ipv4OptionKind::Security { classification: 0x5a, authority } if authority == &[0x80]
+        ));
+        assert_eq!(
+            options[0].to_proto_item().text,
+            "IP Option - Security (4 bytes): Secret"
+        );
+        assert!(matches!(options[1].kind, Ipv4OptionKind::RouterAlert(0)));
+    }
+
+    #[test]
+    fn end_of_list_stops_decoding() {
+        // What follows the end of the list would be a bad option if it were read
+        let (options, error) = parse(&[1, 0, 7, 0xff]);
+        assert_eq!(error, None);
+        assert_eq!(types(&options), vec![1, 0]);
+    }
+
+    #[test]
+    fn bad_lengths_stop_decoding_with_an_error() {
+        let (options, error) = parse(&[1, 7]);
+        assert_eq!(types(&options), vec![1]);
+        assert_eq!(error.as_deref(), Some("Record Route option has no length"));
+
+        let (options, error) = parse(&[148, 1, 0, 0]);
+        assert!(options.is_empty());
+        assert_eq!(
+            error.as_deref(),
+            Some("Router Alert option length 1 is invalid, 4 bytes of options remain")
+        );
+
+        let (_, error) = parse(&[1, 68, 12, 5, 0]);
+        assert_eq!(
+            error.as_deref(),
+            Some("Time Stamp option length 12 is invalid, 4 bytes of options remain")
+        );
+
+        // Long enough to hold, but too short for what the option carries
+        let (options, error) = parse(&[1, 148, 3, 0, 1, 1, 1, 1]);
+        assert_eq!(types(&options), vec![1]);
+        assert_eq!(
+            error.as_deref(),
+            Some("Router Alert option length 3 is invalid")
+        );
+        let (_, error) = parse(&[7, 2, 0, 0]);
+        assert_eq!(
+            error.as_deref(),
+            Some("Record Route option length 2 is invalid")
+        );
+        let (_, error) = parse(&[68, 3, 5, 0]);
+        assert_eq!(
+            error.as_deref(),
+            Some("Time Stamp option length 3 is invalid")
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_skipped_by_their_length() {
+        let (options, error) = parse(&[0x9e, 4, 0xde, 0xad, 1, 0]);
+        assert_eq!(error, None);
+        assert_eq!(types(&options), vec![0x9e, 1, 0]);
+        assert!(matches!(options[0].kind, Ipv4OptionKind::Other));
+        assert_eq!(options[1].offset, HEADER_LEN + 4);
+    }
+
+    #[test]
+    fn option_errors_are_shown_with_the_header() {
+        let registry = Registry::default();
+        let ctx = DissectCtx::new(&registry, 0, Default::default());
+        // A 24 byte header whose only option claims to be longer than the header
+        let mut bytes = vec![0x46, 0, 0, 24, 0, 1, 0, 0, 64, 17, 0, 0];
+        bytes.extend([10, 0, 0, 1, 10, 0, 0, 5]);
+        bytes.extend([7, 8, 4, 0]);
+        let (layer, next_byte, _) = IPv4::from_bytes(&ctx, 0, &bytes).unwrap();
+        assert_eq!(next_byte, 24);
+        assert!(layer.options.is_empty());
+        let item = layer.to_proto_item();
+        let options = item
+            .children
+            .iter()
+            .find(|c| c.text.starts_with("Options"))
+            .unwrap();
+        assert_eq!(
+            options.children[0].text,
+            "[Record Route option length 8 is invalid, 4 bytes of options remain]"
+        );
+
+        // A header length running past the captured bytes is an error of its own
+        let err = IPv4::from_bytes(&ctx, 0, &bytes[..22]).unwrap_err();
+        assert_eq!(err.reason, "IPv4 header needs 24 bytes, only 22 captured");
+    }
+
+    #[test]
+    fn header_checksum_is_verified() {
+        let mut bytes = vec![0x45, 0, 0, 40, 0, 1, 0x40, 0, 64, 6, 0x26, 0xca];
+        bytes.extend([10, 0, 0, 1, 10, 0, 0, 5]);
+        let registry = Registry::default();
+        let ctx = DissectCtx::new(&registry, 0, Default::default());
+        let (layer, _, _) = IPv4::from_bytes(&ctx, 0, &bytes).unwrap();
+        assert_eq!(layer.xsum_status, ChecksumStatus::Correct);
+
+        bytes[8] = 63;
+        let (layer, _, _) = IPv4::from_bytes(&ctx, 0, &bytes).unwrap();
+        assert_eq!(layer.xsum_status, ChecksumStatus::Incorrect(0x27ca));
+        assert!(layer.fields().contains(&("ip.checksum.status", 0u8.into())));
+
+        let registry = Registry::with_prefs(Preferences {
+            validate_checksums: false,
+        });
+        let ctx = DissectCtx::new(&registry, 0, Default::default());
+        let (layer, _, _) = IPv4::from_bytes(&ctx, 0, &bytes).unwrap();
+        assert_eq!(layer.xsum_status, ChecksumStatus::Offloaded);
+    }
+
+    #[test]
+    fn bad_checksum_and_options_are_expert_info() {
+        // A bad option, and a checksum left at zero
+        let mut bytes = vec![0x46, 0, 0, 24, 0, 1, 0, 0, 64, 17, 0, 0];
+        bytes.extend([10, 0, 0, 1, 10, 0, 0, 5]);
+        bytes.extend([148, 1, 0, 0]);
+        let registry = Registry::default();
+        let ctx = DissectCtx::new(&registry, 0, Default::default());
+        let (layer, _, _) = IPv4::from_bytes(&ctx, 0, &bytes).unwrap();
+        let infos = layer.expert_infos();
+        let kinds: Vec<(Severity, &str, &str)> = infos
+            .iter()
+            .map(|i| (i.severity, i.group, i.protocol.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Severity::Error, "Checksum", "IPv4"),
+                (Severity::Warning, "Protocol", "IPv4")
+            ]
+        );
+        assert_eq!(
+            infos[1].summary,
+            "Router Alert option length 1 is invalid, 4 bytes of options remain"
+        );
+    }
+}
@@ -0,0 +1,957 @@
+use core::fmt;
+use std::any::Any;
+use std::cell::Cell;
+use std::net::Ipv6Addr;
+use std::rc::Rc;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::reassembly::{FragmentTable, Reassembly};
+use crate::pkt::dissectors::util;
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+const HEADER_LEN: usize = 40;
+
+const HOP_BY_HOP: u8 = 0;
+const ROUTING: u8 = 43;
+const FRAGMENT: u8 = 44;
+const ESP: u8 = 50;
+const AUTH: u8 = 51;
+const NO_NEXT_HEADER: u8 = 59;
+const DEST_OPTIONS: u8 = 60;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("ipv6.version", FieldKind::UInt, "Version"),
+    FieldInfo::new("ipv6.tclass", FieldKind::UInt, "Traffic Class"),
+    FieldInfo::new(
+        "ipv6.tclass.dscp",
+        FieldKind::UInt,
+        "Differentiated Services Codepoint",
+    ),
+    FieldInfo::new(
+        "ipv6.tclass.ecn",
+        FieldKind::UInt,
+        "Explicit Congestion Notification",
+    ),
+    FieldInfo::new("ipv6.flow", FieldKind::UInt, "Flow Label"),
+    FieldInfo::new("ipv6.plen", FieldKind::UInt, "Payload Length"),
+    FieldInfo::new("ipv6.nxt", FieldKind::UInt, "Next Header"),
+    FieldInfo::new("ipv6.hlim", FieldKind::UInt, "Hop Limit"),
+    FieldInfo::new("ipv6.src", FieldKind::Ipv6, "Source Address"),
+    FieldInfo::new("ipv6.dst", FieldKind::Ipv6, "Destination Address"),
+    FieldInfo::new(
+        "ipv6.addr",
+        FieldKind::Ipv6,
+        "Source or Destination Address",
+    ),
+    FieldInfo::new("ipv6.hopopts", FieldKind::Bool, "Hop-by-Hop Option"),
+    FieldInfo::new("ipv6.dstopts", FieldKind::Bool, "Destination Options"),
+    FieldInfo::new("ipv6.opt.type", FieldKind::UInt, "Option Type"),
+    FieldInfo::new("ipv6.routing.type", FieldKind::UInt, "Routing Type"),
+    FieldInfo::new("ipv6.routing.segleft", FieldKind::UInt, "Segments Left"),
+    FieldInfo::new("ipv6.routing.addr", FieldKind::Ipv6, "Routing Address"),
+    FieldInfo::new("ipv6.fragment.offset", FieldKind::UInt, "Fragment Offset"),
+    FieldInfo::new("ipv6.fragment.more", FieldKind::Bool, "More Fragments"),
+    FieldInfo::new("ipv6.fragment.id", FieldKind::UInt, "Identification"),
+    FieldInfo::new("ipv6.fragment", FieldKind::UInt, "IPv6 Fragment"),
+    FieldInfo::new("ipv6.fragment.count", FieldKind::UInt, "Fragment count"),
+    FieldInfo::new(
+        "ipv6.reassembled_in",
+        FieldKind::UInt,
+        "Reassembled IPv6 in frame",
+    ),
+    FieldInfo::new(
+        "ipv6.reassembled.length",
+        FieldKind::UInt,
+        "Reassembled IPv6 length",
+    ),
+    FieldInfo::new("ah.spi", FieldKind::UInt, "AH SPI"),
+    FieldInfo::new("ah.sequence", FieldKind::UInt, "AH Sequence"),
+    FieldInfo::new("esp.spi", FieldKind::UInt, "ESP SPI"),
+    FieldInfo::new("esp.sequence", FieldKind::UInt, "ESP Sequence"),
+];
+
+fn option_name(option_type: u8) -> &'static str {
+    match option_type {
+        0x00 => "Pad1",
+        0x01 => "PadN",
+        0x04 => "Tunnel Encapsulation Limit",
+        0x05 => "Router Alert",
+        0x07 => "CALIPSO",
+        0x63 => "RPL Option",
+        0xc2 => "Jumbo Payload",
+        0xc9 => "Home Address",
+        _ => "Unknown",
+    }
+}
+
+fn routing_type_name(routing_type: u8) -> &'static str {
+    match routing_type {
+        0 => "Source Route",
+        2 => "Type 2 Routing",
+        3 => "RPL Source Route",
+        4 => "Segment Routing",
+        _ => "Unknown",
+    }
+}
+
+/// What a particular extension header carries beyond its next header and length
+#[derive(Clone, Debug)]
+enum ExtHeaderKind {
+    /// Hop-by-Hop or Destination Options, as the type, offset and length of each option
+    Options(Vec<(u8, usize, usize)>),
+    Routing {
+        routing_type: u8,
+        segments_left: u8,
+        addresses: Vec<[u8; 16]>,
+    },
+    Fragment {
+        /// In units of 8 bytes
+        frag_offset: u16,
+        more: bool,
+        identification: u32,
+    },
+    Auth {
+        spi: u32,
+        sequence: u32,
+    },
+    /// Only the SPI and sequence number are in the clear; the next header is encrypted
+    Esp {
+        spi: u32,
+        sequence: u32,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct ExtHeader {
+    /// Protocol number identifying this header
+    protocol: u8,
+    /// Offset of the header in the packet
+    offset: usize,
+    length: usize,
+    next_header: u8,
+    kind: ExtHeaderKind,
+}
+
+impl ExtHeader {
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let mut children = vec![];
+        if !matches!(self.kind, ExtHeaderKind::Esp { .. }) {
+            children.push(ProtoItem::new_leaf(
+                format!(
+                    "Next Header: {} ({})",
+                    util::ip_proto_name(self.next_header),
+                    self.next_header
+                ),
+                off,
+                1,
+            ));
+        }
+        if matches!(
+            self.kind,
+            ExtHeaderKind::Options(_) | ExtHeaderKind::Routing { .. } | ExtHeaderKind::Auth { .. }
+        ) {
+            children.push(ProtoItem::new_leaf(
+                format!("Length: {} bytes", self.length),
+                off + 1,
+                1,
+            ));
+        }
+
+        let mut text = util::ip_proto_name(self.protocol).to_string();
+        match &self.kind {
+            ExtHeaderKind::Options(options) => {
+                for (option_type, opt_off, opt_len) in options {
+                    children.push(ProtoItem::new_leaf(
+                        format!(
+                            "{} ({:#04x}), {} bytes",
+                            option_name(*option_type),
+                            option_type,
+                            opt_len
+                        ),
+                        *opt_off,
+                        *opt_len,
+                    ));
+                }
+            }
+            ExtHeaderKind::Routing {
+                routing_type,
+                segments_left,
+                addresses,
+            } => {
+                text += &format!(" ({})", routing_type_name(*routing_type));
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "Type: {} ({})",
+                        routing_type_name(*routing_type),
+                        routing_type
+                    ),
+                    off + 2,
+                    1,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Segments Left: {}", segments_left),
+                    off + 3,
+                    1,
+                ));
+                for (i, addr) in addresses.iter().enumerate() {
+                    children.push(ProtoItem::new_leaf(
+                        format!("Address[{}]: {}", i, Ipv6Addr::from(*addr)),
+                        off + 8 + 16 * i,
+                        16,
+                    ));
+                }
+            }
+            ExtHeaderKind::Fragment {
+                frag_offset,
+                more,
+                identification,
+            } => {
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "Offset: {} ({} bytes)",
+                        frag_offset,
+                        8 * *frag_offset as usize
+                    ),
+                    off + 2,
+                    2,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("More Fragments: {}", if *more { "Yes" } else { "No" }),
+                    off + 3,
+                    1,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Identification: {:#010x}", identification),
+                    off + 4,
+                    4,
+                ));
+            }
+            ExtHeaderKind::Auth { spi, sequence } | ExtHeaderKind::Esp { spi, sequence } => {
+                let fields_off = if matches!(self.kind, ExtHeaderKind::Auth { .. }) {
+                    off + 4
+                } else {
+                    off
+                };
+                children.push(ProtoItem::new_leaf(
+                    format!("SPI: {:#010x}", spi),
+                    fields_off,
+                    4,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Sequence: {}", sequence),
+                    fields_off + 4,
+                    4,
+                ));
+                if matches!(self.kind, ExtHeaderKind::Auth { .. }) && self.length > 12 {
+                    children.push(ProtoItem::new_leaf(
+                        format!("ICV ({} bytes)", self.length - 12),
+                        off + 12,
+                        self.length - 12,
+                    ));
+                }
+            }
+        }
+        ProtoItem::new(text, off, self.length, children)
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        match &self.kind {
+            ExtHeaderKind::Options(options) => {
+                let name = if self.protocol == HOP_BY_HOP {
+                    "ipv6.hopopts"
+                } else {
+                    "ipv6.dstopts"
+                };
+                let mut fields: Vec<Field> = vec![(name, true.into())];
+                fields.extend(
+                    options
+                        .iter()
+                        .map(|(t, _, _)| ("ipv6.opt.type", (*t).into())),
+                );
+                fields
+            }
+            ExtHeaderKind::Routing {
+                routing_type,
+                segments_left,
+                addresses,
+            } => {
+                let mut fields: Vec<Field> = vec![
+                    ("ipv6.routing.type", (*routing_type).into()),
+                    ("ipv6.routing.segleft", (*segments_left).into()),
+                ];
+                fields.extend(addresses.iter().map(|a| ("ipv6.routing.addr", (*a).into())));
+                fields
+            }
+            ExtHeaderKind::Fragment {
+                frag_offset,
+                more,
+                identification,
+            } => vec![
+                ("ipv6.fragment.offset", (*frag_offset).into()),
+                ("ipv6.fragment.more", (*more).into()),
+                ("ipv6.fragment.id", (*identification).into()),
+            ],
+            ExtHeaderKind::Auth { spi, sequence } => vec![
+                ("ah.spi", (*spi).into()),
+                ("ah.sequence", (*sequence).into()),
+            ],
+            ExtHeaderKind::Esp { spi, sequence } => vec![
+                ("esp.spi", (*spi).into()),
+                ("esp.sequence", (*sequence).into()),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IPv6 {
+    /// Offset of the header in the packet
+    offset: usize,
+    version: u8,
+    traffic_class: u8,
+    flow_label: u32,
+    payload_length: u16,
+    next_header: u8,
+    hop_limit: u8,
+    source_addr: [u8; 16],
+    dest_addr: [u8; 16],
+    ext_headers: Vec<ExtHeader>,
+    /// Protocol after the extension headers, unless it is encrypted by ESP
+    upper_protocol: Option<u8>,
+    /// For fragments, the frame the packet was reassembled in once that has happened
+    reassembled_in: Option<Rc<Cell<Option<usize>>>>,
+    /// The packet's fragmentable part, if this fragment completed it
+    reassembly: Option<Reassembly>,
+}
+
+impl IPv6 {
+    pub fn from_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated("IPv6", HEADER_LEN, bytes.len()));
+        }
+
+        let version = bytes[0] >> 4;
+        if version != 6 {
+            return Err(DissectError::new(format!(
+                "bogus IP version ({}, must be 6)",
+                version
+            )));
+        }
+        let traffic_class = (bytes[0] << 4) | (bytes[1] >> 4);
+        let flow_label = util::four_bytes_to_u32(&bytes[0..4]) & 0x000f_ffff;
+        let payload_length = util::two_bytes_to_u16(&bytes[4..6]);
+        let next_header = bytes[6];
+        let hop_limit = bytes[7];
+        let mut source_addr: [u8; 16] = [0; 16];
+        source_addr.clone_from_slice(&bytes[8..24]);
+        let mut dest_addr: [u8; 16] = [0; 16];
+        dest_addr.clone_from_slice(&bytes[24..40]);
+
+        // Walk the extension header chain until an upper layer protocol turns up
+        let mut ext_headers = vec![];
+        let mut protocol = next_header;
+        let mut pos = HEADER_LEN;
+        let upper_protocol = loop {
+            let header = match protocol {
+                HOP_BY_HOP | DEST_OPTIONS => {
+                    let length = ext_header_len(bytes, pos, protocol)?;
+                    let mut options = vec![];
+                    let mut opt = pos + 2;
+                    while opt < pos + length {
+                        let option_type = bytes[opt];
+                        let opt_len = if option_type == 0 {
+                            1
+                        } else if opt + 1 < pos + length {
+                            (2 + bytes[opt + 1] as usize).min(pos + length - opt)
+                        } else {
+                            pos + length - opt
+                        };
+                        options.push((option_type, next_byte + opt, opt_len));
+                        opt += opt_len;
+                    }
+                    (length, ExtHeaderKind::Options(options))
+                }
+                ROUTING => {
+                    let length = ext_header_len(bytes, pos, protocol)?;
+                    let routing_type = bytes[pos + 2];
+                    let addresses = if matches!(routing_type, 0 | 2 | 4) {
+                        bytes[pos + 8..pos + length]
+                            .chunks_exact(16)
+                            .map(|chunk| {
+                                let mut addr = [0u8; 16];
+                                addr.clone_from_slice(chunk);
+                                addr
+                            })
+                            .collect()
+                    } else {
+                        vec![]
+                    };
+                    (
+                        length,
+                        ExtHeaderKind::Routing {
+                            routing_type,
+                            segments_left: bytes[pos + 3],
+                            addresses,
+                        },
+                    )
+                }
+                FRAGMENT => {
+                    ext_header_len(bytes, pos, protocol)?;
+                    let offset_flags = util::two_bytes_to_u16(&bytes[pos + 2..pos + 4]);
+                    (
+                        8,
+                        ExtHeaderKind::Fragment {
+                            frag_offset: offset_flags >> 3,
+                            more: offset_flags & 1 == 1,
+                            identification: util::four_bytes_to_u32(&bytes[pos + 4..pos + 8]),
+                        },
+                    )
+                }
+                AUTH => {
+                    let length = ext_header_len(bytes, pos, protocol)?;
+                    (
+                        length,
+                        ExtHeaderKind::Auth {
+                            spi: util::four_bytes_to_u32(&bytes[pos + 4..pos + 8]),
+                            sequence: util::four_bytes_to_u32(&bytes[pos + 8..pos + 12]),
+                        },
+                    )
+                }
+                ESP => {
+                    if bytes.len() < pos + 8 {
+                        return Err(DissectError::truncated(
+                            util::ip_proto_name(ESP),
+                            8,
+                            bytes.len() - pos,
+                        ));
+                    }
+                    (
+                        8,
+                        ExtHeaderKind::Esp {
+                            spi: util::four_bytes_to_u32(&bytes[pos..pos + 4]),
+                            sequence: util::four_bytes_to_u32(&bytes[pos + 4..pos + 8]),
+                        },
+                    )
+                }
+                _ => break Some(protocol),
+            };
+
+            let (length, kind) = header;
+            let next = if protocol == ESP {
+                protocol
+            } else {
+                bytes[pos]
+            };
+            // Past the first fragment, what follows the fragment header is data, not headers
+            let later_fragment = matches!(
+                kind,
+                ExtHeaderKind::Fragment { frag_offset, .. } if frag_offset != 0
+            );
+            ext_headers.push(ExtHeader {
+                protocol,
+                offset: next_byte + pos,
+                length,
+                next_header: next,
+                kind,
+            });
+            pos += length;
+            if protocol == ESP {
+                break None;
+            }
+            if later_fragment {
+                break Some(next);
+            }
+            protocol = next;
+        };
+
+        let is_fragment = ext_headers.iter().any(|h| {
+            matches!(
+                h.kind,
+                ExtHeaderKind::Fragment { frag_offset, more, .. } if frag_offset != 0 || more
+            )
+        });
+
+        let ip_layer = IPv6 {
+            offset: next_byte,
+            version,
+            traffic_class,
+            flow_label,
+            payload_length,
+            next_header,
+            hop_limit,
+            source_addr,
+            dest_addr,
+            ext_headers,
+            upper_protocol,
+            reassembled_in: None,
+            reassembly: None,
+        };
+
+        // A lone fragment can't be handed to the upper layer, and neither can encrypted or
+        //   absent payloads
+        let next_layer = match upper_protocol {
+            Some(protocol) if protocol != NO_NEXT_HEADER && !is_fragment => {
+                NextLayer::Table(Table::IpProto, protocol as u32)
+            }
+            _ => NextLayer::Undecoded,
+        };
+
+        Ok((ip_layer, next_byte + pos, next_layer))
+    }
+
+    fn header_len(&self) -> usize {
+        HEADER_LEN + self.ext_headers.iter().map(|h| h.length).sum::<usize>()
+    }
+
+    /// The fragment header and its offset in bytes, more flag and identification, if this
+    /// packet only carries part of its payload
+    fn fragment(&self) -> Option<(&ExtHeader, usize, bool, u32)> {
+        self.ext_headers.iter().find_map(|h| match h.kind {
+            ExtHeaderKind::Fragment {
+                frag_offset,
+                more,
+                identification,
+            } if frag_offset != 0 || more => {
+                Some((h, 8 * frag_offset as usize, more, identification))
+            }
+            _ => None,
+        })
+    }
+
+    /// Frame the packet this fragment belongs to was reassembled in, if it was
+    fn reassembled_in(&self) -> Option<usize> {
+        self.reassembled_in.as_ref().and_then(|link| link.get())
+    }
+}
+
+/// Length in bytes of the extension header at `pos`, after checking all of it was captured
+fn ext_header_len(bytes: &[u8], pos: usize, protocol: u8) -> Result<usize, DissectError> {
+    let min_len = if protocol == AUTH { 12 } else { 8 };
+    let available = bytes.len() - pos;
+    if available < min_len {
+        return Err(DissectError::truncated(
+            util::ip_proto_name(protocol),
+            min_len,
+            available,
+        ));
+    }
+    let length = match protocol {
+        FRAGMENT => 8,
+        // AH counts 4 byte units minus 2 (RFC 4302), the others 8 byte units minus 1
+        AUTH => (bytes[pos + 1] as usize + 2) * 4,
+        _ => (bytes[pos + 1] as usize + 1) * 8,
+    };
+    if available < length {
+        return Err(DissectError::truncated(
+            util::ip_proto_name(protocol),
+            length,
+            available,
+        ));
+    }
+    Ok(length)
+}
+
+impl fmt::Display for IPv6 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Internet Protocol Version 6, Src: {}, Dst: {}",
+            Ipv6Addr::from(self.source_addr),
+            Ipv6Addr::from(self.dest_addr)
+        )
+    }
+}
+
+impl ProtocolLayer for IPv6 {
+    fn name(&self) -> &'static str {
+        "ipv6"
+    }
+
+    fn label(&self) -> String {
+        "IPv6".to_string()
+    }
+
+    fn info(&self) -> String {
+        if let Some((header, offset, _, identification)) = self.fragment() {
+            let mut info = format!(
+                "Fragmented IPv6 protocol (proto={} {}, off={}, ID={:08x})",
+                util::ip_proto_name(header.next_header),
+                header.next_header,
+                offset,
+                identification
+            );
+            if let Some(frame) = self.reassembled_in() {
+                info += &format!(" [Reassembled in #{}]", frame);
+            }
+            return info;
+        }
+        match self.upper_protocol {
+            Some(protocol) => format!(
+                "Next Header {} ({})",
+                util::ip_proto_name(protocol),
+                protocol
+            ),
+            None => "Encrypted payload".to_string(),
+        }
+    }
+
+    fn addresses(&self) -> Option<(String, String)> {
+        Some((
+            Ipv6Addr::from(self.source_addr).to_string(),
+            Ipv6Addr::from(self.dest_addr).to_string(),
+        ))
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("ipv6.version", self.version.into()),
+            ("ipv6.tclass", self.traffic_class.into()),
+            ("ipv6.tclass.dscp", (self.traffic_class >> 2).into()),
+            ("ipv6.tclass.ecn", (self.traffic_class & 0x03).into()),
+            ("ipv6.flow", self.flow_label.into()),
+            ("ipv6.plen", self.payload_length.into()),
+            ("ipv6.nxt", self.next_header.into()),
+            ("ipv6.hlim", self.hop_limit.into()),
+            ("ipv6.src", self.source_addr.into()),
+            ("ipv6.dst", self.dest_addr.into()),
+            ("ipv6.addr", self.source_addr.into()),
+            ("ipv6.addr", self.dest_addr.into()),
+        ];
+        for header in &self.ext_headers {
+            fields.extend(header.fields());
+        }
+        if let Some(reassembly) = &self.reassembly {
+            fields.extend(
+                reassembly
+                    .fragments
+                    .iter()
+                    .map(|f| ("ipv6.fragment", f.frame_num.into())),
+            );
+            fields.push(("ipv6.fragment.count", reassembly.fragments.len().into()));
+            fields.push(("ipv6.reassembled.length", reassembly.bytes.len().into()));
+        } else if let Some(frame) = self.reassembled_in() {
+            fields.push(("ipv6.reassembled_in", frame.into()));
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let mut children = vec![
+            ProtoItem::new_leaf(format!("Version: {}", self.version), off, 1),
+            ProtoItem::new_leaf(
+                format!(
+                    "Traffic Class: {:#04x} (DSCP: {}, ECN: {})",
+                    self.traffic_class,
+                    util::dscp_name(self.traffic_class >> 2),
+                    util::ecn_name(self.traffic_class)
+                ),
+                off,
+                2,
+            ),
+            ProtoItem::new_leaf(format!("Flow Label: {:#07x}", self.flow_label), off + 1, 3),
+            ProtoItem::new_leaf(
+                format!("Payload Length: {}", self.payload_length),
+                off + 4,
+                2,
+            ),
+            ProtoItem::new_leaf(
+                format!(
+                    "Next Header: {} ({})",
+                    util::ip_proto_name(self.next_header),
+                    self.next_header
+                ),
+                off + 6,
+                1,
+            ),
+            ProtoItem::new_leaf(format!("Hop Limit: {}", self.hop_limit), off + 7, 1),
+            ProtoItem::new_leaf(
+                format!("Source Address: {}", Ipv6Addr::from(self.source_addr)),
+                off + 8,
+                16,
+            ),
+            ProtoItem::new_leaf(
+                format!("Destination Address: {}", Ipv6Addr::from(self.dest_addr)),
+                off + 24,
+                16,
+            ),
+        ];
+        children.extend(self.ext_headers.iter().map(|h| h.to_proto_item()));
+        if let Some(reassembly) = &self.reassembly {
+            children.push(reassembly.to_proto_item("IPv6", "Fragment"));
+        } else if let Some(frame) = self.reassembled_in() {
+            children.push(ProtoItem::new_leaf(
+                format!("[Reassembled in #{}]", frame),
+                0,
+                0,
+            ));
+        }
+        ProtoItem::new(self.to_string(), off, self.header_len(), children)
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Identifies the packet a fragment belongs to: source, destination, the protocol after the
+/// fragment header and identification (RFC 8200)
+type FragmentKey = ([u8; 16], [u8; 16], u8, u32);
+
+pub struct IPv6Dissector {
+    fragments: FragmentTable<FragmentKey>,
+}
+
+impl IPv6Dissector {
+    pub fn new() -> Self {
+        IPv6Dissector {
+            fragments: FragmentTable::new(),
+        }
+    }
+}
+
+impl Dissector for IPv6Dissector {
+    fn name(&self) -> &'static str {
+        "ipv6"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (mut layer, next_byte, next_layer) = IPv6::from_bytes(next_byte, bytes)?;
+        ctx.net_src = Some(Ipv6Addr::from(layer.source_addr).into());
+        ctx.net_dst = Some(Ipv6Addr::from(layer.dest_addr).into());
+        // A zero payload length means a jumbogram, whose real length is in a hop-by-hop option
+        let total_length = HEADER_LEN + layer.payload_length as usize;
+        let whole_packet = layer.payload_length != 0 && total_length <= bytes.len();
+        if whole_packet {
+            ctx.payload_end = Some(layer.offset + total_length);
+        }
+
+        let (key, data_start, offset, more) = match layer.fragment() {
+            Some((header, offset, more, identification)) if whole_packet => (
+                (
+                    layer.source_addr,
+                    layer.dest_addr,
+                    header.next_header,
+                    identification,
+                ),
+                header.offset + 8 - layer.offset,
+                offset,
+                more,
+            ),
+            _ => return Ok(Dissection::new(layer, next_byte, next_layer)),
+        };
+        if ctx.in_error_packet {
+            return Ok(Dissection::new(layer, next_byte, next_layer));
+        }
+        let (reassembled_in, reassembly) = self.fragments.add(
+            key,
+            ctx.frame_num,
+            ctx.timestamp,
+            offset,
+            &bytes[data_start..total_length],
+            !more,
+        );
+        layer.reassembled_in = Some(reassembled_in);
+        match reassembly {
+            Some(reassembly) => {
+                let payload = reassembly.bytes.clone();
+                layer.reassembly = Some(reassembly);
+                // The reassembled bytes start right after the fragment header
+                let next_layer = NextLayer::Table(Table::IpProto, key.2 as u32);
+                Ok(Dissection::new(layer, next_byte, next_layer)
+                    .with_reassembled("Reassembled IPv6", payload))
+            }
+            None => Ok(Dissection::new(layer, next_byte, next_layer)),
+        }
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(IPv6Dissector::new());
+    registry.add_to_table(Table::EtherType, 0x86dd, "ipv6");
+    registry.add_to_table(
+        Table::LinkType,
+        pcap_parser::Linktype::IPV6.0 as u32,
+        "ipv6",
+    );
+    // IPv6 tunnelled in IPv4 or IPv6
+    registry.add_to_table(Table::IpProto, 41, "ipv6");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A header from 2001:db8::1 to 2001:db8::2 whose payload, extension headers included, is
+    /// `payload`
+    fn packet(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x60, 0, 0, 0];
+        bytes.extend((payload.len() as u16).to_be_bytes());
+        bytes.extend([next_header, 64]);
+        bytes.extend("2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        bytes.extend("2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        bytes.extend(payload);
+        bytes
+    }
+
+    /// A fragment header for the fragment `offset` 8 byte units in, followed by `data`
+    fn fragment(next_header: u8, offset: u16, more: bool, id: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![next_header, 0];
+        bytes.extend((offset << 3 | more as u16).to_be_bytes());
+        bytes.extend(id.to_be_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn protocols(layer: &IPv6) -> Vec<u8> {
+        layer.ext_headers.iter().map(|h| h.protocol).collect()
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        IPv6::from_bytes(0, bytes).unwrap_err().reason
+    }
+
+    #[test]
+    fn extension_headers_are_walked_to_the_upper_protocol() {
+        // Hop-by-hop options with a PadN, then a source route through one address, then UDP
+        let mut payload = vec![ROUTING, 0, 1, 4, 0, 0, 0, 0];
+        payload.extend([17, 2, 0, 1, 0, 0, 0, 0]);
+        payload.extend("2001:db8::9".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend([0; 8]);
+        let (layer, next_byte, next_layer) =
+            IPv6::from_bytes(0, &packet(HOP_BY_HOP, &payload)).unwrap();
+        assert_eq!(protocols(&layer), vec![HOP_BY_HOP, ROUTING]);
+        assert_eq!(next_byte, HEADER_LEN + 32);
+        assert!(matches!(next_layer, NextLayer::Table(Table::IpProto, 17)));
+        assert_eq!(layer.upper_protocol, Some(17));
+        let fields = layer.fields();
+        assert!(fields.contains(&("ipv6.opt.type", 1u8.into())));
+        assert!(fields.contains(&(
+            "ipv6.routing.addr",
+            "2001:db8::9".parse::<Ipv6Addr>().unwrap().octets().into()
+        )));
+    }
+
+    #[test]
+    fn auth_header_length_counts_four_byte_units() {
+        // A length of 4 makes (4 + 2) * 4 = 24 bytes, 12 of them the ICV
+        let mut payload = vec![6, 4, 0, 0];
+        payload.extend(0x100u32.to_be_bytes());
+        payload.extend(7u32.to_be_bytes());
+        payload.extend([0xaa; 12]);
+        let (layer, next_byte, next_layer) = IPv6::from_bytes(0, &packet(AUTH, &payload)).unwrap();
+        assert_eq!(next_byte, HEADER_LEN + 24);
+        assert!(matches!(next_layer, NextLayer::Table(Table::IpProto, 6)));
+        let item = layer.ext_headers[0].to_proto_item();
+        assert_eq!(item.length, 24);
+        assert_eq!(item.children.last().unwrap().text, "ICV (12 bytes)");
+        assert!(layer.fields().contains(&("ah.sequence", 7u32.into())));
+    }
+
+    #[test]
+    fn esp_ends_the_walk() {
+        let mut payload = 0x200u32.to_be_bytes().to_vec();
+        payload.extend(1u32.to_be_bytes());
+        // Encrypted, so not to be read as another header
+        payload.extend([HOP_BY_HOP, 0xff, 0, 0]);
+        let (layer, next_byte, next_layer) = IPv6::from_bytes(0, &packet(ESP, &payload)).unwrap();
+        assert_eq!(protocols(&layer), vec![ESP]);
+        assert_eq!(next_byte, HEADER_LEN + 8);
+        assert!(matches!(next_layer, NextLayer::Undecoded));
+        assert_eq!(layer.upper_protocol, None);
+        assert_eq!(layer.info(), "Encrypted payload");
+    }
+
+    #[test]
+    fn later_fragments_carry_data_not_headers() {
+        // Data that would be a hop-by-hop header running past the packet if it were walked
+        let bytes = packet(
+            FRAGMENT,
+            &fragment(HOP_BY_HOP, 2, false, 9, &[17, 0xff, 0, 0]),
+        );
+        let (layer, next_byte, next_layer) = IPv6::from_bytes(0, &bytes).unwrap();
+        assert_eq!(protocols(&layer), vec![FRAGMENT]);
+        assert_eq!(next_byte, HEADER_LEN + 8);
+        assert!(matches!(next_layer, NextLayer::Undecoded));
+        assert_eq!(
+            layer.info(),
+            "Fragmented IPv6 protocol (proto=IPv6 Hop-by-Hop Option 0, off=16, ID=00000009)"
+        );
+
+        // The first fragment's headers are walked, but its payload is still held back
+        let data = [17, 0, 1, 4, 0, 0, 0, 0];
+        let bytes = packet(FRAGMENT, &fragment(HOP_BY_HOP, 0, true, 9, &data));
+        let (layer, _, next_layer) = IPv6::from_bytes(0, &bytes).unwrap();
+        assert_eq!(protocols(&layer), vec![FRAGMENT, HOP_BY_HOP]);
+        assert!(matches!(next_layer, NextLayer::Undecoded));
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        assert_eq!(
+            error(&packet(17, &[])[..39]),
+            "IPv6 header needs 40 bytes, only 39 captured"
+        );
+        let mut bytes = packet(17, &[]);
+        bytes[0] = 0x40;
+        assert_eq!(error(&bytes), "bogus IP version (4, must be 6)");
+        // Hop-by-hop options claiming 16 bytes with only 8 there
+        assert_eq!(
+            error(&packet(HOP_BY_HOP, &[17, 1, 1, 4, 0, 0, 0, 0])),
+            "IPv6 Hop-by-Hop Option header needs 16 bytes, only 8 captured"
+        );
+        assert_eq!(
+            error(&packet(ESP, &[0, 0, 2, 0])),
+            "Encapsulating Security Payload header needs 8 bytes, only 4 captured"
+        );
+        assert_eq!(
+            error(&packet(FRAGMENT, &[17, 0, 0, 1])),
+            "Fragment Header for IPv6 header needs 8 bytes, only 4 captured"
+        );
+    }
+
+    #[test]
+    fn fragments_are_reassembled_by_their_key() {
+        let registry = Registry::default();
+        let dissector = IPv6Dissector::new();
+        let dissect = |frame_num: usize, bytes: &[u8]| {
+            let mut ctx = DissectCtx::new(&registry, frame_num, Default::default());
+            dissector.dissect(&mut ctx, 0, bytes).unwrap()
+        };
+
+        let first = dissect(0, &packet(FRAGMENT, &fragment(17, 0, true, 7, b"01234567")));
+        // Same identification, but carrying another protocol, so another packet's
+        let other = dissect(1, &packet(FRAGMENT, &fragment(6, 1, false, 7, b"xxxx")));
+        assert!(other.reassembled.is_none());
+        let last = dissect(2, &packet(FRAGMENT, &fragment(17, 1, false, 7, b"89ab")));
+
+        let reassembled = last.reassembled.as_ref().unwrap();
+        assert_eq!(reassembled.bytepool.bytes, b"0123456789ab");
+        assert!(matches!(last.next, NextLayer::Table(Table::IpProto, 17)));
+        let first = first.layer.as_any().downcast_ref::<IPv6>().unwrap();
+        assert_eq!(first.reassembled_in(), Some(2));
+        let last = last.layer.as_any().downcast_ref::<IPv6>().unwrap();
+        assert!(last
+            .fields()
+            .contains(&("ipv6.fragment.count", 2usize.into())));
+    }
+}
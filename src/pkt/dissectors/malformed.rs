@@ -0,0 +1,54 @@
+use core::fmt;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::prototree::ProtoItem;
+
+/// Bytes a dissector failed to decode, and why
+#[derive(Clone, Debug)]
+pub struct Malformed {
+    pub reason: String,
+    pub start_offset: usize,
+    pub length: usize,
+}
+
+impl Malformed {
+    pub fn new(error: DissectError, start_offset: usize, length: usize) -> Self {
+        Malformed {
+            reason: error.reason,
+            start_offset,
+            length,
+        }
+    }
+
+    pub fn to_proto_item(&self) -> ProtoItem {
+        ProtoItem::new(
+            self.to_string(),
+            self.start_offset,
+            self.length,
+            vec![
+                ProtoItem::new_leaf(format!("Reason: {}", self.reason), 0, 0),
+                ProtoItem::new_leaf(
+                    format!(
+                        "Bytes: {}..{}",
+                        self.start_offset,
+                        self.start_offset + self.length
+                    ),
+                    self.start_offset,
+                    self.length,
+                ),
+            ],
+        )
+        .style(Style::default().fg(Color::White).bg(Color::Red))
+    }
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[Malformed Packet: {}] [Starts: {}, Len: {}]",
+            self.reason, self.start_offset, self.length
+        )
+    }
+}
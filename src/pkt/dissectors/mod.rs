@@ -1,5 +1,61 @@
+use core::fmt;
+
+pub mod arp;
 pub mod ethernet;
+pub mod icmp;
 pub mod ipv4;
+pub mod ipv6;
+pub mod malformed;
+pub mod modbus;
+pub mod null;
+pub mod reassembly;
+pub mod sll;
 pub mod tcp;
+pub mod udp;
 pub mod undecoded;
 pub mod util;
+
+use crate::pkt::registry::Registry;
+
+/// Registers every dissector in this module. New protocols only need a module above and a line
+/// here.
+pub fn register_all(registry: &mut Registry) {
+    ethernet::register(registry);
+    null::register(registry);
+    sll::register(registry);
+    ipv4::register(registry);
+    ipv6::register(registry);
+    arp::register(registry);
+    tcp::register(registry);
+    udp::register(registry);
+    icmp::register(registry);
+    modbus::register(registry);
+}
+
+/// Why a dissector could not decode its layer
+#[derive(Clone, Debug)]
+pub struct DissectError {
+    pub reason: String,
+}
+
+impl DissectError {
+    pub fn new<S: Into<String>>(reason: S) -> Self {
+        DissectError {
+            reason: reason.into(),
+        }
+    }
+
+    /// Error for a header that needs more bytes than were captured
+    pub fn truncated(protocol: &str, needed: usize, available: usize) -> Self {
+        DissectError::new(format!(
+            "{} header needs {} bytes, only {} captured",
+            protocol, needed, available
+        ))
+    }
+}
+
+impl fmt::Display for DissectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
@@ -0,0 +1,1255 @@
+// Modbus/TCP: the MBAP header followed by a Modbus PDU. Requests and responses are paired by
+//   transaction ID within a TCP connection, so each side can point at the other.
+
+use core::fmt;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::rc::Rc;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::util::{self, Pairing};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::field::{Field, FieldInfo, FieldKind, FieldValue};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, PduLength, ProtocolLayer, Registry, Table,
+};
+use crate::pkt::timestamp::Timestamp;
+
+/// Port Modbus/TCP servers listen on
+const MODBUS_PORT: u16 = 502;
+const MBAP_LEN: usize = 7;
+/// Largest PDU the specification allows, function code included
+const MAX_PDU_LEN: usize = 253;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("mbtcp.trans_id", FieldKind::UInt, "Transaction Identifier"),
+    FieldInfo::new("mbtcp.prot_id", FieldKind::UInt, "Protocol Identifier"),
+    FieldInfo::new("mbtcp.len", FieldKind::UInt, "Length"),
+    FieldInfo::new("mbtcp.unit_id", FieldKind::UInt, "Unit Identifier"),
+    FieldInfo::new("modbus.func_code", FieldKind::UInt, "Function Code"),
+    FieldInfo::new("modbus.request", FieldKind::Bool, "Is a Request"),
+    FieldInfo::new("modbus.exception", FieldKind::Bool, "Is an Exception"),
+    FieldInfo::new("modbus.exception_code", FieldKind::UInt, "Exception Code"),
+    FieldInfo::new("modbus.reference_num", FieldKind::UInt, "Reference Number"),
+    FieldInfo::new("modbus.bit_cnt", FieldKind::UInt, "Bit Count"),
+    FieldInfo::new("modbus.word_cnt", FieldKind::UInt, "Word Count"),
+    FieldInfo::new("modbus.byte_cnt", FieldKind::UInt, "Byte Count"),
+    FieldInfo::new("modbus.bitval", FieldKind::Bool, "Coil or Input Value"),
+    FieldInfo::new("modbus.regval_uint16", FieldKind::UInt, "Register Value"),
+    FieldInfo::new("modbus.and_mask", FieldKind::UInt, "AND Mask"),
+    FieldInfo::new("modbus.or_mask", FieldKind::UInt, "OR Mask"),
+    FieldInfo::new("modbus.diagnostic_code", FieldKind::UInt, "Diagnostic Code"),
+    FieldInfo::new("modbus.file_num", FieldKind::UInt, "File Number"),
+    FieldInfo::new("modbus.record_num", FieldKind::UInt, "Record Number"),
+    FieldInfo::new("modbus.mei", FieldKind::UInt, "MEI type"),
+    FieldInfo::new("modbus.read_device_id", FieldKind::UInt, "Read Device ID"),
+    FieldInfo::new("modbus.object_id", FieldKind::UInt, "Object ID"),
+    FieldInfo::new(
+        "modbus.object_str_value",
+        FieldKind::Str,
+        "Object String Value",
+    ),
+    FieldInfo::new("modbus.data", FieldKind::Bytes, "Data"),
+    FieldInfo::new("modbus.request_frame", FieldKind::UInt, "Request Frame"),
+    FieldInfo::new("modbus.response_in", FieldKind::UInt, "Response In"),
+];
+
+fn function_name(code: u8) -> &'static str {
+    match code {
+        1 => "Read Coils",
+        2 => "Read Discrete Inputs",
+        3 => "Read Holding Registers",
+        4 => "Read Input Registers",
+        5 => "Write Single Coil",
+        6 => "Write Single Register",
+        7 => "Read Exception Status",
+        8 => "Diagnostics",
+        11 => "Get Comm Event Counter",
+        12 => "Get Comm Event Log",
+        15 => "Write Multiple Coils",
+        16 => "Write Multiple Registers",
+        17 => "Report Server ID",
+        20 => "Read File Record",
+        21 => "Write File Record",
+        22 => "Mask Write Register",
+        23 => "Read Write Register",
+        24 => "Read FIFO Queue",
+        43 => "Encapsulated Interface Transport",
+        _ => "Unknown Function",
+    }
+}
+
+fn exception_name(code: u8) -> &'static str {
+    match code {
+        1 => "Illegal function",
+        2 => "Illegal data address",
+        3 => "Illegal data value",
+        4 => "Server device failure",
+        5 => "Acknowledge",
+        6 => "Server device busy",
+        8 => "Memory parity error",
+        10 => "Gateway path unavailable",
+        11 => "Gateway target device failed to respond",
+        _ => "Unknown exception",
+    }
+}
+
+fn diagnostic_name(code: u16) -> &'static str {
+    match code {
+        0 => "Return Query Data",
+        1 => "Restart Communications Option",
+        2 => "Return Diagnostic Register",
+        3 => "Change ASCII Input Delimiter",
+        4 => "Force Listen Only Mode",
+        10 => "Clear Counters and Diagnostic Register",
+        11 => "Return Bus Message Count",
+        12 => "Return Bus Communication Error Count",
+        13 => "Return Bus Exception Error Count",
+        14 => "Return Server Message Count",
+        15 => "Return Server No Response Count",
+        16 => "Return Server NAK Count",
+        17 => "Return Server Busy Count",
+        18 => "Return Bus Character Overrun Count",
+        20 => "Clear Overrun Counter and Flag",
+        _ => "Reserved",
+    }
+}
+
+fn mei_name(mei: u8) -> &'static str {
+    match mei {
+        13 => "CANopen General Reference",
+        14 => "Read Device Identification",
+        _ => "Unknown MEI type",
+    }
+}
+
+fn device_id_code_name(code: u8) -> &'static str {
+    match code {
+        1 => "Basic Device Identification",
+        2 => "Regular Device Identification",
+        3 => "Extended Device Identification",
+        4 => "Specific Identification Object",
+        _ => "Unknown",
+    }
+}
+
+fn device_object_name(id: u8) -> &'static str {
+    match id {
+        0 => "VendorName",
+        1 => "ProductCode",
+        2 => "MajorMinorRevision",
+        3 => "VendorUrl",
+        4 => "ProductName",
+        5 => "ModelName",
+        6 => "UserApplicationName",
+        0x07..=0x7f => "Reserved",
+        _ => "Private",
+    }
+}
+
+/// Walks the function specific part of a PDU, turning each value into a details item and
+/// display filter field
+struct PduReader<'a> {
+    bytes: &'a [u8],
+    /// Offset of `bytes` in the packet
+    base: usize,
+    pos: usize,
+    items: Vec<ProtoItem>,
+    fields: Vec<Field>,
+}
+
+impl<'a> PduReader<'a> {
+    fn new(bytes: &'a [u8], base: usize) -> Self {
+        PduReader {
+            bytes,
+            base,
+            pos: 0,
+            items: vec![],
+            fields: vec![],
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Absolute offset of the next unread byte
+    fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], DissectError> {
+        if self.remaining() < len {
+            return Err(DissectError::new(format!(
+                "Modbus {} needs {} bytes, only {} left in the PDU",
+                what,
+                len,
+                self.remaining()
+            )));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn item<T: Into<String>>(&mut self, text: T, start: usize, len: usize) {
+        self.items.push(ProtoItem::new_leaf(text, start, len));
+    }
+
+    fn field<V: Into<FieldValue>>(&mut self, name: &'static str, value: V) {
+        self.fields.push((name, value.into()));
+    }
+
+    /// Reads a one byte value shown as "label: value"
+    fn u8(&mut self, label: &str, field: Option<&'static str>) -> Result<u8, DissectError> {
+        let start = self.offset();
+        let value = self.take(1, label)?[0];
+        self.item(format!("{}: {}", label, value), start, 1);
+        if let Some(name) = field {
+            self.field(name, value);
+        }
+        Ok(value)
+    }
+
+    /// Reads a two byte value shown as "label: value"
+    fn u16(&mut self, label: &str, field: Option<&'static str>) -> Result<u16, DissectError> {
+        let start = self.offset();
+        let value = util::two_bytes_to_u16(self.take(2, label)?);
+        self.item(format!("{}: {}", label, value), start, 2);
+        if let Some(name) = field {
+            self.field(name, value);
+        }
+        Ok(value)
+    }
+
+    /// Reads `len` bytes shown as a hex string
+    fn data(&mut self, label: &str, len: usize) -> Result<(), DissectError> {
+        let start = self.offset();
+        let data = self.take(len, label)?;
+        let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+        self.item(format!("{}: {}", label, hex), start, len);
+        self.field("modbus.data", FieldValue::Bytes(data.to_vec()));
+        Ok(())
+    }
+
+    /// Everything left in the PDU, if anything is
+    fn rest(&mut self, label: &str) -> Result<(), DissectError> {
+        if self.remaining() > 0 {
+            self.data(label, self.remaining())?;
+        }
+        Ok(())
+    }
+
+    /// `count` packed coil or input bits, numbered from `first` when the reference is known
+    fn bits(
+        &mut self,
+        byte_count: usize,
+        count: Option<u16>,
+        first: Option<u16>,
+    ) -> Result<(), DissectError> {
+        let start = self.offset();
+        let bytes = self.take(byte_count, "bit values")?;
+        let count = count.map_or(8 * byte_count, |c| (c as usize).min(8 * byte_count));
+        let mut children = vec![];
+        for i in 0..count {
+            let value = (bytes[i / 8] >> (i % 8)) & 1 == 1;
+            let label = match first {
+                Some(first) => format!("Bit {}", first as usize + i),
+                None => format!("Bit {}", i),
+            };
+            children.push(ProtoItem::new_leaf(
+                format!("{}: {}", label, if value { "1" } else { "0" }),
+                start + i / 8,
+                1,
+            ));
+            self.field("modbus.bitval", value);
+        }
+        self.items.push(ProtoItem::new(
+            format!("Bit values ({})", count),
+            start,
+            byte_count,
+            children,
+        ));
+        Ok(())
+    }
+
+    /// `byte_count / 2` registers, numbered from `first` when the reference is known
+    fn registers(&mut self, byte_count: usize, first: Option<u16>) -> Result<(), DissectError> {
+        let start = self.offset();
+        let bytes = self.take(byte_count, "register values")?;
+        let mut children = vec![];
+        for (i, reg) in bytes.chunks_exact(2).enumerate() {
+            let value = util::two_bytes_to_u16(reg);
+            let label = match first {
+                Some(first) => format!("Register {}", first as usize + i),
+                None => format!("Register {}", i),
+            };
+            children.push(ProtoItem::new_leaf(
+                format!("{} (UINT16): {}", label, value),
+                start + 2 * i,
+                2,
+            ));
+            self.field("modbus.regval_uint16", value);
+        }
+        self.items.push(ProtoItem::new(
+            format!("Register values ({})", byte_count / 2),
+            start,
+            byte_count,
+            children,
+        ));
+        Ok(())
+    }
+
+    fn coil_value(&mut self) -> Result<(), DissectError> {
+        let start = self.offset();
+        let value = util::two_bytes_to_u16(self.take(2, "coil value")?);
+        let state = match value {
+            0xff00 => "ON",
+            0x0000 => "OFF",
+            _ => "invalid",
+        };
+        self.item(format!("Data: {:#06x} ({})", value, state), start, 2);
+        self.field("modbus.bitval", value == 0xff00);
+        Ok(())
+    }
+
+    fn diagnostics(&mut self) -> Result<(), DissectError> {
+        let start = self.offset();
+        let code = util::two_bytes_to_u16(self.take(2, "diagnostic code")?);
+        self.item(
+            format!("Diagnostic Code: {} ({})", diagnostic_name(code), code),
+            start,
+            2,
+        );
+        self.field("modbus.diagnostic_code", code);
+        self.rest("Data")
+    }
+
+    fn mei_type(&mut self) -> Result<u8, DissectError> {
+        let start = self.offset();
+        let mei = self.take(1, "MEI type")?[0];
+        self.item(format!("MEI type: {} ({})", mei_name(mei), mei), start, 1);
+        self.field("modbus.mei", mei);
+        Ok(mei)
+    }
+
+    fn device_id_code(&mut self) -> Result<(), DissectError> {
+        let start = self.offset();
+        let code = self.take(1, "read device ID code")?[0];
+        self.item(
+            format!("Read Device ID: {} ({})", device_id_code_name(code), code),
+            start,
+            1,
+        );
+        self.field("modbus.read_device_id", code);
+        Ok(())
+    }
+
+    fn object_id(&mut self, label: &str) -> Result<u8, DissectError> {
+        let start = self.offset();
+        let id = self.take(1, label)?[0];
+        self.item(
+            format!("{}: {} ({})", label, device_object_name(id), id),
+            start,
+            1,
+        );
+        self.field("modbus.object_id", id);
+        Ok(id)
+    }
+
+    /// The objects list of a Read Device Identification response
+    fn device_objects(&mut self, count: u8) -> Result<(), DissectError> {
+        for _ in 0..count {
+            let start = self.offset();
+            let header = self.take(2, "device object")?;
+            let (id, len) = (header[0], header[1] as usize);
+            let value = self.take(len, "device object value")?;
+            let text = String::from_utf8_lossy(value).into_owned();
+            self.items.push(ProtoItem::new(
+                format!("Object {}: {}", device_object_name(id), text),
+                start,
+                2 + len,
+                vec![
+                    ProtoItem::new_leaf(
+                        format!("Object ID: {} ({})", device_object_name(id), id),
+                        start,
+                        1,
+                    ),
+                    ProtoItem::new_leaf(format!("Object length: {}", len), start + 1, 1),
+                    ProtoItem::new_leaf(format!("Object value: {}", text), start + 2, len),
+                ],
+            ));
+            self.field("modbus.object_id", id);
+            self.field("modbus.object_str_value", text);
+        }
+        Ok(())
+    }
+
+    /// One sub-request of a Read or Write File Record request, with record data for writes
+    fn file_sub_request(&mut self, with_data: bool) -> Result<(), DissectError> {
+        let start = self.offset();
+        let header = self.take(7, "file record sub-request")?;
+        let ref_type = header[0];
+        let file = util::two_bytes_to_u16(&header[1..3]);
+        let record = util::two_bytes_to_u16(&header[3..5]);
+        let length = util::two_bytes_to_u16(&header[5..7]);
+        let mut children = vec![
+            ProtoItem::new_leaf(format!("Reference Type: {}", ref_type), start, 1),
+            ProtoItem::new_leaf(format!("File Number: {}", file), start + 1, 2),
+            ProtoItem::new_leaf(format!("Record Number: {}", record), start + 3, 2),
+            ProtoItem::new_leaf(format!("Record Length: {}", length), start + 5, 2),
+        ];
+        let mut len = 7;
+        if with_data {
+            let data_len = 2 * length as usize;
+            let data_start = self.offset();
+            let data = self.take(data_len, "file record data")?;
+            let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+            children.push(ProtoItem::new_leaf(
+                format!("Record Data: {}", hex),
+                data_start,
+                data_len,
+            ));
+            len += data_len;
+        }
+        self.items.push(ProtoItem::new(
+            format!("File {}, Record {}, Length {}", file, record, length),
+            start,
+            len,
+            children,
+        ));
+        self.field("modbus.file_num", file);
+        self.field("modbus.record_num", record);
+        Ok(())
+    }
+
+    /// One sub-response of a Read File Record response
+    fn file_sub_response(&mut self) -> Result<(), DissectError> {
+        let start = self.offset();
+        let header = self.take(2, "file record sub-response")?;
+        let (length, ref_type) = (header[0] as usize, header[1]);
+        let data_len = length.saturating_sub(1);
+        let data = self.take(data_len, "file record data")?;
+        let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+        self.items.push(ProtoItem::new(
+            format!("File Response Length {}", length),
+            start,
+            2 + data_len,
+            vec![
+                ProtoItem::new_leaf(format!("File Response Length: {}", length), start, 1),
+                ProtoItem::new_leaf(format!("Reference Type: {}", ref_type), start + 1, 1),
+                ProtoItem::new_leaf(format!("Record Data: {}", hex), start + 2, data_len),
+            ],
+        ));
+        Ok(())
+    }
+
+    fn decode_request(&mut self, function: u8) -> Result<(), DissectError> {
+        match function {
+            1 | 2 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Bit Count", Some("modbus.bit_cnt"))?;
+            }
+            3 | 4 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Word Count", Some("modbus.word_cnt"))?;
+            }
+            5 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.coil_value()?;
+            }
+            6 => {
+                let reference = self.u16("Reference Number", Some("modbus.reference_num"))?;
+                let start = self.offset();
+                let value = util::two_bytes_to_u16(self.take(2, "register value")?);
+                self.item(
+                    format!("Register {} (UINT16): {}", reference, value),
+                    start,
+                    2,
+                );
+                self.field("modbus.regval_uint16", value);
+            }
+            8 => self.diagnostics()?,
+            15 => {
+                let reference = self.u16("Reference Number", Some("modbus.reference_num"))?;
+                let count = self.u16("Bit Count", Some("modbus.bit_cnt"))?;
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                self.bits(byte_count as usize, Some(count), Some(reference))?;
+            }
+            16 => {
+                let reference = self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Word Count", Some("modbus.word_cnt"))?;
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                self.registers(byte_count as usize, Some(reference))?;
+            }
+            20 | 21 => {
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))? as usize;
+                let end = self.pos + byte_count.min(self.remaining());
+                while self.pos < end {
+                    self.file_sub_request(function == 21)?;
+                }
+            }
+            22 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                let start = self.offset();
+                let and_mask = util::two_bytes_to_u16(self.take(2, "AND mask")?);
+                self.item(format!("AND mask: {:#06x}", and_mask), start, 2);
+                self.field("modbus.and_mask", and_mask);
+                let start = self.offset();
+                let or_mask = util::two_bytes_to_u16(self.take(2, "OR mask")?);
+                self.item(format!("OR mask: {:#06x}", or_mask), start, 2);
+                self.field("modbus.or_mask", or_mask);
+            }
+            23 => {
+                self.u16("Read Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Read Word Count", Some("modbus.word_cnt"))?;
+                let reference = self.u16("Write Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Write Word Count", Some("modbus.word_cnt"))?;
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                self.registers(byte_count as usize, Some(reference))?;
+            }
+            24 => {
+                self.u16("FIFO Pointer Address", Some("modbus.reference_num"))?;
+            }
+            43 => match self.mei_type()? {
+                14 => {
+                    self.device_id_code()?;
+                    self.object_id("Object ID")?;
+                }
+                _ => self.rest("Data")?,
+            },
+            // Read Exception Status, Get Comm Event Counter/Log and Report Server ID carry
+            //   no data in the request
+            _ => self.rest("Data")?,
+        }
+        Ok(())
+    }
+
+    fn decode_response(
+        &mut self,
+        function: u8,
+        request: Option<&PendingRequest>,
+    ) -> Result<(), DissectError> {
+        let reference = request.and_then(|r| r.reference);
+        match function {
+            1 | 2 => {
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                let count = request.and_then(|r| r.count);
+                self.bits(byte_count as usize, count, reference)?;
+            }
+            3 | 4 | 23 => {
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                self.registers(byte_count as usize, reference)?;
+            }
+            5 | 6 | 22 => self.decode_request(function)?,
+            7 => {
+                let start = self.offset();
+                let status = self.take(1, "exception status")?[0];
+                self.item(format!("Output Data: {:#04x}", status), start, 1);
+            }
+            8 => self.diagnostics()?,
+            11 => {
+                let start = self.offset();
+                let status = util::two_bytes_to_u16(self.take(2, "status")?);
+                self.item(format!("Status: {:#06x}", status), start, 2);
+                self.u16("Event Count", None)?;
+            }
+            12 => {
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))? as usize;
+                let start = self.offset();
+                let status = util::two_bytes_to_u16(self.take(2, "status")?);
+                self.item(format!("Status: {:#06x}", status), start, 2);
+                self.u16("Event Count", None)?;
+                self.u16("Message Count", None)?;
+                self.data("Events", byte_count.saturating_sub(6))?;
+            }
+            15 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Bit Count", Some("modbus.bit_cnt"))?;
+            }
+            16 => {
+                self.u16("Reference Number", Some("modbus.reference_num"))?;
+                self.u16("Word Count", Some("modbus.word_cnt"))?;
+            }
+            17 => {
+                let byte_count = self.u8("Byte Count", Some("modbus.byte_cnt"))?;
+                self.data("Server ID and Run Indicator", byte_count as usize)?;
+            }
+            20 => {
+                let length = self.u8("Response Data Length", Some("modbus.byte_cnt"))? as usize;
+                let end = self.pos + length.min(self.remaining());
+                while self.pos < end {
+                    self.file_sub_response()?;
+                }
+            }
+            21 => self.decode_request(function)?,
+            24 => {
+                self.u16("Byte Count", Some("modbus.byte_cnt"))?;
+                let count = self.u16("FIFO Count", None)?;
+                self.registers(2 * count as usize, None)?;
+            }
+            43 => match self.mei_type()? {
+                14 => {
+                    self.device_id_code()?;
+                    self.u8("Conformity Level", None)?;
+                    let start = self.offset();
+                    let more = self.take(1, "more follows")?[0];
+                    self.item(
+                        format!("More Follows: {}", if more == 0xff { "Yes" } else { "No" }),
+                        start,
+                        1,
+                    );
+                    self.object_id("Next Object ID")?;
+                    let count = self.u8("Number of Objects", None)?;
+                    self.device_objects(count)?;
+                }
+                _ => self.rest("Data")?,
+            },
+            _ => self.rest("Data")?,
+        }
+        Ok(())
+    }
+}
+
+/// Key pairing a request with its response: client address and port, server address and
+/// port, transaction ID
+type TransactionKey = (Option<IpAddr>, u16, Option<IpAddr>, u16, u16);
+
+/// A request still waiting for its response
+#[derive(Clone, Debug)]
+struct PendingRequest {
+    frame: usize,
+    timestamp: Timestamp,
+    function: u8,
+    /// First coil or register the request addresses, for numbering values in the response
+    reference: Option<u16>,
+    /// Number of coils or inputs requested
+    count: Option<u16>,
+    /// Shared with the request's layer, filled in once the response is seen
+    response_in: Rc<Cell<Option<usize>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModbusTcp {
+    /// Offset of the MBAP header in the packet
+    offset: usize,
+    transaction_id: u16,
+    protocol_id: u16,
+    length: u16,
+    unit_id: u8,
+    /// Function code with the exception bit cleared
+    function: u8,
+    exception: Option<u8>,
+    pairing: Pairing,
+    /// Items and fields decoded from the PDU after the function code
+    pdu_items: Vec<ProtoItem>,
+    pdu_fields: Vec<Field>,
+}
+
+impl ModbusTcp {
+    pub fn is_request(&self) -> bool {
+        matches!(self.pairing, Pairing::Request(_))
+    }
+
+    /// Frame of the matching response, for requests that got one
+    pub fn response_in(&self) -> Option<usize> {
+        match &self.pairing {
+            Pairing::Request(cell) => cell.get(),
+            Pairing::Response(_) => None,
+        }
+    }
+
+    /// Frame of the matching request, for responses
+    pub fn request_frame(&self) -> Option<usize> {
+        match &self.pairing {
+            Pairing::Response(request) => request.map(|(frame, _)| frame),
+            Pairing::Request(_) => None,
+        }
+    }
+
+    fn pdu_len(&self) -> usize {
+        (self.length as usize).saturating_sub(1)
+    }
+}
+
+impl fmt::Display for ModbusTcp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Modbus/TCP, {}: Trans: {}; Unit: {}, Func: {}: {}",
+            if self.is_request() {
+                "Query"
+            } else {
+                "Response"
+            },
+            self.transaction_id,
+            self.unit_id,
+            self.function,
+            function_name(self.function)
+        )?;
+        if let Some(code) = self.exception {
+            write!(f, ". Exception: {}", exception_name(code))?;
+        }
+        Ok(())
+    }
+}
+
+impl ProtocolLayer for ModbusTcp {
+    fn name(&self) -> &'static str {
+        "mbtcp"
+    }
+
+    fn label(&self) -> String {
+        "Modbus/TCP".to_string()
+    }
+
+    fn info(&self) -> String {
+        let mut info = format!(
+            "{}: Trans: {}; Unit: {}, Func: {}: {}",
+            if self.is_request() {
+                "Query"
+            } else {
+                "Response"
+            },
+            self.transaction_id,
+            self.unit_id,
+            self.function,
+            function_name(self.function)
+        );
+        if let Some(code) = self.exception {
+            info += &format!(". Exception returned: {}", exception_name(code));
+        }
+        info
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("mbtcp.trans_id", self.transaction_id.into()),
+            ("mbtcp.prot_id", self.protocol_id.into()),
+            ("mbtcp.len", self.length.into()),
+            ("mbtcp.unit_id", self.unit_id.into()),
+            ("modbus.func_code", self.function.into()),
+            ("modbus.request", self.is_request().into()),
+        ];
+        if let Some(code) = self.exception {
+            fields.push(("modbus.exception", true.into()));
+            fields.push(("modbus.exception_code", code.into()));
+        }
+        if let Some(frame) = self.response_in() {
+            fields.push(("modbus.response_in", frame.into()));
+        }
+        if let Some(frame) = self.request_frame() {
+            fields.push(("modbus.request_frame", frame.into()));
+        }
+        fields.extend(self.pdu_fields.iter().cloned());
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let mbap = ProtoItem::new(
+            format!(
+                "Modbus Application Protocol Header, Trans: {}, Unit: {}",
+                self.transaction_id, self.unit_id
+            ),
+            off,
+            MBAP_LEN,
+            vec![
+                ProtoItem::new_leaf(
+                    format!("Transaction Identifier: {}", self.transaction_id),
+                    off,
+                    2,
+                ),
+                ProtoItem::new_leaf(
+                    format!("Protocol Identifier: {}", self.protocol_id),
+                    off + 2,
+                    2,
+                ),
+                ProtoItem::new_leaf(format!("Length: {}", self.length), off + 4, 2),
+                ProtoItem::new_leaf(format!("Unit Identifier: {}", self.unit_id), off + 6, 1),
+            ],
+        );
+
+        let pdu_off = off + MBAP_LEN;
+        let mut pdu_children = vec![ProtoItem::new_leaf(
+            format!(
+                "Function Code: {} ({}){}",
+                function_name(self.function),
+                self.function,
+                if self.exception.is_some() {
+                    " [exception]"
+                } else {
+                    ""
+                }
+            ),
+            pdu_off,
+            1,
+        )];
+        if let Some(code) = self.exception {
+            pdu_children.push(ProtoItem::new_leaf(
+                format!("Exception Code: {} ({})", exception_name(code), code),
+                pdu_off + 1,
+                1,
+            ));
+        }
+        pdu_children.extend(self.pdu_items.iter().cloned());
+        match &self.pairing {
+            Pairing::Request(cell) => {
+                if let Some(frame) = cell.get() {
+                    pdu_children.push(ProtoItem::new_leaf(
+                        format!("[Response In: {}]", frame),
+                        0,
+                        0,
+                    ));
+                }
+            }
+            Pairing::Response(Some((frame, nanos))) => {
+                pdu_children.push(ProtoItem::new_leaf(
+                    format!("[Request Frame: {}]", frame),
+                    0,
+                    0,
+                ));
+                pdu_children.push(ProtoItem::new_leaf(
+                    format!("[Time from request: {:.6} seconds]", *nanos as f64 / 1e9),
+                    0,
+                    0,
+                ));
+            }
+            Pairing::Response(None) => {}
+        }
+        let pdu = ProtoItem::new(
+            format!(
+                "Modbus, {}: {}",
+                if self.is_request() {
+                    "Query"
+                } else {
+                    "Response"
+                },
+                function_name(self.function)
+            ),
+            pdu_off,
+            self.pdu_len(),
+            pdu_children,
+        );
+
+        ProtoItem::new(
+            self.to_string(),
+            off,
+            MBAP_LEN + self.pdu_len(),
+            vec![mbap, pdu],
+        )
+        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct ModbusTcpDissector {
+    /// Requests seen so far that no response has answered yet
+    pending: RefCell<HashMap<TransactionKey, PendingRequest>>,
+}
+
+impl Dissector for ModbusTcpDissector {
+    fn name(&self) -> &'static str {
+        "mbtcp"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        if bytes.len() < MBAP_LEN + 1 {
+            return Err(DissectError::truncated(
+                "Modbus/TCP",
+                MBAP_LEN + 1,
+                bytes.len(),
+            ));
+        }
+        let transaction_id = util::two_bytes_to_u16(&bytes[0..2]);
+        let protocol_id = util::two_bytes_to_u16(&bytes[2..4]);
+        let length = util::two_bytes_to_u16(&bytes[4..6]);
+        let unit_id = bytes[6];
+        if protocol_id != 0 {
+            return Err(DissectError::new(format!(
+                "bogus Modbus/TCP protocol identifier ({}, must be 0)",
+                protocol_id
+            )));
+        }
+        let pdu_len = (length as usize).saturating_sub(1);
+        if pdu_len == 0 || pdu_len > MAX_PDU_LEN {
+            return Err(DissectError::new(format!(
+                "bogus Modbus/TCP length ({}, must be 2 to {})",
+                length,
+                MAX_PDU_LEN + 1
+            )));
+        }
+        if bytes.len() < MBAP_LEN + pdu_len {
+            return Err(DissectError::truncated(
+                "Modbus/TCP",
+                MBAP_LEN + pdu_len,
+                bytes.len(),
+            ));
+        }
+        let pdu = &bytes[MBAP_LEN..MBAP_LEN + pdu_len];
+        let function = pdu[0] & 0x7f;
+        let is_exception = pdu[0] & 0x80 != 0;
+
+        // Queries go to the server port; anything coming from it is a response
+        let is_request = ctx.dst_port == MODBUS_PORT || ctx.src_port != MODBUS_PORT;
+        let key: TransactionKey = if is_request {
+            (
+                ctx.net_src,
+                ctx.src_port,
+                ctx.net_dst,
+                ctx.dst_port,
+                transaction_id,
+            )
+        } else {
+            (
+                ctx.net_dst,
+                ctx.dst_port,
+                ctx.net_src,
+                ctx.src_port,
+                transaction_id,
+            )
+        };
+
+        let mut reader = PduReader::new(&pdu[1..], next_byte + MBAP_LEN + 1);
+        let mut exception = None;
+        let pairing = if is_request {
+            reader.decode_request(function)?;
+            let response_in = Rc::new(Cell::new(None));
+            let mut request = PendingRequest {
+                frame: ctx.frame_num,
+                timestamp: ctx.timestamp,
+                function,
+                reference: None,
+                count: None,
+                response_in: response_in.clone(),
+            };
+            if matches!(function, 1..=4 | 15 | 16) && pdu.len() >= 5 {
+                request.reference = Some(util::two_bytes_to_u16(&pdu[1..3]));
+                request.count = Some(util::two_bytes_to_u16(&pdu[3..5]));
+            } else if function == 23 && pdu.len() >= 3 {
+                request.reference = Some(util::two_bytes_to_u16(&pdu[1..3]));
+            }
+            if !ctx.in_error_packet {
+                self.pending.borrow_mut().insert(key, request);
+            }
+            Pairing::Request(response_in)
+        } else {
+            let request = if ctx.in_error_packet {
+                None
+            } else {
+                self.pending.borrow_mut().remove(&key)
+            };
+            // A response to a different function than was asked for can't be the answer
+            let request = request.filter(|r| r.function == function);
+            if let Some(request) = &request {
+                request.response_in.set(Some(ctx.frame_num));
+            }
+            if is_exception {
+                // Shown by the layer itself, next to the function code
+                exception = Some(reader.take(1, "exception code")?[0]);
+            } else {
+                reader.decode_response(function, request.as_ref())?;
+            }
+            Pairing::Response(request.map(|r| (r.frame, ctx.timestamp.nanos_since(&r.timestamp))))
+        };
+
+        let layer = ModbusTcp {
+            offset: next_byte,
+            transaction_id,
+            protocol_id,
+            length,
+            unit_id,
+            function,
+            exception,
+            pairing,
+            pdu_items: reader.items,
+            pdu_fields: reader.fields,
+        };
+
+        // A segment may carry several ADUs back to back
+        let ret_next_byte = next_byte + MBAP_LEN + pdu_len;
+        Ok(Dissection::new(
+            layer,
+            ret_next_byte,
+            NextLayer::Named("mbtcp"),
+        ))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+
+    /// The MBAP length counts the unit identifier and the PDU, which follow it
+    fn pdu_length(&self, bytes: &[u8]) -> Option<PduLength> {
+        if bytes.len() < MBAP_LEN {
+            return Some(PduLength::NeedMore(MBAP_LEN - bytes.len()));
+        }
+        let protocol_id = util::two_bytes_to_u16(&bytes[2..4]);
+        let length = util::two_bytes_to_u16(&bytes[4..6]) as usize;
+        if protocol_id != 0 || !(2..=MAX_PDU_LEN + 1).contains(&length) {
+            return Some(PduLength::Length(bytes.len()));
+        }
+        Some(PduLength::Length(MBAP_LEN - 1 + length))
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(ModbusTcpDissector::default());
+    registry.add_to_table(Table::TcpPort, MODBUS_PORT as u32, "mbtcp");
+    registry.add_alias("modbus", "mbtcp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An MBAP header for unit 1 followed by `pdu`
+    fn adu(transaction: u16, pdu: &[u8]) -> Vec<u8> {
+        let mut bytes = transaction.to_be_bytes().to_vec();
+        bytes.extend([0, 0]);
+        bytes.extend((pdu.len() as u16 + 1).to_be_bytes());
+        bytes.push(1);
+        bytes.extend(pdu);
+        bytes
+    }
+
+    /// Runs the dissector over `bytes` as frame `frame_num`, sent between 10.0.0.1:40000 and
+    /// the server at 10.0.0.5:502
+    fn dissect(
+        dissector: &ModbusTcpDissector,
+        frame_num: usize,
+        to_server: bool,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let registry = Registry::default();
+        let timestamp = Timestamp {
+            secs: frame_num as i64,
+            ..Timestamp::default()
+        };
+        let mut ctx = DissectCtx::new(&registry, frame_num, timestamp);
+        let (client, server) = (
+            Some("10.0.0.1".parse().unwrap()),
+            Some("10.0.0.5".parse().unwrap()),
+        );
+        (ctx.net_src, ctx.src_port, ctx.net_dst, ctx.dst_port) = if to_server {
+            (client, 40000, server, MODBUS_PORT)
+        } else {
+            (server, MODBUS_PORT, client, 40000)
+        };
+        dissector.dissect(&mut ctx, 0, bytes)
+    }
+
+    fn layer(dissection: &Dissection) -> &ModbusTcp {
+        dissection
+            .layer
+            .as_any()
+            .downcast_ref::<ModbusTcp>()
+            .unwrap()
+    }
+
+    fn values(layer: &ModbusTcp, name: &str) -> Vec<FieldValue> {
+        layer
+            .fields()
+            .into_iter()
+            .filter(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        match dissect(&ModbusTcpDissector::default(), 0, true, bytes) {
+            Ok(_) => panic!("{:02x?} was decoded", bytes),
+            Err(err) => err.reason,
+        }
+    }
+
+    #[test]
+    fn response_registers_are_numbered_from_the_request() {
+        let dissector = ModbusTcpDissector::default();
+        let query = dissect(&dissector, 0, true, &adu(7, &[3, 0, 100, 0, 2])).unwrap();
+        let response = dissect(&dissector, 1, false, &adu(7, &[3, 4, 0, 7, 0, 9])).unwrap();
+
+        let (query, response) = (layer(&query), layer(&response));
+        assert!(query.is_request());
+        assert_eq!(query.response_in(), Some(1));
+        assert!(!response.is_request());
+        assert_eq!(response.request_frame(), Some(0));
+        assert_eq!(
+            values(response, "modbus.regval_uint16"),
+            vec![FieldValue::UInt(7), FieldValue::UInt(9)]
+        );
+        let registers = &response.pdu_items[1];
+        assert_eq!(registers.text, "Register values (2)");
+        assert_eq!(registers.children[1].text, "Register 101 (UINT16): 9");
+        assert_eq!(registers.children[1].start, MBAP_LEN + 4);
+    }
+
+    #[test]
+    fn coil_bits_stop_at_the_requested_count() {
+        let dissector = ModbusTcpDissector::default();
+        dissect(&dissector, 0, true, &adu(1, &[1, 0, 10, 0, 3])).unwrap();
+        let response = dissect(&dissector, 1, false, &adu(1, &[1, 1, 0b1111_1101])).unwrap();
+        let response = layer(&response);
+        assert_eq!(
+            values(response, "modbus.bitval"),
+            vec![
+                FieldValue::Bool(true),
+                FieldValue::Bool(false),
+                FieldValue::Bool(true)
+            ]
+        );
+        assert_eq!(response.pdu_items[1].children[2].text, "Bit 12: 1");
+    }
+
+    #[test]
+    fn write_requests_decode_their_values() {
+        let dissector = ModbusTcpDissector::default();
+        let coil = dissect(&dissector, 0, true, &adu(1, &[5, 0, 4, 0xff, 0])).unwrap();
+        assert_eq!(
+            values(layer(&coil), "modbus.bitval"),
+            vec![FieldValue::Bool(true)]
+        );
+        let register = dissect(&dissector, 1, true, &adu(2, &[6, 0, 4, 0x12, 0x34])).unwrap();
+        assert_eq!(
+            layer(&register).pdu_items[1].text,
+            "Register 4 (UINT16): 4660"
+        );
+        let mask = dissect(&dissector, 2, true, &adu(3, &[22, 0, 4, 0, 0xf2, 0, 0x25])).unwrap();
+        assert_eq!(
+            values(layer(&mask), "modbus.or_mask"),
+            vec![FieldValue::UInt(0x25)]
+        );
+    }
+
+    #[test]
+    fn exception_responses_carry_their_code() {
+        let dissector = ModbusTcpDissector::default();
+        dissect(&dissector, 0, true, &adu(9, &[3, 0, 0, 0, 1])).unwrap();
+        let response = dissect(&dissector, 1, false, &adu(9, &[0x83, 2])).unwrap();
+        let response = layer(&response);
+        assert_eq!(response.function, 3);
+        assert_eq!(response.exception, Some(2));
+        assert_eq!(response.request_frame(), Some(0));
+        assert_eq!(
+            values(response, "modbus.exception"),
+            vec![FieldValue::Bool(true)]
+        );
+        assert!(response
+            .info()
+            .ends_with("Exception returned: Illegal data address"));
+    }
+
+    #[test]
+    fn responses_only_pair_with_their_own_request() {
+        let dissector = ModbusTcpDissector::default();
+        let query = dissect(&dissector, 0, true, &adu(1, &[3, 0, 0, 0, 1])).unwrap();
+        // Another transaction, then the right one for another function
+        let other = dissect(&dissector, 1, false, &adu(2, &[3, 2, 0, 1])).unwrap();
+        let wrong = dissect(&dissector, 2, false, &adu(1, &[4, 2, 0, 1])).unwrap();
+        assert_eq!(layer(&other).request_frame(), None);
+        assert_eq!(layer(&wrong).request_frame(), None);
+        assert_eq!(layer(&query).response_in(), None);
+        // The mismatched response used up the request
+        let late = dissect(&dissector, 3, false, &adu(1, &[3, 2, 0, 1])).unwrap();
+        assert_eq!(layer(&late).request_frame(), None);
+    }
+
+    #[test]
+    fn several_adus_in_one_segment_are_chained() {
+        let dissector = ModbusTcpDissector::default();
+        let mut bytes = adu(1, &[3, 0, 0, 0, 1]);
+        bytes.extend(adu(2, &[3, 0, 1, 0, 1]));
+        let first = dissect(&dissector, 0, true, &bytes).unwrap();
+        assert_eq!(first.next_byte, 12);
+        assert!(matches!(first.next, NextLayer::Named("mbtcp")));
+    }
+
+    #[test]
+    fn malformed_headers_are_errors() {
+        assert_eq!(
+            error(&[0, 1, 0, 0, 0, 6, 1]),
+            "Modbus/TCP header needs 8 bytes, only 7 captured"
+        );
+        let mut bytes = adu(1, &[3, 0, 0, 0, 1]);
+        bytes[3] = 1;
+        assert_eq!(
+            error(&bytes),
+            "bogus Modbus/TCP protocol identifier (1, must be 0)"
+        );
+        bytes = adu(1, &[3, 0, 0, 0, 1]);
+        bytes[5] = 1;
+        assert_eq!(
+            error(&bytes),
+            "bogus Modbus/TCP length (1, must be 2 to 254)"
+        );
+        bytes[4..6].copy_from_slice(&300u16.to_be_bytes());
+        assert_eq!(
+            error(&bytes),
+            "bogus Modbus/TCP length (300, must be 2 to 254)"
+        );
+        bytes[4..6].copy_from_slice(&10u16.to_be_bytes());
+        assert_eq!(
+            error(&bytes),
+            "Modbus/TCP header needs 16 bytes, only 12 captured"
+        );
+    }
+
+    #[test]
+    fn pdus_shorter_than_their_function_are_errors() {
+        assert_eq!(
+            error(&adu(1, &[3, 0, 0])),
+            "Modbus Word Count needs 2 bytes, only 0 left in the PDU"
+        );
+        // Write Multiple Registers claiming more values than it carries
+        assert_eq!(
+            error(&adu(1, &[16, 0, 0, 0, 2, 4, 0, 1])),
+            "Modbus register values needs 4 bytes, only 2 left in the PDU"
+        );
+        let dissector = ModbusTcpDissector::default();
+        dissect(&dissector, 0, true, &adu(1, &[1, 0, 0, 0, 16])).unwrap();
+        let err = match dissect(&dissector, 1, false, &adu(1, &[1, 2, 0xff])) {
+            Ok(_) => panic!("short coil response was decoded"),
+            Err(err) => err.reason,
+        };
+        assert_eq!(
+            err,
+            "Modbus bit values needs 2 bytes, only 1 left in the PDU"
+        );
+    }
+
+    #[test]
+    fn pdu_length_follows_the_mbap_length() {
+        let dissector = ModbusTcpDissector::default();
+        let bytes = adu(1, &[3, 0, 0, 0, 1]);
+        assert_eq!(
+            dissector.pdu_length(&bytes[..4]),
+            Some(PduLength::NeedMore(3))
+        );
+        assert_eq!(
+            dissector.pdu_length(&bytes[..7]),
+            Some(PduLength::Length(12))
+        );
+        // A bogus header can't be framed, so the rest of the stream is taken as it is
+        let mut bogus = bytes.clone();
+        bogus[2] = 1;
+        assert_eq!(dissector.pdu_length(&bogus), Some(PduLength::Length(12)));
+        bogus = bytes;
+        bogus[5] = 0;
+        assert_eq!(
+            dissector.pdu_length(&bogus[..9]),
+            Some(PduLength::Length(9))
+        );
+    }
+}
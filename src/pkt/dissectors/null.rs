@@ -0,0 +1,187 @@
+// BSD loopback encapsulation (DLT_NULL and DLT_LOOP): a 4 byte address family ahead of the
+//   packet. DLT_NULL stores it in the byte order of the capturing host, DLT_LOOP in network
+//   order.
+
+use core::fmt;
+use std::any::Any;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+const HEADER_LEN: usize = 4;
+
+const FIELDS: &[FieldInfo] = &[FieldInfo::new("null.family", FieldKind::UInt, "Family")];
+
+const AF_INET: u32 = 2;
+/// AF_INET6 differs between systems: Linux, NetBSD/OpenBSD, FreeBSD and macOS, in that order
+const AF_INET6: [u32; 4] = [10, 24, 28, 30];
+
+fn family_name(family: u32) -> &'static str {
+    match family {
+        AF_INET => "IP",
+        f if AF_INET6.contains(&f) => "IPv6",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Null {
+    /// Offset of the header in the packet
+    offset: usize,
+    family: u32,
+}
+
+impl Null {
+    fn from_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated(
+                "Null/Loopback",
+                HEADER_LEN,
+                bytes.len(),
+            ));
+        }
+        // Families are small numbers, so one that only fits in the upper half was written in
+        //   the other byte order
+        let mut family = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if family > 0xffff {
+            family = family.swap_bytes();
+        }
+        let next_layer = match family {
+            AF_INET => NextLayer::Named("ip"),
+            f if AF_INET6.contains(&f) => NextLayer::Named("ipv6"),
+            _ => NextLayer::Undecoded,
+        };
+        let layer = Null {
+            offset: next_byte,
+            family,
+        };
+        Ok((layer, next_byte + HEADER_LEN, next_layer))
+    }
+}
+
+impl fmt::Display for Null {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Null/Loopback, Family: {} ({})",
+            family_name(self.family),
+            self.family
+        )
+    }
+}
+
+impl ProtocolLayer for Null {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn label(&self) -> String {
+        "Null".to_string()
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        vec![("null.family", self.family.into())]
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        ProtoItem::new(
+            self.to_string(),
+            self.offset,
+            HEADER_LEN,
+            vec![ProtoItem::new_leaf(
+                format!("Family: {} ({})", family_name(self.family), self.family),
+                self.offset,
+                HEADER_LEN,
+            )],
+        )
+        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct NullDissector;
+
+impl Dissector for NullDissector {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn dissect(
+        &self,
+        _ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (layer, next_byte, next_layer) = Null::from_bytes(next_byte, bytes)?;
+        Ok(Dissection::new(layer, next_byte, next_layer))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(NullDissector);
+    for linktype in [pcap_parser::Linktype::NULL, pcap_parser::Linktype::LOOP] {
+        registry.add_to_table(Table::LinkType, linktype.0 as u32, "null");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(bytes: &[u8]) -> (u32, NextLayer) {
+        let (layer, next_byte, next_layer) = Null::from_bytes(0, bytes).unwrap();
+        assert_eq!(next_byte, HEADER_LEN);
+        (layer.family, next_layer)
+    }
+
+    #[test]
+    fn family_is_read_in_either_byte_order() {
+        assert!(matches!(
+            family(&[2, 0, 0, 0, 0x45]),
+            (2, NextLayer::Named("ip"))
+        ));
+        assert!(matches!(
+            family(&[0, 0, 0, 2, 0x45]),
+            (2, NextLayer::Named("ip"))
+        ));
+    }
+
+    #[test]
+    fn every_ipv6_family_goes_to_ipv6() {
+        for af in AF_INET6 {
+            let (_, next_layer) = family(&af.to_be_bytes());
+            assert!(matches!(next_layer, NextLayer::Named("ipv6")));
+            let (_, next_layer) = family(&af.to_le_bytes());
+            assert!(matches!(next_layer, NextLayer::Named("ipv6")));
+        }
+    }
+
+    #[test]
+    fn unknown_family_is_left_undecoded() {
+        assert!(matches!(family(&[7, 0, 0, 0]), (7, NextLayer::Undecoded)));
+    }
+
+    #[test]
+    fn short_header_is_an_error() {
+        let err = Null::from_bytes(0, &[2, 0, 0]).unwrap_err();
+        assert_eq!(
+            err.reason,
+            "Null/Loopback header needs 4 bytes, only 3 captured"
+        );
+    }
+}
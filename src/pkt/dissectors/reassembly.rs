@@ -0,0 +1,658 @@
+// Reassembly of payloads split across several packets: fragmented IP datagrams, and PDUs
+//   carried in a TCP byte stream. Tables live in the dissectors that use them and span the whole
+//   capture, so a payload is put back together on whichever packet fills its last hole, in
+//   capture order.
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::timestamp::Timestamp;
+
+/// How long fragments wait for the rest of their payload, as RFC 8200 has IPv6 hosts do. A
+/// fragment arriving later starts a new payload, since the identification has likely been
+/// reused by then.
+const FRAGMENT_TIMEOUT_NANOS: i128 = 60_000_000_000;
+
+/// Where one fragment of a reassembled payload came from
+#[derive(Clone, Debug)]
+pub struct FragmentInfo {
+    pub frame_num: usize,
+    /// Offset of the fragment's data in the reassembled payload
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// A payload put back together from its fragments
+#[derive(Clone, Debug)]
+pub struct Reassembly {
+    /// Every fragment, in payload order
+    pub fragments: Vec<FragmentInfo>,
+    pub bytes: Rc<Vec<u8>>,
+}
+
+impl Reassembly {
+    /// Summary of the pieces, such as "Fragment" or "Segment", for the tree of the packet that
+    /// completed the payload, e.g. `[2 IPv4 Fragments (3008 bytes): #1(1480), #2(1528)]`
+    pub fn to_proto_item(&self, protocol: &str, piece: &str) -> ProtoItem {
+        let frames: Vec<String> = self
+            .fragments
+            .iter()
+            .map(|f| format!("#{}({})", f.frame_num, f.length))
+            .collect();
+        let mut children: Vec<ProtoItem> = self
+            .fragments
+            .iter()
+            .map(|f| {
+                ProtoItem::new_leaf(
+                    format!(
+                        "[Frame: {}, payload: {}-{} ({} bytes)]",
+                        f.frame_num,
+                        f.offset,
+                        (f.offset + f.length).saturating_sub(1),
+                        f.length
+                    ),
+                    0,
+                    0,
+                )
+            })
+            .collect();
+        children.push(ProtoItem::new_leaf(
+            format!("[{} count: {}]", piece, self.fragments.len()),
+            0,
+            0,
+        ));
+        children.push(ProtoItem::new_leaf(
+            format!("[Reassembled {} length: {}]", protocol, self.bytes.len()),
+            0,
+            0,
+        ));
+        ProtoItem::new(
+            format!(
+                "[{} {} {}s ({} bytes): {}]",
+                self.fragments.len(),
+                protocol,
+                piece,
+                self.bytes.len(),
+                frames.join(", ")
+            ),
+            0,
+            0,
+            children,
+        )
+    }
+}
+
+/// Fragments of one payload seen so far
+struct FragmentSet {
+    /// Each fragment and its data, in capture order
+    fragments: Vec<(FragmentInfo, Vec<u8>)>,
+    /// Known once the last fragment has turned up
+    total_len: Option<usize>,
+    reassembled_in: Rc<Cell<Option<usize>>>,
+    /// Capture time of the first fragment
+    first_seen: Timestamp,
+}
+
+impl FragmentSet {
+    fn new(first_seen: Timestamp) -> Self {
+        FragmentSet {
+            fragments: vec![],
+            total_len: None,
+            reassembled_in: Rc::new(Cell::new(None)),
+            first_seen,
+        }
+    }
+
+    /// Whether a fragment at `offset` carrying `data`, captured at `timestamp`, can't belong
+    /// to the same payload: the set has waited too long, or already has different data at
+    /// that offset
+    fn is_stale(&self, timestamp: &Timestamp, offset: usize, data: &[u8]) -> bool {
+        timestamp.nanos_since(&self.first_seen) > FRAGMENT_TIMEOUT_NANOS
+            || self
+                .fragments
+                .iter()
+                .any(|(f, d)| f.offset == offset && d != data)
+    }
+
+    fn has_duplicate(&self, offset: usize, data: &[u8]) -> bool {
+        self.fragments
+            .iter()
+            .any(|(f, d)| f.offset == offset && d == data)
+    }
+
+    /// Whether the fragments cover every byte up to the end of the last one
+    fn is_complete(&self) -> bool {
+        let total_len = match self.total_len {
+            Some(total_len) => total_len,
+            None => return false,
+        };
+        let mut ranges: Vec<(usize, usize)> = self
+            .fragments
+            .iter()
+            .map(|(f, _)| (f.offset, f.offset + f.length))
+            .collect();
+        ranges.sort_unstable();
+        let mut covered = 0;
+        for (start, end) in ranges {
+            if start > covered {
+                return false;
+            }
+            covered = covered.max(end);
+        }
+        covered >= total_len
+    }
+
+    fn reassemble(self) -> Reassembly {
+        let total_len = self.total_len.unwrap_or_default();
+        let mut bytes = vec![0; total_len];
+        // Where fragments overlap, the one captured first wins
+        for (info, data) in self.fragments.iter().rev() {
+            let end = (info.offset + data.len()).min(total_len);
+            if info.offset < end {
+                bytes[info.offset..end].copy_from_slice(&data[..end - info.offset]);
+            }
+        }
+        let mut fragments: Vec<FragmentInfo> =
+            self.fragments.into_iter().map(|(info, _)| info).collect();
+        fragments.sort_by_key(|f| (f.offset, f.frame_num));
+        Reassembly {
+            fragments,
+            bytes: Rc::new(bytes),
+        }
+    }
+}
+
+/// Fragments waiting for the rest of their payload, keyed on whatever identifies a payload in
+/// the protocol, e.g. addresses, protocol and identification for IPv4
+pub struct FragmentTable<K> {
+    pending: RefCell<HashMap<K, FragmentSet>>,
+}
+
+impl<K: Hash + Eq> FragmentTable<K> {
+    pub fn new() -> Self {
+        FragmentTable {
+            pending: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Adds the fragment of the payload `key` that frame `frame_num`, captured at `timestamp`,
+    /// carries at `offset`. `last` marks the fragment that ends the payload. Returns the frame
+    /// number the payload gets reassembled in, shared by all its fragments and filled in once
+    /// that happens, and the reassembled payload when this fragment completes it.
+    ///
+    /// Fragments still waiting when one turns up that can't belong with them are given up on,
+    /// so a payload reusing the key isn't built from stale data. Exact duplicates are ignored.
+    pub fn add(
+        &self,
+        key: K,
+        frame_num: usize,
+        timestamp: Timestamp,
+        offset: usize,
+        data: &[u8],
+        last: bool,
+    ) -> (Rc<Cell<Option<usize>>>, Option<Reassembly>) {
+        let mut pending = self.pending.borrow_mut();
+        let mut entry = match pending.entry(key) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().is_stale(&timestamp, offset, data) {
+                    entry.insert(FragmentSet::new(timestamp));
+                } else if entry.get().has_duplicate(offset, data) {
+                    return (entry.get().reassembled_in.clone(), None);
+                }
+                entry
+            }
+            Entry::Vacant(entry) => entry.insert_entry(FragmentSet::new(timestamp)),
+        };
+        let set = entry.get_mut();
+        if last {
+            set.total_len = Some(offset + data.len());
+        }
+        set.fragments.push((
+            FragmentInfo {
+                frame_num,
+                offset,
+                length: data.len(),
+            },
+            data.to_vec(),
+        ));
+        let reassembled_in = set.reassembled_in.clone();
+        if !set.is_complete() {
+            return (reassembled_in, None);
+        }
+
+        reassembled_in.set(Some(frame_num));
+        (reassembled_in, Some(entry.remove().reassemble()))
+    }
+}
+
+/// How a segment's bytes fit into the stream received so far
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentOrder {
+    /// Starts where the stream left off, or overlaps what came before but adds to it
+    InOrder,
+    /// Starts past a gap, and is held back until the gap is filled
+    OutOfOrder,
+    /// Every byte had already arrived
+    Retransmission,
+}
+
+/// One direction of a TCP connection's byte stream. Segments are put back in sequence order,
+/// and the bytes no PDU has claimed yet are kept until the application protocol has enough of
+/// them.
+#[derive(Default)]
+pub struct StreamBuffer {
+    /// Sequence number of the next byte expected, once the first segment has been seen
+    next_seq: Option<u32>,
+    /// In-order bytes no PDU has claimed yet
+    pending: Vec<u8>,
+    /// The frames the bytes in `pending` came from, with offsets into it
+    pending_frames: Vec<FragmentInfo>,
+    /// Frame the PDU at the start of `pending` gets completed in, shared by every frame it spans
+    pdu_in: Rc<Cell<Option<usize>>>,
+    /// Segments held back by a gap: sequence number, frame number and payload
+    out_of_order: Vec<(u32, usize, Vec<u8>)>,
+}
+
+impl StreamBuffer {
+    /// Starts the sequence space at `seq`, unless a segment already has
+    pub fn sync(&mut self, seq: u32) {
+        self.next_seq.get_or_insert(seq);
+    }
+
+    /// Adds the payload of the segment in frame `frame_num`, whose first byte has sequence
+    /// number `seq`. Returns how it fits into the stream, and how many of its leading bytes
+    /// had already arrived.
+    pub fn add(&mut self, frame_num: usize, seq: u32, payload: &[u8]) -> (SegmentOrder, usize) {
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        // Sequence numbers wrap around, so compare them by their distance
+        let ahead = seq.wrapping_sub(next_seq) as i32;
+        if ahead > 0 {
+            self.out_of_order.push((seq, frame_num, payload.to_vec()));
+            return (SegmentOrder::OutOfOrder, 0);
+        }
+        let seen = ahead.unsigned_abs() as usize;
+        if seen >= payload.len() {
+            return (SegmentOrder::Retransmission, payload.len());
+        }
+        self.append(frame_num, &payload[seen..]);
+        self.catch_up();
+        (SegmentOrder::InOrder, seen)
+    }
+
+    fn append(&mut self, frame_num: usize, bytes: &[u8]) {
+        self.pending_frames.push(FragmentInfo {
+            frame_num,
+            offset: self.pending.len(),
+            length: bytes.len(),
+        });
+        self.pending.extend_from_slice(bytes);
+        self.next_seq = self
+            .next_seq
+            .map(|seq| seq.wrapping_add(bytes.len() as u32));
+    }
+
+    /// Whether a segment starting at `seq` lies past a gap in the stream
+    pub fn is_past_gap(&self, seq: u32) -> bool {
+        self.next_seq
+            .is_some_and(|next_seq| seq.wrapping_sub(next_seq) as i32 > 0)
+    }
+
+    /// Gives up on the bytes before `seq`, which the capture is missing. The unclaimed bytes are
+    /// dropped with the PDU they started, and the stream carries on from `seq`, taking in the
+    /// held back segments that follow on. Returns how many bytes were skipped.
+    pub fn skip_to(&mut self, seq: u32) -> usize {
+        let missing = match self.next_seq {
+            Some(next_seq) if self.is_past_gap(seq) => seq.wrapping_sub(next_seq) as usize,
+            _ => 0,
+        };
+        self.pending.clear();
+        self.pending_frames.clear();
+        self.pdu_in = Rc::new(Cell::new(None));
+        self.next_seq = Some(seq);
+        self.catch_up();
+        missing
+    }
+
+    /// Gives up on the first gap, carrying on from the earliest held back segment as
+    /// `skip_to` does. Returns how many bytes were skipped, or nothing when no segment is held
+    /// back.
+    pub fn skip_gap(&mut self) -> Option<usize> {
+        let next_seq = self.next_seq?;
+        let seq = self
+            .out_of_order
+            .iter()
+            .map(|(seq, _, _)| *seq)
+            .min_by_key(|seq| seq.wrapping_sub(next_seq))?;
+        Some(self.skip_to(seq))
+    }
+
+    /// Moves held back segments the stream has caught up with into `pending`
+    fn catch_up(&mut self) {
+        while let Some(next_seq) = self.next_seq {
+            let reached = self
+                .out_of_order
+                .iter()
+                .position(|(seq, _, _)| seq.wrapping_sub(next_seq) as i32 <= 0);
+            let (seq, frame_num, payload) = match reached {
+                Some(idx) => self.out_of_order.remove(idx),
+                None => break,
+            };
+            let seen = next_seq.wrapping_sub(seq) as usize;
+            if seen < payload.len() {
+                self.append(frame_num, &payload[seen..]);
+            }
+        }
+    }
+
+    /// Bytes no PDU has claimed yet
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    /// Whether any unclaimed bytes came from frame `frame_num`
+    pub fn pending_from(&self, frame_num: usize) -> bool {
+        self.pending_frames.iter().any(|f| f.frame_num == frame_num)
+    }
+
+    /// Frame the PDU at the start of the unclaimed bytes gets completed in, once it is
+    pub fn pdu_in(&self) -> Rc<Cell<Option<usize>>> {
+        self.pdu_in.clone()
+    }
+
+    /// Claims the first `len` unclaimed bytes as a PDU completed in frame `frame_num`.
+    /// Returns them and the frames they came from, with offsets into the PDU.
+    pub fn take(&mut self, len: usize, frame_num: usize) -> (Vec<u8>, Vec<FragmentInfo>) {
+        let len = len.min(self.pending.len());
+        let bytes: Vec<u8> = self.pending.drain(..len).collect();
+        let mut taken = vec![];
+        let mut rest = vec![];
+        for piece in self.pending_frames.drain(..) {
+            let end = piece.offset + piece.length;
+            if end <= len {
+                taken.push(piece);
+            } else if piece.offset >= len {
+                rest.push(FragmentInfo {
+                    offset: piece.offset - len,
+                    ..piece
+                });
+            } else {
+                taken.push(FragmentInfo {
+                    length: len - piece.offset,
+                    ..piece
+                });
+                rest.push(FragmentInfo {
+                    frame_num: piece.frame_num,
+                    offset: 0,
+                    length: end - len,
+                });
+            }
+        }
+        self.pending_frames = rest;
+        self.pdu_in.set(Some(frame_num));
+        self.pdu_in = Rc::new(Cell::new(None));
+        (bytes, taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame numbers of the unclaimed bytes, in stream order
+    fn pending_frames(stream: &StreamBuffer) -> Vec<usize> {
+        stream.pending_frames.iter().map(|f| f.frame_num).collect()
+    }
+
+    fn at(secs: i64) -> Timestamp {
+        Timestamp {
+            secs,
+            ..Timestamp::default()
+        }
+    }
+
+    /// Adds a fragment of payload 1 captured `secs` into the capture, returning the
+    /// reassembled bytes if it completes the payload
+    fn add(
+        table: &FragmentTable<u32>,
+        frame_num: usize,
+        secs: i64,
+        offset: usize,
+        data: &[u8],
+        last: bool,
+    ) -> Option<Vec<u8>> {
+        let (_, reassembly) = table.add(1, frame_num, at(secs), offset, data, last);
+        reassembly.map(|r| r.bytes.to_vec())
+    }
+
+    /// Frame numbers of a reassembled payload's fragments, in payload order
+    fn fragment_frames(reassembly: &Reassembly) -> Vec<usize> {
+        reassembly.fragments.iter().map(|f| f.frame_num).collect()
+    }
+
+    #[test]
+    fn fragments_in_order_are_reassembled_on_the_last() {
+        let table = FragmentTable::new();
+        assert_eq!(add(&table, 1, 0, 0, b"abc", false), None);
+        assert_eq!(add(&table, 2, 0, 3, b"def", false), None);
+        assert_eq!(
+            add(&table, 3, 0, 6, b"gh", true),
+            Some(b"abcdefgh".to_vec())
+        );
+    }
+
+    #[test]
+    fn fragments_out_of_order_are_reassembled_on_the_one_filling_the_hole() {
+        let table = FragmentTable::new();
+        let (first_in, _) = table.add(1, 1, at(0), 6, b"gh", true);
+        assert_eq!(add(&table, 2, 0, 0, b"abc", false), None);
+        let (last_in, reassembly) = table.add(1, 3, at(0), 3, b"def", false);
+        let reassembly = reassembly.unwrap();
+        assert_eq!(*reassembly.bytes, b"abcdefgh");
+        assert_eq!(fragment_frames(&reassembly), vec![2, 3, 1]);
+        assert_eq!(first_in.get(), Some(3));
+        assert!(Rc::ptr_eq(&first_in, &last_in));
+    }
+
+    #[test]
+    fn incomplete_payload_is_not_reassembled() {
+        let table = FragmentTable::new();
+        let (reassembled_in, _) = table.add(1, 1, at(0), 0, b"abc", false);
+        assert_eq!(add(&table, 2, 0, 6, b"gh", true), None);
+        assert_eq!(reassembled_in.get(), None);
+    }
+
+    #[test]
+    fn duplicate_fragment_is_ignored() {
+        let table = FragmentTable::new();
+        add(&table, 1, 0, 0, b"abc", false);
+        assert_eq!(add(&table, 2, 0, 0, b"abc", false), None);
+        let (_, reassembly) = table.add(1, 3, at(0), 3, b"de", true);
+        let reassembly = reassembly.unwrap();
+        assert_eq!(*reassembly.bytes, b"abcde");
+        assert_eq!(fragment_frames(&reassembly), vec![1, 3]);
+    }
+
+    #[test]
+    fn overlapping_fragments_keep_the_first_captured_bytes() {
+        let table = FragmentTable::new();
+        add(&table, 1, 0, 0, b"abcd", false);
+        assert_eq!(
+            add(&table, 2, 0, 2, b"XYef", true),
+            Some(b"abcdef".to_vec())
+        );
+    }
+
+    #[test]
+    fn different_data_at_the_same_offset_starts_a_new_payload() {
+        let table = FragmentTable::new();
+        let (stale_in, _) = table.add(1, 1, at(0), 0, b"old", false);
+        assert_eq!(add(&table, 2, 0, 0, b"new", false), None);
+        let (_, reassembly) = table.add(1, 3, at(0), 3, b"!", true);
+        let reassembly = reassembly.unwrap();
+        assert_eq!(*reassembly.bytes, b"new!");
+        assert_eq!(fragment_frames(&reassembly), vec![2, 3]);
+        assert_eq!(stale_in.get(), None);
+    }
+
+    #[test]
+    fn fragments_past_the_timeout_start_a_new_payload() {
+        let table = FragmentTable::new();
+        add(&table, 1, 0, 0, b"abc", false);
+        assert_eq!(add(&table, 2, 61, 6, b"gh", true), None);
+        assert_eq!(add(&table, 3, 62, 3, b"def", false), None);
+        assert_eq!(
+            add(&table, 4, 63, 0, b"ABC", false),
+            Some(b"ABCdefgh".to_vec())
+        );
+    }
+
+    #[test]
+    fn fragments_within_the_timeout_are_kept() {
+        let table = FragmentTable::new();
+        add(&table, 1, 0, 0, b"abc", false);
+        assert_eq!(add(&table, 2, 60, 3, b"de", true), Some(b"abcde".to_vec()));
+    }
+
+    #[test]
+    fn payloads_with_different_keys_are_kept_apart() {
+        let table = FragmentTable::new();
+        table.add(1, 1, at(0), 0, b"abc", false);
+        table.add(2, 2, at(0), 0, b"ABC", false);
+        let (_, reassembly) = table.add(2, 3, at(0), 3, b"D", true);
+        assert_eq!(*reassembly.unwrap().bytes, b"ABCD");
+        assert_eq!(add(&table, 4, 0, 3, b"d", true), Some(b"abcd".to_vec()));
+    }
+
+    #[test]
+    fn in_order_segments_are_appended() {
+        let mut stream = StreamBuffer::default();
+        stream.sync(100);
+        assert_eq!(stream.add(1, 100, b"abc"), (SegmentOrder::InOrder, 0));
+        assert_eq!(stream.add(2, 103, b"def"), (SegmentOrder::InOrder, 0));
+        assert_eq!(stream.pending(), b"abcdef");
+        assert_eq!(pending_frames(&stream), vec![1, 2]);
+    }
+
+    #[test]
+    fn segment_past_a_gap_is_held_back_until_it_is_filled() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abc");
+        assert!(stream.is_past_gap(106));
+        assert_eq!(stream.add(2, 106, b"ghi"), (SegmentOrder::OutOfOrder, 0));
+        assert_eq!(stream.pending(), b"abc");
+        assert_eq!(stream.add(3, 103, b"def"), (SegmentOrder::InOrder, 0));
+        assert_eq!(stream.pending(), b"abcdefghi");
+        assert_eq!(pending_frames(&stream), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn skipping_a_gap_drops_the_unclaimed_bytes() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abc");
+        let pdu_in = stream.pdu_in();
+        assert_eq!(stream.skip_to(110), 7);
+        assert_eq!(stream.pending(), b"");
+        assert_eq!(stream.add(2, 110, b"xyz"), (SegmentOrder::InOrder, 0));
+        assert_eq!(stream.pending(), b"xyz");
+        // The PDU the dropped bytes started is never completed
+        assert!(!Rc::ptr_eq(&pdu_in, &stream.pdu_in()));
+        assert_eq!(pdu_in.get(), None);
+    }
+
+    #[test]
+    fn skipping_takes_in_held_back_segments_that_follow_on() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abc");
+        stream.add(2, 110, b"xyz");
+        stream.add(3, 113, b"!");
+        assert_eq!(stream.skip_to(110), 7);
+        assert_eq!(stream.pending(), b"xyz!");
+        assert!(!stream.is_past_gap(114));
+    }
+
+    #[test]
+    fn skipping_the_first_gap_keeps_held_back_bytes() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abc");
+        stream.add(3, 120, b"uvw");
+        stream.add(2, 110, b"xyz");
+        assert_eq!(stream.skip_gap(), Some(7));
+        assert_eq!(stream.pending(), b"xyz");
+        assert_eq!(stream.skip_gap(), Some(7));
+        assert_eq!(stream.pending(), b"uvw");
+        assert_eq!(stream.skip_gap(), None);
+    }
+
+    #[test]
+    fn retransmission_adds_nothing() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abc");
+        stream.add(2, 103, b"def");
+        assert_eq!(
+            stream.add(3, 100, b"abc"),
+            (SegmentOrder::Retransmission, 3)
+        );
+        assert_eq!(stream.add(4, 103, b"de"), (SegmentOrder::Retransmission, 2));
+        assert_eq!(stream.pending(), b"abcdef");
+    }
+
+    #[test]
+    fn overlapping_segment_adds_only_new_bytes() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"abcd");
+        assert_eq!(stream.add(2, 102, b"cdef"), (SegmentOrder::InOrder, 2));
+        assert_eq!(stream.pending(), b"abcdef");
+        let (_, pieces) = stream.take(6, 2);
+        let pieces: Vec<(usize, usize, usize)> = pieces
+            .iter()
+            .map(|f| (f.frame_num, f.offset, f.length))
+            .collect();
+        assert_eq!(pieces, vec![(1, 0, 4), (2, 4, 2)]);
+    }
+
+    #[test]
+    fn held_back_segment_overlapping_the_stream_is_trimmed() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 100, b"ab");
+        stream.add(2, 103, b"def");
+        stream.add(3, 102, b"cd");
+        assert_eq!(stream.pending(), b"abcdef");
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut stream = StreamBuffer::default();
+        stream.sync(u32::MAX - 1);
+        assert_eq!(
+            stream.add(1, u32::MAX - 1, b"abc"),
+            (SegmentOrder::InOrder, 0)
+        );
+        assert!(!stream.is_past_gap(1));
+        assert_eq!(stream.add(2, 1, b"def"), (SegmentOrder::InOrder, 0));
+        assert_eq!(
+            stream.add(3, u32::MAX, b"bc"),
+            (SegmentOrder::Retransmission, 2)
+        );
+        assert_eq!(stream.add(4, 7, b"jkl"), (SegmentOrder::OutOfOrder, 0));
+        assert_eq!(stream.add(5, 4, b"ghi"), (SegmentOrder::InOrder, 0));
+        assert_eq!(stream.pending(), b"abcdefghijkl");
+    }
+
+    #[test]
+    fn take_splits_a_segment_between_pdus() {
+        let mut stream = StreamBuffer::default();
+        stream.add(1, 0, b"abcdef");
+        let pdu_in = stream.pdu_in();
+        let (pdu, pieces) = stream.take(4, 1);
+        assert_eq!(pdu, b"abcd");
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pdu_in.get(), Some(1));
+        assert_eq!(stream.pending(), b"ef");
+        assert_eq!(stream.pending_frames[0].offset, 0);
+        assert_eq!(stream.pending_frames[0].length, 2);
+    }
+}
@@ -0,0 +1,284 @@
+// Linux cooked capture (DLT_LINUX_SLL), the pseudo link header Linux gives packets captured on
+//   the "any" device or on interfaces without a link header of their own.
+
+use core::fmt;
+use std::any::Any;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::ethernet::{mac_to_string, Ethertype};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+const HEADER_LEN: usize = 16;
+/// Room the header has for the link layer address
+const ADDRESS_LEN: usize = 8;
+/// Protocol values below this are not ethertypes, but Linux's own codes for 802.2 and the like
+const MIN_ETHERTYPE: u16 = 0x0600;
+/// ARPHRD_ETHER, the hardware type of Ethernet devices
+const HATYPE_ETHER: u16 = 1;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("sll.pkttype", FieldKind::UInt, "Packet type"),
+    FieldInfo::new("sll.hatype", FieldKind::UInt, "Link-layer address type"),
+    FieldInfo::new("sll.halen", FieldKind::UInt, "Link-layer address length"),
+    FieldInfo::new("sll.src.eth", FieldKind::Ether, "Source"),
+    FieldInfo::new("sll.etype", FieldKind::UInt, "Protocol"),
+];
+
+fn packet_type_name(packet_type: u16) -> &'static str {
+    match packet_type {
+        0 => "Unicast to us",
+        1 => "Broadcast",
+        2 => "Multicast",
+        3 => "Unicast to another host",
+        4 => "Sent by us",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sll {
+    /// Offset of the header in the packet
+    offset: usize,
+    packet_type: u16,
+    hatype: u16,
+    halen: u16,
+    /// The first `halen` bytes hold the sender's link layer address
+    address: [u8; ADDRESS_LEN],
+    protocol: u16,
+}
+
+impl Sll {
+    fn from_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated(
+                "Linux cooked capture",
+                HEADER_LEN,
+                bytes.len(),
+            ));
+        }
+        let mut address = [0u8; ADDRESS_LEN];
+        address.copy_from_slice(&bytes[6..14]);
+        let layer = Sll {
+            offset: next_byte,
+            packet_type: u16::from_be_bytes([bytes[0], bytes[1]]),
+            hatype: u16::from_be_bytes([bytes[2], bytes[3]]),
+            halen: u16::from_be_bytes([bytes[4], bytes[5]]),
+            address,
+            protocol: u16::from_be_bytes([bytes[14], bytes[15]]),
+        };
+        let next_layer = if layer.protocol >= MIN_ETHERTYPE {
+            NextLayer::Table(Table::EtherType, layer.protocol as u32)
+        } else {
+            NextLayer::Undecoded
+        };
+        Ok((layer, next_byte + HEADER_LEN, next_layer))
+    }
+
+    /// Sender's address, when it is an Ethernet one
+    fn source_mac(&self) -> Option<[u8; 6]> {
+        if self.hatype != HATYPE_ETHER || self.halen != 6 {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&self.address[..6]);
+        Some(mac)
+    }
+
+    /// Sender's address as colon-separated hex, however long it is
+    fn source(&self) -> String {
+        let len = (self.halen as usize).min(ADDRESS_LEN);
+        match self.source_mac() {
+            Some(mac) => mac_to_string(&mac),
+            None => {
+                let hex: Vec<String> = self.address[..len]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                hex.join(":")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Sll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Linux cooked capture, {}, Source: {}, Protocol: {}",
+            packet_type_name(self.packet_type),
+            self.source(),
+            Ethertype::from(self.protocol)
+        )
+    }
+}
+
+impl ProtocolLayer for Sll {
+    fn name(&self) -> &'static str {
+        "sll"
+    }
+
+    fn label(&self) -> String {
+        "SLL".to_string()
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("sll.pkttype", self.packet_type.into()),
+            ("sll.hatype", self.hatype.into()),
+            ("sll.halen", self.halen.into()),
+        ];
+        if let Some(mac) = self.source_mac() {
+            fields.push(("sll.src.eth", mac.into()));
+        }
+        fields.push(("sll.etype", self.protocol.into()));
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        ProtoItem::new(
+            self.to_string(),
+            off,
+            HEADER_LEN,
+            vec![
+                ProtoItem::new_leaf(
+                    format!(
+                        "Packet type: {} ({})",
+                        packet_type_name(self.packet_type),
+                        self.packet_type
+                    ),
+                    off,
+                    2,
+                ),
+                ProtoItem::new_leaf(
+                    format!("Link-layer address type: {}", self.hatype),
+                    off + 2,
+                    2,
+                ),
+                ProtoItem::new_leaf(
+                    format!("Link-layer address length: {}", self.halen),
+                    off + 4,
+                    2,
+                ),
+                ProtoItem::new_leaf(format!("Source: {}", self.source()), off + 6, ADDRESS_LEN),
+                ProtoItem::new_leaf(
+                    format!(
+                        "Protocol: {} (0x{:04x})",
+                        Ethertype::from(self.protocol),
+                        self.protocol
+                    ),
+                    off + 14,
+                    2,
+                ),
+            ],
+        )
+        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct SllDissector;
+
+impl Dissector for SllDissector {
+    fn name(&self) -> &'static str {
+        "sll"
+    }
+
+    fn dissect(
+        &self,
+        _ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (layer, next_byte, next_layer) = Sll::from_bytes(next_byte, bytes)?;
+        Ok(Dissection::new(layer, next_byte, next_layer))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(SllDissector);
+    registry.add_to_table(
+        Table::LinkType,
+        pcap_parser::Linktype::LINUX_SLL.0 as u32,
+        "sll",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An outgoing packet from Ethernet address 00:11:22:33:44:55 carrying `protocol`
+    fn header(protocol: u16) -> Vec<u8> {
+        let mut bytes = vec![0, 4, 0, 1, 0, 6, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0, 0];
+        bytes.extend(protocol.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn ethertype_picks_the_next_layer() {
+        let (layer, next_byte, next_layer) = Sll::from_bytes(0, &header(0x0800)).unwrap();
+        assert_eq!(next_byte, HEADER_LEN);
+        assert!(matches!(
+            next_layer,
+            NextLayer::Table(Table::EtherType, 0x0800)
+        ));
+        assert_eq!(
+            layer.source_mac(),
+            Some([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+        assert_eq!(
+            layer.to_string(),
+            "Linux cooked capture, Sent by us, Source: 00:11:22:33:44:55, Protocol: IPV4"
+        );
+    }
+
+    #[test]
+    fn linux_protocol_codes_are_left_undecoded() {
+        let (_, _, next_layer) = Sll::from_bytes(0, &header(0x0004)).unwrap();
+        assert!(matches!(next_layer, NextLayer::Undecoded));
+    }
+
+    #[test]
+    fn other_hardware_addresses_are_shown_in_hex() {
+        let mut bytes = header(0x86dd);
+        bytes[3] = 0x18;
+        bytes[5] = 3;
+        let (layer, _, _) = Sll::from_bytes(0, &bytes).unwrap();
+        assert_eq!(layer.source_mac(), None);
+        assert_eq!(layer.source(), "00:11:22");
+    }
+
+    #[test]
+    fn address_length_past_the_header_is_capped() {
+        let mut bytes = header(0x0800);
+        bytes[3] = 0x18;
+        bytes[5] = 0xff;
+        let (layer, _, _) = Sll::from_bytes(0, &bytes).unwrap();
+        assert_eq!(layer.source(), "00:11:22:33:44:55:00:00");
+    }
+
+    #[test]
+    fn short_header_is_an_error() {
+        let err = Sll::from_bytes(0, &header(0x0800)[..15]).unwrap_err();
+        assert_eq!(
+            err.reason,
+            "Linux cooked capture header needs 16 bytes, only 15 captured"
+        );
+    }
+}
@@ -1,98 +1,2290 @@
 use core::fmt;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::ops::Range;
+use std::rc::Rc;
 use tui::style::{Color, Style};
-use tui_tree_widget::TreeItem;
 
-use crate::pkt::dissectors::util;
-use crate::pkt::LayerHint;
+use crate::pkt::dissectors::reassembly::{FragmentInfo, Reassembly, SegmentOrder, StreamBuffer};
+use crate::pkt::dissectors::util::{self, ChecksumStatus};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::expert::{ExpertInfo, Severity};
+use crate::pkt::field::{Field, FieldInfo, FieldKind, FieldValue};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, PduLength, ProtocolLayer, Registry, Table,
+};
+use crate::pkt::timestamp::{self, Timestamp};
+
+/// Length of the header without options
+const HEADER_LEN: usize = 20;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("tcp.srcport", FieldKind::UInt, "Source Port"),
+    FieldInfo::new("tcp.dstport", FieldKind::UInt, "Destination Port"),
+    FieldInfo::new("tcp.port", FieldKind::UInt, "Source or Destination Port"),
+    FieldInfo::new("tcp.len", FieldKind::UInt, "TCP Segment Len"),
+    FieldInfo::new("tcp.stream", FieldKind::UInt, "Stream index"),
+    FieldInfo::new("tcp.seq", FieldKind::UInt, "Sequence Number"),
+    FieldInfo::new("tcp.seq_raw", FieldKind::UInt, "Sequence Number (raw)"),
+    FieldInfo::new("tcp.nxtseq", FieldKind::UInt, "Next Sequence Number"),
+    FieldInfo::new("tcp.ack", FieldKind::UInt, "Acknowledgment Number"),
+    FieldInfo::new(
+        "tcp.ack_raw",
+        FieldKind::UInt,
+        "Acknowledgment number (raw)",
+    ),
+    FieldInfo::new("tcp.hdr_len", FieldKind::UInt, "Header Length"),
+    FieldInfo::new("tcp.flags", FieldKind::UInt, "Flags"),
+    FieldInfo::new("tcp.flags.res", FieldKind::Bool, "Reserved"),
+    FieldInfo::new("tcp.flags.ns", FieldKind::Bool, "Nonce"),
+    FieldInfo::new(
+        "tcp.flags.cwr",
+        FieldKind::Bool,
+        "Congestion Window Reduced (CWR)",
+    ),
+    FieldInfo::new("tcp.flags.ece", FieldKind::Bool, "ECN-Echo"),
+    FieldInfo::new("tcp.flags.urg", FieldKind::Bool, "Urgent"),
+    FieldInfo::new("tcp.flags.ack", FieldKind::Bool, "Acknowledgment"),
+    FieldInfo::new("tcp.flags.push", FieldKind::Bool, "Push"),
+    FieldInfo::new("tcp.flags.reset", FieldKind::Bool, "Reset"),
+    FieldInfo::new("tcp.flags.syn", FieldKind::Bool, "Syn"),
+    FieldInfo::new("tcp.flags.fin", FieldKind::Bool, "Fin"),
+    FieldInfo::new("tcp.flags.str", FieldKind::Str, "TCP Flags"),
+    FieldInfo::new("tcp.window_size", FieldKind::UInt, "Window"),
+    FieldInfo::new("tcp.checksum", FieldKind::UInt, "Checksum"),
+    FieldInfo::new(
+        "tcp.checksum.status",
+        FieldKind::UInt,
+        "Checksum Status (0 bad, 1 good, 2 unverified)",
+    ),
+    FieldInfo::new("tcp.urgent_pointer", FieldKind::UInt, "Urgent Pointer"),
+    FieldInfo::new("tcp.option_kind", FieldKind::UInt, "Option Kind"),
+    FieldInfo::new("tcp.options.mss_val", FieldKind::UInt, "MSS Value"),
+    FieldInfo::new("tcp.options.wscale.shift", FieldKind::UInt, "Shift count"),
+    FieldInfo::new(
+        "tcp.options.wscale.multiplier",
+        FieldKind::UInt,
+        "Multiplier",
+    ),
+    FieldInfo::new(
+        "tcp.options.sack_perm",
+        FieldKind::Bool,
+        "TCP SACK Permitted Option",
+    ),
+    FieldInfo::new("tcp.options.sack_le", FieldKind::UInt, "TCP SACK Left Edge"),
+    FieldInfo::new(
+        "tcp.options.sack_re",
+        FieldKind::UInt,
+        "TCP SACK Right Edge",
+    ),
+    FieldInfo::new("tcp.options.sack.count", FieldKind::UInt, "TCP SACK Count"),
+    FieldInfo::new(
+        "tcp.options.timestamp.tsval",
+        FieldKind::UInt,
+        "Timestamp value",
+    ),
+    FieldInfo::new(
+        "tcp.options.timestamp.tsecr",
+        FieldKind::UInt,
+        "Timestamp echo reply",
+    ),
+    FieldInfo::new(
+        "tcp.options.tfo.request",
+        FieldKind::Bool,
+        "Fast Open Cookie Request",
+    ),
+    FieldInfo::new(
+        "tcp.options.tfo.cookie",
+        FieldKind::Bytes,
+        "Fast Open Cookie",
+    ),
+    FieldInfo::new(
+        "tcp.options.mptcp.subtype",
+        FieldKind::UInt,
+        "Multipath TCP subtype",
+    ),
+    FieldInfo::new("tcp.segment", FieldKind::UInt, "TCP Segment"),
+    FieldInfo::new("tcp.segment.count", FieldKind::UInt, "Segment count"),
+    FieldInfo::new(
+        "tcp.reassembled_in",
+        FieldKind::UInt,
+        "Reassembled PDU in frame",
+    ),
+    FieldInfo::new(
+        "tcp.reassembled.length",
+        FieldKind::UInt,
+        "Reassembled TCP length",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.acks_frame",
+        FieldKind::UInt,
+        "This is an ACK to the segment in frame",
+    ),
+    FieldInfo::new("tcp.analysis.acked_in", FieldKind::UInt, "ACKed in frame"),
+    FieldInfo::new("tcp.analysis.flags", FieldKind::Bool, "TCP Analysis Flags"),
+    FieldInfo::new(
+        "tcp.analysis.retransmission",
+        FieldKind::Bool,
+        "Retransmission",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.fast_retransmission",
+        FieldKind::Bool,
+        "Fast Retransmission",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.spurious_retransmission",
+        FieldKind::Bool,
+        "Spurious Retransmission",
+    ),
+    FieldInfo::new("tcp.analysis.out_of_order", FieldKind::Bool, "Out Of Order"),
+    FieldInfo::new(
+        "tcp.analysis.duplicate_ack",
+        FieldKind::Bool,
+        "Duplicate ACK",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.duplicate_ack_num",
+        FieldKind::UInt,
+        "Duplicate ACK #",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.duplicate_ack_frame",
+        FieldKind::UInt,
+        "Duplicate to the ACK in frame",
+    ),
+    FieldInfo::new("tcp.analysis.zero_window", FieldKind::Bool, "Zero Window"),
+    FieldInfo::new("tcp.analysis.window_full", FieldKind::Bool, "Window Full"),
+    FieldInfo::new("tcp.analysis.keep_alive", FieldKind::Bool, "Keep-Alive"),
+    FieldInfo::new(
+        "tcp.analysis.lost_segment",
+        FieldKind::Bool,
+        "Previous segment not captured",
+    ),
+    FieldInfo::new(
+        "tcp.analysis.ack_lost_segment",
+        FieldKind::Bool,
+        "ACKed segment that wasn't captured",
+    ),
+];
+
+/// The 12 bits after the header length, most significant first: mask, abbreviation, filter
+/// field and name
+const FLAG_BITS: &[(u16, &str, &str, &str)] = &[
+    (0x100, "NS", "tcp.flags.ns", "Nonce"),
+    (
+        0x080,
+        "CWR",
+        "tcp.flags.cwr",
+        "Congestion Window Reduced (CWR)",
+    ),
+    (0x040, "ECE", "tcp.flags.ece", "ECN-Echo"),
+    (0x020, "URG", "tcp.flags.urg", "Urgent"),
+    (0x010, "ACK", "tcp.flags.ack", "Acknowledgment"),
+    (0x008, "PSH", "tcp.flags.push", "Push"),
+    (0x004, "RST", "tcp.flags.reset", "Reset"),
+    (0x002, "SYN", "tcp.flags.syn", "Syn"),
+    (0x001, "FIN", "tcp.flags.fin", "Fin"),
+];
+const RESERVED_FLAGS: u16 = 0xe00;
+
+pub const FLAG_ACK: u16 = 0x010;
+pub const FLAG_SYN: u16 = 0x002;
+const FLAG_RST: u16 = 0x004;
+const FLAG_FIN: u16 = 0x001;
+
+/// Something the sequence and acknowledgment analysis noticed about a segment, given the
+/// segments of its connection before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TcpAnalysis {
+    /// Resends data already sent
+    Retransmission,
+    /// Resends the data the receiver asked for with duplicate ACKs
+    FastRetransmission,
+    /// Resends data the receiver had already acknowledged
+    SpuriousRetransmission,
+    /// Fills a gap left by segments sent after it
+    OutOfOrder,
+    /// The `num`th repeat of the acknowledgment first sent in frame `original`
+    DuplicateAck {
+        num: u32,
+        original: usize,
+    },
+    ZeroWindow,
+    /// Sends up to the very end of the window the receiver offered
+    WindowFull,
+    /// Resends the byte before the next one, only to get an acknowledgment back
+    KeepAlive,
+    /// Starts past where the sender's previous segment ended
+    LostSegment,
+    /// Acknowledges data the capture hasn't seen
+    AckedUnseen,
+}
+
+impl TcpAnalysis {
+    /// Tag for the start of the segment summary, as in "[TCP Dup ACK 5#1]"
+    fn tag(self) -> String {
+        match self {
+            TcpAnalysis::Retransmission => "[TCP Retransmission]".to_string(),
+            TcpAnalysis::FastRetransmission => "[TCP Fast Retransmission]".to_string(),
+            TcpAnalysis::SpuriousRetransmission => "[TCP Spurious Retransmission]".to_string(),
+            TcpAnalysis::OutOfOrder => "[TCP Out-Of-Order]".to_string(),
+            TcpAnalysis::DuplicateAck { num, original } => {
+                format!("[TCP Dup ACK {}#{}]", original, num)
+            }
+            TcpAnalysis::ZeroWindow => "[TCP ZeroWindow]".to_string(),
+            TcpAnalysis::WindowFull => "[TCP Window Full]".to_string(),
+            TcpAnalysis::KeepAlive => "[TCP Keep-Alive]".to_string(),
+            TcpAnalysis::LostSegment => "[TCP Previous segment not captured]".to_string(),
+            TcpAnalysis::AckedUnseen => "[TCP ACKed unseen segment]".to_string(),
+        }
+    }
+
+    /// Filter field that is true on segments flagged this way
+    fn field(self) -> &'static str {
+        match self {
+            TcpAnalysis::Retransmission => "tcp.analysis.retransmission",
+            TcpAnalysis::FastRetransmission => "tcp.analysis.fast_retransmission",
+            TcpAnalysis::SpuriousRetransmission => "tcp.analysis.spurious_retransmission",
+            TcpAnalysis::OutOfOrder => "tcp.analysis.out_of_order",
+            TcpAnalysis::DuplicateAck { .. } => "tcp.analysis.duplicate_ack",
+            TcpAnalysis::ZeroWindow => "tcp.analysis.zero_window",
+            TcpAnalysis::WindowFull => "tcp.analysis.window_full",
+            TcpAnalysis::KeepAlive => "tcp.analysis.keep_alive",
+            TcpAnalysis::LostSegment => "tcp.analysis.lost_segment",
+            TcpAnalysis::AckedUnseen => "tcp.analysis.ack_lost_segment",
+        }
+    }
+
+    fn expert_info(self) -> ExpertInfo {
+        let (severity, summary) = match self {
+            TcpAnalysis::Retransmission => (
+                Severity::Note,
+                "This frame is a (suspected) retransmission".to_string(),
+            ),
+            TcpAnalysis::FastRetransmission => (
+                Severity::Note,
+                "This frame is a (suspected) fast retransmission".to_string(),
+            ),
+            TcpAnalysis::SpuriousRetransmission => (
+                Severity::Note,
+                "This frame is a (suspected) spurious retransmission".to_string(),
+            ),
+            TcpAnalysis::OutOfOrder => (
+                Severity::Warning,
+                "This frame is a (suspected) out-of-order segment".to_string(),
+            ),
+            TcpAnalysis::DuplicateAck { num, .. } => {
+                (Severity::Note, format!("Duplicate ACK (#{})", num))
+            }
+            TcpAnalysis::ZeroWindow => (Severity::Warning, "TCP Zero Window segment".to_string()),
+            TcpAnalysis::WindowFull => (
+                Severity::Warning,
+                "TCP window specified by the receiver is now completely full".to_string(),
+            ),
+            TcpAnalysis::KeepAlive => (Severity::Note, "TCP keep-alive segment".to_string()),
+            TcpAnalysis::LostSegment => (
+                Severity::Warning,
+                "Previous segment(s) not captured (common at capture start)".to_string(),
+            ),
+            TcpAnalysis::AckedUnseen => (
+                Severity::Warning,
+                "ACKed segment that wasn't captured (common at capture start)".to_string(),
+            ),
+        };
+        ExpertInfo::new(severity, "Sequence", "TCP", summary)
+    }
+}
+
+/// Where a segment stands in its connection, known for every segment but those an ICMP error
+/// quotes
+#[derive(Clone, Debug)]
+struct SeqAck {
+    /// Index of the connection, in order of the first segment of each in the capture
+    stream: usize,
+    /// Initial sequence number of the sender, which sequence numbers are shown relative to
+    base_seq: u32,
+    /// Initial sequence number of the receiver, for acknowledgment numbers
+    base_ack: Option<u32>,
+    /// Frame whose acknowledgment covers the segment, filled in once it turns up
+    acked_in: Option<Rc<Cell<Option<usize>>>>,
+    /// Frame of the segment this one acknowledges, and nanoseconds since it was sent
+    acks: Option<(usize, i128)>,
+    /// Nanoseconds from the SYN to the ACK that completes the handshake, shared by every
+    ///   segment of the connection
+    irtt: Rc<Cell<Option<i128>>>,
+    /// Fractional digits the capture's timestamps resolve
+    digits: u8,
+}
+
+/// Whether sequence number `a` comes after `b`. Sequence numbers wrap around, so they are
+/// compared by their distance.
+fn seq_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+fn option_name(kind: u8) -> &'static str {
+    match kind {
+        0 => "End of Option List (EOL)",
+        1 => "No-Operation (NOP)",
+        2 => "Maximum segment size",
+        3 => "Window scale",
+        4 => "SACK permitted",
+        5 => "SACK",
+        8 => "Timestamps",
+        19 => "MD5 signature",
+        28 => "User Timeout",
+        29 => "TCP Authentication Option",
+        30 => "Multipath TCP",
+        34 => "TCP Fast Open Cookie",
+        253 | 254 => "Experimental",
+        _ => "Unknown",
+    }
+}
+
+fn mptcp_subtype_name(subtype: u8) -> &'static str {
+    match subtype {
+        0 => "Multipath Capable",
+        1 => "Join Connection",
+        2 => "Data Sequence Signal",
+        3 => "Add Address",
+        4 => "Remove Address",
+        5 => "Change Subflow Priority",
+        6 => "Fallback",
+        7 => "Fast Close",
+        8 => "Subflow Reset",
+        _ => "Unknown",
+    }
+}
+
+/// What a particular option carries beyond its kind and length
+#[derive(Clone, Debug)]
+enum TcpOptionKind {
+    EndOfList,
+    Nop,
+    Mss(u16),
+    WindowScale(u8),
+    SackPermitted,
+    /// Left and right edge of each block
+    Sack(Vec<(u32, u32)>),
+    Timestamps {
+        tsval: u32,
+        tsecr: u32,
+    },
+    /// The cookie, which is empty in a cookie request
+    FastOpen(Vec<u8>),
+    Mptcp {
+        subtype: u8,
+    },
+    Other(u8),
+}
+
+#[derive(Clone, Debug)]
+struct TcpOption {
+    /// Offset of the option in the packet
+    offset: usize,
+    length: usize,
+    kind: TcpOptionKind,
+}
+
+impl TcpOption {
+    fn kind_num(&self) -> u8 {
+        match &self.kind {
+            TcpOptionKind::EndOfList => 0,
+            TcpOptionKind::Nop => 1,
+            TcpOptionKind::Mss(_) => 2,
+            TcpOptionKind::WindowScale(_) => 3,
+            TcpOptionKind::SackPermitted => 4,
+            TcpOptionKind::Sack(_) => 5,
+            TcpOptionKind::Timestamps { .. } => 8,
+            TcpOptionKind::FastOpen(_) => 34,
+            TcpOptionKind::Mptcp { .. } => 30,
+            TcpOptionKind::Other(kind) => *kind,
+        }
+    }
+
+    /// Short form for the segment summary, as in "MSS=1460"
+    fn summary(&self) -> Option<String> {
+        match &self.kind {
+            TcpOptionKind::Mss(mss) => Some(format!("MSS={}", mss)),
+            TcpOptionKind::WindowScale(shift) => Some(format!(
+                "WS={}",
+                1u32.checked_shl(*shift as u32).unwrap_or(0)
+            )),
+            TcpOptionKind::SackPermitted => Some("SACK_PERM".to_string()),
+            TcpOptionKind::Sack(blocks) => Some(
+                blocks
+                    .iter()
+                    .map(|(left, right)| format!("SLE={} SRE={}", left, right))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            TcpOptionKind::Timestamps { tsval, tsecr } => {
+                Some(format!("TSval={} TSecr={}", tsval, tsecr))
+            }
+            TcpOptionKind::FastOpen(cookie) if cookie.is_empty() => Some("TFO=R".to_string()),
+            TcpOptionKind::FastOpen(_) => Some("TFO=C".to_string()),
+            _ => None,
+        }
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let name = option_name(self.kind_num());
+        let mut children = vec![];
+        if self.length > 1 {
+            children.push(ProtoItem::new_leaf(
+                format!("Kind: {} ({})", name, self.kind_num()),
+                off,
+                1,
+            ));
+            children.push(ProtoItem::new_leaf(
+                format!("Length: {}", self.length),
+                off + 1,
+                1,
+            ));
+        }
+        let text = match &self.kind {
+            TcpOptionKind::EndOfList | TcpOptionKind::Nop | TcpOptionKind::Other(_) => {
+                format!("TCP Option - {}", name)
+            }
+            TcpOptionKind::Mss(mss) => {
+                children.push(ProtoItem::new_leaf(
+                    format!("MSS Value: {}", mss),
+                    off + 2,
+                    2,
+                ));
+                format!("TCP Option - Maximum segment size: {} bytes", mss)
+            }
+            TcpOptionKind::WindowScale(shift) => {
+                let multiplier = match 1u32.checked_shl(*shift as u32) {
+                    Some(multiplier) if *shift <= 14 => multiplier.to_string(),
+                    _ => "invalid, exceeds 14".to_string(),
+                };
+                children.push(ProtoItem::new_leaf(
+                    format!("Shift count: {}", shift),
+                    off + 2,
+                    1,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("[Multiplier: {}]", multiplier),
+                    0,
+                    0,
+                ));
+                format!(
+                    "TCP Option - Window scale: {} (multiply by {})",
+                    shift, multiplier
+                )
+            }
+            TcpOptionKind::SackPermitted => "TCP Option - SACK permitted".to_string(),
+            TcpOptionKind::Sack(blocks) => {
+                for (i, (left, right)) in blocks.iter().enumerate() {
+                    children.push(ProtoItem::new_leaf(
+                        format!("left edge = {}", left),
+                        off + 2 + 8 * i,
+                        4,
+                    ));
+                    children.push(ProtoItem::new_leaf(
+                        format!("right edge = {}", right),
+                        off + 6 + 8 * i,
+                        4,
+                    ));
+                }
+                children.push(ProtoItem::new_leaf(
+                    format!("[TCP SACK Count: {}]", blocks.len()),
+                    0,
+                    0,
+                ));
+                let edges: Vec<String> = blocks
+                    .iter()
+                    .map(|(left, right)| format!("{}-{}", left, right))
+                    .collect();
+                format!("TCP Option - SACK {}", edges.join(" "))
+            }
+            TcpOptionKind::Timestamps { tsval, tsecr } => {
+                children.push(ProtoItem::new_leaf(
+                    format!("Timestamp value: {}", tsval),
+                    off + 2,
+                    4,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Timestamp echo reply: {}", tsecr),
+                    off + 6,
+                    4,
+                ));
+                format!("TCP Option - Timestamps: TSval {}, TSecr {}", tsval, tsecr)
+            }
+            TcpOptionKind::FastOpen(cookie) if cookie.is_empty() => {
+                "TCP Option - TFO=R".to_string()
+            }
+            TcpOptionKind::FastOpen(cookie) => {
+                let hex: String = cookie.iter().map(|b| format!("{:02x}", b)).collect();
+                children.push(ProtoItem::new_leaf(
+                    format!("Fast Open Cookie: {}", hex),
+                    off + 2,
+                    cookie.len(),
+                ));
+                "TCP Option - TFO=C".to_string()
+            }
+            TcpOptionKind::Mptcp { subtype } => {
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "Multipath TCP subtype: {} ({})",
+                        mptcp_subtype_name(*subtype),
+                        subtype
+                    ),
+                    off + 2,
+                    1,
+                ));
+                format!(
+                    "TCP Option - Multipath TCP: {}",
+                    mptcp_subtype_name(*subtype)
+                )
+            }
+        };
+        ProtoItem::new(text, off, self.length, children)
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![("tcp.option_kind", self.kind_num().into())];
+        match &self.kind {
+            TcpOptionKind::Mss(mss) => fields.push(("tcp.options.mss_val", (*mss).into())),
+            TcpOptionKind::WindowScale(shift) => {
+                fields.push(("tcp.options.wscale.shift", (*shift).into()));
+                if let Some(multiplier) = 1u32.checked_shl(*shift as u32) {
+                    fields.push(("tcp.options.wscale.multiplier", multiplier.into()));
+                }
+            }
+            TcpOptionKind::SackPermitted => fields.push(("tcp.options.sack_perm", true.into())),
+            TcpOptionKind::Sack(blocks) => {
+                for (left, right) in blocks {
+                    fields.push(("tcp.options.sack_le", (*left).into()));
+                    fields.push(("tcp.options.sack_re", (*right).into()));
+                }
+                fields.push(("tcp.options.sack.count", blocks.len().into()));
+            }
+            TcpOptionKind::Timestamps { tsval, tsecr } => {
+                fields.push(("tcp.options.timestamp.tsval", (*tsval).into()));
+                fields.push(("tcp.options.timestamp.tsecr", (*tsecr).into()));
+            }
+            TcpOptionKind::FastOpen(cookie) if cookie.is_empty() => {
+                fields.push(("tcp.options.tfo.request", true.into()))
+            }
+            TcpOptionKind::FastOpen(cookie) => {
+                fields.push(("tcp.options.tfo.cookie", FieldValue::Bytes(cookie.clone())))
+            }
+            TcpOptionKind::Mptcp { subtype } => {
+                fields.push(("tcp.options.mptcp.subtype", (*subtype).into()))
+            }
+            _ => {}
+        }
+        fields
+    }
+}
+
+/// Decodes the options in `bytes`, which start at offset `offset` in the packet. Decoding
+/// stops at the first option whose length doesn't make sense, which is reported alongside the
+/// options before it.
+fn parse_options(offset: usize, bytes: &[u8]) -> (Vec<TcpOption>, Option<String>) {
+    let mut options = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let kind = bytes[pos];
+        let option_offset = offset + pos;
+        match kind {
+            0 | 1 => {
+                options.push(TcpOption {
+                    offset: option_offset,
+                    length: 1,
+                    kind: if kind == 0 {
+                        TcpOptionKind::EndOfList
+                    } else {
+                        TcpOptionKind::Nop
+                    },
+                });
+                pos += 1;
+                // Whatever follows the end of the list is padding
+                if kind == 0 {
+                    break;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        if pos + 1 >= bytes.len() {
+            return (
+                options,
+                Some(format!("{} option has no length", option_name(kind))),
+            );
+        }
+        let length = bytes[pos + 1] as usize;
+        if length < 2 || pos + length > bytes.len() {
+            return (
+                options,
+                Some(format!(
+                    "{} option length {} is invalid, {} bytes of options remain",
+                    option_name(kind),
+                    length,
+                    bytes.len() - pos
+                )),
+            );
+        }
+        let value = &bytes[pos + 2..pos + length];
+        let expected_len = match kind {
+            2 => Some(4),
+            3 => Some(3),
+            4 => Some(2),
+            8 => Some(10),
+            _ => None,
+        };
+        let bad_sack = kind == 5 && !value.len().is_multiple_of(8);
+        if expected_len.is_some_and(|expected| expected != length) || bad_sack {
+            return (
+                options,
+                Some(format!(
+                    "{} option length {} is invalid",
+                    option_name(kind),
+                    length
+                )),
+            );
+        }
+
+        let option_kind = match kind {
+            2 => TcpOptionKind::Mss(util::two_bytes_to_u16(value)),
+            3 => TcpOptionKind::WindowScale(value[0]),
+            4 => TcpOptionKind::SackPermitted,
+            5 => TcpOptionKind::Sack(
+                value
+                    .chunks(8)
+                    .map(|block| {
+                        (
+                            util::four_bytes_to_u32(&block[0..4]),
+                            util::four_bytes_to_u32(&block[4..8]),
+                        )
+                    })
+                    .collect(),
+            ),
+            8 => TcpOptionKind::Timestamps {
+                tsval: util::four_bytes_to_u32(&value[0..4]),
+                tsecr: util::four_bytes_to_u32(&value[4..8]),
+            },
+            30 if !value.is_empty() => TcpOptionKind::Mptcp {
+                subtype: value[0] >> 4,
+            },
+            34 => TcpOptionKind::FastOpen(value.to_vec()),
+            _ => TcpOptionKind::Other(kind),
+        };
+        options.push(TcpOption {
+            offset: option_offset,
+            length,
+            kind: option_kind,
+        });
+        pos += length;
+    }
+    (options, None)
+}
 
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub struct Tcp {
+    /// Offset of the header in the packet
+    offset: usize,
     source_port: u16,
     dest_port: u16,
     sequence_num: u32,
     ack_num: u32,
     header_len: u8,
-    resrv_0: u8,
-    flags: u8,
+    /// The 12 bits after the header length, reserved bits included
+    flags: u16,
     window_size: u16,
     tcp_xsum: u16,
+    xsum_status: ChecksumStatus,
     urg_ptr: u16,
+    options: Vec<TcpOption>,
+    /// Why option decoding stopped early, if it did
+    options_error: Option<String>,
+    payload_len: usize,
+    /// Only the ports and sequence number are known, as when an ICMP error quotes the first
+    ///   8 bytes of a segment
+    quoted: bool,
+    /// PDUs this segment completed that span earlier segments
+    reassembly: Option<Reassembly>,
+    /// When the payload ends partway through a PDU: the frame that completes it, filled in once
+    ///   that happens, and how many more bytes it needed when this segment arrived
+    pdu_continues: Option<(Rc<Cell<Option<usize>>>, usize)>,
+    /// What the sequence and acknowledgment analysis noticed
+    analysis: Vec<TcpAnalysis>,
+    seq_ack: Option<SeqAck>,
 }
 
-#[allow(dead_code)]
+/// Bytes of a segment an ICMP error is guaranteed to quote
+const QUOTED_LEN: usize = 8;
+
 impl Tcp {
     pub fn new() -> Self {
         Tcp {
+            offset: 0,
             source_port: 0,
             dest_port: 0,
             sequence_num: 0,
             ack_num: 0,
             header_len: 0,
-            resrv_0: 0,
             flags: 0,
             window_size: 0,
             tcp_xsum: 0,
+            xsum_status: ChecksumStatus::Unverified,
             urg_ptr: 0,
+            options: vec![],
+            options_error: None,
+            payload_len: 0,
+            quoted: false,
+            reassembly: None,
+            pdu_continues: None,
+            analysis: vec![],
+            seq_ack: None,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn to_tree_item_verbose<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new(self.to_string(), vec![])
-            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-    }
-
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
-        //TreeItem::new_leaf(self.to_string())
-        //    .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-        TreeItem::new(self.to_string(), vec![])
-            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
-    }
-
-    pub fn from_bytes(next_byte: usize, bytes: &[u8]) -> (Self, usize, LayerHint) {
-        assert!(bytes.len() >= 20);
+    /// Decodes the header and its options. `bytes` runs to the end of the IP payload, so
+    /// whatever follows the header is the segment's payload, and the checksum is verified
+    /// against the pseudo-header built from the addresses in `ctx`.
+    pub fn from_bytes(
+        ctx: &DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated("TCP", HEADER_LEN, bytes.len()));
+        }
         let source_port = util::two_bytes_to_u16(&bytes[0..2]);
         let dest_port = util::two_bytes_to_u16(&bytes[2..4]);
         let sequence_num = util::four_bytes_to_u32(&bytes[4..8]);
         let ack_num = util::four_bytes_to_u32(&bytes[8..12]);
 
         let header_len = (bytes[12] & 0xf0u8) >> 4;
-        let resrv_0 = bytes[12] & 0x0fu8;
+        if header_len < 5 {
+            return Err(DissectError::new(format!(
+                "bogus TCP header length ({} bytes, must be at least 20)",
+                4 * header_len
+            )));
+        }
+        let header_bytes = 4 * header_len as usize;
+        if bytes.len() < header_bytes {
+            return Err(DissectError::truncated("TCP", header_bytes, bytes.len()));
+        }
 
-        let flags = bytes[13];
+        let flags = util::two_bytes_to_u16(&bytes[12..14]) & 0x0fff;
         let window_size = util::two_bytes_to_u16(&bytes[14..16]);
         let tcp_xsum = util::two_bytes_to_u16(&bytes[16..18]);
         let urg_ptr = util::two_bytes_to_u16(&bytes[18..20]);
+        let (options, options_error) =
+            parse_options(next_byte + HEADER_LEN, &bytes[HEADER_LEN..header_bytes]);
+
+        // The checksum covers the whole segment, which the IP layer bounds `bytes` to only
+        //   when it was captured in full
+        let whole_segment = ctx.payload_end == Some(next_byte + bytes.len());
+        let xsum_status = match (ctx.net_src, ctx.net_dst) {
+            _ if !whole_segment || ctx.in_error_packet => ChecksumStatus::Unverified,
+            _ if !ctx.registry.prefs.validate_checksums => ChecksumStatus::Offloaded,
+            (Some(src), Some(dst)) => {
+                let pseudo = util::pseudo_header(src, dst, 6, bytes.len());
+                match util::internet_checksum(&[&pseudo, bytes]) {
+                    0 => ChecksumStatus::Correct,
+                    _ => ChecksumStatus::Incorrect(util::internet_checksum(&[
+                        &pseudo,
+                        &bytes[0..16],
+                        &bytes[18..],
+                    ])),
+                }
+            }
+            _ => ChecksumStatus::Unverified,
+        };
 
-        // TODO: Parse options:
         let tcp_layer = Tcp {
+            offset: next_byte,
             source_port,
             dest_port,
             sequence_num,
             ack_num,
             header_len,
-            resrv_0,
             flags,
             window_size,
             tcp_xsum,
+            xsum_status,
             urg_ptr,
+            options,
+            options_error,
+            payload_len: bytes.len() - header_bytes,
+            quoted: false,
+            reassembly: None,
+            pdu_continues: None,
+            analysis: vec![],
+            seq_ack: None,
+        };
+
+        let ret_next_byte = next_byte + header_bytes;
+        let next_layer = NextLayer::Ports(Table::TcpPort, source_port, dest_port);
+
+        Ok((tcp_layer, ret_next_byte, next_layer))
+    }
+
+    /// Decodes what an ICMP error quotes of a segment when that's less than a full header
+    pub fn from_quoted_bytes(
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < QUOTED_LEN {
+            return Err(DissectError::truncated("TCP", QUOTED_LEN, bytes.len()));
+        }
+        let tcp_layer = Tcp {
+            offset: next_byte,
+            source_port: util::two_bytes_to_u16(&bytes[0..2]),
+            dest_port: util::two_bytes_to_u16(&bytes[2..4]),
+            sequence_num: util::four_bytes_to_u32(&bytes[4..8]),
+            quoted: true,
+            ..Tcp::new()
         };
+        Ok((tcp_layer, next_byte + QUOTED_LEN, NextLayer::Undecoded))
+    }
+
+    fn header_bytes(&self) -> usize {
+        4 * self.header_len as usize
+    }
+
+    pub fn has_flags(&self, mask: u16) -> bool {
+        self.flags & mask == mask
+    }
+
+    pub fn ports(&self) -> (u16, u16) {
+        (self.source_port, self.dest_port)
+    }
+
+    /// Index of the connection the segment belongs to, the `tcp.stream` field. Segments an
+    /// ICMP error quotes belong to none.
+    pub fn stream(&self) -> Option<usize> {
+        self.seq_ack.as_ref().map(|seq_ack| seq_ack.stream)
+    }
+
+    /// Whether the analysis found the segment leaving a gap in the sequence space or filling
+    /// one, so the segments around it may only be out of order rather than missing
+    pub fn may_be_reordered(&self) -> bool {
+        self.analysis
+            .iter()
+            .any(|a| matches!(a, TcpAnalysis::LostSegment | TcpAnalysis::OutOfOrder))
+    }
+
+    /// Sequence number of the first payload byte, which follows the one a SYN takes up
+    pub fn payload_seq(&self) -> u32 {
+        if self.has_flags(FLAG_SYN) {
+            self.sequence_num.wrapping_add(1)
+        } else {
+            self.sequence_num
+        }
+    }
+
+    /// Sequence space the segment takes up: its payload, plus one for each of SYN and FIN
+    fn segment_len(&self) -> u32 {
+        self.payload_len as u32 + self.has_flags(FLAG_SYN) as u32 + self.has_flags(FLAG_FIN) as u32
+    }
+
+    /// Sequence number relative to the sender's initial one, when that's known
+    fn relative_seq(&self) -> u32 {
+        match &self.seq_ack {
+            Some(seq_ack) => self.sequence_num.wrapping_sub(seq_ack.base_seq),
+            None => self.sequence_num,
+        }
+    }
+
+    /// Acknowledgment number relative to the receiver's initial sequence number
+    fn relative_ack(&self) -> u32 {
+        match self.seq_ack.as_ref().and_then(|s| s.base_ack) {
+            Some(base_ack) => self.ack_num.wrapping_sub(base_ack),
+            None => self.ack_num,
+        }
+    }
+
+    /// Shift count of the window scale option, which only SYNs carry
+    fn window_shift(&self) -> Option<u8> {
+        self.options.iter().find_map(|o| match o.kind {
+            TcpOptionKind::WindowScale(shift) => Some(shift.min(14)),
+            _ => None,
+        })
+    }
+
+    /// Where the payload lies in the bytes the segment was dissected from
+    pub fn payload_range(&self) -> Range<usize> {
+        let start = self.offset + self.header_bytes();
+        start..start + self.payload_len
+    }
+
+    /// Frame the PDU this segment's payload ends in was reassembled in, when that's another one
+    fn reassembled_in(&self) -> Option<usize> {
+        let (link, _) = self.pdu_continues.as_ref()?;
+        link.get()
+    }
 
-        let calculated_header_len = tcp_layer.header_len * 4;
-        let ret_next_byte = next_byte + calculated_header_len as usize;
-        let layer_hint = LayerHint::Undecoded;
+    /// Whether the whole payload went to PDUs that get dissected in other frames
+    fn only_continues_pdu(&self) -> bool {
+        self.pdu_continues.is_some() && self.reassembly.is_none()
+    }
+
+    /// Names of the flags that are set, lowest bit first as in "SYN, ACK"
+    fn flag_names(&self) -> String {
+        let names: Vec<&str> = FLAG_BITS
+            .iter()
+            .rev()
+            .filter(|(mask, ..)| self.flags & mask != 0)
+            .map(|(_, abbrev, ..)| *abbrev)
+            .collect();
+        if names.is_empty() {
+            "<None>".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+
+    /// One character per flag bit, reserved bits first, as in "·······AP···"
+    fn flag_string(&self) -> String {
+        "RRRNCEUAPRSF"
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if self.flags & (0x800 >> i) != 0 {
+                    c
+                } else {
+                    '\u{b7}'
+                }
+            })
+            .collect()
+    }
+
+    fn flags_to_proto_item(&self) -> ProtoItem {
+        let off = self.offset + 12;
+        let bit = |mask: u16, name: &str| {
+            ProtoItem::new_leaf(
+                format!(
+                    "{} = {}: {}",
+                    util::bit_pattern(self.flags as u32, mask as u32, 12),
+                    name,
+                    if self.flags & mask != 0 {
+                        "Set"
+                    } else {
+                        "Not set"
+                    }
+                ),
+                off,
+                2,
+            )
+        };
+        let mut children = vec![bit(RESERVED_FLAGS, "Reserved")];
+        children.extend(FLAG_BITS.iter().map(|(mask, _, _, name)| bit(*mask, name)));
+        children.push(ProtoItem::new_leaf(
+            format!("[TCP Flags: {}]", self.flag_string()),
+            0,
+            0,
+        ));
+        ProtoItem::new(
+            format!("Flags: {:#05x} ({})", self.flags, self.flag_names()),
+            off,
+            2,
+            children,
+        )
+    }
 
-        (tcp_layer, ret_next_byte, layer_hint)
+    /// The "SEQ/ACK analysis" subtree, when there is anything to put in it
+    fn analysis_to_proto_item(&self) -> Option<ProtoItem> {
+        let mut children = vec![];
+        if let Some(seq_ack) = &self.seq_ack {
+            if let Some(frame) = seq_ack.acked_in.as_ref().and_then(|link| link.get()) {
+                children.push(ProtoItem::new_leaf(
+                    format!("[ACKed in frame: {}]", frame),
+                    0,
+                    0,
+                ));
+            }
+            if let Some((frame, rtt)) = seq_ack.acks {
+                children.push(ProtoItem::new_leaf(
+                    format!("[This is an ACK to the segment in frame: {}]", frame),
+                    0,
+                    0,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "[The RTT to ACK the segment was: {} seconds]",
+                        timestamp::format_interval(rtt, seq_ack.digits)
+                    ),
+                    0,
+                    0,
+                ));
+            }
+            if let Some(irtt) = seq_ack.irtt.get() {
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "[iRTT: {} seconds]",
+                        timestamp::format_interval(irtt, seq_ack.digits)
+                    ),
+                    0,
+                    0,
+                ));
+            }
+        }
+        for analysis in &self.analysis {
+            if let TcpAnalysis::DuplicateAck { num, original } = analysis {
+                children.push(ProtoItem::new_leaf(
+                    format!("[Duplicate ACK #: {}]", num),
+                    0,
+                    0,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("[Duplicate to the ACK in frame: {}]", original),
+                    0,
+                    0,
+                ));
+            }
+        }
+        if !self.analysis.is_empty() {
+            children.push(ProtoItem::new(
+                "[TCP Analysis Flags]",
+                0,
+                0,
+                self.analysis
+                    .iter()
+                    .map(|a| a.expert_info().to_proto_item())
+                    .collect(),
+            ));
+        }
+        if children.is_empty() {
+            return None;
+        }
+        Some(ProtoItem::new("[SEQ/ACK analysis]", 0, 0, children))
     }
 }
 
 impl fmt::Display for Tcp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.quoted {
+            return write!(
+                f,
+                "Transmission Control Protocol, Src Port: {}, Dst Port: {}, Seq: {}",
+                self.source_port, self.dest_port, self.sequence_num,
+            );
+        }
         write!(
             f,
-            "Transmission Control Protocol, Src Port: {}, Dst Port: {}, Seq: {}, Ack: {}, Len: xx",
-            self.source_port, self.dest_port, self.sequence_num, self.ack_num,
+            "Transmission Control Protocol, Src Port: {}, Dst Port: {}, Seq: {}, Ack: {}, Len: {}",
+            self.source_port,
+            self.dest_port,
+            self.relative_seq(),
+            self.relative_ack(),
+            self.payload_len,
         )
     }
 }
+
+impl ProtocolLayer for Tcp {
+    fn name(&self) -> &'static str {
+        "tcp"
+    }
+
+    fn info(&self) -> String {
+        let mut info: String = self.analysis.iter().map(|a| a.tag() + " ").collect();
+        info += &format!(
+            "{} \u{2192} {} [{}] Seq={}",
+            self.source_port,
+            self.dest_port,
+            self.flag_names(),
+            self.relative_seq()
+        );
+        if self.has_flags(FLAG_ACK) {
+            info += &format!(" Ack={}", self.relative_ack());
+        }
+        info += &format!(" Win={} Len={}", self.window_size, self.payload_len);
+        for summary in self.options.iter().filter_map(|o| o.summary()) {
+            info += " ";
+            info += &summary;
+        }
+        if self.only_continues_pdu() {
+            info += " [TCP segment of a reassembled PDU]";
+        }
+        info
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("tcp.srcport", self.source_port.into()),
+            ("tcp.dstport", self.dest_port.into()),
+            ("tcp.port", self.source_port.into()),
+            ("tcp.port", self.dest_port.into()),
+            ("tcp.seq", self.relative_seq().into()),
+            ("tcp.seq_raw", self.sequence_num.into()),
+        ];
+        if self.quoted {
+            return fields;
+        }
+        fields.extend([
+            ("tcp.len", self.payload_len.into()),
+            ("tcp.ack", self.relative_ack().into()),
+            ("tcp.ack_raw", self.ack_num.into()),
+            ("tcp.hdr_len", (4 * self.header_len).into()),
+            ("tcp.flags", self.flags.into()),
+            ("tcp.flags.res", (self.flags & RESERVED_FLAGS != 0).into()),
+            ("tcp.flags.str", self.flag_string().into()),
+            ("tcp.window_size", self.window_size.into()),
+            ("tcp.checksum", self.tcp_xsum.into()),
+            ("tcp.checksum.status", self.xsum_status.code().into()),
+            ("tcp.urgent_pointer", self.urg_ptr.into()),
+        ]);
+        fields.extend(
+            FLAG_BITS
+                .iter()
+                .map(|(mask, _, name, _)| (*name, (self.flags & mask != 0).into())),
+        );
+        for option in &self.options {
+            fields.extend(option.fields());
+        }
+        if let Some(reassembly) = &self.reassembly {
+            fields.extend(
+                reassembly
+                    .fragments
+                    .iter()
+                    .map(|f| ("tcp.segment", f.frame_num.into())),
+            );
+            fields.push(("tcp.segment.count", reassembly.fragments.len().into()));
+            fields.push(("tcp.reassembled.length", reassembly.bytes.len().into()));
+        }
+        if let Some(frame) = self.reassembled_in() {
+            fields.push(("tcp.reassembled_in", frame.into()));
+        }
+        if let Some(seq_ack) = &self.seq_ack {
+            fields.push(("tcp.stream", seq_ack.stream.into()));
+            let next_seq = self.relative_seq().wrapping_add(self.segment_len());
+            fields.push(("tcp.nxtseq", next_seq.into()));
+            if let Some((frame, _)) = seq_ack.acks {
+                fields.push(("tcp.analysis.acks_frame", frame.into()));
+            }
+            if let Some(frame) = seq_ack.acked_in.as_ref().and_then(|link| link.get()) {
+                fields.push(("tcp.analysis.acked_in", frame.into()));
+            }
+        }
+        if !self.analysis.is_empty() {
+            fields.push(("tcp.analysis.flags", true.into()));
+        }
+        for analysis in &self.analysis {
+            fields.push((analysis.field(), true.into()));
+            if let TcpAnalysis::DuplicateAck { num, original } = analysis {
+                fields.push(("tcp.analysis.duplicate_ack_num", (*num).into()));
+                fields.push(("tcp.analysis.duplicate_ack_frame", (*original).into()));
+            }
+        }
+        fields
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        if self.quoted {
+            return ProtoItem::new(
+                self.to_string(),
+                off,
+                QUOTED_LEN,
+                vec![
+                    ProtoItem::new_leaf(format!("Source Port: {}", self.source_port), off, 2),
+                    ProtoItem::new_leaf(
+                        format!("Destination Port: {}", self.dest_port),
+                        off + 2,
+                        2,
+                    ),
+                    ProtoItem::new_leaf(
+                        format!("Sequence Number: {}", self.sequence_num),
+                        off + 4,
+                        4,
+                    ),
+                ],
+            )
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow));
+        }
+
+        let mut children = vec![
+            ProtoItem::new_leaf(format!("Source Port: {}", self.source_port), off, 2),
+            ProtoItem::new_leaf(format!("Destination Port: {}", self.dest_port), off + 2, 2),
+        ];
+        if let Some(seq_ack) = &self.seq_ack {
+            children.push(ProtoItem::new_leaf(
+                format!("[Stream index: {}]", seq_ack.stream),
+                0,
+                0,
+            ));
+        }
+        children.push(ProtoItem::new_leaf(
+            format!("[TCP Segment Len: {}]", self.payload_len),
+            0,
+            0,
+        ));
+        match &self.seq_ack {
+            Some(seq_ack) => {
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "Sequence Number: {}    (relative sequence number)",
+                        self.relative_seq()
+                    ),
+                    off + 4,
+                    4,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Sequence Number (raw): {}", self.sequence_num),
+                    off + 4,
+                    4,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!(
+                        "[Next Sequence Number: {}    (relative sequence number)]",
+                        self.relative_seq().wrapping_add(self.segment_len())
+                    ),
+                    0,
+                    0,
+                ));
+                if seq_ack.base_ack.is_some() {
+                    children.push(ProtoItem::new_leaf(
+                        format!(
+                            "Acknowledgment Number: {}    (relative ack number)",
+                            self.relative_ack()
+                        ),
+                        off + 8,
+                        4,
+                    ));
+                }
+                children.push(ProtoItem::new_leaf(
+                    format!("Acknowledgment number (raw): {}", self.ack_num),
+                    off + 8,
+                    4,
+                ));
+            }
+            None => {
+                children.push(ProtoItem::new_leaf(
+                    format!("Sequence Number: {}", self.sequence_num),
+                    off + 4,
+                    4,
+                ));
+                children.push(ProtoItem::new_leaf(
+                    format!("Acknowledgment Number: {}", self.ack_num),
+                    off + 8,
+                    4,
+                ));
+            }
+        }
+        children.extend([
+            ProtoItem::new_leaf(
+                format!(
+                    "{} .... = Header Length: {} bytes ({})",
+                    util::bit_pattern(self.header_len as u32, 0xf, 4),
+                    self.header_bytes(),
+                    self.header_len
+                ),
+                off + 12,
+                1,
+            ),
+            self.flags_to_proto_item(),
+            ProtoItem::new_leaf(format!("Window: {}", self.window_size), off + 14, 2),
+            self.xsum_status
+                .to_proto_item("Checksum", self.tcp_xsum, off + 16),
+            ProtoItem::new_leaf(format!("Urgent Pointer: {}", self.urg_ptr), off + 18, 2),
+        ]);
+
+        let options_len = self.header_bytes() - HEADER_LEN;
+        if options_len > 0 {
+            let mut option_items: Vec<ProtoItem> =
+                self.options.iter().map(|o| o.to_proto_item()).collect();
+            if let Some(err) = &self.options_error {
+                option_items.push(
+                    ProtoItem::new_leaf(format!("[{}]", err), 0, 0)
+                        .style(Style::default().fg(Color::White).bg(Color::Red)),
+                );
+            }
+            let summaries: Vec<String> = self.options.iter().filter_map(|o| o.summary()).collect();
+            let text = if summaries.is_empty() {
+                format!("Options: ({} bytes)", options_len)
+            } else {
+                format!("Options: ({} bytes), {}", options_len, summaries.join(", "))
+            };
+            children.push(ProtoItem::new(
+                text,
+                off + HEADER_LEN,
+                options_len,
+                option_items,
+            ));
+        }
+        if self.payload_len > 0 {
+            children.push(ProtoItem::new_leaf(
+                format!("TCP payload ({} bytes)", self.payload_len),
+                off + self.header_bytes(),
+                self.payload_len,
+            ));
+        }
+        if let Some(reassembly) = &self.reassembly {
+            children.push(reassembly.to_proto_item("TCP", "Segment"));
+        }
+        match (self.reassembled_in(), &self.pdu_continues) {
+            (Some(frame), _) => children.push(ProtoItem::new_leaf(
+                format!("[Reassembled PDU in frame: {}]", frame),
+                0,
+                0,
+            )),
+            (None, Some((_, needed))) => children.push(ProtoItem::new_leaf(
+                format!("[Incomplete PDU, {} more bytes needed]", needed),
+                0,
+                0,
+            )),
+            _ => {}
+        }
+        if let Some(item) = self.analysis_to_proto_item() {
+            children.push(item);
+        }
+
+        ProtoItem::new(self.to_string(), off, self.header_bytes(), children)
+            .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        let mut infos: Vec<ExpertInfo> = self.xsum_status.expert_info("TCP").into_iter().collect();
+        if self.has_flags(FLAG_SYN) {
+            let summary = if self.has_flags(FLAG_ACK) {
+                "Connection establish acknowledge (SYN+ACK)"
+            } else {
+                "Connection establish request (SYN)"
+            };
+            infos.push(ExpertInfo::new(Severity::Chat, "Sequence", "TCP", summary));
+        }
+        if self.has_flags(FLAG_FIN) {
+            infos.push(ExpertInfo::new(
+                Severity::Chat,
+                "Sequence",
+                "TCP",
+                "Connection finish (FIN)",
+            ));
+        }
+        infos.extend(self.analysis.iter().map(|a| a.expert_info()));
+        infos
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A connection's two endpoints, lower one first, so both directions share a key
+type ConnectionKey = ((IpAddr, u16), (IpAddr, u16));
+
+/// A segment waiting for its acknowledgment
+struct Unacked {
+    /// Sequence number just past the segment
+    end: u32,
+    frame_num: usize,
+    timestamp: Timestamp,
+    acked_in: Rc<Cell<Option<usize>>>,
+}
+
+/// What the analysis remembers about the segments one endpoint has sent
+#[derive(Default)]
+struct Flow {
+    /// Initial sequence number, from the SYN, or else taken to be just before the first segment
+    base_seq: Option<u32>,
+    sent_syn: bool,
+    /// Sequence number just past the furthest the endpoint has sent
+    next_seq: Option<u32>,
+    /// Sequence ranges skipped by a segment that started past `next_seq`. Segments that later
+    ///   fall in one arrived out of order rather than being retransmitted.
+    holes: Vec<(u32, u32)>,
+    /// Last acknowledgment number and window sent, and the frame that first sent them
+    last_ack: Option<(u32, u16, usize)>,
+    /// How many times `last_ack` has been repeated since
+    dup_acks: u32,
+    /// Window scale shift count from the endpoint's SYN
+    window_shift: Option<u8>,
+    /// Segments the other endpoint hasn't acknowledged yet, in capture order
+    unacked: Vec<Unacked>,
+}
+
+/// What's kept about a connection across the capture
+#[derive(Default)]
+struct Connection {
+    /// Index of the connection, in order of the first segment of each in the capture
+    index: usize,
+    /// Byte stream sent by each endpoint, in the order of the key
+    streams: [StreamBuffer; 2],
+    /// Analysis state of each endpoint, in the same order
+    flows: [Flow; 2],
+    /// Endpoint that sent the first SYN, and when
+    syn: Option<(usize, Timestamp)>,
+    irtt: Rc<Cell<Option<i128>>>,
+    /// Whether either endpoint has sent a FIN or RST
+    closed: bool,
+}
+
+impl Connection {
+    fn new(index: usize) -> Self {
+        Connection {
+            index,
+            ..Default::default()
+        }
+    }
+
+    /// Whether `segment`, sent by endpoint `direction`, opens a new connection on the same
+    /// addresses and ports: a SYN after this one was closed, or with another initial sequence
+    /// number
+    fn is_reused_by(&self, direction: usize, segment: &Tcp) -> bool {
+        if !segment.has_flags(FLAG_SYN) || segment.has_flags(FLAG_ACK) {
+            return false;
+        }
+        self.closed
+            || self.flows[direction]
+                .base_seq
+                .is_some_and(|isn| isn != segment.sequence_num)
+    }
+
+    /// State of endpoint `direction`, then of the other one
+    fn flows(&mut self, direction: usize) -> (&mut Flow, &mut Flow) {
+        let [first, second] = &mut self.flows;
+        if direction == 0 {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+
+    /// Places `segment`, sent at `timestamp` by endpoint `direction`, in the connection,
+    /// learning the initial sequence numbers and the handshake's round trip time from it
+    fn seq_ack(&mut self, direction: usize, segment: &Tcp, timestamp: Timestamp) -> SeqAck {
+        if segment.flags & (FLAG_FIN | FLAG_RST) != 0 {
+            self.closed = true;
+        }
+        let (flow, peer) = self.flows(direction);
+        let syn = segment.has_flags(FLAG_SYN);
+        let ack = segment.has_flags(FLAG_ACK);
+        if syn {
+            flow.base_seq = Some(segment.sequence_num);
+            flow.sent_syn = true;
+        }
+        let base_seq = *flow
+            .base_seq
+            .get_or_insert(segment.sequence_num.wrapping_sub(1));
+        let base_ack = if ack {
+            Some(*peer.base_seq.get_or_insert(segment.ack_num.wrapping_sub(1)))
+        } else {
+            None
+        };
+        let peer_sent_syn = peer.sent_syn;
+
+        // The handshake is over once the SYN's sender acknowledges the SYN/ACK
+        match self.syn {
+            None if syn && !ack => self.syn = Some((direction, timestamp)),
+            Some((syn_direction, syn_time))
+                if syn_direction == direction
+                    && ack
+                    && !syn
+                    && peer_sent_syn
+                    && self.irtt.get().is_none() =>
+            {
+                self.irtt.set(Some(timestamp.nanos_since(&syn_time)))
+            }
+            _ => {}
+        }
+
+        SeqAck {
+            stream: self.index,
+            base_seq,
+            base_ack,
+            acked_in: None,
+            acks: None,
+            irtt: self.irtt.clone(),
+            digits: timestamp.digits,
+        }
+    }
+
+    /// Checks `segment`, sent in frame `frame_num` at `timestamp` by endpoint `direction`,
+    /// against what both endpoints sent before it, and updates the sender's state
+    fn analyze(
+        &mut self,
+        direction: usize,
+        segment: &mut Tcp,
+        frame_num: usize,
+        timestamp: Timestamp,
+    ) {
+        let (flow, peer) = self.flows(direction);
+        if segment.has_flags(FLAG_SYN) {
+            flow.window_shift = segment.window_shift();
+        }
+        // Windows are only scaled once both ends have agreed to it
+        let peer_shift = match (flow.window_shift, peer.window_shift) {
+            (Some(_), Some(shift)) => shift,
+            _ => 0,
+        };
+
+        let mut analysis = vec![];
+        let seq = segment.sequence_num;
+        let seg_len = segment.segment_len();
+        let end = seq.wrapping_add(seg_len);
+        let control = segment.flags & (FLAG_SYN | FLAG_FIN | FLAG_RST) != 0;
+
+        let keep_alive = seg_len <= 1 && !control && flow.next_seq == Some(seq.wrapping_add(1));
+        if keep_alive {
+            analysis.push(TcpAnalysis::KeepAlive);
+        }
+        if segment.window_size == 0 && !control {
+            analysis.push(TcpAnalysis::ZeroWindow);
+        }
+        if let Some((ack, window, _)) = peer.last_ack {
+            let limit = ack.wrapping_add((window as u32) << peer_shift);
+            let payload_end = seq.wrapping_add(segment.payload_len as u32);
+            if segment.payload_len > 0 && !keep_alive && payload_end == limit {
+                analysis.push(TcpAnalysis::WindowFull);
+            }
+        }
+
+        if seg_len > 0 && !keep_alive {
+            match flow.next_seq {
+                Some(next) if seq_after(seq, next) => {
+                    analysis.push(TcpAnalysis::LostSegment);
+                    flow.holes.push((next, seq));
+                }
+                Some(next) if seq_after(next, seq) => {
+                    let hole = flow
+                        .holes
+                        .iter()
+                        .position(|&(start, stop)| !seq_after(start, seq) && seq_after(stop, seq));
+                    if let Some(idx) = hole {
+                        analysis.push(TcpAnalysis::OutOfOrder);
+                        // Whatever the segment filled in is no longer missing
+                        let (start, stop) = flow.holes.remove(idx);
+                        if seq_after(seq, start) {
+                            flow.holes.push((start, seq));
+                        }
+                        if seq_after(stop, end) {
+                            flow.holes.push((end, stop));
+                        }
+                    } else {
+                        analysis.push(match peer.last_ack {
+                            Some((ack, ..)) if !seq_after(end, ack) => {
+                                TcpAnalysis::SpuriousRetransmission
+                            }
+                            Some((ack, ..)) if ack == seq && peer.dup_acks >= 2 => {
+                                TcpAnalysis::FastRetransmission
+                            }
+                            _ => TcpAnalysis::Retransmission,
+                        });
+                    }
+                }
+                _ => {}
+            }
+            if flow.next_seq.is_none_or(|next| seq_after(end, next)) {
+                flow.next_seq = Some(end);
+            }
+            let acked_in = Rc::new(Cell::new(None));
+            flow.unacked.push(Unacked {
+                end,
+                frame_num,
+                timestamp,
+                acked_in: acked_in.clone(),
+            });
+            if let Some(seq_ack) = &mut segment.seq_ack {
+                seq_ack.acked_in = Some(acked_in);
+            }
+        } else if !segment.has_flags(FLAG_RST) {
+            flow.next_seq.get_or_insert(seq);
+        }
+
+        if segment.has_flags(FLAG_ACK) {
+            let ack = segment.ack_num;
+            if peer.next_seq.is_some_and(|next| seq_after(ack, next)) {
+                analysis.push(TcpAnalysis::AckedUnseen);
+            }
+            // The ACK is reported against the furthest segment it covers, the latest sent of
+            //   any that end at the same place
+            let mut acks: Option<(u32, usize, Timestamp)> = None;
+            peer.unacked.retain(|unacked| {
+                if seq_after(unacked.end, ack) {
+                    return true;
+                }
+                unacked.acked_in.set(Some(frame_num));
+                if acks.is_none_or(|(end, ..)| !seq_after(end, unacked.end)) {
+                    acks = Some((unacked.end, unacked.frame_num, unacked.timestamp));
+                }
+                false
+            });
+            if let Some(seq_ack) = &mut segment.seq_ack {
+                seq_ack.acks = acks.map(|(_, frame, sent)| (frame, timestamp.nanos_since(&sent)));
+            }
+            match flow.last_ack {
+                Some((last, window, original))
+                    if seg_len == 0
+                        && !control
+                        && !keep_alive
+                        && last == ack
+                        && window == segment.window_size =>
+                {
+                    flow.dup_acks += 1;
+                    analysis.push(TcpAnalysis::DuplicateAck {
+                        num: flow.dup_acks,
+                        original,
+                    });
+                }
+                _ => {
+                    flow.last_ack = Some((ack, segment.window_size, frame_num));
+                    flow.dup_acks = 0;
+                }
+            }
+        }
+        segment.analysis = analysis;
+    }
+}
+
+pub struct TcpDissector {
+    connections: RefCell<HashMap<ConnectionKey, Connection>>,
+    /// Index the next connection gets. Connections reusing a key replace the old one in
+    ///   `connections`, so its length doesn't count them all.
+    next_index: Cell<usize>,
+}
+
+impl TcpDissector {
+    pub fn new() -> Self {
+        TcpDissector {
+            connections: RefCell::new(HashMap::new()),
+            next_index: Cell::new(0),
+        }
+    }
+}
+
+impl Dissector for TcpDissector {
+    fn name(&self) -> &'static str {
+        "tcp"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let offset = next_byte;
+        let (mut layer, next_byte, next_layer) = if ctx.in_error_packet && bytes.len() < HEADER_LEN
+        {
+            Tcp::from_quoted_bytes(next_byte, bytes)?
+        } else {
+            Tcp::from_bytes(ctx, next_byte, bytes)?
+        };
+        ctx.src_port = layer.source_port;
+        ctx.dst_port = layer.dest_port;
+        let (src, dst) = match (ctx.net_src, ctx.net_dst) {
+            (Some(src), Some(dst)) if !layer.quoted && !ctx.in_error_packet => (src, dst),
+            _ => return Ok(Dissection::new(layer, next_byte, next_layer)),
+        };
+
+        let from = (src, layer.source_port);
+        let to = (dst, layer.dest_port);
+        let (key, direction) = if from <= to {
+            ((from, to), 0)
+        } else {
+            ((to, from), 1)
+        };
+        let mut connections = self.connections.borrow_mut();
+        let connection = match connections.entry(key) {
+            Entry::Occupied(entry) if !entry.get().is_reused_by(direction, &layer) => {
+                entry.into_mut()
+            }
+            entry => {
+                let index = self.next_index.get();
+                self.next_index.set(index + 1);
+                entry.insert_entry(Connection::new(index)).into_mut()
+            }
+        };
+        layer.seq_ack = Some(connection.seq_ack(direction, &layer, ctx.timestamp));
+        // Part of the segment is missing, so neither its length nor the stream past it is known
+        if ctx.payload_end != Some(offset + bytes.len()) {
+            connection.streams[direction] = StreamBuffer::default();
+            return Ok(Dissection::new(layer, next_byte, next_layer));
+        }
+        connection.analyze(direction, &mut layer, ctx.frame_num, ctx.timestamp);
+        let stream = &mut connection.streams[direction];
+        let seq = layer.payload_seq();
+        if layer.has_flags(FLAG_SYN) {
+            stream.sync(seq);
+        }
+
+        // Only protocols that frame their PDUs need the stream put back together
+        let payload = &bytes[layer.header_bytes()..];
+        let app = match ctx.registry.lookup(&next_layer) {
+            Some(app) if !payload.is_empty() && app.pdu_length(payload).is_some() => app,
+            _ => return Ok(Dissection::new(layer, next_byte, next_layer)),
+        };
+
+        // A segment past a gap is held back in case the gap gets filled, and meanwhile
+        //   dissected on its own. Once a segment that doesn't fill it comes along, the missing
+        //   bytes are given up on and the stream carries on from that segment.
+        if stream.is_past_gap(seq) && !layer.may_be_reordered() {
+            stream.skip_to(seq);
+        }
+        let already_pending = stream.pending().len();
+        let (order, seen) = stream.add(ctx.frame_num, seq, payload);
+        match order {
+            SegmentOrder::InOrder => {}
+            SegmentOrder::OutOfOrder => return Ok(Dissection::new(layer, next_byte, next_layer)),
+            SegmentOrder::Retransmission => {
+                return Ok(Dissection::new(layer, next_byte, NextLayer::Undecoded))
+            }
+        }
+        // Claim every PDU that is now whole
+        let mut pdus: Vec<u8> = vec![];
+        let mut pieces: Vec<FragmentInfo> = vec![];
+        while !stream.pending().is_empty() {
+            let available = stream.pending().len();
+            let len = match app.pdu_length(stream.pending()) {
+                Some(PduLength::Length(len)) => len.max(1),
+                Some(PduLength::NeedMore(more)) => available + more,
+                None => available,
+            };
+            if len > available {
+                if stream.pending_from(ctx.frame_num) {
+                    layer.pdu_continues = Some((stream.pdu_in(), len - available));
+                }
+                break;
+            }
+            let (pdu, taken) = stream.take(len, ctx.frame_num);
+            for piece in taken {
+                // Pieces of the same segment that follow on from each other are shown as one
+                let offset = pdus.len() + piece.offset;
+                match pieces.last_mut() {
+                    Some(last)
+                        if last.frame_num == piece.frame_num
+                            && last.offset + last.length == offset =>
+                    {
+                        last.length += piece.length
+                    }
+                    _ => pieces.push(FragmentInfo { offset, ..piece }),
+                }
+            }
+            pdus.extend(pdu);
+        }
+
+        if pdus.is_empty() {
+            return Ok(Dissection::new(layer, next_byte, NextLayer::Undecoded));
+        }
+        // PDUs that lie wholly within this segment are dissected where they are
+        if already_pending == 0 && seen == 0 && pieces.iter().all(|p| p.frame_num == ctx.frame_num)
+        {
+            ctx.payload_end = Some(next_byte + pdus.len());
+            return Ok(Dissection::new(layer, next_byte, next_layer));
+        }
+        let pdus = Rc::new(pdus);
+        layer.reassembly = Some(Reassembly {
+            fragments: pieces,
+            bytes: pdus.clone(),
+        });
+        Ok(Dissection::new(layer, next_byte, next_layer).with_reassembled("Reassembled TCP", pdus))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(TcpDissector::new());
+    registry.add_to_table(Table::IpProto, 6, "tcp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pkt::registry::{Preferences, Registry};
+
+    /// A TCP header without options, followed by `payload`
+    fn segment(sport: u16, dport: u16, seq: u32, ack: u32, flags: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(sport.to_be_bytes());
+        bytes.extend(dport.to_be_bytes());
+        bytes.extend(seq.to_be_bytes());
+        bytes.extend(ack.to_be_bytes());
+        bytes.extend((0x5000 | flags).to_be_bytes());
+        bytes.extend(8192u16.to_be_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend(payload);
+        bytes
+    }
+
+    /// Runs the dissector over `bytes` as frame `frame_num`, sent from 10.0.0.1 to 10.0.0.5
+    fn dissect(
+        dissector: &TcpDissector,
+        registry: &Registry,
+        frame_num: usize,
+        bytes: &[u8],
+    ) -> Dissection {
+        let mut ctx = DissectCtx::new(registry, frame_num, Timestamp::default());
+        ctx.net_src = Some("10.0.0.1".parse().unwrap());
+        ctx.net_dst = Some("10.0.0.5".parse().unwrap());
+        ctx.payload_end = Some(bytes.len());
+        dissector.dissect(&mut ctx, 0, bytes).unwrap()
+    }
+
+    /// A 12 byte Modbus/TCP read request
+    fn modbus_query(transaction: u16) -> Vec<u8> {
+        let mut bytes = transaction.to_be_bytes().to_vec();
+        bytes.extend([0, 0, 0, 6, 1, 3, 0, 0, 0, 2]);
+        bytes
+    }
+
+    #[test]
+    fn pdus_after_a_missing_segment_are_still_decoded() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = TcpDissector::new();
+        dissect(
+            &dissector,
+            &registry,
+            0,
+            &segment(40000, 502, 1000, 0, FLAG_SYN, &[]),
+        );
+        let mut seq = 1001;
+        let mut frame_num = 1;
+        for transaction in 1..=5 {
+            let query = modbus_query(transaction);
+            // The second query never made it into the capture
+            if transaction != 2 {
+                let bytes = segment(40000, 502, seq, 0, 0, &query);
+                let dissection = dissect(&dissector, &registry, frame_num, &bytes);
+                assert!(
+                    matches!(dissection.next, NextLayer::Ports(..)),
+                    "frame {} was not handed to Modbus",
+                    frame_num
+                );
+                assert!(dissection.reassembled.is_none());
+                frame_num += 1;
+            }
+            seq += query.len() as u32;
+        }
+
+        // Reassembly picks up again past the gap
+        let query = modbus_query(6);
+        let bytes = segment(40000, 502, seq, 0, 0, &query[..5]);
+        let first = dissect(&dissector, &registry, frame_num, &bytes);
+        assert!(matches!(first.next, NextLayer::Undecoded));
+        let bytes = segment(40000, 502, seq + 5, 0, 0, &query[5..]);
+        let second = dissect(&dissector, &registry, frame_num + 1, &bytes);
+        let source = second.reassembled.expect("query was not reassembled");
+        assert_eq!(source.bytepool.bytes, query);
+    }
+
+    #[test]
+    fn pdu_split_across_segments_is_reassembled() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = TcpDissector::new();
+        dissect(
+            &dissector,
+            &registry,
+            0,
+            &segment(40000, 502, 1000, 0, FLAG_SYN, &[]),
+        );
+        let query = modbus_query(1);
+        let first = dissect(
+            &dissector,
+            &registry,
+            1,
+            &segment(40000, 502, 1001, 0, 0, &query[..5]),
+        );
+        assert!(matches!(first.next, NextLayer::Undecoded));
+        let second = dissect(
+            &dissector,
+            &registry,
+            2,
+            &segment(40000, 502, 1006, 0, 0, &query[5..]),
+        );
+        let source = second.reassembled.expect("query was not reassembled");
+        assert_eq!(source.bytepool.bytes, query);
+    }
+
+    const CLIENT: usize = 0;
+    const SERVER: usize = 1;
+
+    /// Feeds segments of one connection to the analysis, numbering frames as it goes
+    struct Exchange {
+        connection: Connection,
+        frame_num: usize,
+    }
+
+    impl Exchange {
+        fn new() -> Self {
+            Exchange {
+                connection: Connection::new(0),
+                frame_num: 0,
+            }
+        }
+
+        /// Analyzes `segment`, sent by endpoint `from`
+        fn send_segment(&mut self, from: usize, mut segment: Tcp) -> Vec<TcpAnalysis> {
+            let timestamp = Timestamp::default();
+            segment.seq_ack = Some(self.connection.seq_ack(from, &segment, timestamp));
+            self.frame_num += 1;
+            self.connection
+                .analyze(from, &mut segment, self.frame_num, timestamp);
+            segment.analysis
+        }
+
+        /// Analyzes an ACK `from` one endpoint carrying `len` bytes of payload
+        fn send(&mut self, from: usize, seq: u32, ack: u32, len: usize) -> Vec<TcpAnalysis> {
+            self.send_segment(from, ack_segment(seq, ack, len))
+        }
+
+        /// Opens the connection with the client's ISN 1000 and the server's 5000, each end
+        /// offering the window scale shift given for it
+        fn handshake(&mut self, client_shift: Option<u8>, server_shift: Option<u8>) {
+            let syn = self.send_segment(CLIENT, syn_segment(1000, 0, FLAG_SYN, client_shift));
+            let syn_ack = self.send_segment(
+                SERVER,
+                syn_segment(5000, 1001, FLAG_SYN | FLAG_ACK, server_shift),
+            );
+            let ack = self.send(CLIENT, 1001, 5001, 0);
+            assert_eq!((syn, syn_ack, ack), (vec![], vec![], vec![]));
+        }
+    }
+
+    /// An ACK with a window of 8192 carrying `len` bytes of payload
+    fn ack_segment(seq: u32, ack: u32, len: usize) -> Tcp {
+        Tcp {
+            sequence_num: seq,
+            ack_num: ack,
+            flags: FLAG_ACK,
+            window_size: 8192,
+            payload_len: len,
+            ..Tcp::new()
+        }
+    }
+
+    /// A SYN, with the window scale option when `window_shift` is given
+    fn syn_segment(seq: u32, ack: u32, flags: u16, window_shift: Option<u8>) -> Tcp {
+        let options = window_shift
+            .map(|shift| TcpOption {
+                offset: 0,
+                length: 3,
+                kind: TcpOptionKind::WindowScale(shift),
+            })
+            .into_iter()
+            .collect();
+        Tcp {
+            flags,
+            options,
+            ..ack_segment(seq, ack, 0)
+        }
+    }
+
+    /// An ACK without payload advertising `window`
+    fn window_update(seq: u32, ack: u32, window: u16) -> Tcp {
+        Tcp {
+            window_size: window,
+            ..ack_segment(seq, ack, 0)
+        }
+    }
+
+    #[test]
+    fn segments_in_order_are_not_flagged() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        assert_eq!(exchange.send(CLIENT, 1001, 5001, 10), vec![]);
+        assert_eq!(exchange.send(SERVER, 5001, 1011, 20), vec![]);
+        assert_eq!(exchange.send(CLIENT, 1011, 5021, 10), vec![]);
+    }
+
+    #[test]
+    fn resent_data_is_a_retransmission() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        exchange.send(CLIENT, 1011, 5001, 10);
+        assert_eq!(
+            exchange.send(CLIENT, 1001, 5001, 10),
+            vec![TcpAnalysis::Retransmission]
+        );
+    }
+
+    #[test]
+    fn resend_after_two_duplicate_acks_is_a_fast_retransmission() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        exchange.send(CLIENT, 1011, 5001, 10);
+        exchange.send(CLIENT, 1021, 5001, 10);
+        assert_eq!(exchange.send(SERVER, 5001, 1011, 0), vec![]);
+        assert_eq!(
+            exchange.send(SERVER, 5001, 1011, 0),
+            vec![TcpAnalysis::DuplicateAck {
+                num: 1,
+                original: 7
+            }]
+        );
+        assert_eq!(
+            exchange.send(SERVER, 5001, 1011, 0),
+            vec![TcpAnalysis::DuplicateAck {
+                num: 2,
+                original: 7
+            }]
+        );
+        assert_eq!(
+            exchange.send(CLIENT, 1011, 5001, 10),
+            vec![TcpAnalysis::FastRetransmission]
+        );
+    }
+
+    #[test]
+    fn resend_after_a_single_duplicate_ack_is_a_plain_retransmission() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        exchange.send(CLIENT, 1011, 5001, 10);
+        exchange.send(SERVER, 5001, 1011, 0);
+        exchange.send(SERVER, 5001, 1011, 0);
+        assert_eq!(
+            exchange.send(CLIENT, 1011, 5001, 10),
+            vec![TcpAnalysis::Retransmission]
+        );
+    }
+
+    #[test]
+    fn resending_acknowledged_data_is_a_spurious_retransmission() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        exchange.send(SERVER, 5001, 1011, 0);
+        assert_eq!(
+            exchange.send(CLIENT, 1001, 5001, 10),
+            vec![TcpAnalysis::SpuriousRetransmission]
+        );
+    }
+
+    #[test]
+    fn segment_filling_a_hole_is_out_of_order() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        assert_eq!(
+            exchange.send(CLIENT, 1021, 5001, 10),
+            vec![TcpAnalysis::LostSegment]
+        );
+        assert_eq!(
+            exchange.send(CLIENT, 1011, 5001, 10),
+            vec![TcpAnalysis::OutOfOrder]
+        );
+        // Once the hole is filled, the same data again is a retransmission
+        assert_eq!(
+            exchange.send(CLIENT, 1011, 5001, 10),
+            vec![TcpAnalysis::Retransmission]
+        );
+    }
+
+    #[test]
+    fn segment_filling_part_of_a_hole_leaves_the_rest_out_of_order() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1031, 5001, 10);
+        assert_eq!(
+            exchange.send(CLIENT, 1011, 5001, 10),
+            vec![TcpAnalysis::OutOfOrder]
+        );
+        assert_eq!(
+            exchange.send(CLIENT, 1001, 5001, 10),
+            vec![TcpAnalysis::OutOfOrder]
+        );
+        assert_eq!(
+            exchange.send(CLIENT, 1021, 5001, 10),
+            vec![TcpAnalysis::OutOfOrder]
+        );
+    }
+
+    #[test]
+    fn byte_before_the_next_one_is_a_keep_alive() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        exchange.send(SERVER, 5001, 1011, 0);
+        assert_eq!(
+            exchange.send(CLIENT, 1010, 5001, 0),
+            vec![TcpAnalysis::KeepAlive]
+        );
+        assert_eq!(
+            exchange.send(CLIENT, 1010, 5001, 1),
+            vec![TcpAnalysis::KeepAlive]
+        );
+        // Keep-alives don't move the sequence on
+        assert_eq!(exchange.send(CLIENT, 1011, 5001, 10), vec![]);
+    }
+
+    #[test]
+    fn zero_window_is_flagged_except_on_control_segments() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        assert_eq!(
+            exchange.send_segment(SERVER, window_update(5001, 1011, 0)),
+            vec![TcpAnalysis::ZeroWindow]
+        );
+        assert_eq!(
+            exchange.send_segment(
+                SERVER,
+                Tcp {
+                    flags: FLAG_ACK | FLAG_RST,
+                    ..window_update(5001, 1011, 0)
+                }
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn filling_the_scaled_window_is_window_full() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(Some(2), Some(3));
+        // A window of 4 scaled by 2^3 leaves room for 32 bytes
+        exchange.send_segment(SERVER, window_update(5001, 1001, 4));
+        assert_eq!(exchange.send(CLIENT, 1001, 5001, 4), vec![]);
+        assert_eq!(
+            exchange.send(CLIENT, 1005, 5001, 28),
+            vec![TcpAnalysis::WindowFull]
+        );
+    }
+
+    #[test]
+    fn windows_are_not_scaled_unless_both_ends_agree() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(Some(2), None);
+        exchange.send_segment(SERVER, window_update(5001, 1001, 4));
+        assert_eq!(
+            exchange.send(CLIENT, 1001, 5001, 4),
+            vec![TcpAnalysis::WindowFull]
+        );
+    }
+
+    #[test]
+    fn acknowledging_data_never_sent_is_an_acked_unseen_segment() {
+        let mut exchange = Exchange::new();
+        exchange.handshake(None, None);
+        exchange.send(CLIENT, 1001, 5001, 10);
+        assert_eq!(
+            exchange.send(SERVER, 5001, 2001, 0),
+            vec![TcpAnalysis::AckedUnseen]
+        );
+    }
+
+    /// Stream index, relative sequence number and analysis of the TCP layer `dissection` made
+    fn numbering(dissection: &Dissection) -> (usize, u32, Vec<TcpAnalysis>) {
+        let tcp = dissection.layer.as_any().downcast_ref::<Tcp>().unwrap();
+        let stream = tcp.seq_ack.as_ref().unwrap().stream;
+        (stream, tcp.relative_seq(), tcp.analysis.clone())
+    }
+
+    #[test]
+    fn syn_after_fin_starts_a_new_stream() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = TcpDissector::new();
+        let frames = [
+            segment(40000, 502, 1000, 0, FLAG_SYN, &[]),
+            segment(40000, 502, 1001, 0, FLAG_ACK, b"abcd"),
+            segment(40000, 502, 1005, 0, FLAG_FIN | FLAG_ACK, &[]),
+            // Same ports again, with an ISN that happens to sit below the old stream's
+            segment(40000, 502, 500, 0, FLAG_SYN, &[]),
+            segment(40000, 502, 501, 0, FLAG_ACK, b"efgh"),
+        ];
+        let numbering: Vec<_> = frames
+            .iter()
+            .enumerate()
+            .map(|(frame_num, bytes)| numbering(&dissect(&dissector, &registry, frame_num, bytes)))
+            .collect();
+        assert_eq!(
+            numbering,
+            vec![
+                (0, 0, vec![]),
+                (0, 1, vec![]),
+                (0, 5, vec![]),
+                (1, 0, vec![]),
+                (1, 1, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn syn_with_another_isn_starts_a_new_stream() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = TcpDissector::new();
+        let first = segment(40000, 502, 1000, 0, FLAG_SYN, &[]);
+        assert_eq!(numbering(&dissect(&dissector, &registry, 0, &first)).0, 0);
+        // A retransmitted SYN belongs to the same connection
+        assert_eq!(numbering(&dissect(&dissector, &registry, 1, &first)).0, 0);
+        let other = segment(40000, 502, 9000, 0, FLAG_SYN, &[]);
+        assert_eq!(
+            numbering(&dissect(&dissector, &registry, 2, &other)),
+            (1, 0, vec![])
+        );
+        // Another connection in between doesn't change how later ones are numbered
+        let unrelated = segment(40001, 502, 1000, 0, FLAG_SYN, &[]);
+        assert_eq!(
+            numbering(&dissect(&dissector, &registry, 3, &unrelated)).0,
+            2
+        );
+        let third = segment(40000, 502, 100, 0, FLAG_SYN, &[]);
+        assert_eq!(numbering(&dissect(&dissector, &registry, 4, &third)).0, 3);
+    }
+
+    #[test]
+    fn connection_setup_and_teardown_are_chat() {
+        let registry = Registry::with_all_dissectors();
+        let dissector = TcpDissector::new();
+        let frames = [
+            segment(40000, 502, 1000, 0, FLAG_SYN, &[]),
+            segment(502, 40000, 7000, 1001, FLAG_SYN | FLAG_ACK, &[]),
+            segment(40000, 502, 1001, 7001, FLAG_ACK, b"abcd"),
+            segment(40000, 502, 1005, 7001, FLAG_FIN | FLAG_ACK, &[]),
+        ];
+        let chats: Vec<Vec<String>> = frames
+            .iter()
+            .enumerate()
+            .map(|(frame_num, bytes)| {
+                dissect(&dissector, &registry, frame_num, bytes)
+                    .layer
+                    .expert_infos()
+                    .into_iter()
+                    .filter(|info| info.severity == Severity::Chat)
+                    .map(|info| info.summary)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            chats,
+            vec![
+                vec!["Connection establish request (SYN)".to_string()],
+                vec!["Connection establish acknowledge (SYN+ACK)".to_string()],
+                vec![],
+                vec!["Connection finish (FIN)".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn checksum_covers_the_pseudo_header() {
+        let status = |registry: &Registry, checksum: u16| {
+            let mut bytes = segment(40000, 502, 1000, 0, FLAG_SYN, &[]);
+            bytes[16..18].copy_from_slice(&checksum.to_be_bytes());
+            let dissection = dissect(&TcpDissector::new(), registry, 0, &bytes);
+            let tcp = dissection.layer.as_any().downcast_ref::<Tcp>().unwrap();
+            (tcp.xsum_status, tcp.fields())
+        };
+        let registry = Registry::with_all_dissectors();
+        let (xsum_status, _) = status(&registry, 0xd9be);
+        assert_eq!(xsum_status, ChecksumStatus::Correct);
+        let (xsum_status, fields) = status(&registry, 0xd9bf);
+        assert_eq!(xsum_status, ChecksumStatus::Incorrect(0xd9be));
+        assert!(fields.contains(&("tcp.checksum.status", 0u8.into())));
+
+        let registry = Registry::with_prefs(Preferences {
+            validate_checksums: false,
+        });
+        let (xsum_status, _) = status(&registry, 0xd9bf);
+        assert_eq!(xsum_status, ChecksumStatus::Offloaded);
+    }
+}
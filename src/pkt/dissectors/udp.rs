@@ -0,0 +1,386 @@
+use core::fmt;
+use std::any::Any;
+use std::ops::Range;
+use tui::style::{Color, Style};
+
+use crate::pkt::dissectors::util::{self, ChecksumStatus};
+use crate::pkt::dissectors::DissectError;
+use crate::pkt::expert::ExpertInfo;
+use crate::pkt::field::{Field, FieldInfo, FieldKind};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::registry::{
+    DissectCtx, Dissection, Dissector, NextLayer, ProtocolLayer, Registry, Table,
+};
+
+const HEADER_LEN: usize = 8;
+
+const FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("udp.srcport", FieldKind::UInt, "Source Port"),
+    FieldInfo::new("udp.dstport", FieldKind::UInt, "Destination Port"),
+    FieldInfo::new("udp.port", FieldKind::UInt, "Source or Destination Port"),
+    FieldInfo::new("udp.length", FieldKind::UInt, "Length"),
+    FieldInfo::new("udp.checksum", FieldKind::UInt, "Checksum"),
+    FieldInfo::new(
+        "udp.checksum.status",
+        FieldKind::UInt,
+        "Checksum Status (0 bad, 1 good, 2 unverified)",
+    ),
+];
+
+#[derive(Clone, Debug)]
+pub struct Udp {
+    /// Offset of the header in the packet
+    offset: usize,
+    source_port: u16,
+    dest_port: u16,
+    length: u16,
+    udp_xsum: u16,
+    xsum_status: ChecksumStatus,
+}
+
+impl Udp {
+    /// Decodes the header and checks its length against the IP payload and its checksum
+    /// against the pseudo-header built from the addresses in `ctx`
+    pub fn from_bytes(
+        ctx: &DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<(Self, usize, NextLayer), DissectError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DissectError::truncated("UDP", HEADER_LEN, bytes.len()));
+        }
+        let source_port = util::two_bytes_to_u16(&bytes[0..2]);
+        let dest_port = util::two_bytes_to_u16(&bytes[2..4]);
+        let length = util::two_bytes_to_u16(&bytes[4..6]);
+        let udp_xsum = util::two_bytes_to_u16(&bytes[6..8]);
+
+        if (length as usize) < HEADER_LEN {
+            return Err(DissectError::new(format!(
+                "bogus UDP length ({}, must be at least {})",
+                length, HEADER_LEN
+            )));
+        }
+        // The IP layer bounds `bytes` when the whole datagram was captured, so only then can
+        //   a length running past the end be told apart from a short snapshot length
+        let whole_ip_payload = ctx.payload_end == Some(next_byte + bytes.len());
+        if whole_ip_payload && length as usize > bytes.len() {
+            return Err(DissectError::new(format!(
+                "bogus UDP length ({}, IP payload is only {} bytes)",
+                length,
+                bytes.len()
+            )));
+        }
+
+        let xsum_status = match (ctx.net_src, ctx.net_dst) {
+            _ if udp_xsum == 0 => ChecksumStatus::Unverified,
+            _ if !ctx.registry.prefs.validate_checksums => ChecksumStatus::Offloaded,
+            (Some(src), Some(dst)) if length as usize <= bytes.len() => {
+                let pseudo = util::pseudo_header(src, dst, 17, length as usize);
+                let expected = match util::internet_checksum(&[
+                    &pseudo,
+                    &bytes[0..6],
+                    &bytes[HEADER_LEN..length as usize],
+                ]) {
+                    // An all-zero checksum means "none" in UDP, so 0 goes on the wire as 0xffff
+                    0 => 0xffff,
+                    xsum => xsum,
+                };
+                if expected == udp_xsum {
+                    ChecksumStatus::Correct
+                } else {
+                    ChecksumStatus::Incorrect(expected)
+                }
+            }
+            _ => ChecksumStatus::Unverified,
+        };
+
+        let udp_layer = Udp {
+            offset: next_byte,
+            source_port,
+            dest_port,
+            length,
+            udp_xsum,
+            xsum_status,
+        };
+
+        let ret_next_byte = next_byte + HEADER_LEN;
+        let next_layer = NextLayer::Ports(Table::UdpPort, source_port, dest_port);
+
+        Ok((udp_layer, ret_next_byte, next_layer))
+    }
+
+    fn payload_len(&self) -> usize {
+        self.length as usize - HEADER_LEN
+    }
+
+    pub fn ports(&self) -> (u16, u16) {
+        (self.source_port, self.dest_port)
+    }
+
+    /// Where the payload lies in the bytes the datagram was dissected from, which may run past
+    /// the end of a short capture
+    pub fn payload_range(&self) -> Range<usize> {
+        let start = self.offset + HEADER_LEN;
+        start..start + self.payload_len()
+    }
+}
+
+impl fmt::Display for Udp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "User Datagram Protocol, Src Port: {}, Dst Port: {}",
+            self.source_port, self.dest_port,
+        )
+    }
+}
+
+impl ProtocolLayer for Udp {
+    fn name(&self) -> &'static str {
+        "udp"
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "{} \u{2192} {} Len={}",
+            self.source_port,
+            self.dest_port,
+            self.payload_len()
+        )
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        vec![
+            ("udp.srcport", self.source_port.into()),
+            ("udp.dstport", self.dest_port.into()),
+            ("udp.port", self.source_port.into()),
+            ("udp.port", self.dest_port.into()),
+            ("udp.length", self.length.into()),
+            ("udp.checksum", self.udp_xsum.into()),
+            ("udp.checksum.status", self.xsum_status.code().into()),
+        ]
+    }
+
+    fn to_proto_item(&self) -> ProtoItem {
+        let off = self.offset;
+        let xsum_item = if self.udp_xsum == 0 {
+            ProtoItem::new_leaf("Checksum: 0x0000 [zero-value ignored]", off + 6, 2)
+        } else {
+            self.xsum_status
+                .to_proto_item("Checksum", self.udp_xsum, off + 6)
+        };
+        ProtoItem::new(
+            self.to_string(),
+            off,
+            HEADER_LEN,
+            vec![
+                ProtoItem::new_leaf(format!("Source Port: {}", self.source_port), off, 2),
+                ProtoItem::new_leaf(format!("Destination Port: {}", self.dest_port), off + 2, 2),
+                ProtoItem::new_leaf(format!("Length: {}", self.length), off + 4, 2),
+                xsum_item,
+                ProtoItem::new_leaf(
+                    format!("UDP payload ({} bytes)", self.payload_len()),
+                    off + HEADER_LEN,
+                    self.payload_len(),
+                ),
+            ],
+        )
+        .style(Style::default().fg(Color::Black).bg(Color::LightYellow))
+    }
+
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        self.xsum_status.expert_info("UDP").into_iter().collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct UdpDissector;
+
+impl Dissector for UdpDissector {
+    fn name(&self) -> &'static str {
+        "udp"
+    }
+
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError> {
+        let (layer, next_byte, next_layer) = Udp::from_bytes(ctx, next_byte, bytes)?;
+        ctx.src_port = layer.source_port;
+        ctx.dst_port = layer.dest_port;
+        // Anything after the datagram within the IP payload is not UDP's
+        let end = layer.offset + layer.length as usize;
+        if ctx.payload_end.is_none_or(|payload_end| end < payload_end) {
+            ctx.payload_end = Some(end);
+        }
+        Ok(Dissection::new(layer, next_byte, next_layer))
+    }
+
+    fn fields(&self) -> &'static [FieldInfo] {
+        FIELDS
+    }
+}
+
+pub fn register(registry: &mut Registry) {
+    registry.register(UdpDissector);
+    registry.add_to_table(Table::IpProto, 17, "udp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pkt::expert::Severity;
+    use crate::pkt::registry::Preferences;
+
+    /// A datagram from port 1234 to 53 carrying `payload`, with `checksum` as given
+    fn datagram(checksum: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(1234u16.to_be_bytes());
+        bytes.extend(53u16.to_be_bytes());
+        bytes.extend((HEADER_LEN as u16 + payload.len() as u16).to_be_bytes());
+        bytes.extend(checksum.to_be_bytes());
+        bytes.extend(payload);
+        bytes
+    }
+
+    /// Decodes `bytes` as the whole payload of an IP packet from `src` to `dst`
+    fn decode(registry: &Registry, src: &str, dst: &str, bytes: &[u8]) -> Udp {
+        let mut ctx = DissectCtx::new(registry, 0, Default::default());
+        ctx.net_src = Some(src.parse().unwrap());
+        ctx.net_dst = Some(dst.parse().unwrap());
+        ctx.payload_end = Some(bytes.len());
+        Udp::from_bytes(&ctx, 0, bytes).unwrap().0
+    }
+
+    fn status(checksum: u16, payload: &[u8]) -> ChecksumStatus {
+        let registry = Registry::default();
+        decode(
+            &registry,
+            "10.0.0.1",
+            "10.0.0.5",
+            &datagram(checksum, payload),
+        )
+        .xsum_status
+    }
+
+    fn error(ctx: &DissectCtx, bytes: &[u8]) -> String {
+        Udp::from_bytes(ctx, 0, bytes).unwrap_err().reason
+    }
+
+    #[test]
+    fn checksum_covers_the_pseudo_header() {
+        assert_eq!(status(0x7e64, b"hi"), ChecksumStatus::Correct);
+        assert_eq!(status(0x7e65, b"hi"), ChecksumStatus::Incorrect(0x7e64));
+        // The same datagram between other addresses sums differently
+        let registry = Registry::default();
+        let udp = decode(
+            &registry,
+            "2001:db8::1",
+            "2001:db8::2",
+            &datagram(0x36f5, b"hi"),
+        );
+        assert_eq!(udp.xsum_status, ChecksumStatus::Correct);
+        let udp = decode(&registry, "10.0.0.2", "10.0.0.5", &datagram(0x7e64, b"hi"));
+        assert_eq!(udp.xsum_status, ChecksumStatus::Incorrect(0x7e63));
+    }
+
+    #[test]
+    fn checksum_summing_to_zero_is_sent_as_ffff() {
+        assert_eq!(status(0xffff, &[0xe6, 0xcd]), ChecksumStatus::Correct);
+    }
+
+    #[test]
+    fn zero_checksum_means_none() {
+        assert_eq!(status(0, b"hi"), ChecksumStatus::Unverified);
+        let registry = Registry::default();
+        let udp = decode(&registry, "10.0.0.1", "10.0.0.5", &datagram(0, b"hi"));
+        assert_eq!(
+            udp.to_proto_item().children[3].text,
+            "Checksum: 0x0000 [zero-value ignored]"
+        );
+    }
+
+    #[test]
+    fn checksum_is_not_checked_when_validation_is_off() {
+        let mut registry = Registry::default();
+        registry.prefs = Preferences {
+            validate_checksums: false,
+        };
+        let udp = decode(&registry, "10.0.0.1", "10.0.0.5", &datagram(0x1234, b"hi"));
+        assert_eq!(udp.xsum_status, ChecksumStatus::Offloaded);
+    }
+
+    #[test]
+    fn bogus_lengths_are_errors() {
+        let registry = Registry::default();
+        let mut ctx = DissectCtx::new(&registry, 0, Default::default());
+        assert_eq!(
+            error(&ctx, &[0, 1, 0, 2, 0, 8, 0]),
+            "UDP header needs 8 bytes, only 7 captured"
+        );
+
+        let mut bytes = datagram(0, b"hi");
+        bytes[5] = 7;
+        assert_eq!(
+            error(&ctx, &bytes),
+            "bogus UDP length (7, must be at least 8)"
+        );
+
+        bytes[5] = 20;
+        ctx.payload_end = Some(bytes.len());
+        assert_eq!(
+            error(&ctx, &bytes),
+            "bogus UDP length (20, IP payload is only 10 bytes)"
+        );
+    }
+
+    #[test]
+    fn length_past_a_short_capture_is_accepted() {
+        let registry = Registry::default();
+        let mut ctx = DissectCtx::new(&registry, 0, Default::default());
+        ctx.net_src = Some("10.0.0.1".parse().unwrap());
+        ctx.net_dst = Some("10.0.0.5".parse().unwrap());
+        // The IP layer didn't get to bound the payload, so the rest may not have been captured
+        let mut bytes = datagram(0x7e64, b"hi");
+        bytes[5] = 20;
+        let (udp, next_byte, _) = Udp::from_bytes(&ctx, 0, &bytes).unwrap();
+        assert_eq!(next_byte, HEADER_LEN);
+        assert_eq!(udp.xsum_status, ChecksumStatus::Unverified);
+        assert_eq!(udp.payload_range(), 8..20);
+    }
+
+    #[test]
+    fn bytes_after_the_datagram_are_not_its_payload() {
+        let registry = Registry::default();
+        let mut ctx = DissectCtx::new(&registry, 0, Default::default());
+        let mut bytes = datagram(0, b"hi");
+        bytes.extend([0; 4]);
+        ctx.payload_end = Some(bytes.len());
+        let dissection = UdpDissector.dissect(&mut ctx, 0, &bytes).unwrap();
+        assert_eq!(ctx.payload_end, Some(10));
+        assert_eq!((ctx.src_port, ctx.dst_port), (1234, 53));
+        assert!(matches!(
+            dissection.next,
+            NextLayer::Ports(Table::UdpPort, 1234, 53)
+        ));
+    }
+
+    #[test]
+    fn bad_checksum_is_an_expert_error() {
+        let registry = Registry::default();
+        let udp = decode(&registry, "10.0.0.1", "10.0.0.5", &datagram(0x7e65, b"hi"));
+        let infos = udp.expert_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(
+            (infos[0].severity, infos[0].group, infos[0].summary.as_str()),
+            (Severity::Error, "Checksum", "Bad checksum")
+        );
+        let udp = decode(&registry, "10.0.0.1", "10.0.0.5", &datagram(0x7e64, b"hi"));
+        assert!(udp.expert_infos().is_empty());
+    }
+}
@@ -1,8 +1,7 @@
 use core::fmt;
 use tui::style::{Color, Style};
-use tui_tree_widget::TreeItem;
 
-use crate::pkt::LayerHint;
+use crate::pkt::prototree::ProtoItem;
 
 #[derive(Clone, Debug)]
 pub struct Undecoded {
@@ -10,28 +9,17 @@ pub struct Undecoded {
     pub length: usize,
 }
 
-#[allow(dead_code)]
 impl Undecoded {
-    pub fn new() -> Self {
-        Undecoded {
-            start_offset: 0,
-            length: 0,
-        }
-    }
-
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new_leaf(self.to_string()).style(Style::default().bg(Color::LightRed))
+    pub fn to_proto_item(&self) -> ProtoItem {
+        ProtoItem::new_leaf(self.to_string(), self.start_offset, self.length)
+            .style(Style::default().bg(Color::LightRed))
     }
 
-    pub fn from_bytes(next_byte: usize, bytes: &[u8]) -> (Self, usize, LayerHint) {
-        (
-            Undecoded {
-                start_offset: next_byte,
-                length: bytes.len(),
-            },
-            next_byte + bytes.len(),
-            LayerHint::Undecoded,
-        )
+    pub fn from_bytes(next_byte: usize, bytes: &[u8]) -> Self {
+        Undecoded {
+            start_offset: next_byte,
+            length: bytes.len(),
+        }
     }
 }
 
@@ -1,3 +1,12 @@
+use core::fmt;
+use std::cell::Cell;
+use std::net::IpAddr;
+use std::rc::Rc;
+use tui::style::{Color, Style};
+
+use crate::pkt::expert::{ExpertInfo, Severity};
+use crate::pkt::prototree::ProtoItem;
+
 pub fn two_bytes_to_u16(bytes: &[u8]) -> u16 {
     assert!(bytes.len() == 2);
     (256u16 * bytes[0] as u16) + bytes[1] as u16
@@ -10,3 +19,290 @@ pub fn four_bytes_to_u32(bytes: &[u8]) -> u32 {
         + ((bytes[2] as u32) << 8)
         + (bytes[3] as u32)
 }
+
+/// Bits of `value` selected by `mask` written out the way Wireshark labels bit fields, e.g.
+/// `.... ..1.` for bit 1 of a byte. `width` is the size of the whole field in bits.
+pub fn bit_pattern(value: u32, mask: u32, width: u32) -> String {
+    let mut pattern = String::new();
+    for bit in (0..width).rev() {
+        if bit != width - 1 && (bit + 1) % 4 == 0 {
+            pattern.push(' ');
+        }
+        pattern.push(match (mask >> bit & 1, value >> bit & 1) {
+            (0, _) => '.',
+            (_, 0) => '0',
+            _ => '1',
+        });
+    }
+    pattern
+}
+
+/// Internet checksum (RFC 1071) over `chunks` taken as one contiguous byte string. A correct
+/// header that includes its own checksum sums to 0.
+pub fn internet_checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut odd: Option<u8> = None;
+    for chunk in chunks {
+        for &byte in chunk.iter() {
+            match odd.take() {
+                Some(high) => sum += ((high as u32) << 8) | byte as u32,
+                None => odd = Some(byte),
+            }
+        }
+    }
+    if let Some(high) = odd {
+        sum += (high as u32) << 8;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Pseudo-header transport checksums cover, for IPv4 (RFC 768) or IPv6 (RFC 8200)
+pub fn pseudo_header(src: IpAddr, dst: IpAddr, protocol: u8, length: usize) -> Vec<u8> {
+    let mut header = vec![];
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+            header.extend_from_slice(&[0, protocol]);
+            header.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            header.extend_from_slice(&to_ipv6_octets(src));
+            header.extend_from_slice(&to_ipv6_octets(dst));
+            header.extend_from_slice(&(length as u32).to_be_bytes());
+            header.extend_from_slice(&[0, 0, 0, protocol]);
+        }
+    }
+    header
+}
+
+fn to_ipv6_octets(addr: IpAddr) -> [u8; 16] {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+        IpAddr::V6(addr) => addr.octets(),
+    }
+}
+
+/// Result of verifying a checksum carried in a header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    Correct,
+    /// Holds the checksum the header should have carried
+    Incorrect(u16),
+    /// Too few bytes were captured to check it, or the sender left it out
+    Unverified,
+    /// Checking is turned off, as it should be for captures taken on hosts that leave
+    /// checksums to the network card
+    Offloaded,
+}
+
+impl ChecksumStatus {
+    /// Value of the `*.checksum.status` filter fields: 0 bad, 1 good, 2 unverified
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Incorrect(_) => 0,
+            Self::Correct => 1,
+            Self::Unverified | Self::Offloaded => 2,
+        }
+    }
+
+    /// Tree leaf for the checksum field `label`, in red when the checksum is wrong
+    pub fn to_proto_item(self, label: &str, checksum: u16, offset: usize) -> ProtoItem {
+        let item = ProtoItem::new_leaf(format!("{}: {:#06x} {}", label, checksum, self), offset, 2);
+        match self {
+            Self::Incorrect(_) => item.style(Style::default().fg(Color::White).bg(Color::Red)),
+            _ => item,
+        }
+    }
+
+    /// Expert info for `protocol`'s checksum, when it is wrong
+    pub fn expert_info(self, protocol: &str) -> Option<ExpertInfo> {
+        match self {
+            Self::Incorrect(_) => Some(ExpertInfo::new(
+                Severity::Error,
+                "Checksum",
+                protocol,
+                "Bad checksum",
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChecksumStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Correct => write!(f, "[correct]"),
+            Self::Incorrect(expected) => write!(f, "[incorrect, should be {:#06x}]", expected),
+            Self::Unverified => write!(f, "[unverified]"),
+            Self::Offloaded => write!(f, "[unverified \u{2013} offloaded]"),
+        }
+    }
+}
+
+/// Where the other half of a request/response pair is, for protocols whose dissectors match
+/// the two up
+#[derive(Clone, Debug)]
+pub enum Pairing {
+    /// A request, with the frame of its response once one has been dissected
+    Request(Rc<Cell<Option<usize>>>),
+    /// A response, with the frame of its request and the time between the two in nanoseconds
+    Response(Option<(usize, i128)>),
+}
+
+/// Name of a Differentiated Services Codepoint (RFC 2474, RFC 2597, RFC 3246, RFC 8622)
+pub fn dscp_name(dscp: u8) -> &'static str {
+    match dscp {
+        0 => "Default",
+        1 => "Lower Effort",
+        8 => "Class Selector 1",
+        10 => "Assured Forwarding 11",
+        12 => "Assured Forwarding 12",
+        14 => "Assured Forwarding 13",
+        16 => "Class Selector 2",
+        18 => "Assured Forwarding 21",
+        20 => "Assured Forwarding 22",
+        22 => "Assured Forwarding 23",
+        24 => "Class Selector 3",
+        26 => "Assured Forwarding 31",
+        28 => "Assured Forwarding 32",
+        30 => "Assured Forwarding 33",
+        32 => "Class Selector 4",
+        34 => "Assured Forwarding 41",
+        36 => "Assured Forwarding 42",
+        38 => "Assured Forwarding 43",
+        40 => "Class Selector 5",
+        44 => "Voice Admit",
+        46 => "Expedited Forwarding",
+        48 => "Class Selector 6",
+        56 => "Class Selector 7",
+        _ => "Unknown",
+    }
+}
+
+/// Short name of an Explicit Congestion Notification codepoint (RFC 3168)
+pub fn ecn_name(ecn: u8) -> &'static str {
+    match ecn & 0x03 {
+        0 => "Not-ECT",
+        1 => "ECT(1)",
+        2 => "ECT(0)",
+        _ => "CE",
+    }
+}
+
+/// Name of an IP protocol number, as used by IPv4's Protocol and IPv6's Next Header fields
+pub fn ip_proto_name(protocol: u8) -> &'static str {
+    match protocol {
+        0 => "IPv6 Hop-by-Hop Option",
+        1 => "ICMP",
+        2 => "IGMP",
+        4 => "IPIP",
+        6 => "TCP",
+        17 => "UDP",
+        41 => "IPv6",
+        43 => "Routing Header for IPv6",
+        44 => "Fragment Header for IPv6",
+        47 => "GRE",
+        50 => "Encapsulating Security Payload",
+        51 => "Authentication Header",
+        58 => "ICMPv6",
+        59 => "No Next Header for IPv6",
+        60 => "Destination Options for IPv6",
+        89 => "OSPF",
+        103 => "PIM",
+        112 => "VRRP",
+        132 => "SCTP",
+        136 => "UDPLite",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internet_checksum_matches_rfc_1071() {
+        let bytes = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet_checksum(&[&bytes]), 0x220d);
+        // Chunks are summed as one string, even when one ends halfway through a word
+        assert_eq!(
+            internet_checksum(&[&bytes[..3], &bytes[3..5], &bytes[5..]]),
+            0x220d
+        );
+        // An odd byte at the end is padded with zero
+        assert_eq!(internet_checksum(&[&[0x12]]), !0x1200);
+        assert_eq!(internet_checksum(&[]), 0xffff);
+    }
+
+    #[test]
+    fn header_with_its_checksum_sums_to_zero() {
+        let mut header = [
+            0x45, 0, 0, 40, 0, 1, 0x40, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 5,
+        ];
+        let checksum = internet_checksum(&[&header]);
+        assert_eq!(checksum, 0x26ca);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(internet_checksum(&[&header]), 0);
+    }
+
+    #[test]
+    fn pseudo_header_layouts() {
+        let v4 = pseudo_header(
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.5".parse().unwrap(),
+            17,
+            10,
+        );
+        assert_eq!(v4, vec![10, 0, 0, 1, 10, 0, 0, 5, 0, 17, 0, 10]);
+
+        let v6 = pseudo_header(
+            "2001:db8::1".parse().unwrap(),
+            "2001:db8::2".parse().unwrap(),
+            6,
+            0x10000,
+        );
+        assert_eq!(v6.len(), 40);
+        assert_eq!(&v6[32..], &[0, 1, 0, 0, 0, 0, 0, 6]);
+    }
+
+    #[test]
+    fn checksum_status_codes_and_text() {
+        let statuses = [
+            (ChecksumStatus::Correct, 1, "[correct]"),
+            (
+                ChecksumStatus::Incorrect(0x26ca),
+                0,
+                "[incorrect, should be 0x26ca]",
+            ),
+            (ChecksumStatus::Unverified, 2, "[unverified]"),
+            (
+                ChecksumStatus::Offloaded,
+                2,
+                "[unverified \u{2013} offloaded]",
+            ),
+        ];
+        for (status, code, text) in statuses {
+            assert_eq!(status.code(), code);
+            assert_eq!(status.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn only_incorrect_checksums_are_highlighted() {
+        let item = ChecksumStatus::Incorrect(0x26ca).to_proto_item("Header Checksum", 0x1234, 10);
+        assert_eq!(
+            item.text,
+            "Header Checksum: 0x1234 [incorrect, should be 0x26ca]"
+        );
+        assert_eq!((item.start, item.length), (10, 2));
+        assert_eq!(item.style.bg, Some(Color::Red));
+
+        let item = ChecksumStatus::Correct.to_proto_item("Header Checksum", 0x26ca, 10);
+        assert_eq!(item.text, "Header Checksum: 0x26ca [correct]");
+        assert_eq!(item.style.bg, None);
+    }
+}
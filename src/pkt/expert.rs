@@ -0,0 +1,84 @@
+// Expert info: problems and notable events dissectors point out in a packet, beyond decoding
+//   its fields, such as retransmissions. They show in the packet's details and are gathered
+//   across the capture into a summary.
+
+use core::fmt;
+use tui::style::{Color, Style};
+
+use crate::pkt::prototree::ProtoItem;
+
+/// How much an expert info item deserves attention, least first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Normal workflow, e.g. a connection being set up
+    Chat,
+    /// Unusual but possibly harmless, e.g. a retransmission
+    Note,
+    /// Likely a problem, e.g. a segment missing from the capture
+    Warning,
+    /// Something is broken, e.g. a malformed packet
+    Error,
+}
+
+impl Severity {
+    /// Colors of the severity in the packet details and the summary, as in Wireshark
+    pub fn style(self) -> Style {
+        match self {
+            Severity::Chat => Style::default().fg(Color::Black).bg(Color::LightBlue),
+            Severity::Note => Style::default().fg(Color::Black).bg(Color::LightCyan),
+            Severity::Warning => Style::default().fg(Color::Black).bg(Color::Yellow),
+            Severity::Error => Style::default().fg(Color::White).bg(Color::Red),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Chat => write!(f, "Chat"),
+            Severity::Note => write!(f, "Note"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "Error"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpertInfo {
+    pub severity: Severity,
+    /// What the item is about, e.g. "Sequence" or "Malformed"
+    pub group: &'static str,
+    pub summary: String,
+    /// Protocol that reported it, e.g. "TCP"
+    pub protocol: String,
+}
+
+impl ExpertInfo {
+    pub fn new<S: Into<String>>(
+        severity: Severity,
+        group: &'static str,
+        protocol: &str,
+        summary: S,
+    ) -> Self {
+        ExpertInfo {
+            severity,
+            group,
+            summary: summary.into(),
+            protocol: protocol.to_string(),
+        }
+    }
+
+    /// Leaf for the packet details, e.g. `[Expert Info (Note/Sequence): This frame is a
+    /// (suspected) retransmission]`
+    pub fn to_proto_item(&self) -> ProtoItem {
+        ProtoItem::new_leaf(
+            format!(
+                "[Expert Info ({}/{}): {}]",
+                self.severity, self.group, self.summary
+            ),
+            0,
+            0,
+        )
+        .style(self.severity.style())
+    }
+}
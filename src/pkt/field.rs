@@ -0,0 +1,127 @@
+use core::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Type of a filterable field, which decides how filter literals compared against it are parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    UInt,
+    Bool,
+    Ipv4,
+    Ipv6,
+    Ether,
+    Bytes,
+    Str,
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UInt => write!(f, "unsigned integer"),
+            Self::Bool => write!(f, "boolean"),
+            Self::Ipv4 => write!(f, "IPv4 address"),
+            Self::Ipv6 => write!(f, "IPv6 address"),
+            Self::Ether => write!(f, "Ethernet address"),
+            Self::Bytes => write!(f, "byte sequence"),
+            Self::Str => write!(f, "character string"),
+        }
+    }
+}
+
+/// Declaration of a field a dissector exposes to display filters, e.g. `ip.src`
+#[derive(Clone, Copy, Debug)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub description: &'static str,
+}
+
+impl FieldInfo {
+    pub const fn new(name: &'static str, kind: FieldKind, description: &'static str) -> Self {
+        FieldInfo {
+            name,
+            kind,
+            description,
+        }
+    }
+}
+
+/// Value of a field in one packet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldValue {
+    UInt(u64),
+    Bool(bool),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    Ether([u8; 6]),
+    Bytes(Vec<u8>),
+    Str(String),
+}
+
+impl From<u8> for FieldValue {
+    fn from(v: u8) -> Self {
+        FieldValue::UInt(v as u64)
+    }
+}
+
+impl From<u16> for FieldValue {
+    fn from(v: u16) -> Self {
+        FieldValue::UInt(v as u64)
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(v: u32) -> Self {
+        FieldValue::UInt(v as u64)
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(v: u64) -> Self {
+        FieldValue::UInt(v)
+    }
+}
+
+impl From<usize> for FieldValue {
+    fn from(v: usize) -> Self {
+        FieldValue::UInt(v as u64)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(v: bool) -> Self {
+        FieldValue::Bool(v)
+    }
+}
+
+impl From<[u8; 4]> for FieldValue {
+    fn from(v: [u8; 4]) -> Self {
+        FieldValue::Ipv4(Ipv4Addr::from(v))
+    }
+}
+
+impl From<[u8; 16]> for FieldValue {
+    fn from(v: [u8; 16]) -> Self {
+        FieldValue::Ipv6(Ipv6Addr::from(v))
+    }
+}
+
+impl From<[u8; 6]> for FieldValue {
+    fn from(v: [u8; 6]) -> Self {
+        FieldValue::Ether(v)
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(v: String) -> Self {
+        FieldValue::Str(v)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(v: &str) -> Self {
+        FieldValue::Str(v.to_string())
+    }
+}
+
+/// A field name and its value in one packet
+pub type Field = (&'static str, FieldValue);
@@ -0,0 +1,386 @@
+// Following a stream: the payload a TCP connection or UDP conversation carried, in the order it
+//   was sent, with the side that sent each piece. TCP payload is put back in sequence order and
+//   retransmitted bytes are left out, the same way PDUs are reassembled.
+
+use core::fmt;
+use std::collections::HashSet;
+
+use crate::pkt::dissectors::reassembly::StreamBuffer;
+use crate::pkt::dissectors::tcp::{self, Tcp};
+use crate::pkt::dissectors::udp::Udp;
+use crate::pkt::{Layer, Packet};
+
+/// Transport protocols whose streams can be followed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StreamProtocol {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for StreamProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamProtocol::Tcp => write!(f, "TCP"),
+            StreamProtocol::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+/// Address and port of one side of a stream
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Endpoint {
+    pub address: String,
+    pub port: u16,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // IPv6 addresses are bracketed so the port can't be mistaken for part of them
+        if self.address.contains(':') {
+            write!(f, "[{}]:{}", self.address, self.port)
+        } else {
+            write!(f, "{}:{}", self.address, self.port)
+        }
+    }
+}
+
+/// Identifies a stream whichever way its packets go: its protocol and its endpoints, lower
+/// first. TCP connections also carry their stream index, which tells apart connections that
+/// reused the same ports.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StreamId {
+    pub protocol: StreamProtocol,
+    pub endpoints: (Endpoint, Endpoint),
+    pub tcp_stream: Option<usize>,
+}
+
+/// Payload one side sent in one packet
+#[derive(Clone, Debug)]
+pub struct StreamChunk {
+    pub frame_num: usize,
+    pub from_client: bool,
+    pub bytes: Vec<u8>,
+    /// Bytes the capture is missing before the next chunk, for a chunk that only marks the gap
+    pub missing: usize,
+}
+
+/// Everything a stream carried, in order
+#[derive(Clone, Debug)]
+pub struct Stream {
+    pub id: StreamId,
+    /// Position among the capture's streams of the same protocol, in order of their first
+    /// packet
+    pub index: usize,
+    /// The side that sent the first packet, or the SYN if the capture has it
+    pub client: Endpoint,
+    pub server: Endpoint,
+    pub chunks: Vec<StreamChunk>,
+}
+
+impl Stream {
+    /// Bytes the client and the server sent
+    pub fn byte_counts(&self) -> (usize, usize) {
+        self.chunks.iter().fold((0, 0), |(client, server), chunk| {
+            if chunk.from_client {
+                (client + chunk.bytes.len(), server)
+            } else {
+                (client, server + chunk.bytes.len())
+            }
+        })
+    }
+}
+
+/// The transport layer of a packet, as much of it as following a stream needs
+struct Transport<'p> {
+    protocol: StreamProtocol,
+    src: Endpoint,
+    dst: Endpoint,
+    payload: &'p [u8],
+    tcp: Option<&'p Tcp>,
+}
+
+impl<'p> Transport<'p> {
+    /// The first TCP or UDP layer of `pkt`, addressed by the network layer below it
+    fn of(pkt: &'p Packet) -> Option<Self> {
+        let mut addresses = None;
+        for (idx, layer) in pkt.layers.iter().enumerate() {
+            let inner = match layer {
+                Layer::Protocol(inner) => inner,
+                _ => return None,
+            };
+            let (protocol, (sport, dport), range, tcp) = if let Some(tcp) = layer.downcast::<Tcp>()
+            {
+                (
+                    StreamProtocol::Tcp,
+                    tcp.ports(),
+                    tcp.payload_range(),
+                    Some(tcp),
+                )
+            } else if let Some(udp) = layer.downcast::<Udp>() {
+                (StreamProtocol::Udp, udp.ports(), udp.payload_range(), None)
+            } else {
+                addresses = inner.addresses().or(addresses);
+                continue;
+            };
+
+            let (src, dst) = addresses?;
+            let (_, bytepool) = pkt.data_source(pkt.layer_source(idx))?;
+            let end = range.end.min(bytepool.len());
+            let start = range.start.min(end);
+            return Some(Transport {
+                protocol,
+                src: Endpoint {
+                    address: src,
+                    port: sport,
+                },
+                dst: Endpoint {
+                    address: dst,
+                    port: dport,
+                },
+                payload: &bytepool.bytes[start..end],
+                tcp,
+            });
+        }
+        None
+    }
+
+    fn id(&self) -> StreamId {
+        let endpoints = if self.src <= self.dst {
+            (self.src.clone(), self.dst.clone())
+        } else {
+            (self.dst.clone(), self.src.clone())
+        };
+        StreamId {
+            protocol: self.protocol,
+            endpoints,
+            tcp_stream: self.tcp.and_then(Tcp::stream),
+        }
+    }
+}
+
+/// The stream `pkt` belongs to, if it has a TCP or UDP layer. A TCP segment quoted by an ICMP
+/// error is not part of any.
+pub fn stream_id(pkt: &Packet) -> Option<StreamId> {
+    let id = Transport::of(pkt)?.id();
+    match (id.protocol, id.tcp_stream) {
+        (StreamProtocol::Tcp, None) => None,
+        _ => Some(id),
+    }
+}
+
+/// Every `protocol` stream in the capture, in order of their first packet
+pub fn stream_ids(pkts: &[Packet], protocol: StreamProtocol) -> Vec<StreamId> {
+    let mut seen = HashSet::new();
+    pkts.iter()
+        .filter_map(stream_id)
+        .filter(|id| id.protocol == protocol && seen.insert(id.clone()))
+        .collect()
+}
+
+/// Collects the payload of stream `id` from `pkts`. `index` is its position among the
+/// streams of its protocol.
+pub fn follow(pkts: &[Packet], id: &StreamId, index: usize) -> Stream {
+    let transports: Vec<(usize, Transport)> = pkts
+        .iter()
+        .filter_map(|pkt| Transport::of(pkt).map(|t| (pkt.num, t)))
+        .filter(|(_, t)| t.id() == *id)
+        .collect();
+
+    // Whoever answers a SYN is the server
+    let (client, server) = match transports.first() {
+        Some((_, t))
+            if t.tcp
+                .is_some_and(|tcp| tcp.has_flags(tcp::FLAG_SYN | tcp::FLAG_ACK)) =>
+        {
+            (t.dst.clone(), t.src.clone())
+        }
+        Some((_, t)) => (t.src.clone(), t.dst.clone()),
+        None => id.endpoints.clone(),
+    };
+
+    let mut chunks = vec![];
+    // What the client sent, then what the server sent
+    let mut streams: [StreamBuffer; 2] = Default::default();
+    for (frame_num, t) in &transports {
+        let from_client = t.src == client;
+        let tcp = match t.tcp {
+            Some(tcp) => tcp,
+            None => {
+                if !t.payload.is_empty() {
+                    chunks.push(StreamChunk {
+                        frame_num: *frame_num,
+                        from_client,
+                        bytes: t.payload.to_vec(),
+                        missing: 0,
+                    });
+                }
+                continue;
+            }
+        };
+        let stream = &mut streams[if from_client { 0 } else { 1 }];
+        if tcp.has_flags(tcp::FLAG_SYN) {
+            stream.sync(tcp.payload_seq());
+        }
+        if t.payload.is_empty() {
+            continue;
+        }
+        // Bytes that aren't going to turn up are marked as missing, and the stream carries on
+        //   past them
+        let seq = tcp.payload_seq();
+        let give_up = stream.is_past_gap(seq) && !tcp.may_be_reordered();
+        stream.add(*frame_num, seq, t.payload);
+        if give_up {
+            skip_gaps(stream, from_client, &mut chunks);
+        }
+        take_pending(stream, *frame_num, from_client, &mut chunks);
+    }
+    // Whatever is still held back came after a gap that was never filled
+    for (idx, stream) in streams.iter_mut().enumerate() {
+        skip_gaps(stream, idx == 0, &mut chunks);
+    }
+
+    Stream {
+        id: id.clone(),
+        index,
+        client,
+        server,
+        chunks,
+    }
+}
+
+/// Moves the bytes `stream` has in order to `chunks`, one chunk per packet they came from
+fn take_pending(
+    stream: &mut StreamBuffer,
+    frame_num: usize,
+    from_client: bool,
+    chunks: &mut Vec<StreamChunk>,
+) {
+    let (bytes, pieces) = stream.take(stream.pending().len(), frame_num);
+    chunks.extend(pieces.into_iter().map(|piece| StreamChunk {
+        frame_num: piece.frame_num,
+        from_client,
+        bytes: bytes[piece.offset..piece.offset + piece.length].to_vec(),
+        missing: 0,
+    }));
+}
+
+/// Gives up on every gap before the segments `stream` holds back, marking each with a chunk
+/// ahead of the bytes that follow it
+fn skip_gaps(stream: &mut StreamBuffer, from_client: bool, chunks: &mut Vec<StreamChunk>) {
+    while let Some(missing) = stream.skip_gap() {
+        let marker = chunks.len();
+        take_pending(stream, 0, from_client, chunks);
+        let frame_num = chunks.get(marker).map_or(0, |chunk| chunk.frame_num);
+        chunks.insert(
+            marker,
+            StreamChunk {
+                frame_num,
+                from_client,
+                bytes: vec![],
+                missing,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pkt::registry::Registry;
+
+    /// An Ethernet frame carrying a TCP segment from 10.0.0.1:40000 to 10.0.0.5:502
+    fn frame(seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![
+            0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0, 0x11, 0x22, 0x33, 0x44, 0x55,
+        ];
+        bytes.extend([0x08, 0x00]);
+        bytes.extend([0x45, 0, 0, 0, 0, 1, 0x40, 0, 64, 6, 0, 0]);
+        bytes[16..18].copy_from_slice(&(40 + payload.len() as u16).to_be_bytes());
+        bytes.extend([10, 0, 0, 1, 10, 0, 0, 5]);
+        bytes.extend(40000u16.to_be_bytes());
+        bytes.extend(502u16.to_be_bytes());
+        bytes.extend(seq.to_be_bytes());
+        bytes.extend([0, 0, 0, 0, 0x50, flags, 0x20, 0, 0, 0, 0, 0]);
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn decode(frames: &[Vec<u8>]) -> Vec<Packet> {
+        let registry = Registry::with_all_dissectors();
+        frames
+            .iter()
+            .enumerate()
+            .map(|(num, bytes)| {
+                let mut pkt = Packet::new();
+                pkt.num = num;
+                pkt.linktype = pcap_parser::Linktype::ETHERNET;
+                pkt.bytepool.bytes = bytes.clone();
+                pkt.decode(&registry);
+                pkt
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gap_is_marked_and_the_stream_carries_on() {
+        let pkts = decode(&[
+            frame(1000, 0x02, b""),
+            frame(1001, 0x18, b"one "),
+            // "two " never made it into the capture
+            frame(1009, 0x18, b"three "),
+            frame(1015, 0x18, b"four"),
+        ]);
+        let id = stream_id(&pkts[0]).unwrap();
+        let stream = follow(&pkts, &id, 0);
+        let chunks: Vec<(usize, &[u8], usize)> = stream
+            .chunks
+            .iter()
+            .map(|c| (c.frame_num, c.bytes.as_slice(), c.missing))
+            .collect();
+        let expected: Vec<(usize, &[u8], usize)> = vec![
+            (1, b"one ", 0),
+            (2, b"", 4),
+            (2, b"three ", 0),
+            (3, b"four", 0),
+        ];
+        assert_eq!(chunks, expected);
+    }
+
+    #[test]
+    fn segments_out_of_order_are_put_back_in_order() {
+        let pkts = decode(&[
+            frame(1000, 0x02, b""),
+            frame(1001, 0x18, b"one "),
+            frame(1009, 0x18, b"three"),
+            frame(1005, 0x18, b"two "),
+        ]);
+        let id = stream_id(&pkts[0]).unwrap();
+        let stream = follow(&pkts, &id, 0);
+        let bytes: Vec<u8> = stream.chunks.iter().flat_map(|c| c.bytes.clone()).collect();
+        assert_eq!(bytes, b"one two three");
+        assert!(stream.chunks.iter().all(|c| c.missing == 0));
+    }
+
+    #[test]
+    fn connections_reusing_ports_are_separate_streams() {
+        let pkts = decode(&[
+            frame(1000, 0x02, b""),
+            frame(1001, 0x18, b"first"),
+            frame(1006, 0x11, b""),
+            frame(5000, 0x02, b""),
+            frame(5001, 0x18, b"second"),
+        ]);
+        let ids = stream_ids(&pkts, StreamProtocol::Tcp);
+        assert_eq!(
+            ids.iter().map(|id| id.tcp_stream).collect::<Vec<_>>(),
+            vec![Some(0), Some(1)]
+        );
+        assert_eq!(stream_id(&pkts[4]).as_ref(), Some(&ids[1]));
+        assert!(stream_ids(&pkts, StreamProtocol::Udp).is_empty());
+
+        let stream = follow(&pkts, &ids[1], 1);
+        let bytes: Vec<u8> = stream.chunks.iter().flat_map(|c| c.bytes.clone()).collect();
+        assert_eq!(bytes, b"second");
+        assert_eq!(stream.index, 1);
+    }
+}
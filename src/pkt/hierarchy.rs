@@ -0,0 +1,55 @@
+// Protocol hierarchy statistics: every path of layers the capture's packets decode to, e.g.
+//   Ethernet → IPv4 → TCP → Modbus/TCP, with how many packets and bytes take each step.
+
+use crate::pkt::Packet;
+
+/// One protocol at one position in the hierarchy, with the protocols seen on top of it
+#[derive(Clone, Debug)]
+pub struct HierarchyNode {
+    pub label: String,
+    /// Packets that have this layer, and their total length
+    pub packets: usize,
+    pub bytes: usize,
+    /// In order of their first packet
+    pub children: Vec<HierarchyNode>,
+}
+
+impl HierarchyNode {
+    fn new(label: String) -> Self {
+        HierarchyNode {
+            label,
+            packets: 0,
+            bytes: 0,
+            children: vec![],
+        }
+    }
+
+    /// Counts a packet of `bytes` bytes whose layers above this one are `labels`
+    fn add(&mut self, labels: &[String], bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes;
+        let (label, rest) = match labels.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let idx = match self.children.iter().position(|c| c.label == *label) {
+            Some(idx) => idx,
+            None => {
+                self.children.push(HierarchyNode::new(label.clone()));
+                self.children.len() - 1
+            }
+        };
+        self.children[idx].add(rest, bytes);
+    }
+}
+
+/// The hierarchy of the packets of `pkts` at `displayed`, under a "Frame" node that counts
+/// every one of them
+pub fn hierarchy(pkts: &[Packet], displayed: &[usize]) -> HierarchyNode {
+    let mut root = HierarchyNode::new("Frame".to_string());
+    for pkt in displayed.iter().map(|&idx| &pkts[idx]) {
+        let labels: Vec<String> = pkt.layers.iter().map(|layer| layer.label()).collect();
+        root.add(&labels, pkt.caplen());
+    }
+    root
+}
@@ -0,0 +1,88 @@
+// I/O statistics: how many packets and bytes fall into each interval of capture time, counted
+//   from the first packet, as plotted by the I/O graph.
+
+use crate::pkt::timestamp::Timestamp;
+use crate::pkt::Packet;
+
+const NANOS_PER_MS: i128 = 1_000_000;
+const NANOS_PER_SEC: i128 = 1_000 * NANOS_PER_MS;
+const NANOS_PER_MIN: i128 = 60 * NANOS_PER_SEC;
+
+/// Interval lengths to choose from, in nanoseconds, from 1 ms up to 10 min
+pub const INTERVALS: [i128; 19] = [
+    NANOS_PER_MS,
+    2 * NANOS_PER_MS,
+    5 * NANOS_PER_MS,
+    10 * NANOS_PER_MS,
+    20 * NANOS_PER_MS,
+    50 * NANOS_PER_MS,
+    100 * NANOS_PER_MS,
+    200 * NANOS_PER_MS,
+    500 * NANOS_PER_MS,
+    NANOS_PER_SEC,
+    2 * NANOS_PER_SEC,
+    5 * NANOS_PER_SEC,
+    10 * NANOS_PER_SEC,
+    20 * NANOS_PER_SEC,
+    30 * NANOS_PER_SEC,
+    NANOS_PER_MIN,
+    2 * NANOS_PER_MIN,
+    5 * NANOS_PER_MIN,
+    10 * NANOS_PER_MIN,
+];
+
+/// Formats one of `INTERVALS` in its own unit, e.g. `20 ms` or `5 min`
+pub fn format_interval_length(nanos: i128) -> String {
+    if nanos >= NANOS_PER_MIN && nanos % NANOS_PER_MIN == 0 {
+        format!("{} min", nanos / NANOS_PER_MIN)
+    } else if nanos >= NANOS_PER_SEC && nanos % NANOS_PER_SEC == 0 {
+        format!("{} s", nanos / NANOS_PER_SEC)
+    } else {
+        format!("{} ms", nanos / NANOS_PER_MS)
+    }
+}
+
+/// Packets and bytes in one interval
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bin {
+    pub packets: usize,
+    pub bytes: usize,
+    /// Index of the interval's first packet, in capture order
+    pub first: Option<usize>,
+}
+
+/// Interval of `interval` nanoseconds that `timestamp` falls into. Packets captured before the
+/// first one, as happens when clocks step back, count towards the first interval.
+pub fn bin_index(timestamp: &Timestamp, start: &Timestamp, interval: i128) -> usize {
+    (timestamp.nanos_since(start).max(0) / interval) as usize
+}
+
+/// Number of intervals of `interval` nanoseconds it takes to cover `pkts`
+pub fn bin_count(pkts: &[Packet], interval: i128) -> usize {
+    let start = match pkts.first() {
+        Some(pkt) => pkt.timestamp,
+        None => return 0,
+    };
+    pkts.iter()
+        .map(|pkt| bin_index(&pkt.timestamp, &start, interval) + 1)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Packets and bytes per interval of `interval` nanoseconds of the packets of `pkts` at the
+/// indices in `matching`. Every series of the same capture and interval has the same length.
+pub fn bins(pkts: &[Packet], matching: &[usize], interval: i128) -> Vec<Bin> {
+    let mut bins = vec![Bin::default(); bin_count(pkts, interval)];
+    let start = match pkts.first() {
+        Some(pkt) => pkt.timestamp,
+        None => return bins,
+    };
+    for &idx in matching {
+        let pkt = &pkts[idx];
+        let bin = &mut bins[bin_index(&pkt.timestamp, &start, interval)];
+        bin.packets += 1;
+        bin.bytes += pkt.caplen();
+        bin.first = Some(bin.first.map_or(idx, |first| first.min(idx)));
+    }
+    bins
+}
@@ -1,12 +1,29 @@
 use std::fmt;
-use std::fs::File;
+use std::ops::Range;
+use std::rc::Rc;
 
-use tui_tree_widget::TreeItem;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
 
+pub mod capture;
+pub mod conversation;
 pub mod dissectors;
+pub mod expert;
+pub mod field;
+pub mod follow;
+pub mod hierarchy;
+pub mod iostat;
+pub mod prototree;
+pub mod registry;
+pub mod timestamp;
 
-#[allow(dead_code)]
-pub const MTU: usize = 1500;
+use capture::CustomBlock;
+use dissectors::DissectError;
+use expert::{ExpertInfo, Severity};
+use field::{Field, FieldInfo, FieldKind};
+use prototree::ProtoItem;
+use registry::{DissectCtx, NextLayer, ProtocolLayer, Registry, Table};
+use timestamp::Timestamp;
 
 #[derive(Clone, Debug)]
 pub struct BytePool {
@@ -18,65 +35,87 @@ impl BytePool {
         BytePool { bytes: vec![] }
     }
 
-    pub fn hexdump(&self, window_width: usize) -> String {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Hex and ASCII dump sized to `window_width` columns, with the bytes in `highlight`
+    /// shown in reverse video
+    pub fn hexdump(
+        &self,
+        window_width: usize,
+        highlight: Option<Range<usize>>,
+    ) -> Vec<Spans<'static>> {
         const ADDRESS_WIDTH: usize = 4;
         const ROW_PREAMBLE_WIDTH: usize = ADDRESS_WIDTH + 2 + 2; // Hex 0x + ": "
 
-        let useful_space = window_width - ROW_PREAMBLE_WIDTH - 2; // Subtract further 2 for bytes/ascii break
-        let maximum_bytes_per_line = useful_space / 4; // "XX " per byte
+        // Subtract further 2 for bytes/ascii break
+        let useful_space = window_width.saturating_sub(ROW_PREAMBLE_WIDTH + 2);
+        let maximum_bytes_per_line = (useful_space / 4).max(1); // "XX " plus the ASCII column
         let bytes_per_line = (2usize).pow((maximum_bytes_per_line as f32).log2() as u32);
 
-        let mut retstr: String = String::new();
-        retstr.extend([" "; ROW_PREAMBLE_WIDTH]);
+        let highlight = highlight.unwrap_or(0..0);
+        let highlight_style = Style::default()
+            .fg(Color::Black)
+            .bg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD);
+
+        let mut lines: Vec<Spans> = vec![];
 
+        let mut header: String = String::new();
+        header.extend([" "; ROW_PREAMBLE_WIDTH]);
         for i in 0..bytes_per_line {
-            retstr += format!("{:02x} ", i).as_str();
+            header += format!("{:02x} ", i).as_str();
         }
-        retstr += "|\n";
-        for _ in 0..(ROW_PREAMBLE_WIDTH + bytes_per_line * 4) {
-            retstr += "-";
-        }
-        retstr += "\n";
+        header += "|";
+        lines.push(Spans::from(header));
+        lines.push(Spans::from(
+            "-".repeat(ROW_PREAMBLE_WIDTH + bytes_per_line * 4),
+        ));
 
-        let num_lines = self.bytes.len() / bytes_per_line;
-        for i in 0..num_lines {
-            retstr += format!("{:#06X}| ", i * bytes_per_line).as_str();
-            for j in 0..bytes_per_line {
-                retstr += format!("{:02x} ", self.bytes[i * bytes_per_line + j]).as_str();
-            }
-            retstr += "| ";
-            for j in 0..bytes_per_line {
-                let byte = self.bytes[i * bytes_per_line + j];
-                if byte.is_ascii() && !byte.is_ascii_control() {
-                    retstr.push(byte as char);
+        for (line_num, chunk) in self.bytes.chunks(bytes_per_line).enumerate() {
+            let line_start = line_num * bytes_per_line;
+            let mut spans = vec![Span::raw(format!("{:#06X}| ", line_start))];
+
+            // Group consecutive bytes sharing a highlight state into one span per column
+            let mut hex_runs: Vec<(bool, String)> = vec![];
+            let mut ascii_runs: Vec<(bool, String)> = vec![];
+            for (i, byte) in chunk.iter().enumerate() {
+                let lit = highlight.contains(&(line_start + i));
+                let ascii = if byte.is_ascii() && !byte.is_ascii_control() {
+                    *byte as char
                 } else {
-                    retstr.push('.');
+                    '.'
+                };
+                match hex_runs.last_mut() {
+                    Some((run_lit, text)) if *run_lit == lit => {
+                        text.push_str(&format!("{:02x} ", byte));
+                        ascii_runs.last_mut().unwrap().1.push(ascii);
+                    }
+                    _ => {
+                        hex_runs.push((lit, format!("{:02x} ", byte)));
+                        ascii_runs.push((lit, ascii.to_string()));
+                    }
                 }
             }
-            retstr += "\n";
-        }
 
-        let leftover_bytes = self.bytes.len() % bytes_per_line;
-        if leftover_bytes != 0 {
-            retstr += format!("{:#06X}| ", num_lines * bytes_per_line).as_str();
-            for i in 0..leftover_bytes {
-                retstr += format!("{:02x} ", self.bytes[num_lines * bytes_per_line + i]).as_str();
-            }
-            for _ in 0..bytes_per_line - leftover_bytes {
-                retstr += "   ";
-            }
-            retstr += "| ";
-            for j in 0..leftover_bytes {
-                let byte = self.bytes[num_lines * bytes_per_line + j];
-                if byte.is_ascii() && !byte.is_ascii_control() {
-                    retstr.push(byte as char);
+            let to_span = |(lit, text): (bool, String)| {
+                if lit {
+                    Span::styled(text, highlight_style)
                 } else {
-                    retstr.push('.');
+                    Span::raw(text)
                 }
-            }
+            };
+            spans.extend(hex_runs.into_iter().map(to_span));
+            spans.push(Span::raw(format!(
+                "{}| ",
+                "   ".repeat(bytes_per_line - chunk.len())
+            )));
+            spans.extend(ascii_runs.into_iter().map(to_span));
+            lines.push(Spans::from(spans));
         }
 
-        retstr
+        lines
     }
 }
 
@@ -91,39 +130,91 @@ impl fmt::Display for BytePool {
     }
 }
 
+/// Display filter fields describing the frame itself rather than any protocol
+pub const FRAME_FIELDS: &[FieldInfo] = &[
+    FieldInfo::new("frame.number", FieldKind::UInt, "Frame Number"),
+    FieldInfo::new("frame.len", FieldKind::UInt, "Capture Length"),
+    FieldInfo::new("frame.interface_id", FieldKind::UInt, "Interface id"),
+    FieldInfo::new(
+        "frame.time_epoch",
+        FieldKind::UInt,
+        "Epoch Time, in seconds",
+    ),
+];
+
+/// Bytes some layers of a packet were dissected from other than the frame's own, such as a
+/// datagram reassembled from fragments
 #[derive(Clone, Debug)]
-pub enum Layer {
-    Ethernet(dissectors::ethernet::Ethernet),
-    IPv4(dissectors::ipv4::IPv4),
-    Tcp(dissectors::tcp::Tcp),
-    Undecoded(dissectors::undecoded::Undecoded),
+pub struct DataSource {
+    /// Label in the byte view, e.g. "Reassembled IPv4"
+    pub name: String,
+    pub bytepool: BytePool,
+    /// Index of the first layer dissected from these bytes. The layers after it were too, up
+    /// to the next data source.
+    pub first_layer: usize,
+}
+
+impl DataSource {
+    pub fn new(name: &str, bytes: Rc<Vec<u8>>) -> Self {
+        DataSource {
+            name: name.to_string(),
+            bytepool: BytePool {
+                bytes: bytes.to_vec(),
+            },
+            first_layer: 0,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-pub enum LayerHint {
-    Ethernet,
-    IPv4,
-    Tcp,
-    Undecoded,
+pub enum Layer {
+    Protocol(Rc<dyn ProtocolLayer>),
+    Undecoded(dissectors::undecoded::Undecoded),
+    Malformed(dissectors::malformed::Malformed),
 }
 
 impl Layer {
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
+    pub fn to_proto_item(&self) -> ProtoItem {
+        match self {
+            Layer::Protocol(inner) => inner.to_proto_item(),
+            Layer::Undecoded(inner) => inner.to_proto_item(),
+            Layer::Malformed(inner) => inner.to_proto_item(),
+        }
+    }
+
+    /// Short protocol name of this layer
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Protocol(inner) => inner.name(),
+            Layer::Undecoded(_) => "data",
+            Layer::Malformed(_) => "_ws.malformed",
+        }
+    }
+
+    /// Protocol column text of this layer
+    pub fn label(&self) -> String {
         match self {
-            Layer::Ethernet(inner) => inner.to_tree_item(),
-            Layer::IPv4(inner) => inner.to_tree_item(),
-            Layer::Tcp(inner) => inner.to_tree_item(),
-            Layer::Undecoded(inner) => inner.to_tree_item(),
+            Layer::Protocol(inner) => inner.label(),
+            Layer::Undecoded(_) => "Data".to_string(),
+            Layer::Malformed(_) => "Malformed Packet".to_string(),
+        }
+    }
+
+    /// The concrete protocol layer, if this layer is one of type `T`
+    pub fn downcast<T: 'static>(&self) -> Option<&T> {
+        match self {
+            Layer::Protocol(inner) => inner.as_any().downcast_ref::<T>(),
+            _ => None,
         }
     }
 }
+
 impl fmt::Display for Layer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Layer::Ethernet(layer) => write!(f, "{}", layer)?,
-            Layer::IPv4(layer) => write!(f, "{}", layer)?,
-            Layer::Tcp(layer) => write!(f, "{}", layer)?,
+            Layer::Protocol(layer) => write!(f, "{}", layer)?,
             Layer::Undecoded(layer) => write!(f, "{}", layer)?,
+            Layer::Malformed(layer) => write!(f, "{}", layer)?,
         }
         Ok(())
     }
@@ -131,11 +222,109 @@ impl fmt::Display for Layer {
 
 #[derive(Clone, Debug)]
 pub struct Packet {
-    num: usize,
+    pub num: usize,
     pub bytepool: BytePool,
     pub linktype: pcap_parser::Linktype,
+    /// Index into the capture's interface list
+    pub interface_id: usize,
+    pub timestamp: Timestamp,
     pub decoded: bool,
     pub layers: Vec<Layer>,
+    /// Reassembled payloads later layers were dissected from
+    pub data_sources: Vec<DataSource>,
+    /// pcapng Custom Blocks read just before the packet, or after it when it is the last one
+    pub custom_blocks: Vec<CustomBlock>,
+}
+
+/// Runs dissectors over `bytes`, which start at offset `start` in the packet, beginning with
+/// `next_layer` and following whatever each dissector says comes next. Once a dissector
+/// reassembles a payload, the layers above it are dissected from that payload instead, which
+/// is added to `ctx.data_sources`.
+pub fn dissect_layers(
+    ctx: &mut DissectCtx,
+    next_layer: NextLayer,
+    start: usize,
+    bytes: &[u8],
+) -> Vec<Layer> {
+    let mut layers = vec![];
+    let mut reassembled = dissect_data_source(ctx, next_layer, start, bytes, &mut layers);
+    while let Some((next_layer, mut source)) = reassembled {
+        source.first_layer = layers.len();
+        let bytepool = source.bytepool.clone();
+        ctx.payload_end = Some(bytepool.len());
+        ctx.data_sources.push(source);
+        reassembled = dissect_data_source(ctx, next_layer, 0, &bytepool.bytes, &mut layers);
+    }
+    layers
+}
+
+/// Appends the layers decoded from one data source to `layers`, stopping early to return the
+/// payload a dissector reassembled and what to dissect it as
+fn dissect_data_source(
+    ctx: &mut DissectCtx,
+    mut next_layer: NextLayer,
+    start: usize,
+    bytes: &[u8],
+    layers: &mut Vec<Layer>,
+) -> Option<(NextLayer, DataSource)> {
+    let packet_end = start + bytes.len();
+    let mut next_byte = start;
+
+    // Marks everything from `from` to the end of `bytes` as malformed
+    let malformed = |err: DissectError, from: usize| {
+        Layer::Malformed(dissectors::malformed::Malformed::new(
+            err,
+            from,
+            packet_end - from,
+        ))
+    };
+
+    loop {
+        let end = ctx.payload_end.unwrap_or(packet_end).min(packet_end);
+        if next_byte >= end {
+            break;
+        }
+        let layer_bytes = &bytes[next_byte - start..end - start];
+        let dissector = match ctx.registry.lookup(&next_layer) {
+            Some(dissector) => dissector,
+            None => {
+                // Everything not positively identified is undecoded
+                layers.push(Layer::Undecoded(
+                    dissectors::undecoded::Undecoded::from_bytes(next_byte, layer_bytes),
+                ));
+                break;
+            }
+        };
+
+        match dissector.dissect(ctx, next_byte, layer_bytes) {
+            Ok(dissection) => {
+                layers.push(Layer::Protocol(Rc::from(dissection.layer)));
+                // A dissector must consume at least one byte and stay within the packet,
+                //   otherwise this loop would spin forever or index out of bounds
+                if dissection.next_byte <= next_byte || dissection.next_byte > packet_end {
+                    let reason = format!(
+                        "{} dissector advanced to offset {} from offset {} in a {} byte packet",
+                        dissector.name(),
+                        dissection.next_byte,
+                        next_byte,
+                        packet_end
+                    );
+                    layers.push(malformed(DissectError::new(reason), next_byte));
+                    break;
+                }
+                if let Some(source) = dissection.reassembled {
+                    return Some((dissection.next, source));
+                }
+                next_byte = dissection.next_byte;
+                next_layer = dissection.next;
+            }
+            Err(err) => {
+                layers.push(malformed(err, next_byte));
+                break;
+            }
+        }
+    }
+    None
 }
 
 impl Packet {
@@ -144,81 +333,166 @@ impl Packet {
             num: 0,
             bytepool: BytePool::new(),
             linktype: pcap_parser::Linktype::NULL,
+            interface_id: 0,
+            timestamp: Timestamp::default(),
             decoded: false,
             layers: vec![],
+            data_sources: vec![],
+            custom_blocks: vec![],
         }
     }
 
-    pub fn decode(&mut self) {
-        // TODO: fill out current stub
-        let num_bytes = self.bytepool.bytes.len();
-        let mut next_byte: usize = 0;
+    pub fn decode(&mut self, registry: &Registry) {
+        let mut ctx = DissectCtx::new(registry, self.num, self.timestamp);
 
-        // Derive the initial layer hint from the link type specified by the pcap
-        let mut layer_hint = match self.linktype {
-            pcap_parser::Linktype::ETHERNET => LayerHint::Ethernet,
-            _ => LayerHint::Undecoded,
+        // Derive the first dissector from the link type specified by the pcap. Raw IP has no
+        //   link header at all, only the version nibble to tell IPv4 from IPv6.
+        let linktype = match (self.linktype, self.bytepool.bytes.first()) {
+            (pcap_parser::Linktype::RAW, Some(byte)) if byte >> 4 == 6 => {
+                pcap_parser::Linktype::IPV6
+            }
+            (pcap_parser::Linktype::RAW, _) => pcap_parser::Linktype::IPV4,
+            (linktype, _) => linktype,
         };
+        let first_layer = NextLayer::Table(Table::LinkType, linktype.0 as u32);
 
-        while next_byte != num_bytes {
-            match layer_hint {
-                LayerHint::Ethernet => {
-                    let (layer, next_byte_local, layer_hint_local) =
-                        dissectors::ethernet::Ethernet::from_bytes(
-                            next_byte,
-                            &self.bytepool.bytes[next_byte..],
-                        );
-                    self.layers.push(Layer::Ethernet(layer));
-                    next_byte = next_byte_local;
-                    layer_hint = layer_hint_local;
-                }
-                LayerHint::IPv4 => {
-                    let (layer, next_byte_local, layer_hint_local) =
-                        dissectors::ipv4::IPv4::from_bytes(
-                            next_byte,
-                            &self.bytepool.bytes[next_byte..],
-                        );
-                    next_byte = next_byte_local;
-                    self.layers.push(Layer::IPv4(layer));
-                    layer_hint = layer_hint_local;
-                }
-                LayerHint::Tcp => {
-                    let (layer, next_byte_local, layer_hint_local) =
-                        dissectors::tcp::Tcp::from_bytes(
-                            next_byte,
-                            &self.bytepool.bytes[next_byte..],
-                        );
-                    next_byte = next_byte_local;
-                    self.layers.push(Layer::Tcp(layer));
-                    layer_hint = layer_hint_local;
-                }
-                LayerHint::Undecoded => {
-                    // TODO: parse higher layer protocols
-                    // This will need a more thoughtful soluntion; maybe dissectors also return
-                    //   a hint enum on what dissectors should be used on the payload
-                    // TODO: look at how wireshark does this
-                    // For now, just assume everything else not positively identified is undecoded
-                    let (layer, next_byte_local, layer_hint_local) =
-                        dissectors::undecoded::Undecoded::from_bytes(
-                            next_byte,
-                            &self.bytepool.bytes[next_byte..],
-                        );
-                    next_byte = next_byte_local;
-                    self.layers.push(Layer::Undecoded(layer));
-                    layer_hint = layer_hint_local;
+        self.layers = dissect_layers(&mut ctx, first_layer, 0, &self.bytepool.bytes);
+        self.data_sources = ctx.data_sources;
+        self.decoded = true;
+    }
+
+    pub fn caplen(&self) -> usize {
+        self.bytepool.bytes.len()
+    }
+
+    /// Source, destination, protocol and info columns of the packet list
+    pub fn columns(&self) -> (String, String, String, String) {
+        let mut source = String::new();
+        let mut destination = String::new();
+        let mut protocol = "Data".to_string();
+        let mut info = String::new();
+        let mut malformed = false;
+
+        for layer in &self.layers {
+            match layer {
+                Layer::Protocol(inner) => {
+                    if let Some((src, dst)) = inner.addresses() {
+                        source = src;
+                        destination = dst;
+                    }
+                    protocol = inner.label();
+                    info = inner.info();
                 }
+                Layer::Malformed(_) => malformed = true,
+                Layer::Undecoded(_) => {}
+            }
+        }
+        if malformed {
+            info += " [Malformed Packet]";
+        }
+
+        (source, destination, protocol, info)
+    }
+
+    /// Every display filter field of the frame and its layers
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![
+            ("frame.number", self.num.into()),
+            ("frame.len", self.caplen().into()),
+            ("frame.interface_id", self.interface_id.into()),
+            ("frame.time_epoch", (self.timestamp.secs as u64).into()),
+        ];
+        for layer in &self.layers {
+            if let Layer::Protocol(inner) = layer {
+                fields.extend(inner.fields());
+            }
+        }
+        fields
+    }
+
+    /// Expert info of every layer, with malformed layers as errors
+    pub fn expert_infos(&self) -> Vec<ExpertInfo> {
+        let mut infos = vec![];
+        for layer in &self.layers {
+            match layer {
+                Layer::Protocol(inner) => infos.extend(inner.expert_infos()),
+                Layer::Malformed(inner) => infos.push(ExpertInfo::new(
+                    Severity::Error,
+                    "Malformed",
+                    "Malformed",
+                    format!("Malformed Packet: {}", inner.reason),
+                )),
+                Layer::Undecoded(_) => {}
             }
         }
+        infos
     }
 
-    pub fn to_tree_item<'a, 'b>(&'a self) -> TreeItem<'b> {
-        TreeItem::new(
-            self.to_string(),
-            //vec![TreeItem::new_leaf(self.layers[0].to_string())],
+    /// Frame summary and every layer, for the packet details pane
+    pub fn detail_items(&self, time: &str) -> Vec<ProtoItem> {
+        let len = self.caplen();
+        let mut frame_children = vec![
+            ProtoItem::new_leaf(format!("Arrival Time: {}", time), 0, 0),
+            ProtoItem::new_leaf(
+                format!(
+                    "Epoch Time: {}.{:09} seconds",
+                    self.timestamp.secs, self.timestamp.nanos
+                ),
+                0,
+                0,
+            ),
+            ProtoItem::new_leaf(format!("Frame Number: {}", self.num), 0, 0),
+            ProtoItem::new_leaf(format!("Capture Length: {} bytes", len), 0, 0),
+            ProtoItem::new_leaf(format!("Interface id: {}", self.interface_id), 0, 0),
+            ProtoItem::new_leaf(format!("Encapsulation type: {}", self.linktype), 0, 0),
+        ];
+        frame_children.extend(self.custom_blocks.iter().map(|b| b.to_proto_item()));
+        let frame = ProtoItem::new(
+            format!(
+                "Frame {}: {} bytes captured on interface {}",
+                self.num, len, self.interface_id
+            ),
+            0,
+            len,
+            frame_children,
+        );
+
+        let mut items = vec![frame];
+        items.extend(
             self.layers
                 .iter()
-                .map(|l| l.to_tree_item())
-                .collect::<Vec<TreeItem>>(),
+                .enumerate()
+                .map(|(idx, layer)| layer.to_proto_item().with_source(self.layer_source(idx))),
+        );
+        items
+    }
+
+    /// Data source layer `idx` was dissected from, as numbered in `ProtoItem::source`
+    pub fn layer_source(&self, idx: usize) -> usize {
+        self.data_sources
+            .iter()
+            .filter(|s| s.first_layer <= idx)
+            .count()
+    }
+
+    /// Name and bytes of data source `source`, as numbered in `ProtoItem::source`
+    pub fn data_source(&self, source: usize) -> Option<(&str, &BytePool)> {
+        match source {
+            0 => Some(("Frame", &self.bytepool)),
+            _ => self
+                .data_sources
+                .get(source - 1)
+                .map(|s| (s.name.as_str(), &s.bytepool)),
+        }
+    }
+
+    /// One-line summary of the packet, with its capture time already formatted
+    pub fn summary(&self, time: &str) -> String {
+        format!(
+            "Packet Num {} | {} | [ {}B ]",
+            self.num,
+            time,
+            self.bytepool.bytes.len()
         )
     }
 }
@@ -233,43 +507,3 @@ impl fmt::Display for Packet {
         )
     }
 }
-
-pub fn legacy_pcap_to_packet(path: String) -> Vec<Packet> {
-    let file = File::open(path).unwrap();
-    let mut num_datablocks: usize = 0;
-    let mut reader = pcap_parser::create_reader(65536, file).expect("PcapNGReader");
-
-    let mut ret_vec: Vec<Packet> = vec![];
-    let mut linktype: pcap_parser::Linktype = pcap_parser::Linktype::NULL;
-
-    loop {
-        match reader.next() {
-            Ok((offset, block)) => {
-                match block {
-                    pcap_parser::PcapBlockOwned::Legacy(legacyblock) => {
-                        let mut pkt = Packet::new();
-                        pkt.num = num_datablocks;
-                        num_datablocks += 1;
-                        let pkt_len = legacyblock.caplen as usize;
-                        pkt.bytepool.bytes.reserve(pkt_len);
-                        pkt.bytepool.bytes.extend_from_slice(legacyblock.data);
-                        pkt.linktype = linktype;
-                        ret_vec.push(pkt);
-                    }
-                    pcap_parser::PcapBlockOwned::LegacyHeader(legacyheader) => {
-                        linktype = legacyheader.network;
-                    }
-                    pcap_parser::PcapBlockOwned::NG(_ng) => {}
-                }
-                reader.consume(offset);
-            }
-            Err(pcap_parser::PcapError::Eof) => break,
-            Err(pcap_parser::PcapError::Incomplete) => {
-                reader.refill().unwrap();
-            }
-            Err(e) => panic!("error while reading: {:?}", e),
-        }
-    }
-
-    ret_vec
-}
@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+use tui::style::Style;
+use tui_tree_widget::TreeItem;
+
+/// A node of the packet details tree, remembering which bytes of the packet it was decoded from
+/// so the byte view can highlight them
+#[derive(Clone, Debug)]
+pub struct ProtoItem {
+    pub text: String,
+    /// Absolute offset of the first byte in the packet
+    pub start: usize,
+    /// Number of bytes; 0 for items not backed by packet bytes
+    pub length: usize,
+    /// Data source `start` and `length` refer to: 0 for the frame, then the packet's
+    /// reassembled payloads in order
+    pub source: usize,
+    pub style: Style,
+    pub children: Vec<ProtoItem>,
+}
+
+impl ProtoItem {
+    pub fn new<T: Into<String>>(
+        text: T,
+        start: usize,
+        length: usize,
+        children: Vec<ProtoItem>,
+    ) -> Self {
+        ProtoItem {
+            text: text.into(),
+            start,
+            length,
+            source: 0,
+            style: Style::default(),
+            children,
+        }
+    }
+
+    pub fn new_leaf<T: Into<String>>(text: T, start: usize, length: usize) -> Self {
+        ProtoItem::new(text, start, length, vec![])
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Points this item and all its children at data source `source`
+    pub fn with_source(mut self, source: usize) -> Self {
+        self.source = source;
+        self.children = self
+            .children
+            .into_iter()
+            .map(|c| c.with_source(source))
+            .collect();
+        self
+    }
+
+    pub fn add_child(&mut self, child: ProtoItem) {
+        self.children.push(child);
+    }
+
+    pub fn range(&self) -> Option<Range<usize>> {
+        if self.length == 0 {
+            None
+        } else {
+            Some(self.start..self.start + self.length)
+        }
+    }
+
+    pub fn to_tree_item<'b>(&self) -> TreeItem<'b> {
+        let children: Vec<TreeItem> = self.children.iter().map(|c| c.to_tree_item()).collect();
+        TreeItem::new(self.text.clone(), children).style(self.style)
+    }
+
+    /// The item at `path`, as selected in a `TreeState` built from `items`
+    pub fn item_at<'i>(items: &'i [ProtoItem], path: &[usize]) -> Option<&'i ProtoItem> {
+        let (first, rest) = path.split_first()?;
+        let item = items.get(*first)?;
+        if rest.is_empty() {
+            Some(item)
+        } else {
+            ProtoItem::item_at(&item.children, rest)
+        }
+    }
+}
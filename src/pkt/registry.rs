@@ -0,0 +1,308 @@
+use core::fmt;
+use std::any::Any;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::rc::Rc;
+
+use crate::filter::NameKind;
+use crate::pkt::dissectors::{self, DissectError};
+use crate::pkt::expert::ExpertInfo;
+use crate::pkt::field::{Field, FieldInfo};
+use crate::pkt::prototree::ProtoItem;
+use crate::pkt::timestamp::Timestamp;
+use crate::pkt::{DataSource, FRAME_FIELDS};
+
+/// Dispatch tables dissectors register themselves in, in the spirit of Wireshark's dissector
+/// tables. The key type depends on the table: a pcap link type, an ethertype, an IP protocol
+/// number or a transport port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Table {
+    LinkType,
+    EtherType,
+    IpProto,
+    TcpPort,
+    UdpPort,
+}
+
+/// Which dissector should decode the bytes following a layer
+#[derive(Clone, Debug)]
+pub enum NextLayer {
+    /// Look the key up in a dispatch table
+    Table(Table, u32),
+    /// Look up both ports of a transport header, trying the lower port first
+    Ports(Table, u16, u16),
+    /// Call a dissector by name
+    Named(&'static str),
+    /// Nothing we can decode follows
+    Undecoded,
+}
+
+/// A decoded protocol layer, as produced by a `Dissector`
+pub trait ProtocolLayer: fmt::Display + fmt::Debug {
+    /// Short protocol name, matching the name of the dissector that produced it
+    fn name(&self) -> &'static str;
+
+    /// Details tree for this layer, with byte ranges for every field
+    fn to_proto_item(&self) -> ProtoItem;
+
+    /// Protocol column text in the packet list
+    fn label(&self) -> String {
+        self.name().to_uppercase()
+    }
+
+    /// Info column text in the packet list, when this is the topmost layer
+    fn info(&self) -> String {
+        self.to_string()
+    }
+
+    /// Source and destination column text, for layers that carry addresses. The topmost
+    /// layer that has them wins.
+    fn addresses(&self) -> Option<(String, String)> {
+        None
+    }
+
+    /// Values of the display filter fields this layer's dissector declares
+    fn fields(&self) -> Vec<Field> {
+        vec![]
+    }
+
+    /// Problems and notable events the dissector found in this layer
+    fn expert_infos(&self) -> Vec<ExpertInfo> {
+        vec![]
+    }
+
+    /// Gives access to the concrete layer type, for code that needs more than the trait exposes
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Output of a successful dissection
+pub struct Dissection {
+    pub layer: Box<dyn ProtocolLayer>,
+    /// Offset of the first byte after this layer
+    pub next_byte: usize,
+    pub next: NextLayer,
+    /// Payload this layer completed by reassembling it from several packets. When set, `next`
+    /// is dissected from these bytes instead of the rest of the packet.
+    pub reassembled: Option<DataSource>,
+}
+
+impl Dissection {
+    pub fn new<L: ProtocolLayer + 'static>(layer: L, next_byte: usize, next: NextLayer) -> Self {
+        Dissection {
+            layer: Box::new(layer),
+            next_byte,
+            next,
+            reassembled: None,
+        }
+    }
+
+    /// Hands `bytes`, labelled `name` in the byte view, to the next layer
+    pub fn with_reassembled(mut self, name: &str, bytes: Rc<Vec<u8>>) -> Self {
+        self.reassembled = Some(DataSource::new(name, bytes));
+        self
+    }
+}
+
+/// Per-packet information available to every dissector. Lower layers fill in the addresses
+/// and ports they decode so higher layers can tell conversations apart.
+#[derive(Clone)]
+pub struct DissectCtx<'a> {
+    /// For dissectors that decode nested packets themselves, such as ICMP errors
+    pub registry: &'a Registry,
+    /// Number of the packet being dissected
+    pub frame_num: usize,
+    pub timestamp: Timestamp,
+    /// Network layer source and destination, once an IP layer has been decoded
+    pub net_src: Option<IpAddr>,
+    pub net_dst: Option<IpAddr>,
+    /// Transport layer ports, once a TCP or UDP layer has been decoded
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// Offset just past the payload of the innermost layer that declares its own length, such
+    /// as IPv4's total length. Bytes beyond it are link layer padding and are not dissected.
+    pub payload_end: Option<usize>,
+    /// Set while dissecting the original datagram quoted in an ICMP error. Its headers are
+    /// usually cut short, and they must not update any conversation state.
+    pub in_error_packet: bool,
+    /// Reassembled payloads layers of this packet were dissected from, in order
+    pub data_sources: Vec<DataSource>,
+}
+
+impl<'a> DissectCtx<'a> {
+    pub fn new(registry: &'a Registry, frame_num: usize, timestamp: Timestamp) -> Self {
+        DissectCtx {
+            registry,
+            frame_num,
+            timestamp,
+            net_src: None,
+            net_dst: None,
+            src_port: 0,
+            dst_port: 0,
+            payload_end: None,
+            in_error_packet: false,
+            data_sources: vec![],
+        }
+    }
+
+    /// Context for dissecting a datagram quoted in an ICMP error carried by this packet
+    pub fn for_error_packet(&self) -> Self {
+        DissectCtx {
+            in_error_packet: true,
+            ..DissectCtx::new(self.registry, self.frame_num, self.timestamp)
+        }
+    }
+}
+
+/// How much of a TCP byte stream the PDU at its start takes up, as told by the application
+/// protocol's dissector
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PduLength {
+    /// The whole PDU is this many bytes long, which may be more than have arrived so far
+    Length(usize),
+    /// Its length isn't known until at least this many more bytes arrive
+    NeedMore(usize),
+}
+
+pub trait Dissector {
+    /// Short protocol name dissectors are registered and looked up under, e.g. "eth" or "tcp"
+    fn name(&self) -> &'static str;
+
+    /// Decodes the layer starting at `next_byte`. `bytes` runs from `next_byte` to the end of
+    /// the packet.
+    fn dissect(
+        &self,
+        ctx: &mut DissectCtx,
+        next_byte: usize,
+        bytes: &[u8],
+    ) -> Result<Dissection, DissectError>;
+
+    /// Fields this dissector's layers expose to display filters
+    fn fields(&self) -> &'static [FieldInfo] {
+        &[]
+    }
+
+    /// For protocols carried over TCP whose PDUs can span segments or share one: the length of
+    /// the PDU at the start of `bytes`, the stream's bytes no PDU has claimed yet. TCP then only
+    /// hands this dissector whole PDUs, reassembled from several segments if need be. When
+    /// `bytes` can't be the start of a PDU, claim all of them so the bad data gets reported
+    /// rather than stalling the stream. Dissectors that take each segment as it comes return
+    /// None.
+    fn pdu_length(&self, _bytes: &[u8]) -> Option<PduLength> {
+        None
+    }
+}
+
+/// User settings that change how packets are dissected
+#[derive(Clone, Debug)]
+pub struct Preferences {
+    /// Verify IPv4, TCP, UDP and ICMP checksums. Hosts that offload checksumming to the network
+    /// card capture their outgoing packets before the checksum is filled in, so every one of
+    /// them would be flagged as bad.
+    pub validate_checksums: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            validate_checksums: true,
+        }
+    }
+}
+
+/// All known dissectors and the dispatch tables that select between them
+#[derive(Default)]
+pub struct Registry {
+    dissectors: HashMap<&'static str, Box<dyn Dissector>>,
+    tables: HashMap<(Table, u32), &'static str>,
+    fields: HashMap<&'static str, FieldInfo>,
+    /// Other names filters may use for a dissector, mapped to the dissector's name
+    aliases: HashMap<&'static str, &'static str>,
+    pub prefs: Preferences,
+}
+
+impl Registry {
+    /// A registry holding every dissector under `pkt::dissectors`
+    pub fn with_all_dissectors() -> Self {
+        let mut registry = Registry::default();
+        registry.add_fields(FRAME_FIELDS);
+        dissectors::register_all(&mut registry);
+        registry
+    }
+
+    /// A registry holding every dissector, with fresh conversation state and `prefs`
+    pub fn with_prefs(prefs: Preferences) -> Self {
+        let mut registry = Registry::with_all_dissectors();
+        registry.prefs = prefs;
+        registry
+    }
+
+    fn add_fields(&mut self, fields: &'static [FieldInfo]) {
+        for field in fields {
+            self.fields.insert(field.name, *field);
+        }
+    }
+
+    pub fn register<D: Dissector + 'static>(&mut self, dissector: D) {
+        self.add_fields(dissector.fields());
+        self.dissectors
+            .insert(dissector.name(), Box::new(dissector));
+    }
+
+    /// Routes `key` in `table` to the dissector registered as `name`
+    pub fn add_to_table(&mut self, table: Table, key: u32, name: &'static str) {
+        self.tables.insert((table, key), name);
+    }
+
+    /// Lets filters refer to the dissector registered as `name` as `alias` too
+    pub fn add_alias(&mut self, alias: &'static str, name: &'static str) {
+        self.aliases.insert(alias, name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Dissector> {
+        self.dissectors.get(name).map(|d| d.as_ref())
+    }
+
+    fn lookup_table(&self, table: Table, key: u32) -> Option<&dyn Dissector> {
+        self.tables
+            .get(&(table, key))
+            .and_then(|name| self.get(name))
+    }
+
+    /// Declaration of the display filter field called `name`, if a dissector has one
+    pub fn field_info(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.get(name)
+    }
+
+    /// What `name` refers to in a display filter, if anything
+    pub fn lookup_name(&self, name: &str) -> Option<NameKind> {
+        if let Some(field) = self.fields.get(name) {
+            return Some(NameKind::Field(field.kind));
+        }
+        if let Some(alias) = self.aliases.get(name) {
+            return Some(NameKind::Protocol(alias));
+        }
+        match name {
+            "frame" => Some(NameKind::Protocol("frame")),
+            "data" => Some(NameKind::Protocol("data")),
+            "_ws.malformed" => Some(NameKind::Protocol("_ws.malformed")),
+            _ => self
+                .dissectors
+                .get_key_value(name)
+                .map(|(name, _)| NameKind::Protocol(name)),
+        }
+    }
+
+    /// Finds the dissector for `next`, if any is registered
+    pub fn lookup(&self, next: &NextLayer) -> Option<&dyn Dissector> {
+        match next {
+            NextLayer::Table(table, key) => self.lookup_table(*table, *key),
+            NextLayer::Ports(table, src, dst) => {
+                let (low, high) = if src <= dst { (src, dst) } else { (dst, src) };
+                self.lookup_table(*table, *low as u32)
+                    .or_else(|| self.lookup_table(*table, *high as u32))
+            }
+            NextLayer::Named(name) => self.get(name),
+            NextLayer::Undecoded => None,
+        }
+    }
+}
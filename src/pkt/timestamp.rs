@@ -0,0 +1,135 @@
+use core::fmt;
+
+use chrono::{DateTime, Local, Utc};
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Capture time of a packet, as seconds and nanoseconds since the Unix epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32,
+    /// Number of fractional digits the capture actually resolves (6 for usec, 9 for nsec)
+    pub digits: u8,
+}
+
+impl Timestamp {
+    /// Builds a timestamp from a raw count of `resolution` units per second, plus an offset in
+    /// seconds, as stored by pcap and pcapng
+    pub fn from_units(units: u64, resolution: u64, offset: u64) -> Self {
+        let resolution = resolution.max(1);
+        let secs = (units / resolution).wrapping_add(offset) as i64;
+        let frac = (units % resolution) as u128;
+        let nanos = (frac * NANOS_PER_SEC as u128 / resolution as u128) as u32;
+        Timestamp {
+            secs,
+            nanos,
+            digits: Self::digits_for(resolution),
+        }
+    }
+
+    fn digits_for(resolution: u64) -> u8 {
+        let mut digits = 0u8;
+        let mut res = 1u64;
+        while res < resolution && digits < 9 {
+            res = res.saturating_mul(10);
+            digits += 1;
+        }
+        digits
+    }
+
+    pub fn as_nanos(&self) -> i128 {
+        self.secs as i128 * NANOS_PER_SEC + self.nanos as i128
+    }
+
+    /// Signed time elapsed since `earlier`, in nanoseconds
+    pub fn nanos_since(&self, earlier: &Timestamp) -> i128 {
+        self.as_nanos() - earlier.as_nanos()
+    }
+
+    fn fraction(&self) -> String {
+        format_fraction(self.nanos as i128, self.digits)
+    }
+}
+
+fn format_fraction(nanos: i128, digits: u8) -> String {
+    if digits == 0 {
+        return String::new();
+    }
+    let scaled = nanos / 10i128.pow(9 - digits as u32);
+    format!(".{:0width$}", scaled, width = digits as usize)
+}
+
+/// Formats a signed nanosecond interval as seconds, e.g. `-0.000120`
+pub fn format_interval(nanos: i128, digits: u8) -> String {
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.abs();
+    format!(
+        "{}{}{}",
+        sign,
+        nanos / NANOS_PER_SEC,
+        format_fraction(nanos % NANOS_PER_SEC, digits)
+    )
+}
+
+/// How packet times are shown in the packet list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Date and time of day, in the local timezone
+    Absolute,
+    /// Date and time of day, in UTC
+    Utc,
+    /// Seconds since the first packet of the capture
+    Relative,
+    /// Seconds since the previous displayed packet
+    Delta,
+    /// Seconds since the Unix epoch
+    Epoch,
+}
+
+impl TimeFormat {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Absolute => Self::Utc,
+            Self::Utc => Self::Relative,
+            Self::Relative => Self::Delta,
+            Self::Delta => Self::Epoch,
+            Self::Epoch => Self::Absolute,
+        }
+    }
+
+    /// Formats `ts`, given the first packet of the capture and the previously displayed packet
+    pub fn format(self, ts: &Timestamp, first: &Timestamp, prev_displayed: &Timestamp) -> String {
+        match self {
+            Self::Absolute | Self::Utc => {
+                let utc = match DateTime::<Utc>::from_timestamp(ts.secs, ts.nanos) {
+                    Some(utc) => utc,
+                    None => return format!("{}{}", ts.secs, ts.fraction()),
+                };
+                let date = if self == Self::Absolute {
+                    utc.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                } else {
+                    utc.format("%Y-%m-%d %H:%M:%S").to_string()
+                };
+                format!("{}{}", date, ts.fraction())
+            }
+            Self::Relative => format_interval(ts.nanos_since(first), ts.digits),
+            Self::Delta => format_interval(ts.nanos_since(prev_displayed), ts.digits),
+            Self::Epoch => format_interval(ts.as_nanos(), ts.digits),
+        }
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Absolute => write!(f, "Date and Time of Day"),
+            Self::Utc => write!(f, "UTC Date and Time of Day"),
+            Self::Relative => write!(f, "Seconds Since Beginning of Capture"),
+            Self::Delta => write!(f, "Seconds Since Previous Displayed Packet"),
+            Self::Epoch => write!(f, "Seconds Since Epoch"),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Protocol Hierarchy pane state. The protocol hierarchy of the displayed packets as a tree,
+//   opened all the way down, with the share of packets and bytes that each protocol accounts for.
+
+use tui_tree_widget::TreeItem;
+
+use crate::pkt::hierarchy::{self, HierarchyNode};
+use crate::pkt::Packet;
+use crate::statefultree::StatefulTree;
+
+/// Indent the tree widget adds per level
+const INDENT: usize = 2;
+
+pub struct HierarchyView<'a> {
+    pub tree: StatefulTree<'a>,
+    /// Width of the protocol column, so the numbers line up whatever the depth
+    label_width: usize,
+}
+
+impl<'a> HierarchyView<'a> {
+    pub fn new(pkts: &[Packet], displayed: &[usize]) -> Self {
+        let root = hierarchy::hierarchy(pkts, displayed);
+        let label_width = widest_label(&root, 0).max("Protocol".len());
+        let totals = (root.packets.max(1), root.bytes.max(1));
+        let items = vec![to_tree_item(&root, 0, label_width, totals)];
+
+        let mut tree = StatefulTree::with_items(items);
+        let mut paths = vec![];
+        open_paths(&root, vec![0], &mut paths);
+        for path in paths {
+            tree.state.open(path);
+        }
+        tree.first();
+        HierarchyView { tree, label_width }
+    }
+
+    /// Column headings, lined up with the rows
+    pub fn header(&self) -> String {
+        format!(
+            "{:<width$}  {:>9}  {:>8}  {:>9}  {:>10}",
+            "Protocol",
+            "% Packets",
+            "Packets",
+            "% Bytes",
+            "Bytes",
+            width = self.label_width
+        )
+    }
+}
+
+fn widest_label(node: &HierarchyNode, depth: usize) -> usize {
+    node.children
+        .iter()
+        .map(|child| widest_label(child, depth + 1))
+        .fold(depth * INDENT + node.label.chars().count(), usize::max)
+}
+
+/// Paths of every node that has children, for opening them
+fn open_paths(node: &HierarchyNode, path: Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    if node.children.is_empty() {
+        return;
+    }
+    for (idx, child) in node.children.iter().enumerate() {
+        let mut child_path = path.clone();
+        child_path.push(idx);
+        open_paths(child, child_path, paths);
+    }
+    paths.push(path);
+}
+
+fn to_tree_item<'b>(
+    node: &HierarchyNode,
+    depth: usize,
+    label_width: usize,
+    (total_packets, total_bytes): (usize, usize),
+) -> TreeItem<'b> {
+    let text = format!(
+        "{:<width$}  {:>8.1}%  {:>8}  {:>8.1}%  {:>10}",
+        node.label,
+        100.0 * node.packets as f64 / total_packets as f64,
+        node.packets,
+        100.0 * node.bytes as f64 / total_bytes as f64,
+        node.bytes,
+        width = label_width - depth * INDENT
+    );
+    let children: Vec<TreeItem> = node
+        .children
+        .iter()
+        .map(|child| to_tree_item(child, depth + 1, label_width, (total_packets, total_bytes)))
+        .collect();
+    TreeItem::new(text, children)
+}
@@ -8,16 +8,7 @@ pub struct StatefulTree<'a> {
     pub items: Vec<TreeItem<'a>>,
 }
 
-#[allow(dead_code)]
 impl<'a> StatefulTree<'a> {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        Self {
-            state: TreeState::default(),
-            items: Vec::new(),
-        }
-    }
-
     pub fn with_items(items: Vec<TreeItem<'a>>) -> Self {
         Self {
             state: TreeState::default(),
@@ -25,10 +16,6 @@ impl<'a> StatefulTree<'a> {
         }
     }
 
-    pub fn extend_items(&mut self, items: Vec<TreeItem<'a>>) {
-        self.items.extend(items);
-    }
-
     pub fn first(&mut self) {
         self.state.select_first();
     }